@@ -0,0 +1,200 @@
+use std::{collections::HashSet, hint::black_box};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    agents::Agent,
+    encoder::EncoderSAT,
+    hero::{Hero, HeroConfig},
+    kb::{KnowledgeBase, Var, init_kb},
+    simulation::{Screen, simulate},
+    world::{Perceptions, Position, World},
+};
+
+// partita di riferimento: mappa, pozzi e seed fissati perché i numeri siano confrontabili
+const DIM: usize = 8;
+const PITS: usize = 6;
+const SEED: u64 = 42;
+
+fn seeded_agent(dim: usize) -> Box<dyn Agent> {
+    Box::new(Hero::with_rng(
+        init_kb(dim),
+        dim,
+        HeroConfig::default(),
+        StdRng::seed_from_u64(SEED),
+    ))
+}
+
+// percezioni della prima metà della partita giocata sulla mappa del seed di riferimento
+fn recorded_perceptions(dim: usize, pits: usize) -> Vec<Perceptions> {
+    let mut world = World::new_with_seed(dim, pits, SEED);
+    let mut agent = seeded_agent(dim);
+    let result = simulate(
+        &mut world,
+        agent.as_mut(),
+        &mut |_| {},
+        Screen::hidden(),
+        None,
+    );
+    let half = result.history.len().div_ceil(2);
+    result
+        .history
+        .into_iter()
+        .take(half)
+        .map(|step| step.perceptions)
+        .collect()
+}
+
+// KB a metà partita ricostruita dalle percezioni registrate, con le caselle di frontiera
+// (non visitate e adiacenti a una casella visitata) in ordine di riga
+fn mid_game(dim: usize, pits: usize) -> (EncoderSAT<Var>, Vec<Position>) {
+    let mut kb = init_kb(dim);
+    let mut visited = HashSet::new();
+    for p in recorded_perceptions(dim, pits) {
+        kb.tell(&EncoderSAT::<Var>::create_ground_truth_from_perception(&p));
+        visited.insert(p.position);
+    }
+    let mut frontier: Vec<Position> = visited
+        .iter()
+        .flat_map(|pos| pos.neighbor_positions(dim))
+        .filter(|pos| !visited.contains(pos))
+        .collect();
+    frontier.sort();
+    (kb, frontier)
+}
+
+// la KB di metà della partita di riferimento e la sua prima casella di frontiera
+fn mid_game_kb() -> (EncoderSAT<Var>, Position) {
+    let (kb, frontier) = mid_game(DIM, PITS);
+    let cell = *frontier.first().expect("the game explored part of the map");
+    (kb, cell)
+}
+
+fn bench_init_kb(c: &mut Criterion) {
+    let mut group = c.benchmark_group("init_kb");
+    for dim in [5, 10, 15] {
+        group.bench_with_input(BenchmarkId::from_parameter(dim), &dim, |b, &dim| {
+            b.iter(|| init_kb(black_box(dim)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_ask_safe(c: &mut Criterion) {
+    let (mut kb, cell) = mid_game_kb();
+    let query = EncoderSAT::<Var>::create_safe_formula(&cell);
+    c.bench_function("ask safe frontier cell", |b| {
+        b.iter(|| kb.ask(black_box(&query)).expect("the solver answers"))
+    });
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let (kb, _) = mid_game_kb();
+    c.bench_function("encode mid-game kb", |b| b.iter(|| black_box(&kb).encode()));
+}
+
+// il costo del deposito delle clausole: la KB iniziale, le percezioni registrate, poi encode()
+fn bench_add_and_encode(c: &mut Criterion) {
+    let formulas: Vec<_> = recorded_perceptions(DIM, PITS)
+        .iter()
+        .map(EncoderSAT::<Var>::create_ground_truth_from_perception)
+        .collect();
+    c.bench_function("add and encode mid-game kb", |b| {
+        b.iter(|| {
+            let mut kb = init_kb(DIM);
+            for formula in &formulas {
+                kb.tell(black_box(formula));
+            }
+            kb.encode()
+        })
+    });
+}
+
+// quanto l'eliminazione di variabili riduce la CNF di metà partita e quanto costa la sat()
+fn bench_elimination(c: &mut Criterion) {
+    let (mut kb, _) = mid_game_kb();
+    kb.use_in_process_solver();
+    let mut group = c.benchmark_group("sat mid-game kb");
+    for growth in [None, Some(0), Some(2)] {
+        kb.set_elimination(growth);
+        let report = kb.elimination_report();
+        println!(
+            "elimination {:?}: {} -> {} clauses, {} -> {} literals",
+            growth,
+            report.clauses_before,
+            report.clauses_after,
+            report.literals_before,
+            report.literals_after
+        );
+        let name = growth.map_or("off".to_string(), |growth| format!("growth {}", growth));
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&kb).sat().expect("the solver answers"))
+        });
+    }
+    group.finish();
+}
+
+// le domande di sicurezza sull'angolo 5x5 della partenza di una partita 25x25 a metà,
+// in serie e divise tra i thread (senza la feature rayon ask_batch_parallel è seriale)
+fn bench_parallel_asks(c: &mut Criterion) {
+    let (mut kb, _) = mid_game(25, 20);
+    kb.use_in_process_solver();
+    let queries: Vec<_> = (0..5)
+        .flat_map(|y| (0..5).map(move |x| Position::new(x, y)))
+        .flat_map(|cell| {
+            [
+                EncoderSAT::<Var>::create_safe_formula(&cell),
+                EncoderSAT::<Var>::create_unsafe_formula(&cell),
+            ]
+        })
+        .collect();
+    let mut group = c.benchmark_group("ask batch 25x25");
+    group.sample_size(10);
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            kb.ask_batch(black_box(&queries))
+                .expect("the solver answers")
+        })
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            kb.ask_batch_parallel(black_box(&queries))
+                .expect("the solver answers")
+        })
+    });
+    group.finish();
+}
+
+fn bench_full_game(c: &mut Criterion) {
+    let mut group = c.benchmark_group("game");
+    // ogni partita interroga il solver centinaia di volte
+    group.sample_size(10);
+    group.bench_function("seeded 8x8", |b| {
+        b.iter(|| {
+            let mut world = World::new_with_seed(DIM, PITS, SEED);
+            let mut agent = seeded_agent(DIM);
+            simulate(
+                &mut world,
+                agent.as_mut(),
+                &mut |_| {},
+                Screen::hidden(),
+                None,
+            )
+            .steps
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_init_kb,
+    bench_ask_safe,
+    bench_encode,
+    bench_add_and_encode,
+    bench_elimination,
+    bench_parallel_asks,
+    bench_full_game
+);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+# controlli del modulo Python, dopo `maturin develop --features extension-module` si lanciano con
+# `pytest python`
+
+import pytest
+
+from wumpus import WumpusEnv
+
+
+def test_reset_starts_at_home():
+    env = WumpusEnv(4, 3)
+    assert env.is_done()
+    observation = env.reset(42)
+    assert not env.is_done()
+    assert observation["position"] == (0, 0)
+    assert observation["board_size"] == 4
+    assert not observation["bump"]
+
+
+def test_same_seed_same_dungeon():
+    first, second = WumpusEnv(4, 3), WumpusEnv(4, 3)
+    first.reset(7)
+    second.reset(7)
+    assert first.render() == second.render()
+
+
+def test_wall_gives_a_bump():
+    env = WumpusEnv(4, 3)
+    env.reset(42)
+    # dalla casella di partenza a ovest c'è il muro
+    observation, reward, done, info = env.step("w")
+    assert observation["bump"]
+    assert reward == -1
+    assert not done
+    assert info["legal"] == "false"
+
+
+def test_exit_ends_the_episode():
+    env = WumpusEnv(4, 3)
+    env.reset(42)
+    _, _, done, info = env.step("x")
+    assert done
+    assert env.is_done()
+    assert info["gold_in_dungeon"] == "true"
+    with pytest.raises(RuntimeError):
+        env.step("x")
+
+
+def test_bad_arguments():
+    with pytest.raises(ValueError):
+        WumpusEnv(2, 3)
+    env = WumpusEnv(4, 3)
+    env.reset(1)
+    with pytest.raises(ValueError):
+        env.step("jump")
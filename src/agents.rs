@@ -0,0 +1,293 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use log::{error, warn};
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::{
+    encoder::MemoryFootprint,
+    hero::{Belief, DecisionTrace, Hero, HeroError, HeroStats},
+    kb::KnowledgeBase,
+    world::{Action, Direction, Perceptions, Position},
+};
+
+pub trait Agent {
+    fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError>;
+
+    /// The action with the reasons of the choice, if the agent can explain it.
+    fn next_action_traced(
+        &mut self,
+        p: Perceptions,
+    ) -> Result<(Action, Option<DecisionTrace>), HeroError> {
+        self.next_action(p).map(|a| (a, None))
+    }
+
+    /// True if dying now is an expected consequence of the agent's choices,
+    /// false if the agent believed the position to be safe.
+    fn accepts_risk(&self) -> bool {
+        true
+    }
+
+    /// Work done during the current game, only agents that reason on a knowledge base have it.
+    fn stats(&self) -> Option<HeroStats> {
+        None
+    }
+
+    /// What the agent believes about pos, for the invariant checks of simulate; None for
+    /// the agents without a knowledge base.
+    fn belief(&self, _pos: Position) -> Option<Belief> {
+        None
+    }
+
+    /// What the agent believes about the board, with its plan, when the hero is in `position`.
+    fn render_knowledge(&self, _position: Position) -> Option<String> {
+        None
+    }
+
+    /// Clauses and variables of the knowledge base, if the agent has one.
+    fn kb_size(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Approximate bytes held by the knowledge base, if the agent has one.
+    fn kb_memory(&self) -> Option<MemoryFootprint> {
+        None
+    }
+}
+
+impl<K: KnowledgeBase<Query: fmt::Debug>, R: Rng> Agent for Hero<K, R> {
+    fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+        Hero::next_action(self, p)
+    }
+
+    fn next_action_traced(
+        &mut self,
+        p: Perceptions,
+    ) -> Result<(Action, Option<DecisionTrace>), HeroError> {
+        Hero::next_action_traced(self, p).map(|(a, trace)| (a, Some(trace)))
+    }
+
+    fn accepts_risk(&self) -> bool {
+        self.is_gambling()
+    }
+
+    fn stats(&self) -> Option<HeroStats> {
+        Some(Hero::stats(self))
+    }
+
+    fn belief(&self, pos: Position) -> Option<Belief> {
+        Some(Hero::belief(self, pos))
+    }
+
+    fn render_knowledge(&self, position: Position) -> Option<String> {
+        Some(Hero::render_knowledge(self, position))
+    }
+
+    fn kb_size(&self) -> Option<(usize, usize)> {
+        Some(Hero::kb_size(self))
+    }
+
+    fn kb_memory(&self) -> Option<MemoryFootprint> {
+        Some(Hero::kb_memory(self))
+    }
+}
+
+// il binario sceglie l'agente a runtime, così anche un Box<dyn Agent> si può avvolgere
+// in orientation::Oriented
+impl<A: Agent + ?Sized> Agent for Box<A> {
+    fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+        (**self).next_action(p)
+    }
+
+    fn next_action_traced(
+        &mut self,
+        p: Perceptions,
+    ) -> Result<(Action, Option<DecisionTrace>), HeroError> {
+        (**self).next_action_traced(p)
+    }
+
+    fn accepts_risk(&self) -> bool {
+        (**self).accepts_risk()
+    }
+
+    fn stats(&self) -> Option<HeroStats> {
+        (**self).stats()
+    }
+
+    fn belief(&self, pos: Position) -> Option<Belief> {
+        (**self).belief(pos)
+    }
+
+    fn render_knowledge(&self, position: Position) -> Option<String> {
+        (**self).render_knowledge(position)
+    }
+
+    fn kb_size(&self) -> Option<(usize, usize)> {
+        (**self).kb_size()
+    }
+
+    fn kb_memory(&self) -> Option<MemoryFootprint> {
+        (**self).kb_memory()
+    }
+}
+
+/// Which agent plays a game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AgentKind {
+    #[default]
+    Hero,
+    // l'eroe con la GreedyStrategy
+    Greedy,
+    Random,
+    Human,
+}
+
+impl FromStr for AgentKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hero" => Ok(AgentKind::Hero),
+            "greedy" => Ok(AgentKind::Greedy),
+            "random" => Ok(AgentKind::Random),
+            "human" => Ok(AgentKind::Human),
+            other => Err(format!("unknown agent {}", other)),
+        }
+    }
+}
+
+/// Baseline agent: plays a uniformly random legal action, grabs the gold when it sees
+/// the glitter and exits as soon as it is at the start after `budget` steps.
+pub struct RandomAgent<R> {
+    rng: R,
+    steps: usize,
+    budget: usize,
+    arrow: bool,
+}
+
+impl<R: Rng> RandomAgent<R> {
+    pub fn new(rng: R, budget: usize) -> Self {
+        Self {
+            rng: rng,
+            steps: 0,
+            budget: budget,
+            arrow: true,
+        }
+    }
+}
+
+impl<R: Rng> Agent for RandomAgent<R> {
+    fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+        self.steps += 1;
+        if p.glitter() {
+            return Ok(Action::Grab);
+        }
+        if p.position == Position::new(0, 0) && self.steps > self.budget {
+            return Ok(Action::Exit);
+        }
+        let mut actions = vec![];
+        for dir in Direction::ALL {
+            if p.position.possible_move(dir, p.board_size) {
+                actions.push(Action::Move(dir));
+            }
+            if self.arrow {
+                actions.push(Action::Shoot(dir));
+            }
+        }
+        let action = *actions
+            .choose(&mut self.rng)
+            .expect("there is always a legal move");
+        if let Action::Shoot(_) = action {
+            self.arrow = false;
+        }
+        Ok(action)
+    }
+}
+
+/// Lets a person play from the terminal: n/s/e/w move, g grabs, x exits and
+/// "shoot <dir>" shoots the arrow.
+pub struct HumanAgent {
+    arrow: bool,
+}
+
+impl HumanAgent {
+    pub fn new() -> Self {
+        Self { arrow: true }
+    }
+
+    // anche le azioni dei binding Python usano questi comandi
+    pub(crate) fn parse(command: &str) -> Option<Action> {
+        let direction = |s: &str| s.parse::<Direction>().ok();
+        let mut words = command.split_whitespace();
+        let action = match words.next()? {
+            "g" | "grab" => Action::Grab,
+            "x" | "exit" => Action::Exit,
+            "shoot" => Action::Shoot(direction(words.next()?)?),
+            other => Action::Move(direction(other)?),
+        };
+        // niente parole in più dopo il comando
+        match words.next() {
+            Some(_) => None,
+            None => Some(action),
+        }
+    }
+}
+
+// la partita comincia con la freccia anche per un agente creato con default()
+impl Default for HumanAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Agent for HumanAgent {
+    fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+        // il prompt fa parte della partita, non del log
+        let mut out = io::stdout();
+        let _ = writeln!(out, "{:?}", p);
+        let stdin = io::stdin();
+        loop {
+            let _ = write!(out, "action (n/s/e/w/g/x/shoot <dir>): ");
+            let _ = out.flush();
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    error!("stdin closed");
+                    return Err(HeroError::NoActionAvailable(p.position));
+                }
+                Ok(_) => {}
+            }
+            match Self::parse(line.trim()) {
+                Some(Action::Move(dir)) if !p.position.possible_move(dir, p.board_size) => {
+                    warn!("there is a wall in that direction");
+                }
+                Some(Action::Grab) if !p.glitter() => warn!("there is no gold here"),
+                Some(Action::Exit) if p.position != Position::new(0, 0) => {
+                    warn!("the exit is in the position (0,0)");
+                }
+                Some(Action::Shoot(_)) if !self.arrow => warn!("the arrow is gone"),
+                Some(action) => {
+                    if let Action::Shoot(_) = action {
+                        self.arrow = false;
+                    }
+                    return Ok(action);
+                }
+                None => warn!("unknown command {:?}", line.trim()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_default_human_agent_has_the_arrow() {
+        assert!(HumanAgent::default().arrow);
+        assert!(HumanAgent::new().arrow);
+    }
+}
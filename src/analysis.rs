@@ -0,0 +1,441 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+};
+
+use crate::{
+    kb::KnowledgeBase,
+    solver::SolverError,
+    world::{Perceptions, Position, World},
+};
+
+// contenuto possibile di una casella di frontiera
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hazard {
+    Empty,
+    Pit,
+    Wumpus,
+}
+
+/// Whether an agent entering only the cells proven safe by its perceptions reaches the
+/// gold: the cells are explored from the start and a frontier cell is entered when no
+/// placement of the pits and of the wumpus consistent with what was perceived puts a hazard
+/// in it. The arrow is never used, so the wumpus is only avoided. The placements of the
+/// frontier are enumerated with backtracking, exponential in the worst case but fast on the
+/// dungeons played here.
+pub fn knowledge_reachable(world: &World) -> bool {
+    let size = world.size();
+    let mut visited = HashSet::new();
+    let mut observed: Vec<Perceptions> = vec![];
+    let mut queue = vec![Position::new(0, 0)];
+    loop {
+        while let Some(pos) = queue.pop() {
+            if !visited.insert(pos) {
+                continue;
+            }
+            let p = world.perceptions_at(pos);
+            if p.glitter() {
+                return true;
+            }
+            observed.push(p);
+        }
+        let mut fringe: Vec<Position> = visited
+            .iter()
+            .flat_map(|pos| pos.neighbor_positions(size))
+            .filter(|pos| !visited.contains(pos))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        fringe.sort();
+        let frontier = Frontier::new(
+            &fringe,
+            &observed,
+            visited.len() == size * size - fringe.len(),
+            size,
+        );
+        queue.extend(
+            (0..fringe.len())
+                .filter(|&cell| {
+                    !frontier.consistent(cell, Hazard::Pit)
+                        && !frontier.consistent(cell, Hazard::Wumpus)
+                })
+                .map(|cell| fringe[cell]),
+        );
+        if queue.is_empty() {
+            return false;
+        }
+    }
+}
+
+/// Why check_soundness stopped.
+#[derive(Debug)]
+pub enum SoundnessError {
+    Solver(SolverError),
+    // la KB ha dimostrato un fatto falso nel dungeon dopo le percezioni in tells
+    Unsound {
+        cell: Position,
+        fact: &'static str,
+        tells: Vec<Perceptions>,
+    },
+    // la KB e quella di riferimento rispondono diversamente sullo stesso fatto falso
+    Disagreement {
+        cell: Position,
+        fact: &'static str,
+        // true se è la KB controllata a dimostrarlo e non quella di riferimento
+        proven_by_kb: bool,
+        tells: Vec<Perceptions>,
+    },
+}
+
+impl fmt::Display for SoundnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoundnessError::Solver(e) => write!(f, "the solver failed: {}", e),
+            SoundnessError::Unsound { cell, fact, tells } => {
+                write!(f, "{:?} was proven {} after the tells:", cell, fact)?;
+                write_tells(f, tells)
+            }
+            SoundnessError::Disagreement {
+                cell,
+                fact,
+                proven_by_kb,
+                tells,
+            } => {
+                let (prover, other) = if *proven_by_kb {
+                    ("the knowledge base", "the reference")
+                } else {
+                    ("the reference", "the knowledge base")
+                };
+                write!(
+                    f,
+                    "{:?} was proven {} by {} but not by {} after the tells:",
+                    cell, fact, prover, other
+                )?;
+                write_tells(f, tells)
+            }
+        }
+    }
+}
+
+fn write_tells(f: &mut fmt::Formatter<'_>, tells: &[Perceptions]) -> fmt::Result {
+    for p in tells {
+        write!(f, "\n  {}", p)?;
+    }
+    Ok(())
+}
+
+/// Walks the cells of world without hazards in breadth-first order from the start and
+/// tells kb the perceptions of each one. After every tell no false fact may be entailed:
+/// Safe for the pits and the wumpus, Pit and Wumpus for the other cells. Returns the
+/// number of facts asked.
+pub fn check_soundness<K: KnowledgeBase>(
+    world: &World,
+    kb: &mut K,
+) -> Result<usize, SoundnessError> {
+    check_knowledge_bases(world, &mut [kb])
+}
+
+/// check_soundness on kb and on reference, a knowledge base with another solver, told
+/// the same perceptions: after every tell the two must also give the same answers.
+pub fn check_soundness_against<K: KnowledgeBase>(
+    world: &World,
+    kb: &mut K,
+    reference: &mut K,
+) -> Result<usize, SoundnessError> {
+    check_knowledge_bases(world, &mut [kb, reference])
+}
+
+// la prima KB è quella controllata, le altre vengono confrontate con lei
+fn check_knowledge_bases<K: KnowledgeBase>(
+    world: &World,
+    kbs: &mut [&mut K],
+) -> Result<usize, SoundnessError> {
+    let size = world.size();
+    let cells: Vec<Position> = (0..size)
+        .flat_map(|y| (0..size).map(move |x| Position::new(x, y)))
+        .collect();
+    // per ogni casella i fatti falsi nel dungeon, con le formule che li chiedono
+    let mut facts = vec![];
+    let mut queries = vec![];
+    for cell in &cells {
+        if world.hazard_at(*cell).is_some() {
+            facts.push((*cell, "safe"));
+            queries.push(K::create_safe_formula(cell));
+        } else {
+            facts.push((*cell, "a pit"));
+            queries.push(K::create_pit_formula(cell));
+            facts.push((*cell, "the wumpus"));
+            queries.push(K::create_wumpus_formula(cell));
+        }
+    }
+    let start = Position::new(0, 0);
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    let mut tells = vec![];
+    let mut asked = 0;
+    while let Some(pos) = queue.pop_front() {
+        let p = world.perceptions_at(pos);
+        let told = K::create_ground_truth_from_perception(&p);
+        tells.push(p);
+        let mut answers: Vec<Vec<bool>> = vec![];
+        for kb in kbs.iter_mut() {
+            kb.tell(&told);
+            answers.push(kb.ask_batch(&queries).map_err(SoundnessError::Solver)?);
+        }
+        for other in &answers[1..] {
+            if let Some(k) = (0..queries.len()).position(|k| answers[0][k] != other[k]) {
+                let (cell, fact) = facts[k];
+                return Err(SoundnessError::Disagreement {
+                    cell: cell,
+                    fact: fact,
+                    proven_by_kb: answers[0][k],
+                    tells: tells,
+                });
+            }
+        }
+        asked += queries.len();
+        if let Some(k) = answers[0].iter().position(|entailed| *entailed) {
+            let (cell, fact) = facts[k];
+            return Err(SoundnessError::Unsound {
+                cell: cell,
+                fact: fact,
+                tells: tells,
+            });
+        }
+        for next in pos.neighbor_positions(size) {
+            if world.hazard_at(next).is_none() && seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    Ok(asked)
+}
+
+// i vincoli delle percezioni sulle caselle di frontiera
+struct Frontier {
+    cells: usize,
+    // per ogni percezione osservata: brezza, puzza e le caselle di frontiera vicine
+    constraints: Vec<(bool, bool, Vec<usize>)>,
+    // per ogni casella di frontiera i vincoli in cui compare
+    touching: Vec<Vec<usize>>,
+    // se tutte le caselle non visitate sono di frontiera il wumpus deve stare tra queste
+    wumpus_in_fringe: bool,
+}
+
+impl Frontier {
+    fn new(fringe: &[Position], observed: &[Perceptions], closed: bool, size: usize) -> Self {
+        let mut touching = vec![vec![]; fringe.len()];
+        let mut constraints = vec![];
+        for p in observed {
+            let cells: Vec<usize> = p
+                .position
+                .neighbor_positions(size)
+                .filter_map(|pos| fringe.iter().position(|cell| *cell == pos))
+                .collect();
+            for &cell in &cells {
+                touching[cell].push(constraints.len());
+            }
+            constraints.push((p.breeze(), p.stench(), cells));
+        }
+        let wumpus_in_fringe = closed || observed.iter().any(|p| p.stench());
+        Self {
+            cells: fringe.len(),
+            constraints: constraints,
+            touching: touching,
+            wumpus_in_fringe: wumpus_in_fringe,
+        }
+    }
+
+    // esiste un'assegnazione della frontiera coerente con le percezioni con hazard in cell
+    fn consistent(&self, cell: usize, hazard: Hazard) -> bool {
+        let mut assignment = vec![None; self.cells];
+        self.search(0, cell, hazard, &mut assignment)
+    }
+
+    fn search(
+        &self,
+        next: usize,
+        fixed: usize,
+        hazard: Hazard,
+        assignment: &mut Vec<Option<Hazard>>,
+    ) -> bool {
+        if next == self.cells {
+            return !self.wumpus_in_fringe || assignment.contains(&Some(Hazard::Wumpus));
+        }
+        let wumpus_placed = assignment.contains(&Some(Hazard::Wumpus));
+        let choices: &[Hazard] = if next == fixed {
+            &[hazard]
+        } else {
+            &[Hazard::Empty, Hazard::Pit, Hazard::Wumpus]
+        };
+        for &choice in choices {
+            if choice == Hazard::Wumpus && wumpus_placed {
+                continue;
+            }
+            assignment[next] = Some(choice);
+            if self.touching[next]
+                .iter()
+                .all(|&constraint| self.satisfiable(constraint, assignment))
+                && self.search(next + 1, fixed, hazard, assignment)
+            {
+                return true;
+            }
+        }
+        assignment[next] = None;
+        false
+    }
+
+    // il vincolo può ancora essere soddisfatto dalle caselle non assegnate
+    fn satisfiable(&self, constraint: usize, assignment: &[Option<Hazard>]) -> bool {
+        let (breeze, stench, cells) = &self.constraints[constraint];
+        let perceived = |kind: Hazard, present: bool| {
+            let any = cells.iter().any(|&cell| assignment[cell] == Some(kind));
+            let open = cells.iter().any(|&cell| assignment[cell].is_none());
+            if present { any || open } else { !any }
+        };
+        perceived(Hazard::Pit, *breeze) && perceived(Hazard::Wumpus, *stench)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, sync::atomic::AtomicBool, time::Duration};
+
+    use super::*;
+    use crate::{
+        kb::init_kb,
+        solver::{Dpll, Solver, test_backends},
+    };
+
+    // dimostra tutto: ogni negazione è insoddisfacibile
+    struct Liar;
+
+    impl Solver for Liar {
+        fn name(&self) -> &str {
+            "liar"
+        }
+
+        fn solve(
+            &self,
+            _dimacs: &str,
+            _timeout: Option<Duration>,
+            _stop: &AtomicBool,
+        ) -> Result<String, SolverError> {
+            Ok("s UNSATISFIABLE\n".to_string())
+        }
+    }
+
+    #[test]
+    fn no_false_fact_is_proven_by_any_backend() {
+        for backend in test_backends() {
+            for seed in 0..10 {
+                let world = World::new_with_seed(4, 2, seed);
+                let mut kb = init_kb(4);
+                kb.set_solver(backend.clone());
+                let mut reference = init_kb(4);
+                reference.use_in_process_solver();
+                let result = check_soundness_against(&world, &mut kb, &mut reference);
+                assert!(
+                    result.is_ok(),
+                    "{} on seed {}:\n{}{}",
+                    backend.name(),
+                    seed,
+                    world,
+                    result.unwrap_err()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_lying_backend_is_caught() {
+        let world = World::new_with_seed(4, 2, 0);
+        let mut kb = init_kb(4);
+        kb.set_solver(Arc::new(Liar));
+        assert!(matches!(
+            check_soundness(&world, &mut kb),
+            Err(SoundnessError::Unsound { .. })
+        ));
+
+        let mut kb = init_kb(4);
+        kb.set_solver(Arc::new(Liar));
+        let mut reference = init_kb(4);
+        reference.set_solver(Arc::new(Dpll));
+        match check_soundness_against(&world, &mut kb, &mut reference) {
+            Err(SoundnessError::Disagreement {
+                proven_by_kb,
+                tells,
+                ..
+            }) => {
+                assert!(proven_by_kb);
+                assert_eq!(tells.len(), 1);
+            }
+            other => panic!("expected a disagreement, got {:?}", other),
+        }
+        // scambiate, è la KB di riferimento a dimostrare il fatto
+        let mut kb = init_kb(4);
+        kb.use_in_process_solver();
+        let mut reference = init_kb(4);
+        reference.set_solver(Arc::new(Liar));
+        assert!(matches!(
+            check_soundness_against(&world, &mut kb, &mut reference),
+            Err(SoundnessError::Disagreement {
+                proven_by_kb: false,
+                ..
+            })
+        ));
+    }
+
+    fn layout(layout: &str) -> World {
+        World::from_layout(layout).expect("the layout is valid")
+    }
+
+    #[test]
+    fn gold_behind_a_provably_safe_corridor_is_reachable() {
+        // la prima riga non ha né brezza né puzza fino all'oro
+        assert!(knowledge_reachable(&layout(
+            "x . . g\n. . . .\n. . . o\n. . . w"
+        )));
+    }
+
+    #[test]
+    fn gold_behind_an_ambiguous_breeze_is_not_reachable() {
+        // (1,0) e (0,1) sentono la brezza del pozzo in (1,1): ognuna delle tre caselle di
+        // frontiera può essere un pozzo, anche se un cammino sicuro verso l'oro esiste
+        assert!(!knowledge_reachable(&layout(
+            "x . . .\n. o . .\n. . . .\n. . g w"
+        )));
+    }
+
+    #[test]
+    fn a_stench_puts_the_wumpus_in_the_fringe() {
+        let fringe = [
+            Position::new(2, 0),
+            Position::new(1, 1),
+            Position::new(0, 2),
+        ];
+        let clean = |x, y| Perceptions::at(Position::new(x, y), 3).build();
+        let observed = [
+            clean(0, 0),
+            Perceptions::at(Position::new(1, 0), 3).stench().build(),
+            clean(0, 1),
+        ];
+        let frontier = Frontier::new(&fringe, &observed, false, 3);
+        assert!(frontier.wumpus_in_fringe);
+        // (0,1) non sente puzza: il wumpus può stare solo in (2,0), che non può avere un pozzo
+        assert!(frontier.consistent(0, Hazard::Wumpus));
+        assert!(!frontier.consistent(0, Hazard::Pit));
+        assert!(!frontier.consistent(0, Hazard::Empty));
+        for cell in [1, 2] {
+            for hazard in [Hazard::Pit, Hazard::Wumpus] {
+                assert!(!frontier.consistent(cell, hazard), "{:?}", fringe[cell]);
+            }
+        }
+        // senza puzza il wumpus è nella frontiera solo se non ci sono altre caselle da visitare
+        let observed = [clean(0, 0), clean(1, 0), clean(0, 1)];
+        assert!(!Frontier::new(&fringe, &observed, false, 3).wumpus_in_fringe);
+        assert!(Frontier::new(&fringe, &observed, true, 3).wumpus_in_fringe);
+        // la stessa deduzione porta all'oro dietro la casella che puzza
+        assert!(knowledge_reachable(&layout("x . w\n. . .\n. . g")));
+    }
+}
@@ -1,14 +1,58 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::io::{BufRead, BufReader, Result, Write};
-use std::process::{Command, Stdio};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::solver::PicoSat;
+use crate::solver::{
+    Dpll, INTERRUPTED, SatResult, Solver, SolverError, parse_picosat_model, picosat_is_sat,
+};
 
 type Clause = Vec<Literal<usize>>;
 
-#[derive(Debug)]
+// le variabili con più occorrenze di così non vengono considerate per l'eliminazione
+const MAX_ELIMINATION_OCCURRENCES: usize = 16;
+
+// numero di chiamate al solver, incrementato anche dalle interrogazioni che prendono &self
+#[derive(Default, Debug)]
+struct CallCounter(AtomicUsize);
+
+impl CallCounter {
+    fn increment(&self) {
+        self.add(1);
+    }
+
+    fn add(&self, calls: usize) {
+        self.0.fetch_add(calls, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// ogni copia della KB conta le proprie chiamate
+impl Clone for CallCounter {
+    fn clone(&self) -> Self {
+        Self(AtomicUsize::new(self.get()))
+    }
+}
+
+#[derive(Clone, Debug)]
 struct Snapshot<T> {
     last_var_counter: usize,
     last_len_clauses: usize,
+    last_len_literals: usize,
+    last_len_xors: usize,
+    last_added_since_simplify: usize,
     new_vars: Vec<T>,
 }
 
@@ -16,41 +60,57 @@ impl<T> From<&mut EncoderSAT<T>> for Snapshot<T> {
     fn from(value: &mut EncoderSAT<T>) -> Self {
         Self {
             last_var_counter: value.counter,
-            last_len_clauses: value.clauses.len(),
+            last_len_clauses: value.offsets.len(),
+            last_len_literals: value.literals.len(),
+            last_len_xors: value.xors.len(),
+            last_added_since_simplify: value.added_since_simplify,
             new_vars: Vec::new(),
         }
     }
 }
 
-#[derive(Default)]
+// Clone copia in profondità la mappa delle variabili e le clausole, quindi ogni copia
+// può essere interrogata in modo indipendente (anche da thread diversi)
+#[derive(Default, Clone)]
 pub struct EncoderSAT<T> {
     map: HashMap<T, usize>,
-    clauses: Vec<Clause>,
+    // clausole memorizzate in modo piatto: i letterali in formato DIMACS uno dopo l'altro,
+    // offsets[i] è l'indice del primo letterale della clausola i
+    literals: Vec<i32>,
+    offsets: Vec<usize>,
     counter: usize,
+    // vincoli XOR nativi (letterali DIMACS, parità), usati solo con i backend che li supportano
+    xors: Vec<(Vec<i32>, bool)>,
+    // tabella inversa: reverse[i - 1] è la variabile con indice DIMACS i (None per le variabili ausiliarie)
+    reverse: Vec<Option<T>>,
     snapshot: Option<Snapshot<T>>,
+    added_since_simplify: usize,
+    timeout: Option<Duration>,
+    // None = picosat
+    solver: Option<Arc<dyn Solver>>,
+    // formule comunicate alla KB, nei termini delle variabili originali
+    told: Vec<Vec<Vec<Literal<T>>>>,
+    dump_on_unsat: Option<PathBuf>,
+    // crescita massima di clausole ammessa eliminando una variabile, None = eliminazione disattivata
+    elimination: Option<usize>,
+    solver_calls: CallCounter,
 }
 
 impl<T: Clone + Eq + std::hash::Hash + fmt::Debug> fmt::Debug for EncoderSAT<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Build reverse map: usize -> T
-        let mut reverse_map: HashMap<usize, &T> = HashMap::new();
-        for (t, &id) in &self.map {
-            reverse_map.insert(id, t);
-        }
-
-        for (i, clause) in self.clauses.iter().enumerate() {
+        for (i, clause) in self.clauses().enumerate() {
             write!(f, "Clause {}: ", i + 1)?;
-            for literal in clause {
-                match literal {
+            for literal in clause.iter().map(|l| Literal::from_dimacs(*l)) {
+                match &literal {
                     Literal::Pos(id) => {
-                        if let Some(t) = reverse_map.get(id) {
+                        if let Some(t) = self.var_for(*id) {
                             write!(f, "{:?} ", t)?;
                         } else {
                             write!(f, "+?({}) ", id)?;
                         }
                     }
                     Literal::Neg(id) => {
-                        if let Some(t) = reverse_map.get(id) {
+                        if let Some(t) = self.var_for(*id) {
                             write!(f, "-{:?} ", t)?;
                         } else {
                             write!(f, "-?({}) ", id)?;
@@ -64,84 +124,525 @@ impl<T: Clone + Eq + std::hash::Hash + fmt::Debug> fmt::Debug for EncoderSAT<T>
     }
 }
 
-pub fn picosat_is_sat(output: String) -> bool {
-    let mut reader = BufReader::new(output.as_bytes());
+#[derive(Default, Debug, Clone, Copy)]
+pub struct SimplifyReport {
+    pub removed: usize,
+    pub strengthened: usize,
+    pub clauses_before: usize,
+    pub clauses_after: usize,
+    pub literals_before: usize,
+    pub literals_after: usize,
+}
 
-    let mut line = String::new();
-    // Read first line
-    if reader
-        .read_line(&mut line)
-        .expect("Could not read the output")
-        == 0
-    {
-        panic!("Could not read first line of the output file");
-    }
-    if line.trim() != "s SATISFIABLE" {
-        false
-    } else {
-        true
-    }
+/// Approximate bytes allocated by an EncoderSAT, by what holds them.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryFootprint {
+    // letterali, inizi delle clausole e vincoli XOR
+    pub clauses: usize,
+    // la mappa dalle variabili agli indici DIMACS
+    pub map: usize,
+    // la tabella inversa
+    pub reverse: usize,
+    // le formule comunicate, tenute per spiegare le risposte
+    pub told: usize,
 }
 
-/// Parses the PicoSAT output file and returns a Vec<Option<bool>> where
-/// index 0 is unused, and each index i corresponds to variable i.
-pub fn parse_picosat_model(output: String, nvars: usize) -> Result<Vec<Option<bool>>> {
-    let mut reader = BufReader::new(output.as_bytes());
+impl MemoryFootprint {
+    pub fn total(&self) -> usize {
+        self.clauses + self.map + self.reverse + self.told
+    }
 
-    let mut line = String::new();
-    // Read first line
-    if reader.read_line(&mut line)? == 0 {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::UnexpectedEof,
-            "Could not read first line of the output file",
-        ));
+    /// The largest value of each part, to keep the peak over a game.
+    pub fn max(self, other: Self) -> Self {
+        Self {
+            clauses: self.clauses.max(other.clauses),
+            map: self.map.max(other.map),
+            reverse: self.reverse.max(other.reverse),
+            told: self.told.max(other.told),
+        }
     }
-    if line.trim() != "s SATISFIABLE" {
-        return Ok(vec![]); // UNSAT
+}
+
+impl fmt::Display for MemoryFootprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kib = |bytes: usize| bytes as f64 / 1024.0;
+        write!(
+            f,
+            "{:.1} KiB (clauses {:.1}, map {:.1}, reverse table {:.1}, told formulas {:.1})",
+            kib(self.total()),
+            kib(self.clauses),
+            kib(self.map),
+            kib(self.reverse),
+            kib(self.told)
+        )
     }
+}
 
-    // Prepare result vector: index 0 is unused
-    let mut result = vec![None; nvars + 1];
+// entrambe le clausole sono ordinate
+#[derive(Default, Debug, Clone, Copy)]
+pub struct EliminationReport {
+    pub clauses_before: usize,
+    pub clauses_after: usize,
+    pub literals_before: usize,
+    pub literals_after: usize,
+}
 
-    for line in reader.lines() {
-        let line = line?;
-        if !line.starts_with("v ") {
+// eliminazione di variabili limitata: una variabile v viene sostituita da tutte le risolventi
+// (non tautologiche) delle clausole con v e di quelle con -v, se queste non superano di più
+// di growth le clausole rimosse. Il risultato è equisoddisfacibile con l'input.
+fn eliminate_variables(
+    clauses: Vec<Vec<i32>>,
+    nvars: usize,
+    frozen: &HashSet<usize>,
+    growth: usize,
+) -> Vec<Vec<i32>> {
+    // le clausole tautologiche sono sempre vere e vengono scartate subito,
+    // così nessuna clausola contiene sia v che -v
+    let mut clauses: Vec<Option<Vec<i32>>> = clauses
+        .into_iter()
+        .map(|mut clause| {
+            clause.sort_unstable();
+            clause.dedup();
+            clause
+        })
+        .filter(|clause| !clause.iter().any(|l| clause.binary_search(&-l).is_ok()))
+        .map(Some)
+        .collect();
+    // occorrenze positive e negative di ogni variabile (possono contenere clausole già rimosse)
+    let mut positive: Vec<Vec<usize>> = vec![vec![]; nvars + 1];
+    let mut negative: Vec<Vec<usize>> = vec![vec![]; nvars + 1];
+    for (i, clause) in clauses.iter().enumerate() {
+        for literal in clause.iter().flatten() {
+            let var = literal.unsigned_abs() as usize;
+            if *literal > 0 {
+                positive[var].push(i);
+            } else {
+                negative[var].push(i);
+            }
+        }
+    }
+
+    for var in 1..=nvars {
+        if frozen.contains(&var) {
+            continue;
+        }
+        positive[var].retain(|&i| clauses[i].is_some());
+        negative[var].retain(|&i| clauses[i].is_some());
+        let (pos, neg) = (&positive[var], &negative[var]);
+        if pos.is_empty() && neg.is_empty() || pos.len() + neg.len() > MAX_ELIMINATION_OCCURRENCES {
             continue;
         }
-        for lit in line[2..].split_whitespace() {
-            if let Ok(lit) = lit.parse::<i32>() {
-                if lit == 0 {
+
+        let limit = pos.len() + neg.len() + growth;
+        let mut resolvents = vec![];
+        'resolution: for &p in pos {
+            for &n in neg {
+                let (Some(a), Some(b)) = (&clauses[p], &clauses[n]) else {
                     continue;
+                };
+                let mut resolvent: Vec<i32> = a
+                    .iter()
+                    .chain(b.iter())
+                    .filter(|literal| literal.unsigned_abs() as usize != var)
+                    .copied()
+                    .collect();
+                resolvent.sort_unstable();
+                resolvent.dedup();
+                if resolvent
+                    .iter()
+                    .any(|l| resolvent.binary_search(&-l).is_ok())
+                {
+                    continue;
+                }
+                resolvents.push(resolvent);
+                if resolvents.len() > limit {
+                    break 'resolution;
                 }
-                let idx = lit.abs() as usize;
-                if idx <= nvars {
-                    result[idx] = Some(lit > 0);
+            }
+        }
+        if resolvents.len() > limit {
+            continue;
+        }
+
+        for i in pos.iter().chain(neg.iter()).copied().collect::<Vec<_>>() {
+            clauses[i] = None;
+        }
+        for resolvent in resolvents {
+            let i = clauses.len();
+            for literal in &resolvent {
+                let other = literal.unsigned_abs() as usize;
+                if *literal > 0 {
+                    positive[other].push(i);
+                } else {
+                    negative[other].push(i);
                 }
             }
+            clauses.push(Some(resolvent));
         }
     }
-    Ok(result)
+
+    clauses.into_iter().flatten().collect()
 }
 
-/// Given the model (as returned by parse_picosat_model) and the variable dictionary,
-/// returns a Vec of (T, Option<bool>) for each variable (excluding index 0).
-pub fn decode_model<T: Clone>(vars: &[T], model: &[Option<bool>]) -> Vec<(T, Option<bool>)> {
-    vars.iter()
-        .cloned()
-        .enumerate()
-        .skip(1)
-        .map(|(i, v)| (v, model.get(i).cloned().unwrap_or(None)))
+// clausole equivalenti a lits[0] xor ... xor lits[k-1] = rhs, una per ogni assegnamento da escludere
+fn xor_to_cnf(lits: &[i32], rhs: bool) -> Vec<Vec<i32>> {
+    (0..1u64 << lits.len())
+        .filter(|assignment| (assignment.count_ones() % 2 == 1) != rhs)
+        .map(|assignment| {
+            lits.iter()
+                .enumerate()
+                .map(|(i, literal)| {
+                    if assignment & (1 << i) != 0 {
+                        -literal
+                    } else {
+                        *literal
+                    }
+                })
+                .collect()
+        })
         .collect()
 }
 
+fn is_subset(small: &[i32], big: &[i32]) -> bool {
+    let mut big = big.iter();
+    small
+        .iter()
+        .all(|literal| big.by_ref().any(|other| other == literal))
+}
+
+impl<T> EncoderSAT<T> {
+    pub fn var_for(&self, index: usize) -> Option<&T> {
+        self.reverse.get(index.checked_sub(1)?)?.as_ref()
+    }
+
+    fn push_clause(&mut self, clause: &[Literal<usize>]) {
+        self.offsets.push(self.literals.len());
+        self.literals
+            .extend(clause.iter().map(|literal| literal.to_dimacs()));
+    }
+
+    fn clauses(&self) -> impl Iterator<Item = &[i32]> {
+        self.offsets.iter().enumerate().map(|(i, &start)| {
+            let end = self
+                .offsets
+                .get(i + 1)
+                .copied()
+                .unwrap_or(self.literals.len());
+            &self.literals[start..end]
+        })
+    }
+
+    /// Every solver call longer than timeout fails with SolverError::Timeout.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_solver(&mut self, solver: Arc<dyn Solver>) {
+        self.solver = Some(solver);
+    }
+
+    /// Solves in the process with the DPLL backend instead of running picosat.
+    pub fn use_in_process_solver(&mut self) {
+        self.set_solver(Arc::new(Dpll));
+    }
+
+    /// Enables bounded variable elimination on the CNF sent by sat() and
+    /// solve_under_assumptions(): a variable is resolved out only if the resolvents are
+    /// at most `growth` clauses more than the clauses they replace. The assumption
+    /// variables are never eliminated. The clause store is never modified and model()
+    /// always sees the full CNF.
+    pub fn set_elimination(&mut self, growth: Option<usize>) {
+        self.elimination = growth;
+    }
+
+    /// True if the configured backend accepts native XOR constraints.
+    pub fn supports_xor(&self) -> bool {
+        self.solver
+            .as_ref()
+            .is_some_and(|solver| solver.supports_xor())
+    }
+
+    /// Directory where the KB state is dumped when it is found inconsistent.
+    pub fn set_dump_on_unsat(&mut self, dir: Option<PathBuf>) {
+        self.dump_on_unsat = dir;
+    }
+
+    pub fn dump_on_unsat(&self) -> Option<&Path> {
+        self.dump_on_unsat.as_deref()
+    }
+
+    pub fn record_told(&mut self, formula: Vec<Vec<Literal<T>>>) {
+        self.told.push(formula);
+    }
+
+    pub fn told(&self) -> &[Vec<Vec<Literal<T>>>] {
+        &self.told
+    }
+
+    /// Number of times the SAT backend has been run.
+    pub fn solver_calls(&self) -> usize {
+        self.solver_calls.get()
+    }
+
+    /// Adds the solver runs done on a copy of this encoder.
+    pub fn add_solver_calls(&self, calls: usize) {
+        self.solver_calls.add(calls);
+    }
+
+    // esegue il backend configurato sul testo DIMACS
+    fn run_solver(&self, encoding: &str) -> Result<String, SolverError> {
+        self.run_solver_assuming(encoding, &[])
+    }
+
+    // come run_solver, con i letterali DIMACS assunti veri solo per questa chiamata
+    fn run_solver_assuming(
+        &self,
+        encoding: &str,
+        assumptions: &[i32],
+    ) -> Result<String, SolverError> {
+        self.solver_calls.increment();
+        // con Ctrl-C il solver in corso viene fermato (e il processo figlio terminato)
+        let stop = &INTERRUPTED;
+        match &self.solver {
+            Some(solver) => {
+                solver.solve_with_assumptions(encoding, assumptions, self.timeout, stop)
+            }
+            // su wasm32 non si possono lanciare processi, il solver gira nel programma
+            #[cfg(not(target_arch = "wasm32"))]
+            None => PicoSat.solve_with_assumptions(encoding, assumptions, self.timeout, stop),
+            #[cfg(target_arch = "wasm32")]
+            None => Dpll.solve_with_assumptions(encoding, assumptions, self.timeout, stop),
+        }
+    }
+
+    fn run_sat(&self, encoding: &str) -> Result<SatResult, SolverError> {
+        self.run_sat_assuming(encoding, &[])
+    }
+
+    fn run_sat_assuming(
+        &self,
+        encoding: &str,
+        assumptions: &[i32],
+    ) -> Result<SatResult, SolverError> {
+        if picosat_is_sat(self.run_solver_assuming(encoding, assumptions)?)? {
+            Ok(SatResult::Sat)
+        } else {
+            Ok(SatResult::Unsat)
+        }
+    }
+
+    pub fn clause_count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    // variabili DIMACS, comprese quelle ausiliarie
+    pub fn variable_count(&self) -> usize {
+        self.counter
+    }
+
+    /// Bytes allocated by the clause store, the variable map and the reverse table, from the
+    /// capacities of the collections. The map counts a key, a value and a control byte per
+    /// slot like the standard HashMap; the snapshot and the solver are left out.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let xors: usize = self
+            .xors
+            .iter()
+            .map(|(literals, _)| {
+                size_of::<(Vec<i32>, bool)>() + literals.capacity() * size_of::<i32>()
+            })
+            .sum();
+        let told: usize = self
+            .told
+            .iter()
+            .map(|formula| {
+                size_of::<Vec<Vec<Literal<T>>>>()
+                    + formula
+                        .iter()
+                        .map(|clause| {
+                            size_of::<Vec<Literal<T>>>()
+                                + clause.capacity() * size_of::<Literal<T>>()
+                        })
+                        .sum::<usize>()
+            })
+            .sum();
+        MemoryFootprint {
+            clauses: self.literals.capacity() * size_of::<i32>()
+                + self.offsets.capacity() * size_of::<usize>()
+                + xors,
+            map: self.map.capacity() * (size_of::<T>() + size_of::<usize>() + 1),
+            reverse: self.reverse.capacity() * size_of::<Option<T>>(),
+            told: told,
+        }
+    }
+
+    /// Removes the clauses subsumed by other clauses and strengthens the clauses
+    /// containing the negation of a unit fact (self-subsuming resolution).
+    /// Returns None without touching anything while a snapshot is live, because the
+    /// snapshot only remembers the length of the clause store.
+    pub fn simplify(&mut self) -> Option<SimplifyReport> {
+        if self.snapshot.is_some() {
+            return None;
+        }
+        let mut report = SimplifyReport {
+            clauses_before: self.clause_count(),
+            literals_before: self.literals.len(),
+            ..Default::default()
+        };
+        let mut clauses: Vec<Vec<i32>> = self
+            .clauses()
+            .map(|clause| {
+                let mut clause = clause.to_vec();
+                clause.sort_unstable();
+                clause.dedup();
+                clause
+            })
+            .collect();
+        let mut removed = vec![false; clauses.len()];
+
+        // rafforzamento con i fatti unitari, fino al punto fisso
+        let mut units: HashSet<i32> = clauses
+            .iter()
+            .filter(|clause| clause.len() == 1)
+            .map(|clause| clause[0])
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, clause) in clauses.iter_mut().enumerate() {
+                if removed[i] || clause.len() <= 1 {
+                    continue;
+                }
+                if clause.iter().any(|literal| units.contains(literal)) {
+                    // la clausola è sussunta da un fatto unitario
+                    removed[i] = true;
+                    report.removed += 1;
+                    continue;
+                }
+                let old_len = clause.len();
+                clause.retain(|literal| !units.contains(&-literal));
+                if clause.len() < old_len {
+                    report.strengthened += 1;
+                    if clause.len() == 1 {
+                        units.insert(clause[0]);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        // eliminazione delle clausole sussunte: C sussume D se C è contenuta in D
+        let mut order: Vec<usize> = (0..clauses.len()).filter(|&i| !removed[i]).collect();
+        order.sort_by_key(|&i| clauses[i].len());
+        let mut occurrences: HashMap<i32, Vec<usize>> = HashMap::new();
+        for &i in &order {
+            for &literal in &clauses[i] {
+                occurrences.entry(literal).or_default().push(i);
+            }
+        }
+        for &i in &order {
+            if removed[i] {
+                continue;
+            }
+            let Some(rarest) = clauses[i]
+                .iter()
+                .min_by_key(|literal| occurrences.get(literal).map_or(0, Vec::len))
+            else {
+                continue; // la clausola vuota rende la formula insoddisfacibile, la lasciamo
+            };
+            for &j in &occurrences[rarest] {
+                if j == i || removed[j] || clauses[j].len() < clauses[i].len() {
+                    continue;
+                }
+                // tra due clausole uguali viene eliminata solo la seconda
+                if clauses[j].len() == clauses[i].len() && j < i {
+                    continue;
+                }
+                if is_subset(&clauses[i], &clauses[j]) {
+                    removed[j] = true;
+                    report.removed += 1;
+                }
+            }
+        }
+
+        self.literals.clear();
+        self.offsets.clear();
+        for (i, clause) in clauses.iter().enumerate() {
+            if !removed[i] {
+                self.offsets.push(self.literals.len());
+                self.literals.extend(clause);
+            }
+        }
+        self.added_since_simplify = 0;
+        report.clauses_after = self.clause_count();
+        report.literals_after = self.literals.len();
+        Some(report)
+    }
+
+    /// Calls simplify once at least every clauses have been added since the last call.
+    pub fn simplify_if_due(&mut self, every: usize) -> Option<SimplifyReport> {
+        if self.added_since_simplify < every {
+            return None;
+        }
+        self.simplify()
+    }
+}
+
 impl<T: fmt::Debug> EncoderSAT<T> {
     pub fn create_raw_variable(&mut self) -> Literal<usize> {
         self.counter += 1;
+        self.reverse.push(None);
         self.counter.into()
     }
 
     pub fn add_raw_clause(&mut self, raw_clause: Clause) {
-        self.clauses.push(raw_clause);
+        self.push_clause(&raw_clause);
+    }
+
+    /// Emits the clauses for d <-> (l_1 or ... or l_k) and returns the definition literal d.
+    pub fn add_or(&mut self, literals: Clause) -> Literal<usize> {
+        let d = self.create_raw_variable();
+        for literal in &literals {
+            self.add_raw_clause(vec![d.clone(), literal.not()]);
+        }
+        let mut clause = literals;
+        clause.push(d.not());
+        self.add_raw_clause(clause);
+        d
+    }
+
+    /// Emits the clauses for d <-> (l_1 and ... and l_k) and returns the definition literal d.
+    pub fn add_and(&mut self, literals: Clause) -> Literal<usize> {
+        let negated = literals.iter().map(|literal| literal.not()).collect();
+        self.add_or(negated).not()
+    }
+
+    /// Emits the clauses for d <-> (a -> b) and returns the definition literal d.
+    pub fn add_implication(&mut self, a: Literal<usize>, b: Literal<usize>) -> Literal<usize> {
+        let d = self.create_raw_variable();
+        self.add_raw_clause(vec![d.not(), a.not(), b.clone()]);
+        self.add_raw_clause(vec![d.clone(), a]);
+        self.add_raw_clause(vec![d.clone(), b.not()]);
+        d
+    }
+
+    /// Emits the clauses for d <-> (a <-> b) and returns the definition literal d.
+    pub fn add_iff(&mut self, a: Literal<usize>, b: Literal<usize>) -> Literal<usize> {
+        let d = self.create_raw_variable();
+        self.add_raw_clause(vec![d.not(), a.not(), b.clone()]);
+        self.add_raw_clause(vec![d.not(), a.clone(), b.not()]);
+        self.add_raw_clause(vec![d.clone(), a.clone(), b.clone()]);
+        self.add_raw_clause(vec![d.clone(), a.not(), b.not()]);
+        d
+    }
+
+    /// Emits the clauses for d <-> (a xor b) and returns the definition literal d.
+    pub fn add_xor(&mut self, a: Literal<usize>, b: Literal<usize>) -> Literal<usize> {
+        let d = self.create_raw_variable();
+        self.add_raw_clause(vec![d.not(), a.clone(), b.clone()]);
+        self.add_raw_clause(vec![d.not(), a.not(), b.not()]);
+        self.add_raw_clause(vec![d.clone(), a.not(), b.clone()]);
+        self.add_raw_clause(vec![d.clone(), a, b.not()]);
+        d
     }
 
     pub fn snapshot(&mut self) {
@@ -165,7 +666,8 @@ impl<T: Default> EncoderSAT<T> {
 impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> EncoderSAT<T> {
     pub fn add(&mut self, clause: Vec<Literal<T>>) {
         let clause = self.register_clause(clause);
-        self.clauses.push(clause);
+        self.push_clause(&clause);
+        self.added_since_simplify += 1;
     }
 
     pub fn register_literal(&mut self, literal: Literal<T>) -> Literal<usize> {
@@ -176,6 +678,7 @@ impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> EncoderSAT<T> {
             .map(|t| *self.map.entry(t).or_insert(next_id));
         if self.map.len() > old_size {
             self.counter += 1;
+            self.reverse.push(Some(literal.clone().inner()));
             if let Some(snapshot) = self.snapshot.as_mut() {
                 snapshot.new_vars.push(literal.inner());
             }
@@ -183,6 +686,10 @@ impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> EncoderSAT<T> {
         result
     }
 
+    pub fn index_of(&self, t: &T) -> Option<usize> {
+        self.map.get(t).copied()
+    }
+
     pub fn register_clause(&mut self, clause: Vec<Literal<T>>) -> Clause {
         clause
             .into_iter()
@@ -190,17 +697,192 @@ impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> EncoderSAT<T> {
             .collect()
     }
 
+    /// Adds the constraint l_1 xor ... xor l_k = rhs. With a backend supporting XOR
+    /// constraints it is kept native, otherwise it is expanded with a chain of Tseytin
+    /// xor definitions (linear in k).
+    pub fn add_xor_clause(&mut self, lits: Vec<Literal<T>>, rhs: bool) {
+        self.added_since_simplify += 1;
+        let lits = self.register_clause(lits);
+        if self.supports_xor() {
+            self.xors
+                .push((lits.iter().map(Literal::to_dimacs).collect(), rhs));
+            return;
+        }
+        let mut lits = lits.into_iter();
+        let Some(first) = lits.next() else {
+            // lo xor vuoto vale false
+            if rhs {
+                self.add_raw_clause(vec![]);
+            }
+            return;
+        };
+        let parity = lits.fold(first, |acc, literal| self.add_xor(acc, literal));
+        self.add_raw_clause(vec![if rhs { parity } else { parity.not() }]);
+    }
+
+    /// Solves the clauses of the encoder together with the assumption literals.
+    /// The assumptions go to Solver::solve_with_assumptions and are never written to the
+    /// clause store, so they can't leak into the next solve.
+    pub fn solve_under_assumptions(
+        &self,
+        assumptions: &[Literal<T>],
+    ) -> Result<SatResult, SolverError> {
+        // le variabili mai registrate non compaiono in nessuna clausola, ricevono un indice temporaneo
+        let mut fresh: HashMap<&T, usize> = HashMap::new();
+        let mut dimacs = Vec::with_capacity(assumptions.len());
+        for literal in assumptions {
+            let t = match literal {
+                Literal::Pos(t) => t,
+                Literal::Neg(t) => t,
+            };
+            let index = match self.map.get(t) {
+                Some(index) => *index,
+                None => {
+                    let next = self.counter + fresh.len() + 1;
+                    *fresh.entry(t).or_insert(next)
+                }
+            };
+            dimacs.push(match literal {
+                Literal::Pos(_) => index as i32,
+                Literal::Neg(_) => -(index as i32),
+            });
+        }
+        // le variabili assunte non possono essere eliminate: il loro valore è fissato dal solver
+        let frozen: Vec<usize> = dimacs
+            .iter()
+            .map(|lit| lit.unsigned_abs() as usize)
+            .collect();
+        let (encoding, _) = self.encode_for_solver(fresh.len(), &frozen, true);
+        self.run_sat_assuming(&encoding, &dimacs)
+    }
+
+    /// For every candidate literal tells if it is entailed, i.e. true in every model.
+    /// Each solve looks for a model falsifying one of the candidates still open and discards
+    /// all the candidates it falsifies, so the solver runs far fewer times than with one
+    /// entailment check per literal: the last solve is UNSAT and the open ones are entailed.
+    pub fn entailed_literals(&mut self, candidates: &[Literal<T>]) -> Result<Vec<bool>, SolverError>
+    where
+        T: Copy,
+    {
+        let mut open: Vec<usize> = (0..candidates.len()).collect();
+        while !open.is_empty() {
+            self.snapshot();
+            // almeno uno dei candidati aperti è falso
+            self.add(open.iter().map(|i| candidates[*i].not()).collect());
+            let model = self.model();
+            // va calcolato prima del rewind, che rimuove le variabili registrate dal letterale nuovo
+            let falsified: HashSet<usize> = match &model {
+                Ok(Some(model)) => open
+                    .iter()
+                    .filter(|i| self.literal_value(&candidates[**i], model) == Some(false))
+                    .copied()
+                    .collect(),
+                _ => HashSet::new(),
+            };
+            self.rewind();
+            match model? {
+                None => break,
+                // un modello incompleto non falsifica nessuno: per sicurezza nessun candidato è conseguenza
+                Some(_) if falsified.is_empty() => open.clear(),
+                Some(_) => open.retain(|i| !falsified.contains(i)),
+            }
+        }
+        let mut result = vec![false; candidates.len()];
+        for i in open {
+            result[i] = true;
+        }
+        Ok(result)
+    }
+
+    // valore del letterale nel modello, None se la variabile non compare
+    fn literal_value(&self, literal: &Literal<T>, model: &[Option<bool>]) -> Option<bool> {
+        let (t, positive) = match literal {
+            Literal::Pos(t) => (t, true),
+            Literal::Neg(t) => (t, false),
+        };
+        let value = model.get(self.index_of(t)?).copied().flatten()?;
+        Some(value == positive)
+    }
+
+    /// Iterates over all the models of the encoder, restricted to the registered variables.
+    /// After each model a blocking clause is added, everything is rewound when the iterator is dropped.
+    pub fn models(&mut self) -> Models<'_, T> {
+        Models::new(self, None)
+    }
+
+    /// Like models, but yields at most limit models.
+    pub fn models_up_to(&mut self, limit: usize) -> Models<'_, T> {
+        Models::new(self, Some(limit))
+    }
+
+    /// Estimates, for each target variable, the fraction of models in which it is true.
+    /// Every sample assigns the targets one at a time in a random order with a random
+    /// polarity, flipping the polarity when it would make the formula UNSAT.
+    /// The result is deterministic for a given seed. Accuracy caveat: the samples are
+    /// uniform over the decision paths, not over the models, so variables whose two
+    /// polarities have very different model counts are pulled towards 0.5; use the value
+    /// as a risk ranking rather than as an exact probability. Returns None if UNSAT.
+    pub fn approx_count(
+        &self,
+        target_vars: &[T],
+        samples: usize,
+        seed: u64,
+    ) -> Result<Option<Vec<f64>>, SolverError> {
+        if self.solve_under_assumptions(&[])? == SatResult::Unsat {
+            return Ok(None);
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut true_count = vec![0usize; target_vars.len()];
+        let mut order: Vec<usize> = (0..target_vars.len()).collect();
+        for _ in 0..samples {
+            order.shuffle(&mut rng);
+            let mut assumptions = Vec::with_capacity(target_vars.len());
+            for &i in &order {
+                let polarity = rng.random_bool(0.5);
+                let literal = if polarity {
+                    Literal::Pos(target_vars[i].clone())
+                } else {
+                    Literal::Neg(target_vars[i].clone())
+                };
+                assumptions.push(literal);
+                let value = if self.solve_under_assumptions(&assumptions)? == SatResult::Sat {
+                    polarity
+                } else {
+                    // le assunzioni precedenti sono soddisfacibili, quindi lo è la polarità opposta
+                    let last = assumptions.len() - 1;
+                    assumptions[last] = if polarity {
+                        Literal::Neg(target_vars[i].clone())
+                    } else {
+                        Literal::Pos(target_vars[i].clone())
+                    };
+                    !polarity
+                };
+                if value {
+                    true_count[i] += 1;
+                }
+            }
+        }
+        Ok(Some(
+            true_count
+                .into_iter()
+                .map(|count| count as f64 / samples.max(1) as f64)
+                .collect(),
+        ))
+    }
+
     pub fn rewind(&mut self) {
         let snapshot = self
             .snapshot
             .as_ref()
             .expect("rewinding the Endored without a snapshot");
-        // println!("Rewind: {:?}, new len: {}", snapshot, self.clauses.len());
+        // println!("Rewind: {:?}, new len: {}", snapshot, self.offsets.len());
         self.counter = snapshot.last_var_counter;
-        while snapshot.last_len_clauses < self.clauses.len() {
-            // TODO: controllare se esiste un modo O(1) per fare la stessa cosa
-            self.clauses.pop();
-        }
+        self.reverse.truncate(snapshot.last_var_counter);
+        self.offsets.truncate(snapshot.last_len_clauses);
+        self.literals.truncate(snapshot.last_len_literals);
+        self.xors.truncate(snapshot.last_len_xors);
+        // le clausole di una domanda non devono anticipare la prossima semplificazione
+        self.added_since_simplify = snapshot.last_added_since_simplify;
         for var in &snapshot.new_vars {
             self.map.remove(var);
         }
@@ -208,58 +890,193 @@ impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> EncoderSAT<T> {
     }
 }
 
+/// Checks that the header of a DIMACS text matches its body: as many clause and XOR lines
+/// as declared and no literal above the declared variables.
+pub fn check_dimacs(text: &str) -> Result<(), String> {
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or("the encoding is empty")?
+        .split_whitespace()
+        .collect();
+    let (variables, clauses) = match header[..] {
+        ["p", "cnf", variables, clauses] => (
+            variables.parse::<i64>().map_err(|e| e.to_string())?,
+            clauses.parse::<usize>().map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(format!("bad header {:?}", header.join(" "))),
+    };
+    let mut body = 0;
+    for (n, line) in lines.enumerate() {
+        // le righe XOR hanno la x davanti al primo letterale
+        for literal in line.trim_start_matches('x').split_whitespace() {
+            let literal: i64 = literal
+                .parse()
+                .map_err(|e| format!("line {}: {}", n + 2, e))?;
+            if literal.abs() > variables {
+                return Err(format!(
+                    "line {}: literal {} above the {} variables of the header",
+                    n + 2,
+                    literal,
+                    variables
+                ));
+            }
+        }
+        body += 1;
+    }
+    if body != clauses {
+        return Err(format!(
+            "the header declares {} clauses, the body has {}",
+            clauses, body
+        ));
+    }
+    Ok(())
+}
+
 impl<T: Clone> EncoderSAT<T> {
     pub fn encode(&self) -> (String, Vec<T>) {
-        let variables_number = self.counter;
+        self.encode_for_solver(0, &[], false)
+    }
 
-        let mut variables = vec![None; variables_number];
-        for (k, v) in &self.map {
-            variables[v - 1] = Some(k.clone());
+    /// Clauses and literals of the CNF sent to the solver by sat(), before and after
+    /// the variable elimination (equal when it is disabled).
+    pub fn elimination_report(&self) -> EliminationReport {
+        let (clauses, _) = self.solver_clauses(0, &[], false);
+        let (eliminated, _) = self.solver_clauses(0, &[], true);
+        EliminationReport {
+            clauses_before: clauses.len(),
+            clauses_after: eliminated.len(),
+            literals_before: clauses.iter().map(Vec::len).sum(),
+            literals_after: eliminated.iter().map(Vec::len).sum(),
         }
+    }
+
+    // tutte le clausole CNF da mandare al solver (più il numero di righe XOR native),
+    // se eliminate è true viene applicata l'eliminazione di variabili su una copia,
+    // lasciando le variabili frozen
+    fn solver_clauses(
+        &self,
+        extra_vars: usize,
+        frozen: &[usize],
+        eliminate: bool,
+    ) -> (Vec<Vec<i32>>, bool) {
+        // se il backend non supporta gli XOR (es. è stato cambiato dopo averli aggiunti)
+        // ogni vincolo viene espanso nelle clausole che escludono gli assegnamenti di parità sbagliata
+        let native = self.supports_xor();
+        let expanded: Vec<Vec<i32>> = if native {
+            vec![]
+        } else {
+            self.xors
+                .iter()
+                .flat_map(|(lits, rhs)| xor_to_cnf(lits, *rhs))
+                .collect()
+        };
 
-        let variables = variables.into_iter().filter_map(|x| x).collect();
+        let clauses: Vec<Vec<i32>> = self
+            .clauses()
+            .map(<[i32]>::to_vec)
+            .chain(expanded)
+            .collect();
+
+        match self.elimination {
+            Some(growth) if eliminate => {
+                // le variabili dei vincoli XOR nativi non compaiono nelle clausole, non possono essere eliminate
+                let mut frozen: HashSet<usize> = frozen.iter().copied().collect();
+                if native {
+                    frozen.extend(
+                        self.xors
+                            .iter()
+                            .flat_map(|(lits, _)| lits.iter().map(|l| l.unsigned_abs() as usize)),
+                    );
+                }
+                (
+                    eliminate_variables(clauses, self.counter + extra_vars, &frozen, growth),
+                    native,
+                )
+            }
+            _ => (clauses, native),
+        }
+    }
+
+    // il testo DIMACS per il solver, con extra_vars variabili in più dichiarate nell'intestazione
+    fn encode_for_solver(
+        &self,
+        extra_vars: usize,
+        frozen: &[usize],
+        eliminate: bool,
+    ) -> (String, Vec<T>) {
+        let variables_number = self.counter + extra_vars;
+
+        let variables = self.reverse.iter().flatten().cloned().collect();
 
         let mut encoding = String::new();
 
+        let (clauses, native) = self.solver_clauses(extra_vars, frozen, eliminate);
+        let xor_lines = if native { self.xors.len() } else { 0 };
+
         encoding.push_str(&format!(
             "p cnf {variables_number} {}\n",
-            self.clauses.len()
+            clauses.len() + xor_lines
         ));
 
-        for clause in &self.clauses {
-            let mut clause: String = clause
-                .into_iter()
-                .map(|literal| match literal {
-                    Literal::Pos(l) => format!("{l} "),
-                    Literal::Neg(l) => format!("-{l} "),
-                })
-                .collect();
+        for clause in &clauses {
+            let mut clause: String = clause.iter().map(|literal| format!("{literal} ")).collect();
             clause.push('0');
             encoding.push_str(&format!("{clause}\n"));
         }
 
+        if native {
+            for (lits, rhs) in &self.xors {
+                // formato esteso: "x1 -2 3 0" significa 1 xor -2 xor 3 = true,
+                // per rhs = false basta negare il primo letterale
+                let mut line = String::from("x");
+                for (i, literal) in lits.iter().enumerate() {
+                    let literal = if i == 0 && !rhs { -literal } else { *literal };
+                    line.push_str(&format!("{literal} "));
+                }
+                line.push('0');
+                encoding.push_str(&format!("{line}\n"));
+            }
+        }
+
         (encoding, variables)
     }
 
-    pub fn picosat_sat(&self) -> bool {
-        let (encoding, _) = self.encode();
-        let output = Command::new("picosat")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .and_then(|mut child| {
-                {
-                    let stdin = child.stdin.as_mut().expect("Failed to open stdin");
-                    stdin.write_all(encoding.as_bytes())?;
-                }
-                let output = child.wait_with_output()?;
-                Ok(output)
+    pub fn sat(&self) -> Result<bool, SolverError> {
+        let (encoding, _) = self.encode_for_solver(0, &[], true);
+        Ok(self.run_sat(&encoding)? == SatResult::Sat)
+    }
+
+    /// Pairs every registered variable with its value in the model (as returned by parse_picosat_model).
+    pub fn decode_model(&self, model: &[Option<bool>]) -> Vec<(T, Option<bool>)> {
+        (1..=self.counter)
+            .filter_map(|index| {
+                self.var_for(index)
+                    .map(|t| (t.clone(), model.get(index).cloned().unwrap_or(None)))
             })
-            .expect("Failed to run picosat");
+            .collect()
+    }
 
-        let picosat_stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    /// DIMACS encoding preceded by a comment line "c <index> <variable>" for every registered variable.
+    pub fn encode_commented(&self) -> String
+    where
+        T: fmt::Debug,
+    {
+        let mut result = String::new();
+        for index in 1..=self.counter {
+            if let Some(t) = self.var_for(index) {
+                result.push_str(&format!("c {index} {:?}\n", t));
+            }
+        }
+        result.push_str(&self.encode().0);
+        result
+    }
 
-        picosat_is_sat(picosat_stdout)
+    /// Returns the model found by the solver (index i is the value of variable i), None if UNSAT.
+    pub fn model(&self) -> Result<Option<Vec<Option<bool>>>, SolverError> {
+        let (encoding, _) = self.encode();
+        let model = parse_picosat_model(self.run_solver(&encoding)?, self.counter)?;
+        Ok(if model.is_empty() { None } else { Some(model) })
     }
 
     pub fn clause(self) -> ClauseBuilder<T> {
@@ -270,6 +1087,73 @@ impl<T: Clone> EncoderSAT<T> {
     }
 }
 
+pub struct Models<'a, T: Eq + std::hash::Hash + Clone + fmt::Debug> {
+    encoder: &'a mut EncoderSAT<T>,
+    named: Vec<(usize, T)>,
+    limit: Option<usize>,
+    yielded: usize,
+    exhausted: bool,
+}
+
+impl<'a, T: Eq + std::hash::Hash + Clone + fmt::Debug> Models<'a, T> {
+    fn new(encoder: &'a mut EncoderSAT<T>, limit: Option<usize>) -> Self {
+        encoder.snapshot();
+        let named: Vec<(usize, T)> = (1..=encoder.counter)
+            .filter_map(|index| encoder.var_for(index).map(|t| (index, t.clone())))
+            .collect();
+        Self {
+            encoder: encoder,
+            named: named,
+            limit: limit,
+            yielded: 0,
+            exhausted: false,
+        }
+    }
+}
+
+impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> Iterator for Models<'_, T> {
+    type Item = Result<Vec<(T, bool)>, SolverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted || self.limit.is_some_and(|limit| self.yielded >= limit) {
+            return None;
+        }
+        let model = match self.encoder.model() {
+            Ok(Some(model)) => model,
+            Ok(None) => {
+                self.exhausted = true;
+                return None;
+            }
+            Err(e) => {
+                // dopo un errore non ha senso continuare l'enumerazione
+                self.exhausted = true;
+                return Some(Err(e));
+            }
+        };
+        let mut decoded = Vec::with_capacity(self.named.len());
+        let mut blocking_clause = Vec::with_capacity(self.named.len());
+        for (index, t) in &self.named {
+            let value = model.get(*index).cloned().flatten().unwrap_or(false);
+            decoded.push((t.clone(), value));
+            // la clausola di blocco è la negazione del modello appena trovato
+            blocking_clause.push(if value {
+                Literal::Neg(*index)
+            } else {
+                Literal::Pos(*index)
+            });
+        }
+        self.encoder.add_raw_clause(blocking_clause);
+        self.yielded += 1;
+        Some(Ok(decoded))
+    }
+}
+
+impl<T: Eq + std::hash::Hash + Clone + fmt::Debug> Drop for Models<'_, T> {
+    fn drop(&mut self) {
+        self.encoder.rewind();
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Literal<T> {
     Pos(T),
@@ -295,6 +1179,23 @@ impl<T> Literal<T> {
     }
 }
 
+impl Literal<usize> {
+    fn to_dimacs(&self) -> i32 {
+        match self {
+            Literal::Pos(l) => *l as i32,
+            Literal::Neg(l) => -(*l as i32),
+        }
+    }
+
+    fn from_dimacs(literal: i32) -> Self {
+        if literal > 0 {
+            Literal::Pos(literal as usize)
+        } else {
+            Literal::Neg(literal.unsigned_abs() as usize)
+        }
+    }
+}
+
 impl<T: Copy> Literal<T> {
     pub fn not(&self) -> Self {
         match self {
@@ -325,7 +1226,789 @@ where
     }
 
     pub fn end(mut self) -> EncoderSAT<T> {
-        self.encoder.clauses.push(self.clause);
+        self.encoder.push_clause(&self.clause);
         self.encoder
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        kb::{Formula, KnowledgeBase, Var, init_kb},
+        solver::test_backends,
+        world::Position,
+    };
+
+    // risolve per forza bruta il DIMACS esteso, righe x comprese: prova la codifica nativa
+    // degli XOR anche senza cryptominisat5
+    struct BruteForceXor;
+
+    impl Solver for BruteForceXor {
+        fn name(&self) -> &str {
+            "brute-force"
+        }
+
+        fn supports_xor(&self) -> bool {
+            true
+        }
+
+        fn solve(
+            &self,
+            dimacs: &str,
+            _timeout: Option<Duration>,
+            _stop: &std::sync::atomic::AtomicBool,
+        ) -> Result<String, SolverError> {
+            let mut vars = 0;
+            let mut constraints = vec![];
+            for line in dimacs.lines() {
+                if let Some(header) = line.strip_prefix("p cnf ") {
+                    vars = header.split_whitespace().next().unwrap().parse().unwrap();
+                    continue;
+                }
+                let (xor, body) = match line.strip_prefix('x') {
+                    Some(body) => (true, body),
+                    None => (false, line),
+                };
+                let lits: Vec<i32> = body
+                    .split_whitespace()
+                    .map(|lit| lit.parse().unwrap())
+                    .take_while(|lit| *lit != 0)
+                    .collect();
+                constraints.push((xor, lits));
+            }
+            for assignment in 0u32..1 << vars {
+                let value =
+                    |lit: &i32| (assignment >> (lit.unsigned_abs() - 1) & 1 == 1) == (*lit > 0);
+                let satisfied = constraints.iter().all(|(xor, lits)| {
+                    if *xor {
+                        lits.iter().filter(|lit| value(lit)).count() % 2 == 1
+                    } else {
+                        lits.iter().any(value)
+                    }
+                });
+                if satisfied {
+                    return Ok("s SATISFIABLE\n".to_string());
+                }
+            }
+            Ok("s UNSATISFIABLE\n".to_string())
+        }
+    }
+
+    // tutti i vincoli xor su al più 5 variabili, con ogni combinazione di segni e parità
+    fn xor_constraints() -> Vec<(Vec<Literal<u8>>, bool)> {
+        let mut constraints = vec![];
+        for k in 1..=5u8 {
+            for signs in 0u32..1 << k {
+                for rhs in [false, true] {
+                    let lits = (0..k)
+                        .map(|i| {
+                            if signs >> i & 1 == 1 {
+                                Literal::Neg(i)
+                            } else {
+                                Literal::Pos(i)
+                            }
+                        })
+                        .collect();
+                    constraints.push((lits, rhs));
+                }
+            }
+        }
+        constraints
+    }
+
+    // per ogni assegnamento delle variabili del vincolo: è soddisfacibile?
+    fn truth_table(encoder: &EncoderSAT<u8>, vars: u8) -> Vec<bool> {
+        (0u32..1 << vars)
+            .map(|assignment| {
+                let assumptions: Vec<Literal<u8>> = (0..vars)
+                    .map(|i| {
+                        if assignment >> i & 1 == 1 {
+                            Literal::Pos(i)
+                        } else {
+                            Literal::Neg(i)
+                        }
+                    })
+                    .collect();
+                encoder.solve_under_assumptions(&assumptions).unwrap() == SatResult::Sat
+            })
+            .collect()
+    }
+
+    fn parity_table(lits: &[Literal<u8>], rhs: bool) -> Vec<bool> {
+        (0u32..1 << lits.len())
+            .map(|assignment| {
+                let parity = lits.iter().fold(false, |parity, literal| match literal {
+                    Literal::Pos(i) => parity ^ (assignment >> i & 1 == 1),
+                    Literal::Neg(i) => parity ^ (assignment >> i & 1 == 0),
+                });
+                parity == rhs
+            })
+            .collect()
+    }
+
+    #[test]
+    fn native_and_expanded_xor_clauses_are_equivalent() {
+        for (lits, rhs) in xor_constraints() {
+            let vars = lits.len() as u8;
+            let expected = parity_table(&lits, rhs);
+
+            let mut native = EncoderSAT::new();
+            native.set_solver(Arc::new(BruteForceXor));
+            native.add_xor_clause(lits.clone(), rhs);
+            assert_eq!(native.xors.len(), 1, "the constraint stays native");
+            assert_eq!(
+                truth_table(&native, vars),
+                expected,
+                "native {:?} = {}",
+                lits,
+                rhs
+            );
+
+            for backend in test_backends() {
+                let mut expanded = EncoderSAT::new();
+                expanded.set_solver(backend.clone());
+                expanded.add_xor_clause(lits.clone(), rhs);
+                assert!(expanded.xors.is_empty());
+                assert_eq!(
+                    truth_table(&expanded, vars),
+                    expected,
+                    "{} {:?} = {}",
+                    backend.name(),
+                    lits,
+                    rhs
+                );
+
+                // il backend cambiato dopo aver aggiunto il vincolo nativo lo riceve espanso
+                let mut switched = native.clone();
+                switched.set_solver(backend);
+                assert_eq!(truth_table(&switched, vars), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn native_xor_lines_are_written_only_for_xor_backends() {
+        let mut encoder = EncoderSAT::new();
+        encoder.set_solver(Arc::new(BruteForceXor));
+        encoder.add_xor_clause(vec![Literal::Pos(0u8), Literal::Neg(1)], false);
+        let (encoding, _) = encoder.encode();
+        assert_eq!(encoding, "p cnf 2 1\nx-1 -2 0\n");
+        check_dimacs(&encoding).unwrap();
+
+        encoder.use_in_process_solver();
+        let (encoding, _) = encoder.encode();
+        assert!(!encoding.contains('x'));
+        check_dimacs(&encoding).unwrap();
+    }
+
+    #[cfg(feature = "cryptominisat")]
+    #[test]
+    fn cryptominisat_agrees_with_the_expansion() {
+        if !crate::solver::installed("cryptominisat5") {
+            return;
+        }
+        for (lits, rhs) in xor_constraints() {
+            let mut native = EncoderSAT::new();
+            native.set_solver(crate::solver::Backend::CryptoMiniSat.solver());
+            native.add_xor_clause(lits.clone(), rhs);
+            assert_eq!(
+                truth_table(&native, lits.len() as u8),
+                parity_table(&lits, rhs)
+            );
+        }
+    }
+
+    // tutto lo stato che rewind deve ripristinare, insieme alla codifica DIMACS
+    type State<T> = (
+        usize,
+        Vec<(T, usize)>,
+        Vec<i32>,
+        Vec<usize>,
+        Vec<Option<T>>,
+        Vec<(Vec<i32>, bool)>,
+        usize,
+        String,
+    );
+
+    fn state<T: Clone + Ord + fmt::Debug>(encoder: &EncoderSAT<T>) -> State<T> {
+        let mut map: Vec<(T, usize)> = encoder.map.iter().map(|(t, i)| (t.clone(), *i)).collect();
+        map.sort();
+        (
+            encoder.counter,
+            map,
+            encoder.literals.clone(),
+            encoder.offsets.clone(),
+            encoder.reverse.clone(),
+            encoder.xors.clone(),
+            encoder.added_since_simplify,
+            encoder.encode().0,
+        )
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Register(u8, bool),
+        Add(Vec<(u8, bool)>),
+        RawVariable,
+        // indici delle variabili esistenti, presi modulo counter
+        RawClause(Vec<(usize, bool)>),
+    }
+
+    fn literal<T>(t: T, negated: bool) -> Literal<T> {
+        if negated {
+            Literal::Neg(t)
+        } else {
+            Literal::Pos(t)
+        }
+    }
+
+    fn apply(encoder: &mut EncoderSAT<u8>, op: &Op) {
+        match op {
+            Op::Register(var, negated) => {
+                encoder.register_literal(literal(*var, *negated));
+            }
+            Op::Add(clause) => {
+                encoder.add(clause.iter().map(|(v, n)| literal(*v, *n)).collect());
+            }
+            Op::RawVariable => {
+                encoder.create_raw_variable();
+            }
+            Op::RawClause(clause) => {
+                if encoder.counter == 0 {
+                    return;
+                }
+                let counter = encoder.counter;
+                encoder.add_raw_clause(
+                    clause
+                        .iter()
+                        .map(|(i, n)| literal(i % counter + 1, *n))
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        let var = 0u8..12;
+        prop_oneof![
+            (var.clone(), any::<bool>()).prop_map(|(v, n)| Op::Register(v, n)),
+            prop::collection::vec((var, any::<bool>()), 0..4).prop_map(Op::Add),
+            Just(Op::RawVariable),
+            prop::collection::vec((any::<usize>(), any::<bool>()), 0..4).prop_map(Op::RawClause),
+        ]
+    }
+
+    // una formula piccola sulle variabili di una KB 2x2
+    fn formula() -> impl Strategy<Value = Formula> {
+        let var = (0usize..4, 0usize..3).prop_map(|(cell, kind)| {
+            let pos = Position::new(cell % 2, cell / 2);
+            match kind {
+                0 => Var::Safe { pos: pos },
+                1 => Var::Pit { pos: pos },
+                _ => Var::Wumpus { pos: pos },
+            }
+        });
+        let lit = (var, any::<bool>()).prop_map(|(v, n)| literal(v, n));
+        prop::collection::vec(prop::collection::vec(lit, 1..3), 1..4)
+    }
+
+    // una CNF casuale su 8 variabili, con clausole vuote e tautologiche
+    fn cnf() -> impl Strategy<Value = Vec<Vec<Literal<u8>>>> {
+        let lit = (0u8..8, any::<bool>()).prop_map(|(v, n)| literal(v, n));
+        prop::collection::vec(prop::collection::vec(lit, 0..4), 0..24)
+    }
+
+    proptest! {
+        #[test]
+        fn rewind_restores_the_state_before_the_snapshot(
+            rounds in prop::collection::vec(
+                (prop::collection::vec(op(), 0..8), prop::collection::vec(op(), 0..8)),
+                1..4,
+            )
+        ) {
+            let mut encoder = EncoderSAT::<u8>::new();
+            for (before, during) in &rounds {
+                for op in before {
+                    apply(&mut encoder, op);
+                }
+                let expected = state(&encoder);
+                encoder.snapshot();
+                for op in during {
+                    apply(&mut encoder, op);
+                }
+                encoder.rewind();
+                prop_assert_eq!(state(&encoder), expected);
+            }
+        }
+
+        #[test]
+        fn elimination_keeps_the_answers(
+            clauses in cnf(),
+            assumptions in prop::collection::vec((0u8..10, any::<bool>()), 0..3),
+            growth in 0usize..3,
+        ) {
+            let mut encoder = EncoderSAT::<u8>::new();
+            encoder.use_in_process_solver();
+            for clause in clauses {
+                encoder.add(clause);
+            }
+            // le variabili 8 e 9 non sono in nessuna clausola
+            let assumptions: Vec<Literal<u8>> =
+                assumptions.into_iter().map(|(v, n)| literal(v, n)).collect();
+            let answers = |encoder: &EncoderSAT<u8>| {
+                (
+                    encoder.sat().unwrap(),
+                    encoder.solve_under_assumptions(&assumptions).unwrap(),
+                )
+            };
+            let expected = answers(&encoder);
+            encoder.set_elimination(Some(growth));
+            prop_assert_eq!(answers(&encoder), expected);
+            if growth == 0 {
+                let report = encoder.elimination_report();
+                prop_assert!(report.clauses_after <= report.clauses_before);
+            }
+        }
+
+        #[test]
+        fn ask_leaves_the_encoder_unchanged(formulas in prop::collection::vec(formula(), 1..5)) {
+            let mut kb = init_kb(2);
+            kb.use_in_process_solver();
+            kb.tell(&EncoderSAT::<Var>::create_ground_truth_from_perception(
+                &crate::fixtures::start_breezy(2),
+            ));
+            let before = kb.encode().0;
+            let counter = kb.counter;
+            for formula in &formulas {
+                kb.ask(formula).unwrap();
+                prop_assert_eq!(kb.counter, counter);
+                prop_assert_eq!(&kb.encode().0, &before);
+                prop_assert!(kb.snapshot.is_none());
+            }
+        }
+    }
+
+    fn holds(literal: &Literal<usize>, assignment: u32) -> bool {
+        match literal {
+            Literal::Pos(i) => assignment >> (i - 1) & 1 == 1,
+            Literal::Neg(i) => assignment >> (i - 1) & 1 == 0,
+        }
+    }
+
+    // gli assegnamenti di tutte le variabili (bit i = variabile i + 1) che soddisfano le clausole
+    fn brute_force_models<T>(encoder: &EncoderSAT<T>) -> Vec<u32> {
+        (0u32..1 << encoder.counter)
+            .filter(|assignment| {
+                encoder.clauses().all(|clause| {
+                    clause
+                        .iter()
+                        .any(|lit| holds(&Literal::from_dimacs(*lit), *assignment))
+                })
+            })
+            .collect()
+    }
+
+    type Definition = fn(&mut EncoderSAT<u8>, Vec<Literal<usize>>) -> Literal<usize>;
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn tseytin_definitions_are_equivalent_to_their_operator() {
+        let definitions: [(&str, usize, Definition, fn(&[bool]) -> bool); 5] = [
+            ("or", 3, |e, l| e.add_or(l), |v| v.iter().any(|x| *x)),
+            ("and", 3, |e, l| e.add_and(l), |v| v.iter().all(|x| *x)),
+            (
+                "implication",
+                2,
+                |e, l| e.add_implication(l[0].clone(), l[1].clone()),
+                |v| !v[0] || v[1],
+            ),
+            (
+                "iff",
+                2,
+                |e, l| e.add_iff(l[0].clone(), l[1].clone()),
+                |v| v[0] == v[1],
+            ),
+            (
+                "xor",
+                2,
+                |e, l| e.add_xor(l[0].clone(), l[1].clone()),
+                |v| v[0] != v[1],
+            ),
+        ];
+        for (name, arity, define, operator) in definitions {
+            // ogni combinazione di segni degli ingressi
+            for signs in 0u32..1 << arity {
+                let mut encoder = EncoderSAT::<u8>::new();
+                let inputs: Vec<Literal<usize>> = (0..arity)
+                    .map(|i| {
+                        let var = encoder.create_raw_variable();
+                        if signs >> i & 1 == 1 { var.not() } else { var }
+                    })
+                    .collect();
+                let d = define(&mut encoder, inputs.clone());
+                let models = brute_force_models(&encoder);
+                // ogni assegnamento degli ingressi si estende in un solo modello, dove d vale
+                // quanto l'operatore
+                for assignment in 0u32..1 << arity {
+                    let extensions: Vec<u32> = models
+                        .iter()
+                        .copied()
+                        .filter(|model| model & ((1 << arity) - 1) == assignment)
+                        .collect();
+                    assert_eq!(extensions.len(), 1, "{} {:b}", name, assignment);
+                    let values: Vec<bool> = inputs.iter().map(|l| holds(l, assignment)).collect();
+                    assert_eq!(
+                        holds(&d, extensions[0]),
+                        operator(&values),
+                        "{} {:?}",
+                        name,
+                        values
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn assumptions_do_not_leak_into_the_next_solve() {
+        for backend in test_backends() {
+            let mut encoder = EncoderSAT::<u8>::new();
+            encoder.set_solver(backend.clone());
+            encoder.add(vec![Literal::Pos(0), Literal::Pos(1)]);
+            let before = state(&encoder);
+            let solve =
+                |assumptions: &[Literal<u8>]| encoder.solve_under_assumptions(assumptions).unwrap();
+            assert_eq!(
+                solve(&[Literal::Neg(0), Literal::Neg(1)]),
+                SatResult::Unsat,
+                "{}",
+                backend.name()
+            );
+            assert_eq!(solve(&[]), SatResult::Sat, "{}", backend.name());
+            assert_eq!(
+                solve(&[Literal::Neg(0)]),
+                SatResult::Sat,
+                "{}",
+                backend.name()
+            );
+            assert_eq!(
+                solve(&[Literal::Neg(1)]),
+                SatResult::Sat,
+                "{}",
+                backend.name()
+            );
+            // una variabile mai registrata riceve un indice solo per quella chiamata
+            assert_eq!(
+                solve(&[Literal::Pos(7), Literal::Neg(7)]),
+                SatResult::Unsat,
+                "{}",
+                backend.name()
+            );
+            assert_eq!(
+                solve(&[Literal::Pos(7)]),
+                SatResult::Sat,
+                "{}",
+                backend.name()
+            );
+            assert_eq!(state(&encoder), before, "{}", backend.name());
+        }
+    }
+
+    // ricorda il DIMACS e le assunzioni di ogni chiamata, poi risolve con il DPLL
+    #[derive(Default)]
+    struct Recording(std::sync::Mutex<Vec<(String, Vec<i32>)>>);
+
+    impl Solver for Recording {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn solve(
+            &self,
+            dimacs: &str,
+            timeout: Option<Duration>,
+            stop: &std::sync::atomic::AtomicBool,
+        ) -> Result<String, SolverError> {
+            self.solve_with_assumptions(dimacs, &[], timeout, stop)
+        }
+
+        fn solve_with_assumptions(
+            &self,
+            dimacs: &str,
+            assumptions: &[i32],
+            timeout: Option<Duration>,
+            stop: &std::sync::atomic::AtomicBool,
+        ) -> Result<String, SolverError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((dimacs.to_string(), assumptions.to_vec()));
+            Dpll.solve_with_assumptions(dimacs, assumptions, timeout, stop)
+        }
+    }
+
+    #[test]
+    fn assumptions_reach_the_solver_outside_the_cnf() {
+        let recording = Arc::new(Recording::default());
+        let mut encoder = EncoderSAT::<u8>::new();
+        encoder.set_solver(recording.clone());
+        encoder.add(vec![Literal::Pos(0), Literal::Pos(1)]);
+        encoder.add(vec![Literal::Neg(0), Literal::Pos(2)]);
+        let (plain, _) = encoder.encode();
+        assert_eq!(
+            encoder
+                .solve_under_assumptions(&[Literal::Neg(1), Literal::Neg(2)])
+                .unwrap(),
+            SatResult::Unsat
+        );
+        // una variabile mai registrata è dichiarata nell'intestazione, solo per questa chiamata
+        assert_eq!(
+            encoder.solve_under_assumptions(&[Literal::Pos(9)]).unwrap(),
+            SatResult::Sat
+        );
+        let calls = recording.0.lock().unwrap();
+        assert_eq!(calls[0], (plain.clone(), vec![-2, -3]));
+        assert_eq!(calls[1].0, plain.replacen("p cnf 3", "p cnf 4", 1));
+        assert_eq!(calls[1].1, vec![4]);
+    }
+
+    #[test]
+    fn the_flat_store_keeps_every_clause_in_order() {
+        let mut encoder = EncoderSAT::<u8>::new();
+        encoder.add(vec![Literal::Pos(0), Literal::Neg(1)]);
+        encoder.add(vec![]);
+        encoder.add(vec![Literal::Neg(2)]);
+        encoder.add(vec![Literal::Pos(1), Literal::Pos(2), Literal::Neg(0)]);
+        let clauses: Vec<Vec<i32>> = encoder.clauses().map(|c| c.to_vec()).collect();
+        assert_eq!(clauses, vec![vec![1, -2], vec![], vec![-3], vec![2, 3, -1]]);
+        assert_eq!(encoder.literals.len(), 6);
+        assert_eq!(encoder.offsets, vec![0, 2, 2, 3]);
+        assert_eq!(encoder.encode().0, "p cnf 3 4\n1 -2 0\n0\n-3 0\n2 3 -1 0\n");
+    }
+
+    // le percezioni di una partita dell'eroe sulla mappa generata dal seme
+    fn recorded_perceptions(seed: u64) -> Vec<crate::world::Perceptions> {
+        use crate::{
+            hero::{Hero, HeroConfig},
+            world::{ActionResult, GameSeeds, World},
+        };
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let mut world = World::new_with_seed(5, 3, seed);
+        let mut kb = init_kb(5);
+        kb.use_in_process_solver();
+        let rng = StdRng::seed_from_u64(GameSeeds::new(seed).agent);
+        let mut hero = Hero::with_rng(kb, 5, HeroConfig::default(), rng);
+        let mut perceptions = vec![];
+        loop {
+            perceptions.push(world.perceptions());
+            let action = hero.next_action(world.perceptions()).unwrap();
+            if world.do_action(action).unwrap() != ActionResult::Continue {
+                return perceptions;
+            }
+        }
+    }
+
+    // il DIMACS come lo scriveva encode() quando le clausole erano un Vec<Vec<Literal<usize>>>
+    fn nested_encoding(counter: usize, clauses: &[Clause]) -> String {
+        let mut encoding = format!("p cnf {counter} {}\n", clauses.len());
+        for clause in clauses {
+            let mut clause: String = clause
+                .iter()
+                .map(|literal| match literal {
+                    Literal::Pos(l) => format!("{l} "),
+                    Literal::Neg(l) => format!("-{l} "),
+                })
+                .collect();
+            clause.push('0');
+            encoding.push_str(&format!("{clause}\n"));
+        }
+        encoding
+    }
+
+    #[test]
+    fn the_flat_store_matches_the_nested_clauses_on_recorded_games() {
+        for seed in 0..4 {
+            let mut flat = EncoderSAT::<Var>::new();
+            // la vecchia rappresentazione, con la stessa numerazione delle variabili
+            let mut numbering = EncoderSAT::<Var>::new();
+            let mut nested: Vec<Clause> = vec![];
+            for p in recorded_perceptions(seed) {
+                let mut formula = EncoderSAT::<Var>::create_ground_truth_from_perception(&p);
+                let neighbours: Vec<Position> = p.position.neighbor_positions(5).collect();
+                formula.extend(EncoderSAT::<Var>::create_wumpus_in_formula(&neighbours));
+                for clause in formula {
+                    nested.push(numbering.register_clause(clause.clone()));
+                    flat.add(clause);
+                }
+                // una domanda aggiunge e poi toglie le sue clausole
+                flat.snapshot();
+                let len = nested.len();
+                for clause in EncoderSAT::<Var>::create_no_wumpus_in_formula(&neighbours) {
+                    nested.push(numbering.register_clause(clause.clone()));
+                    flat.add(clause);
+                }
+                assert_eq!(flat.encode().0, nested_encoding(flat.counter, &nested));
+                flat.rewind();
+                nested.truncate(len);
+
+                let clauses: Vec<&[i32]> = flat.clauses().collect();
+                let dimacs: Vec<Vec<i32>> = nested
+                    .iter()
+                    .map(|clause| clause.iter().map(Literal::to_dimacs).collect())
+                    .collect();
+                assert_eq!(clauses, dimacs, "seed {}", seed);
+                assert_eq!(flat.counter, numbering.counter, "seed {}", seed);
+                assert_eq!(
+                    flat.encode().0,
+                    nested_encoding(flat.counter, &nested),
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn models_are_enumerated_exactly_once() {
+        for backend in test_backends() {
+            let mut encoder = EncoderSAT::<u8>::new();
+            encoder.set_solver(backend.clone());
+            // (a ∨ b) ∧ (¬b ∨ c): 4 modelli su 8 assegnamenti
+            encoder.add(vec![Literal::Pos(0), Literal::Pos(1)]);
+            encoder.add(vec![Literal::Neg(1), Literal::Pos(2)]);
+            let before = state(&encoder);
+            let mut models: Vec<Vec<bool>> = encoder
+                .models()
+                .map(|model| {
+                    let mut model = model.unwrap();
+                    model.sort();
+                    model.into_iter().map(|(_, value)| value).collect()
+                })
+                .collect();
+            models.sort();
+            assert_eq!(
+                models,
+                vec![
+                    vec![false, true, true],
+                    vec![true, false, false],
+                    vec![true, false, true],
+                    vec![true, true, true],
+                ],
+                "{}",
+                backend.name()
+            );
+            // le clausole di blocco spariscono con l'iteratore
+            assert_eq!(state(&encoder), before, "{}", backend.name());
+            assert_eq!(encoder.models_up_to(3).count(), 3, "{}", backend.name());
+            assert_eq!(encoder.models_up_to(9).count(), 4, "{}", backend.name());
+            assert_eq!(state(&encoder), before, "{}", backend.name());
+        }
+    }
+
+    #[test]
+    fn approx_count_follows_the_known_fractions() {
+        for backend in test_backends() {
+            let mut encoder = EncoderSAT::<u8>::new();
+            encoder.set_solver(backend.clone());
+            // (a → b) ∧ c ha 3 modelli: a vera in 1, b in 2, c in tutti
+            encoder.add(vec![Literal::Neg(0), Literal::Pos(1)]);
+            encoder.add(vec![Literal::Pos(2)]);
+            let estimate = encoder.approx_count(&[0, 1, 2], 400, 42).unwrap().unwrap();
+            let exact = [1.0 / 3.0, 2.0 / 3.0, 1.0];
+            for (estimate, exact) in estimate.iter().zip(exact) {
+                assert!(
+                    (estimate - exact).abs() < 0.1,
+                    "{}: {} invece di circa {}",
+                    backend.name(),
+                    estimate,
+                    exact
+                );
+            }
+            assert!(estimate[0] < estimate[1], "{}", backend.name());
+            assert_eq!(
+                encoder.approx_count(&[0, 1, 2], 400, 42).unwrap().unwrap(),
+                estimate,
+                "{}",
+                backend.name()
+            );
+
+            encoder.add(vec![Literal::Neg(2)]);
+            assert_eq!(
+                encoder.approx_count(&[0, 1, 2], 10, 42).unwrap(),
+                None,
+                "{}",
+                backend.name()
+            );
+        }
+    }
+
+    #[test]
+    fn rewind_forgets_fresh_variables_in_both_directions() {
+        let mut encoder = EncoderSAT::<u8>::new();
+        encoder.add(vec![Literal::Pos(10), Literal::Neg(20)]);
+        encoder.snapshot();
+        encoder.add(vec![Literal::Pos(20), Literal::Pos(30)]);
+        encoder.add(vec![Literal::Neg(40)]);
+        assert_eq!(encoder.index_of(&30), Some(3));
+        assert_eq!(encoder.var_for(4), Some(&40));
+        encoder.rewind();
+        assert_eq!(encoder.index_of(&10), Some(1));
+        assert_eq!(encoder.index_of(&20), Some(2));
+        assert_eq!(encoder.var_for(1), Some(&10));
+        assert_eq!(encoder.var_for(2), Some(&20));
+        for (t, index) in [(30, 3), (40, 4)] {
+            assert_eq!(encoder.index_of(&t), None);
+            assert_eq!(encoder.var_for(index), None);
+        }
+        assert_eq!(encoder.var_for(0), None);
+        // dopo il rewind gli indici liberati vengono riassegnati
+        encoder.add(vec![Literal::Pos(40)]);
+        assert_eq!(encoder.index_of(&40), Some(3));
+        assert_eq!(encoder.var_for(3), Some(&40));
+    }
+
+    #[test]
+    fn rewound_clauses_do_not_count_towards_the_next_simplification() {
+        let mut encoder = EncoderSAT::<u8>::new();
+        encoder.add(vec![Literal::Pos(0), Literal::Neg(1)]);
+        encoder.add(vec![Literal::Pos(0)]);
+        for _ in 0..5 {
+            encoder.snapshot();
+            encoder.add(vec![Literal::Neg(0)]);
+            encoder.add_xor_clause(vec![Literal::Pos(0), Literal::Pos(1)], true);
+            encoder.rewind();
+        }
+        assert_eq!(encoder.added_since_simplify, 2);
+        assert!(encoder.simplify_if_due(3).is_none());
+        encoder.add(vec![Literal::Pos(1), Literal::Pos(2)]);
+        assert!(encoder.simplify_if_due(3).is_some());
+    }
+
+    #[test]
+    fn the_footprint_grows_with_the_tells() {
+        let mut kb = init_kb(4);
+        let mut last = kb.memory_footprint();
+        assert!(last.clauses > 0 && last.map > 0 && last.reverse > 0);
+        for x in 0..4 {
+            for y in 0..4 {
+                let pos = Position::new(x, y);
+                kb.tell(&vec![vec![Literal::Neg(Var::Breeze { pos: pos })]]);
+                let footprint = kb.memory_footprint();
+                assert!(
+                    footprint.total() >= last.total(),
+                    "{:?} after {:?}",
+                    footprint,
+                    last
+                );
+                assert!(footprint.clauses >= last.clauses);
+                // ogni formula comunicata viene tenuta
+                assert!(footprint.told > last.told);
+                last = footprint;
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::{
+    simulation::{ACTION_COST, score_change},
+    world::{Action, ActionResult, Perceptions, Position, World},
+};
+
+/// Size of the dungeons of a WumpusEnv.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvConfig {
+    pub dim: usize,
+    pub pits: usize,
+}
+
+/// What the agent sees after an action, the perceptions with the bump of a move against
+/// a wall.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Observation {
+    pub glitter: bool,
+    pub stench: bool,
+    pub breeze: bool,
+    pub howl: bool,
+    pub bump: bool,
+    pub position: Position,
+    pub board_size: usize,
+}
+
+impl From<Perceptions> for Observation {
+    fn from(p: Perceptions) -> Self {
+        Self {
+            glitter: p.glitter(),
+            stench: p.stench(),
+            breeze: p.breeze(),
+            howl: p.howl(),
+            bump: p.bump(),
+            position: p.position,
+            board_size: p.board_size,
+        }
+    }
+}
+
+/// Ground truth returned by WumpusEnv::step, like the true position of the hero.
+pub type Info = HashMap<&'static str, String>;
+
+/// Reinforcement learning loop over a World: `reset` starts an episode, `step` plays an
+/// action and returns the observation, the AIMA reward, whether the episode ended and
+/// some ground truth the agent can't see.
+pub struct WumpusEnv {
+    config: EnvConfig,
+    world: Option<World>,
+    done: bool,
+    steps: usize,
+}
+
+impl WumpusEnv {
+    pub fn new(config: EnvConfig) -> Self {
+        Self {
+            config: config,
+            world: None,
+            done: true,
+            steps: 0,
+        }
+    }
+
+    /// Builds a new dungeon, the same seed gives the same dungeon.
+    pub fn reset(&mut self, seed: Option<u64>) -> Observation {
+        let world = match seed {
+            Some(seed) => World::new_with_seed(self.config.dim, self.config.pits, seed),
+            None => World::new(self.config.dim, self.config.pits),
+        };
+        let observation = world.perceptions().into();
+        self.world = Some(world);
+        self.done = false;
+        self.steps = 0;
+        observation
+    }
+
+    /// Plays an action. The actions that the world would refuse (a move against a wall,
+    /// grabbing where there is no gold, shooting without the arrow, exiting away from the
+    /// start) cost a step and change nothing, a move against a wall is perceived as a bump.
+    pub fn step(&mut self, action: Action) -> (Observation, i32, bool, Info) {
+        assert!(!self.done, "the episode is over, call reset");
+        let world = self.world.as_mut().expect("reset was called");
+        self.steps += 1;
+        let (result, legal) = match world.do_action(action) {
+            Ok(result) => (result, true),
+            Err(_) => (ActionResult::Continue, false),
+        };
+        // una freccia che non parte costa come un'azione qualsiasi, non come una freccia scoccata
+        let reward = if legal {
+            score_change(action, result)
+        } else {
+            -ACTION_COST
+        };
+        self.done = !matches!(result, ActionResult::Continue);
+        let mut observation: Observation = world.perceptions().into();
+        observation.bump = !legal && matches!(action, Action::Move(_));
+        let mut info = HashMap::new();
+        info.insert("hero_position", format!("{:?}", world.hero_position()));
+        info.insert("steps", self.steps.to_string());
+        info.insert("legal", legal.to_string());
+        info.insert("has_arrow", world.has_arrow().to_string());
+        info.insert("wumpus_alive", world.wumpus_alive().to_string());
+        info.insert("gold_in_dungeon", world.gold_in_dungeon().to_string());
+        if let ActionResult::Dead(cause) = result {
+            info.insert("death_cause", format!("{:?}", cause));
+        }
+        (observation, reward, self.done, info)
+    }
+
+    /// The dungeon of the current episode.
+    pub fn world(&self) -> Option<&World> {
+        self.world.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simulation::ARROW_COST, world::Direction};
+
+    #[test]
+    fn a_scripted_episode_collects_the_aima_rewards() {
+        let mut env = WumpusEnv::new(EnvConfig { dim: 4, pits: 2 });
+        let start = env.reset(Some(9));
+        assert_eq!(start.position, Position::new(0, 0));
+        let mut total = 0;
+        // contro il muro, la freccia, una seconda freccia che non c'è più, l'uscita
+        let script = [
+            Action::Move(Direction::North),
+            Action::Shoot(Direction::East),
+            Action::Shoot(Direction::East),
+            Action::Exit,
+        ];
+        for (i, action) in script.into_iter().enumerate() {
+            let (observation, reward, done, info) = env.step(action);
+            total += reward;
+            assert_eq!(done, i == script.len() - 1);
+            assert_eq!(observation.bump, i == 0);
+            assert_eq!(info["legal"], (i != 0 && i != 2).to_string());
+            assert_eq!(info["hero_position"], format!("{:?}", Position::new(0, 0)));
+            assert_eq!(info["steps"], (i + 1).to_string());
+        }
+        assert_eq!(total, -4 * ACTION_COST - ARROW_COST);
+    }
+
+    #[test]
+    fn reset_rebuilds_the_dungeon_of_the_seed() {
+        let mut env = WumpusEnv::new(EnvConfig { dim: 5, pits: 3 });
+        env.reset(Some(4));
+        let first = env.world().unwrap().to_string();
+        env.step(Action::Exit);
+        env.reset(Some(5));
+        env.reset(Some(4));
+        assert_eq!(env.world().unwrap().to_string(), first);
+    }
+
+    #[test]
+    #[should_panic(expected = "the episode is over")]
+    fn stepping_after_the_end_needs_a_reset() {
+        let mut env = WumpusEnv::new(EnvConfig { dim: 4, pits: 1 });
+        env.reset(Some(1));
+        env.step(Action::Exit);
+        env.step(Action::Exit);
+    }
+}
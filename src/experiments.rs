@@ -0,0 +1,322 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    agents::Agent,
+    simulation::{Progress, SimulationResult, interrupted, play_games},
+    stats::BatchSummary,
+    world::World,
+};
+
+/// Sizes and pit counts to sweep, every combination is played `trials` times.
+#[derive(Clone, Debug)]
+pub struct Matrix {
+    pub dims: Vec<usize>,
+    pub pits: Vec<usize>,
+    pub trials: u64,
+    // seed della prima cella, le altre partono da seed ^ (cella << 32)
+    pub seed: u64,
+}
+
+impl Matrix {
+    /// Each (dim, pits) of the matrix with the seed of its first game, dims first.
+    pub fn cells(&self) -> Vec<(usize, usize, u64)> {
+        let mut cells = vec![];
+        for &dim in &self.dims {
+            for &pits in &self.pits {
+                // le celle non condividono seed, così l'intera matrice è riproducibile: la
+                // partita i della cella usa seed ^ i e i < 2^32
+                let seed = self.seed ^ ((cells.len() as u64) << 32);
+                cells.push((dim, pits, seed));
+            }
+        }
+        cells
+    }
+
+    /// Err naming the first cell whose dungeon can't be generated, so that a bad cell stops
+    /// the matrix before any game is played.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dims.is_empty() || self.pits.is_empty() {
+            return Err("the matrix needs at least one dim and one pit count".to_string());
+        }
+        for (dim, pits, _) in self.cells() {
+            World::check_size(dim, pits)
+                .map_err(|e| format!("cell dim {}, {} pits: {}", dim, pits, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// An experiments file in TOML, only `dims` and `pits` are required:
+///
+/// ```toml
+/// dims = [8, 10]
+/// pits = [8, 12]
+/// trials = 200
+/// seed = 42
+/// out = "results.csv"
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentsFile {
+    pub dims: Vec<usize>,
+    pub pits: Vec<usize>,
+    #[serde(default = "default_trials")]
+    pub trials: u64,
+    pub seed: Option<u64>,
+    // il CSV va su stdout se manca
+    pub out: Option<std::path::PathBuf>,
+}
+
+#[cfg(feature = "serde")]
+fn default_trials() -> u64 {
+    100
+}
+
+#[cfg(feature = "serde")]
+impl ExperimentsFile {
+    pub fn load(path: &std::path::Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(io::Error::other)
+    }
+
+    /// The matrix of the file, started from `seed`.
+    pub fn matrix(&self, seed: u64) -> Matrix {
+        Matrix {
+            dims: self.dims.clone(),
+            pits: self.pits.clone(),
+            trials: self.trials,
+            seed: seed,
+        }
+    }
+}
+
+/// Creates the world and the agent of a game of the matrix from its size, pits and seed.
+pub type NewCellGame = dyn Fn(usize, usize, Option<u64>) -> (World, Box<dyn Agent>) + Sync;
+
+/// Summary of the games of one configuration, a line of the CSV.
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub dim: usize,
+    pub pits: usize,
+    pub seed: u64,
+    pub summary: BatchSummary,
+    pub solver_calls: f64,
+}
+
+impl Row {
+    pub const HEADER: &'static str =
+        "dim,pits,seed,games,success_rate,ci_low,ci_high,mean_score,mean_steps,mean_solver_calls";
+
+    fn new(dim: usize, pits: usize, seed: u64, results: &[SimulationResult]) -> Self {
+        let summary = BatchSummary::new(results);
+        let solver_calls =
+            results.iter().map(|r| r.sat_calls).sum::<usize>() as f64 / results.len().max(1) as f64;
+        Self {
+            dim: dim,
+            pits: pits,
+            seed: seed,
+            summary: summary,
+            solver_calls: solver_calls,
+        }
+    }
+
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        let (low, high) = self.summary.success_interval;
+        writeln!(
+            out,
+            "{},{},{},{},{:.4},{:.4},{:.4},{:.2},{:.2},{:.2}",
+            self.dim,
+            self.pits,
+            self.seed,
+            self.summary.games,
+            self.summary.success_rate,
+            low,
+            high,
+            self.summary.score.mean,
+            self.summary.steps.mean,
+            self.solver_calls
+        )
+    }
+}
+
+/// Plays every cell of the matrix with the batch runner and returns a row per cell, in the
+/// order of Matrix::cells. After interrupt the last row has only the games completed and
+/// the cells not started are left out.
+pub fn run(
+    matrix: &Matrix,
+    new_game: &NewCellGame,
+    parallel: bool,
+    timeout: Option<Duration>,
+    quiet: bool,
+    report: fn(&str),
+) -> Vec<Row> {
+    matrix
+        .cells()
+        .into_iter()
+        // dopo interrupt restano le righe delle celle già giocate
+        .take_while(|_| !interrupted())
+        .map(|(dim, pits, seed)| {
+            if !quiet {
+                report(&format!("dim {}, {} pits:", dim, pits));
+            }
+            let progress = Progress::new(matrix.trials as usize, quiet, report);
+            let new_game = |seed: Option<u64>| new_game(dim, pits, seed);
+            let results: Vec<SimulationResult> = play_games(
+                matrix.trials,
+                Some(seed),
+                parallel,
+                &new_game,
+                timeout,
+                &progress,
+            )
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect();
+            Row::new(dim, pits, seed, &results)
+        })
+        .collect()
+}
+
+/// Writes the header and a line per row.
+pub fn write_csv(rows: &[Row], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{}", Row::HEADER)?;
+    for row in rows {
+        row.write_csv(out)?;
+    }
+    Ok(())
+}
+
+/// The pit counts of a dim x dim dungeon for the densities (pits over cells) from `from` to
+/// `to` every `step`. The counts are rounded, repeated ones are dropped and they stop where
+/// the dungeon has no room left for the hero, the wumpus and the gold.
+pub fn density_pits(dim: usize, from: f64, to: f64, step: f64) -> Vec<usize> {
+    assert!(step > 0.0, "the step of the densities must be positive");
+    let cells = dim * dim;
+    let mut pits: Vec<usize> = vec![];
+    let mut point = 0;
+    loop {
+        let density = from + point as f64 * step;
+        // un margine per gli errori di arrotondamento sull'ultimo punto
+        if density > to + step * 1e-9 {
+            break;
+        }
+        let count = (density * cells as f64).round().max(0.0) as usize;
+        if count + 2 >= cells {
+            break;
+        }
+        if pits.last() != Some(&count) {
+            pits.push(count);
+        }
+        point += 1;
+    }
+    pits
+}
+
+/// Writes the rows as whitespace separated columns for gnuplot or matplotlib: density, pits,
+/// success rate and the bounds of its 95% interval, a row per point.
+pub fn write_curve(rows: &[Row], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "# density pits success_rate ci_low ci_high")?;
+    for row in rows {
+        let (low, high) = row.summary.success_interval;
+        writeln!(
+            out,
+            "{:.4} {} {:.4} {:.4} {:.4}",
+            row.pits as f64 / (row.dim * row.dim) as f64,
+            row.pits,
+            row.summary.success_rate,
+            low,
+            high
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::agents::RandomAgent;
+
+    fn matrix(dims: Vec<usize>, pits: Vec<usize>) -> Matrix {
+        Matrix {
+            dims: dims,
+            pits: pits,
+            trials: 3,
+            seed: 42,
+        }
+    }
+
+    #[test]
+    fn bad_cells_are_named() {
+        assert!(matrix(vec![3, 4], vec![1, 2]).validate().is_ok());
+        let error = matrix(vec![4, 0], vec![1]).validate().unwrap_err();
+        assert!(error.starts_with("cell dim 0, 1 pits:"), "{}", error);
+        let error = matrix(vec![4, 3], vec![1, 7]).validate().unwrap_err();
+        assert!(error.starts_with("cell dim 3, 7 pits:"), "{}", error);
+        assert!(matrix(vec![], vec![1]).validate().is_err());
+    }
+
+    #[test]
+    fn a_row_for_each_cell() {
+        let matrix = matrix(vec![3, 4], vec![1, 2]);
+        let new_game = |dim: usize, pits: usize, seed: Option<u64>| {
+            let seed = seed.expect("the matrix seeds every game");
+            let agent: Box<dyn Agent> = Box::new(RandomAgent::new(StdRng::seed_from_u64(seed), 50));
+            (World::new_with_seed(dim, pits, seed), agent)
+        };
+        let rows = run(&matrix, &new_game, false, None, true, |_| {});
+        let cells: Vec<_> = rows.iter().map(|r| (r.dim, r.pits, r.seed)).collect();
+        assert_eq!(cells, matrix.cells());
+        assert!(rows.iter().all(|r| r.summary.games == 3));
+
+        let mut csv = vec![];
+        write_csv(&rows, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], Row::HEADER);
+        let columns = Row::HEADER.split(',').count();
+        assert!(lines.iter().all(|l| l.split(',').count() == columns));
+    }
+
+    #[test]
+    fn the_sweep_curve_has_a_row_per_density() {
+        let pits = density_pits(4, 0.1, 0.3, 0.1);
+        assert_eq!(pits, vec![2, 3, 5]);
+        let matrix = Matrix {
+            dims: vec![4],
+            pits: pits,
+            trials: 10,
+            seed: 7,
+        };
+        let new_game = |dim: usize, pits: usize, seed: Option<u64>| {
+            let seed = seed.expect("the matrix seeds every game");
+            let agent: Box<dyn Agent> = Box::new(RandomAgent::new(StdRng::seed_from_u64(seed), 50));
+            (World::new_with_seed(dim, pits, seed), agent)
+        };
+        let rows = run(&matrix, &new_game, false, None, true, |_| {});
+        let mut curve = vec![];
+        write_curve(&rows, &mut curve).unwrap();
+        let curve = String::from_utf8(curve).unwrap();
+        let lines: Vec<_> = curve.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "# density pits success_rate ci_low ci_high");
+        // solo il formato, non l'andamento della curva
+        for (line, pits) in lines[1..].iter().zip([2, 3, 5]) {
+            let columns: Vec<f64> = line.split(' ').map(|c| c.parse().unwrap()).collect();
+            assert_eq!(columns.len(), 5, "{}", line);
+            assert!((columns[0] - pits as f64 / 16.0).abs() < 1e-4, "{}", line);
+            assert_eq!(columns[1], pits as f64);
+            assert!(
+                columns[3] <= columns[2] && columns[2] <= columns[4],
+                "{}",
+                line
+            );
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// situazioni ricorrenti per chi scrive test sull'eroe e sulla base di conoscenza, anche fuori
+// da questo crate
+
+use crate::world::{Perceptions, Position};
+
+const START: Position = Position { x: 0, y: 0 };
+
+/// The first turn on a quiet start cell: nothing perceived in (0, 0).
+pub fn start_clean(board_size: usize) -> Perceptions {
+    Perceptions::at(START, board_size).build()
+}
+
+/// The first turn with a pit next to the start cell.
+pub fn start_breezy(board_size: usize) -> Perceptions {
+    Perceptions::at(START, board_size).breeze().build()
+}
+
+/// The first turn with the wumpus next to the start cell.
+pub fn start_smelly(board_size: usize) -> Perceptions {
+    Perceptions::at(START, board_size).stench().build()
+}
+
+/// The hero standing on the gold in position, with nothing else around.
+pub fn gold_cell(position: Position, board_size: usize) -> Perceptions {
+    Perceptions::at(position, board_size).glitter().build()
+}
+
+/// The turn after the arrow killed the wumpus, in position.
+pub fn wumpus_killed(position: Position, board_size: usize) -> Perceptions {
+    Perceptions::at(position, board_size).howl().build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_builder_matches_the_with_methods() {
+        let pos = Position::new(2, 1);
+        let built: Perceptions = Perceptions::at(pos, 4).breeze().stench().howl().into();
+        assert_eq!(
+            built,
+            Perceptions::new(pos, 4)
+                .with_howl()
+                .with_stench()
+                .with_breeze()
+        );
+        let every = Perceptions::at(pos, 4)
+            .glitter()
+            .stench()
+            .breeze()
+            .howl()
+            .bump()
+            .build();
+        assert!(
+            every.glitter() && every.stench() && every.breeze() && every.howl() && every.bump()
+        );
+        assert_eq!(Perceptions::at(pos, 4).build(), Perceptions::new(pos, 4));
+    }
+
+    #[test]
+    fn fixtures_perceive_what_they_say() {
+        assert_eq!(start_clean(4).to_string(), "nothing @ (0,0)");
+        assert_eq!(start_breezy(4).to_string(), "breeze @ (0,0)");
+        assert_eq!(start_smelly(4).to_string(), "stench @ (0,0)");
+        let gold = gold_cell(Position::new(3, 2), 5);
+        assert_eq!(gold.to_string(), "glitter @ (3,2)");
+        assert_eq!(gold.board_size, 5);
+        assert_eq!(
+            wumpus_killed(Position::new(1, 1), 4).to_string(),
+            "howl @ (1,1)"
+        );
+    }
+}
@@ -1,107 +1,883 @@
-use std::{collections::HashSet, fmt, process::exit, ptr::eq};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    ops::AddAssign,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use bumpalo::Bump;
+use log::{Level, debug, error, info, log_enabled, warn};
 use rand::{Rng, rngs::ThreadRng};
 
 use crate::{
-    encoder::Literal,
-    kb::{Formula, KnowledgeBase, Var},
+    encoder::MemoryFootprint,
+    kb::{KbError, KnowledgeBase},
+    solver::SolverError,
+    strategy::{ClassicStrategy, DecisionContext, Strategy, Utility},
     world::{Action, Direction, Perceptions, Position},
 };
 
 use agent::{
-    problem::{Problem, SuitableState, Utility},
+    problem::{Problem, SuitableState, Utility as PlanUtility},
     statexplorer::resolver::{AStarExplorer, BFSExplorer},
 };
 
 use agent::problem::CostructSolution;
 
-#[derive(Default)]
-struct Cache {
+#[derive(Debug, PartialEq, Eq)]
+pub enum CacheError {
+    // la casella risulterebbe sia sicura che pericolosa
+    SafeAndUnsafe(Position),
+    // solo le caselle sicure possono essere visitate
+    VisitedNotSafe(Position),
+    OutOfBounds(Position),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::SafeAndUnsafe(p) => {
+                write!(f, "the position {:?} is both safe and unsafe", p)
+            }
+            CacheError::VisitedNotSafe(p) => {
+                write!(f, "the position {:?} is visited but not safe", p)
+            }
+            CacheError::OutOfBounds(p) => write!(f, "the position {:?} is outside the map", p),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// Why a cell was proven unsafe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnsafeReason {
+    Pit,
+    Wumpus,
+    // pericolosa, ma non si sa se per un pozzo o per il wumpus
+    Undetermined,
+}
+
+/// What the hero believes about a cell when it decides, see Hero::belief.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Belief {
+    Safe,
+    Unsafe,
+    // non dimostrata sicura, ma l'eroe ha deciso di rischiare
+    Gamble,
+    Unknown,
+}
+
+/// A literal about one cell entailed by the knowledge base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fact {
+    Safe,
+    Unsafe,
+    Pit,
+    Wumpus,
+    NoWumpus,
+    Gold,
+}
+
+// in JSON le chiavi delle mappe devono essere stringhe: le mappe con chiave Position
+// vengono scritte come liste di coppie (chiave, valore)
+#[cfg(feature = "serde")]
+mod pairs {
+    use std::{collections::HashMap, hash::Hash};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
+    }
+}
+
+/// What the hero has inferred so far. A cell is never both safe and unsafe and every
+/// visited cell is safe.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cache {
     visited: HashSet<Position>,
     safe: HashSet<Position>,
-    _unsafe: HashSet<Position>,
-    wumpus: Option<Position>,
+    // caselle sicure non ancora visitate, aggiornata ad ogni inserimento
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frontier: HashSet<Position>,
+    // caselle sicure non visitate che non si possono raggiungere passando solo per caselle
+    // sicure, entrano nella frontiera quando una nuova casella sicura le collega
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unreachable: HashSet<Position>,
+    #[cfg_attr(feature = "serde", serde(with = "pairs"))]
+    _unsafe: HashMap<Position, UnsafeReason>,
+    pits: HashSet<Position>,
+    // caselle che la KB non ha saputo classificare: (turno dell'ultimo tentativo, generazione
+    // della KB in quel momento), si richiedono solo dopo che la KB ha imparato qualcosa di nuovo
+    #[cfg_attr(feature = "serde", serde(with = "pairs"))]
+    undecided: HashMap<Position, (usize, usize)>,
+    // ogni fatto dimostrato su una casella con il turno in cui è stato dimostrato,
+    // consultato prima di interrogare la KB
+    #[cfg_attr(feature = "serde", serde(with = "pairs"))]
+    facts: HashMap<Position, Vec<(Fact, usize)>>,
+    // un insieme per i mondi con più wumpus, ognuno viene dimenticato solo quando è colpito
+    wumpuses: HashSet<Position>,
+    // caselle da cui la freccia colpisce sicuramente il wumpus, con la direzione del tiro
+    #[cfg_attr(feature = "serde", serde(with = "pairs"))]
+    aim: HashMap<Position, Direction>,
+    // casella non dimostrabilmente sicura verso cui l'eroe ha deciso di rischiare
+    gamble: Option<Position>,
     map_size: usize,
 }
 
 impl Cache {
-    fn new(map_size: usize) -> Self {
+    pub fn new(map_size: usize) -> Self {
         let mut safe = HashSet::new();
         safe.insert(Position::new(0, 0));
         Self {
+            frontier: safe.clone(),
+            unreachable: Default::default(),
             safe: safe,
             visited: Default::default(),
             _unsafe: Default::default(),
-            wumpus: Default::default(),
+            pits: Default::default(),
+            undecided: Default::default(),
+            facts: Default::default(),
+            wumpuses: Default::default(),
+            aim: Default::default(),
+            gamble: Default::default(),
             map_size: map_size,
         }
     }
 
-    fn is_safe(&self, p: &Position) -> bool {
+    pub fn visited(&self) -> &HashSet<Position> {
+        &self.visited
+    }
+
+    pub fn safe(&self) -> &HashSet<Position> {
+        &self.safe
+    }
+
+    pub fn unsafe_cells(&self) -> &HashMap<Position, UnsafeReason> {
+        &self._unsafe
+    }
+
+    pub fn unsafe_reason(&self, p: &Position) -> Option<UnsafeReason> {
+        self._unsafe.get(p).copied()
+    }
+
+    pub fn pits(&self) -> &HashSet<Position> {
+        &self.pits
+    }
+
+    /// Turn of the last attempt to classify p and the knowledge base generation at that time,
+    /// None if p was never asked or was classified.
+    pub fn undecided(&self, p: &Position) -> Option<(usize, usize)> {
+        self.undecided.get(p).copied()
+    }
+
+    /// Facts proven about p, with the turn they were proven.
+    pub fn facts(&self, p: &Position) -> &[(Fact, usize)] {
+        self.facts.get(p).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn knows(&self, p: &Position, fact: Fact) -> bool {
+        self.facts(p).iter().any(|(known, _)| *known == fact)
+    }
+
+    // la KB è monotona: un fatto dimostrato resta vero, si tiene solo la prima volta
+    fn record_fact(&mut self, p: Position, fact: Fact, turn: usize) {
+        if !self.knows(&p, fact) {
+            self.facts.entry(p).or_default().push((fact, turn));
+        }
+    }
+
+    /// Safe cells not visited yet that can be reached moving only on safe cells.
+    pub fn frontier(&self) -> &HashSet<Position> {
+        &self.frontier
+    }
+
+    /// Safe cells not visited yet that are cut off by unknown or unsafe cells.
+    pub fn unreachable(&self) -> &HashSet<Position> {
+        &self.unreachable
+    }
+
+    fn neighbours(&self, p: Position) -> impl Iterator<Item = Position> + '_ {
+        p.neighbor_positions(self.map_size)
+    }
+
+    // la frontiera ricalcolata dalla definizione: flood fill sulle caselle sicure partendo da
+    // quelle visitate e da casa, dove l'eroe si trova all'inizio
+    fn recompute_frontier(&self) -> HashSet<Position> {
+        let home = Position::new(0, 0);
+        let mut reached = self.visited.clone();
+        let mut queue: VecDeque<Position> = self.visited.iter().copied().collect();
+        if self.is_safe(&home) && reached.insert(home) {
+            queue.push_back(home);
+        }
+        while let Some(pos) = queue.pop_front() {
+            for next in self.neighbours(pos) {
+                if self.is_safe(&next) && reached.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached.difference(&self.visited).copied().collect()
+    }
+
+    fn rebuild_frontier(&mut self) {
+        self.frontier = self.recompute_frontier();
+        self.unreachable = self
+            .safe
+            .iter()
+            .filter(|p| !self.visited.contains(p) && !self.frontier.contains(p))
+            .copied()
+            .collect();
+    }
+
+    // sposta nella frontiera le caselle irraggiungibili collegate a p
+    fn reach_from(&mut self, p: Position) {
+        let mut queue = VecDeque::from([p]);
+        while let Some(pos) = queue.pop_front() {
+            let linked: Vec<Position> = self
+                .neighbours(pos)
+                .filter(|next| self.unreachable.contains(next))
+                .collect();
+            for next in linked {
+                self.unreachable.remove(&next);
+                self.frontier.insert(next);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // p è collegata alle caselle visitate da un percorso di caselle sicure
+    fn is_reachable(&self, p: Position) -> bool {
+        p == Position::new(0, 0)
+            || self.is_visited(&p)
+            || self.frontier.contains(&p)
+            || self
+                .neighbours(p)
+                .any(|next| self.is_visited(&next) || self.frontier.contains(&next))
+    }
+
+    pub fn wumpuses(&self) -> &HashSet<Position> {
+        &self.wumpuses
+    }
+
+    pub fn map_size(&self) -> usize {
+        self.map_size
+    }
+
+    fn check_bounds(&self, p: Position) -> Result<(), CacheError> {
+        if p.x < self.map_size && p.y < self.map_size {
+            Ok(())
+        } else {
+            Err(CacheError::OutOfBounds(p))
+        }
+    }
+
+    pub fn insert_safe(&mut self, p: Position) -> Result<(), CacheError> {
+        self.check_bounds(p)?;
+        if self.is_unsafe(&p) {
+            return Err(CacheError::SafeAndUnsafe(p));
+        }
+        self.safe.insert(p);
+        self.undecided.remove(&p);
+        if !self.is_visited(&p) && !self.unreachable.contains(&p) {
+            if self.is_reachable(p) {
+                self.frontier.insert(p);
+                self.reach_from(p);
+            } else {
+                self.unreachable.insert(p);
+            }
+        }
+        debug_assert_eq!(self.frontier, self.recompute_frontier());
+        Ok(())
+    }
+
+    pub fn insert_unsafe(&mut self, p: Position) -> Result<(), CacheError> {
+        self.insert_unsafe_because(p, UnsafeReason::Undetermined)
+    }
+
+    fn insert_unsafe_because(
+        &mut self,
+        p: Position,
+        reason: UnsafeReason,
+    ) -> Result<(), CacheError> {
+        self.check_bounds(p)?;
+        if self.is_safe(&p) {
+            return Err(CacheError::SafeAndUnsafe(p));
+        }
+        self._unsafe.insert(p, reason);
+        self.undecided.remove(&p);
+        Ok(())
+    }
+
+    pub fn insert_visited(&mut self, p: Position) -> Result<(), CacheError> {
+        self.check_bounds(p)?;
+        if !self.is_safe(&p) {
+            return Err(CacheError::VisitedNotSafe(p));
+        }
+        self.visited.insert(p);
+        self.frontier.remove(&p);
+        self.unreachable.remove(&p);
+        self.reach_from(p);
+        debug_assert_eq!(self.frontier, self.recompute_frontier());
+        Ok(())
+    }
+
+    pub fn insert_pit(&mut self, p: Position) -> Result<(), CacheError> {
+        self.insert_unsafe_because(p, UnsafeReason::Pit)?;
+        self.pits.insert(p);
+        Ok(())
+    }
+
+    pub fn insert_wumpus(&mut self, p: Position) -> Result<(), CacheError> {
+        self.insert_unsafe_because(p, UnsafeReason::Wumpus)?;
+        self.wumpuses.insert(p);
+        Ok(())
+    }
+
+    /// Forgets the wumpus hit by an arrow shot along `ray`: the first known wumpus on the
+    /// ray becomes safe, the undetermined cells become unknown again so that they are asked
+    /// to the knowledge base. The other known wumpuses are still avoided.
+    pub fn on_wumpus_killed(&mut self, ray: &[Position]) {
+        let killed = ray.iter().find(|pos| self.wumpuses.contains(pos)).copied();
+        let mut freed = vec![];
+        self._unsafe.retain(|pos, reason| match reason {
+            UnsafeReason::Pit => true,
+            UnsafeReason::Wumpus if Some(*pos) == killed => {
+                freed.push(*pos);
+                false
+            }
+            UnsafeReason::Wumpus => true,
+            UnsafeReason::Undetermined => false,
+        });
+        if let Some(wumpus) = killed {
+            self.wumpuses.remove(&wumpus);
+        }
+        for pos in freed {
+            self.insert_safe(pos)
+                .expect("the wumpus cells were just removed from the unsafe ones");
+        }
+    }
+
+    /// Checks the invariants, used when a cache is restored from outside.
+    pub fn validate(&self) -> Result<(), CacheError> {
+        for p in self
+            .safe
+            .iter()
+            .chain(self._unsafe.keys())
+            .chain(&self.visited)
+            .chain(&self.pits)
+            .chain(self.undecided.keys())
+            .chain(self.facts.keys())
+            .chain(&self.wumpuses)
+        {
+            self.check_bounds(*p)?;
+        }
+        if let Some(p) = self.safe.iter().find(|p| self._unsafe.contains_key(p)) {
+            return Err(CacheError::SafeAndUnsafe(*p));
+        }
+        if let Some(p) = self.visited.difference(&self.safe).next() {
+            return Err(CacheError::VisitedNotSafe(*p));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_safe(&self, p: &Position) -> bool {
         self.safe.contains(p)
     }
 
-    fn is_unsafe(&self, p: &Position) -> bool {
-        self._unsafe.contains(p)
+    pub(crate) fn is_unsafe(&self, p: &Position) -> bool {
+        self._unsafe.contains_key(p)
     }
 
-    fn is_visited(&self, p: &Position) -> bool {
+    pub(crate) fn is_visited(&self, p: &Position) -> bool {
         self.visited.contains(p)
     }
 
     fn there_is_the_wumpus(&self, p: &Position) -> bool {
-        self.is_unsafe(p) && self.wumpus.map_or(false, |x| x == *p)
+        self.is_unsafe(p) && self.wumpuses.contains(p)
+    }
+
+    fn is_aim_spot(&self, p: &Position) -> bool {
+        self.aim.contains_key(p)
+    }
+
+    pub(crate) fn aim_direction(&self, p: &Position) -> Option<Direction> {
+        self.aim.get(p).copied()
+    }
+
+    fn is_gamble(&self, p: &Position) -> bool {
+        self.gamble == Some(*p)
     }
 
     fn safe_but_not_visited(&self, p: &Position) -> bool {
-        self.is_safe(p) && !self.is_visited(p)
+        self.frontier.contains(p)
     }
 
-    fn safe_neighbourhood(&self, p: &Position) -> bool {
-        use Direction::*;
-        for dir in [North, Sud, East, Ovest] {
-            if p.possible_move(dir, self.map_size) && self.safe_but_not_visited(&p.move_clone(dir))
-            {
+    pub(crate) fn safe_neighbourhood(&self, p: &Position) -> bool {
+        for next in p.neighbor_positions(self.map_size) {
+            if self.safe_but_not_visited(&next) {
                 return true;
             }
         }
-        return false;
+        false
+    }
+}
+
+// sequenza di caselle da attraversare a partire da origin, cursor è l'indice del prossimo passo
+#[derive(Debug)]
+pub struct Plan {
+    origin: Position,
+    steps: Vec<Position>,
+    cursor: usize,
+}
+
+impl Plan {
+    fn new(origin: Position, steps: Vec<Position>) -> Self {
+        Self {
+            origin: origin,
+            steps: steps,
+            cursor: 0,
+        }
+    }
+
+    // dove deve trovarsi l'eroe per eseguire il prossimo passo
+    fn expected_position(&self) -> Position {
+        match self.cursor {
+            0 => self.origin,
+            cursor => self.steps[cursor - 1],
+        }
+    }
+
+    pub(crate) fn next_step(&self) -> Option<Position> {
+        self.steps.get(self.cursor).copied()
+    }
+
+    fn remaining(&self) -> &[Position] {
+        &self.steps[self.cursor.min(self.steps.len())..]
+    }
+
+    fn advance(&mut self) {
+        self.cursor += 1;
+    }
+
+    fn is_finished(&self) -> bool {
+        self.cursor >= self.steps.len()
     }
 }
 
-#[derive(PartialEq, Eq)]
-enum Objective {
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Objective {
     TakeGold,
+    // raggiungi una casella allineata con il wumpus e scocca la freccia
+    KillWumpus,
+    // raggiungi la casella meno rischiosa della frontiera
+    Gamble,
     GoHome,
 }
 
-fn distance_to_zero(p: &Position) -> i32 {
-    p.x as i32 + p.y as i32
+// cosa sa l'eroe di una casella, per la visualizzazione
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CellKnowledge {
+    Unknown,
+    Safe,
+    Visited,
+    UnsafePit,
+    UnsafeWumpus,
+    UnsafeUnknownKind,
+    // chiesta alla KB senza riuscire a classificarla
+    Undecided,
+}
+
+impl CellKnowledge {
+    fn symbol(self) -> char {
+        match self {
+            CellKnowledge::Unknown => '?',
+            CellKnowledge::Safe => '.',
+            CellKnowledge::Visited => '_',
+            CellKnowledge::UnsafePit => 'o',
+            CellKnowledge::UnsafeWumpus => 'w',
+            CellKnowledge::UnsafeUnknownKind => '!',
+            CellKnowledge::Undecided => '~',
+        }
+    }
+}
+
+/// Why the destination of a move was considered safe.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Support {
+    // già nella cache prima di questo turno
+    Cached,
+    // dimostrata sicura dalla KB in questo turno
+    Inferred,
+    // non dimostrabilmente sicura, l'eroe ha deciso di rischiare
+    Gamble,
+}
+
+/// Why the hero chose an action, returned by Hero::next_action_traced.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecisionTrace {
+    pub turn: usize,
+    pub position: Position,
+    pub action: Option<Action>,
+    pub objective: Objective,
+    // l'azione è il passo successivo del piano, senza confronto di utilità
+    pub from_plan: bool,
+    // passi del piano rimasti dopo l'azione
+    pub plan: Vec<Position>,
+    // le azioni confrontate nell'ultimo tentativo con la loro utilità, anche quelle scartate
+    pub candidates: Vec<(Action, Utility)>,
+    // caselle classificate dalla KB in questo turno
+    pub inferred_safe: Vec<Position>,
+    pub inferred_unsafe: Vec<Position>,
+    // destinazione di una mossa e perché è considerata sicura
+    pub target: Option<(Position, Support)>,
+}
+
+impl DecisionTrace {
+    fn new(turn: usize, position: Position, objective: Objective) -> Self {
+        Self {
+            turn: turn,
+            position: position,
+            action: None,
+            objective: objective,
+            from_plan: false,
+            plan: vec![],
+            candidates: vec![],
+            inferred_safe: vec![],
+            inferred_unsafe: vec![],
+            target: None,
+        }
+    }
+}
+
+/// Search algorithm used to build the plans of the hero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExplorerKind {
+    #[default]
+    AStar,
+    Bfs,
+    // costo uniforme: A* senza euristica
+    Dijkstra,
+}
+
+impl FromStr for ExplorerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "astar" | "a*" => Ok(ExplorerKind::AStar),
+            "bfs" => Ok(ExplorerKind::Bfs),
+            "dijkstra" | "ucs" => Ok(ExplorerKind::Dijkstra),
+            other => Err(format!("unknown explorer {}", other)),
+        }
+    }
+}
+
+/// How the exploration plan chooses among the frontier cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrontierPolicy {
+    // la casella di frontiera più vicina
+    #[default]
+    Nearest,
+    // le caselle con più vicini sconosciuti, a parità la più vicina
+    InformationGain,
+}
+
+impl FromStr for FrontierPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(FrontierPolicy::Nearest),
+            "information-gain" => Ok(FrontierPolicy::InformationGain),
+            other => Err(format!("unknown frontier policy {}", other)),
+        }
+    }
+}
+
+/// Tunables of the hero, the default values give the original behaviour.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct HeroConfig {
+    // se nessuna mossa è dimostrabilmente sicura l'eroe entra nella casella di frontiera
+    // con il rischio stimato più basso, purché sia minore di questa soglia (0 = mai)
+    pub risk_threshold: f64,
+    // ricalcola i piani di esplorazione anche con la BFS e controlla che abbiano la stessa lunghezza
+    pub cross_check_plans: bool,
+    // esplora tutte le caselle dimostrabilmente sicure prima di uscire, anche dopo aver preso l'oro
+    pub explore_all: bool,
+    // quante volte una casella scoperta pericolosa fa riesaminare le vicine in un turno, None = senza limite
+    pub propagation_depth: Option<usize>,
+    // ogni quanti turni si controlla che la KB sia consistente, 0 = mai
+    pub consistency_check_every: usize,
+    // tentativi di pianificazione in un turno prima di ripiegare sul ritorno a casa
+    pub planning_attempts: usize,
+    // algoritmo per i piani di esplorazione e per quelli di ritorno a casa
+    pub explorer: ExplorerKind,
+    pub home_explorer: ExplorerKind,
+    // turni dopo i quali l'eroe rinuncia all'esplorazione e torna a casa, None = senza limite
+    pub max_steps: Option<usize>,
+    pub frontier_policy: FrontierPolicy,
+    // interrogazioni in blocco divise tra più thread (solo con la feature rayon)
+    pub parallel_queries: bool,
+    // controlla che ogni piano sia lungo quanto il cammino minimo della BFS e che ogni mossa
+    // vada in una casella sicura, alla prima violazione stampa lo stato dell'eroe e va in panic
+    pub check_invariants: bool,
+    // misura il tempo passato nelle varie parti del turno, vedi Timings
+    pub timing: bool,
+}
+
+impl Default for HeroConfig {
+    fn default() -> Self {
+        Self {
+            risk_threshold: 0.0,
+            cross_check_plans: false,
+            explore_all: false,
+            propagation_depth: None,
+            consistency_check_every: 1,
+            planning_attempts: 4,
+            explorer: ExplorerKind::AStar,
+            home_explorer: ExplorerKind::AStar,
+            max_steps: None,
+            frontier_policy: FrontierPolicy::Nearest,
+            parallel_queries: false,
+            check_invariants: false,
+            timing: false,
+        }
+    }
+}
+
+/// Counters of the work done by the hero during one game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeroStats {
+    // interrogazioni fatte alla KB e quelle risolte dalla cache senza chiederle
+    pub asks: usize,
+    pub cached_asks: usize,
+    pub tells: usize,
+    pub solver_calls: usize,
+    pub plans: usize,
+    // somma delle lunghezze dei piani generati
+    pub plan_steps: usize,
+    pub cells_visited: usize,
+    // caselle classificate come sicure o pericolose
+    pub cells_classified: usize,
+    pub turns: usize,
+    // 1 se la partita è finita perché è stato superato max_steps (sommando: numero di partite)
+    pub budget_exhausted: usize,
+    pub timings: Timings,
+}
+
+impl AddAssign for HeroStats {
+    fn add_assign(&mut self, other: Self) {
+        self.asks += other.asks;
+        self.cached_asks += other.cached_asks;
+        self.tells += other.tells;
+        self.solver_calls += other.solver_calls;
+        self.plans += other.plans;
+        self.plan_steps += other.plan_steps;
+        self.cells_visited += other.cells_visited;
+        self.cells_classified += other.cells_classified;
+        self.turns += other.turns;
+        self.budget_exhausted += other.budget_exhausted;
+        self.timings += other.timings;
+    }
+}
+
+/// Where the time of the turns of the hero goes, measured only with HeroConfig::timing.
+/// The parts don't overlap: what is left of `turn` is the bookkeeping of the hero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    // controlli di consistenza della KB
+    pub consistency: Duration,
+    // interrogazioni alla KB, il loro numero è HeroStats::asks
+    pub asks: Duration,
+    pub planning: Duration,
+    // next_action dall'inizio alla fine
+    pub turn: Duration,
+}
+
+impl Timings {
+    fn since(&self, before: &Timings) -> Timings {
+        Timings {
+            consistency: self.consistency - before.consistency,
+            asks: self.asks - before.asks,
+            planning: self.planning - before.planning,
+            turn: self.turn - before.turn,
+        }
+    }
+}
+
+impl AddAssign for Timings {
+    fn add_assign(&mut self, other: Self) {
+        self.consistency += other.consistency;
+        self.asks += other.asks;
+        self.planning += other.planning;
+        self.turn += other.turn;
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let other = self
+            .turn
+            .saturating_sub(self.consistency + self.asks + self.planning);
+        write!(
+            f,
+            "consistency {:?}, asks {:?}, planning {:?}, other {:?}, total {:?}",
+            self.consistency, self.asks, self.planning, other, self.turn
+        )
+    }
+}
+
+// con il timing spento non si legge nemmeno l'orologio
+fn elapsed(start: Option<Instant>) -> Duration {
+    start.map_or(Duration::ZERO, |start| start.elapsed())
+}
+
+// quante delle ultime posizioni dell'eroe vengono ricordate per riconoscere le oscillazioni
+const RECENT_POSITIONS: usize = 4;
+
+// cerca con l'algoritmo scelto un piano verso la casella di goals più vicina: (passi, iterazioni)
+fn search_plan(
+    explorer: ExplorerKind,
+    cache: &Cache,
+    size_map: usize,
+    goals: &HashSet<Position>,
+    from: Position,
+) -> (Option<Vec<Position>>, usize) {
+    let arena = Bump::new();
+    let result = match explorer {
+        ExplorerKind::AStar => {
+            let problem = find_plan_to_goals(cache, size_map, goals);
+            AStarExplorer::new(&problem, &arena).search(from)
+        }
+        ExplorerKind::Bfs => {
+            let problem = find_plan_to_goals(cache, size_map, goals);
+            BFSExplorer::new(&problem, &arena).search(from)
+        }
+        ExplorerKind::Dijkstra => {
+            let problem = FindPlan::new(
+                cache,
+                size_map,
+                |_: &Cache, pos: &Position| goals.contains(pos),
+                no_heuristic,
+            );
+            AStarExplorer::new(&problem, &arena).search(from)
+        }
+    };
+    (result.actions, result.n_iter)
+}
+
+/// Returns the element with the highest score, ties are broken uniformly at random
+/// with reservoir sampling: the k-th tied element replaces the current choice with
+/// probability 1/k. Forbidden elements are never returned.
+fn choose_best<T, R: Rng + ?Sized>(
+    scored: impl IntoIterator<Item = (T, Utility)>,
+    rng: &mut R,
+) -> Option<T> {
+    let mut best = None;
+    let mut best_utility = Utility::Forbidden;
+    let mut ties = 0;
+    for (item, utility) in scored {
+        if utility == Utility::Forbidden {
+            continue;
+        }
+        if best.is_none() || utility > best_utility {
+            best = Some(item);
+            best_utility = utility;
+            ties = 1;
+        } else if utility == best_utility {
+            ties += 1;
+            if rng.random_range(0..ties) == 0 {
+                best = Some(item);
+            }
+        }
+    }
+    best
+}
+
+#[derive(Debug)]
+pub enum HeroError {
+    InconsistentKb(KbError),
+    // il solver non ha risposto a un'interrogazione
+    Solver(SolverError),
+    // non esiste un piano per tornare a casa dalla posizione
+    PlanningFailed(Position),
+    NoActionAvailable(Position),
+}
+
+impl fmt::Display for HeroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeroError::InconsistentKb(e) => write!(f, "inconsistency check failed: {}", e),
+            HeroError::Solver(e) => write!(f, "the solver failed: {}", e),
+            HeroError::PlanningFailed(p) => {
+                write!(f, "the hero can't find a way home from {:?}", p)
+            }
+            HeroError::NoActionAvailable(p) => write!(f, "no action possible in {:?}", p),
+        }
+    }
+}
+
+impl std::error::Error for HeroError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeroError::InconsistentKb(e) => Some(e),
+            HeroError::Solver(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SolverError> for HeroError {
+    fn from(e: SolverError) -> Self {
+        HeroError::Solver(e)
+    }
 }
 
 fn no_heuristic(_p: &Position) -> i32 {
     1
 }
 
-struct FindPlan<'a> {
+// suitable e heuristic possono essere closure che catturano l'obbiettivo della ricerca
+struct FindPlan<'a, S, H> {
     cache: &'a Cache,
     size_map: usize,
-    suitable: fn(&Cache, &Position) -> bool,
-    heuristic: fn(&Position) -> i32,
-}
-
-fn eq_to_zero(_cache: &Cache, _this: &Position) -> bool {
-    *_this == Position::new(0, 0)
+    suitable: S,
+    heuristic: H,
 }
 
-impl<'a> FindPlan<'a> {
-    fn new(
-        cache: &'a Cache,
-        size_map: usize,
-        suitable: fn(&Cache, &Position) -> bool,
-        heuristic: fn(&Position) -> i32,
-    ) -> Self {
+impl<'a, S, H> FindPlan<'a, S, H>
+where
+    S: Fn(&Cache, &Position) -> bool,
+    H: Fn(&Position) -> i32,
+{
+    fn new(cache: &'a Cache, size_map: usize, suitable: S, heuristic: H) -> Self {
         Self {
             cache: cache,
             size_map: size_map,
@@ -111,25 +887,42 @@ impl<'a> FindPlan<'a> {
     }
 }
 
-impl Problem for FindPlan<'_> {
+/// Search problem "reach the nearest cell of goals" moving on cached safe cells.
+/// The heuristic is the Manhattan distance to the nearest goal, which is admissible.
+fn find_plan_to_goals<'a>(
+    cache: &'a Cache,
+    size_map: usize,
+    goals: &'a HashSet<Position>,
+) -> FindPlan<'a, impl Fn(&Cache, &Position) -> bool + 'a, impl Fn(&Position) -> i32 + 'a> {
+    FindPlan::new(
+        cache,
+        size_map,
+        move |_: &Cache, pos: &Position| goals.contains(pos),
+        move |pos: &Position| {
+            // senza obbiettivi ogni casella ha euristica 0
+            goals
+                .iter()
+                .map(|goal| pos.manhattan(goal))
+                .min()
+                .unwrap_or(0) as i32
+        },
+    )
+}
+
+impl<S, H> Problem for FindPlan<'_, S, H> {
     type State = Position;
 }
 
-impl CostructSolution for FindPlan<'_> {
+impl<S, H> CostructSolution for FindPlan<'_, S, H> {
     type Action = Position;
     type Cost = i32;
 
     fn executable_actions(&self, state: &Self::State) -> impl Iterator<Item = Self::Action> {
-        use Direction::*;
-
         let mut result = vec![];
 
-        for dir in [North, Sud, East, Ovest] {
-            if state.possible_move(dir, self.size_map) {
-                let next_pos = state.move_clone(dir);
-                if self.cache.is_safe(&next_pos) {
-                    result.push(next_pos);
-                }
+        for next_pos in state.neighbor_positions(self.size_map) {
+            if self.cache.is_safe(&next_pos) || self.cache.is_gamble(&next_pos) {
+                result.push(next_pos);
             }
         }
 
@@ -141,267 +934,777 @@ impl CostructSolution for FindPlan<'_> {
     }
 }
 
-impl Utility for FindPlan<'_> {
+impl<S, H: Fn(&Position) -> i32> PlanUtility for FindPlan<'_, S, H> {
     fn heuristic(&self, state: &Self::State) -> Self::Cost {
         (self.heuristic)(state)
     }
 }
 
-impl SuitableState for FindPlan<'_> {
+impl<S: Fn(&Cache, &Position) -> bool, H> SuitableState for FindPlan<'_, S, H> {
     fn is_suitable(&self, state: &Self::State) -> bool {
         (self.suitable)(self.cache, state)
     }
 }
 
-pub struct Hero<K> {
+pub struct Hero<K, R = ThreadRng> {
     kb: K,
     obj: Objective,
     t: usize, // time
     cache: Cache,
-    rng: ThreadRng,
-    plan: Option<Vec<Position>>,
+    rng: R,
+    plan: Option<Plan>,
     size_map: usize,
+    arrow: bool,
+    wumpus_dead: bool,
+    // cresce ogni volta che la KB impara un fatto che non è conseguenza di quelli che già
+    // conosceva (percezioni in una casella nuova, esito di un tiro o di un rischio)
+    kb_generation: usize,
+    // posizione e direzione dell'ultimo tiro, l'esito arriva con le percezioni successive
+    last_shot: Option<(Position, Direction)>,
+    // ultime RECENT_POSITIONS posizioni, la più recente in fondo
+    recent: VecDeque<Position>,
+    config: HeroConfig,
+    strategy: Box<dyn Strategy>,
+    stats: HeroStats,
+    budget_exhausted: bool,
+    // spiegazione della decisione del turno in corso
+    trace: Option<DecisionTrace>,
 }
 
 impl<K> Hero<K> {
     pub fn new(kb: K, size_map: usize) -> Self {
+        Self::with_config(kb, size_map, HeroConfig::default())
+    }
+
+    pub fn with_config(kb: K, size_map: usize, config: HeroConfig) -> Self {
+        Hero::with_rng(kb, size_map, config, rand::rng())
+    }
+}
+
+impl<K, R: Rng> Hero<K, R> {
+    /// The rng is used for every random choice of the hero (tie-breaking, risk estimates),
+    /// with a seeded rng the behaviour is deterministic.
+    pub fn with_rng(kb: K, size_map: usize, config: HeroConfig, rng: R) -> Self {
         Self {
             kb: kb,
             t: 0,
             cache: Cache::new(size_map),
-            rng: rand::rng(),
+            rng: rng,
             obj: Objective::TakeGold,
             plan: None,
             size_map: size_map,
+            arrow: true,
+            wumpus_dead: false,
+            kb_generation: 0,
+            last_shot: None,
+            recent: VecDeque::with_capacity(RECENT_POSITIONS),
+            config: config,
+            strategy: Box::new(ClassicStrategy),
+            stats: HeroStats::default(),
+            budget_exhausted: false,
+            trace: None,
         }
     }
 
-    fn utility_take_gold(&mut self, a: &Action, p: &Position) -> i32 {
-        match *a {
-            Action::Move(direction) => {
-                if self.cache.is_visited(&p.move_clone(direction)) {
-                    // costruisci un piano che dalla posizione corrente si sposta in una casella safe non ancora visitata
-                    // l'utilità di questa mossa sarà la lunghezza del piano negativa
-
-                    // il piano utilizzerà BFS perché non mi viene in mente nessuna euristica consistente per questo problema :(
-                    // il costo di una qualsiasi mossa sarà 1, quindi la BFS troverà il piano ottimo
-
-                    // per il principio di ottimalità l'agente continuerà a seguire il path ottimo
-                    // anche al prossimo turno
+    /// Replaces the decision rule used when there is no plan to follow.
+    pub fn set_strategy(&mut self, strategy: Box<dyn Strategy>) {
+        self.strategy = strategy;
+    }
 
-                    // se un piano non esiste allora vuol dire che non possiamo continuare ad esplorare il dungeon
-                    // in sicurezza, quindi siamo costretti a cambiare obbiettivo e tornare a casa senza l'oro
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
 
-                    // Quindi va annullato il piano e va chiamata la funzione utility_go_home e ritornare l'utilità nuova trovata
+    /// Replaces the cache, for example with one saved from a previous episode on the same map.
+    /// The plan is dropped because it was built on the old cache.
+    pub fn restore_cache(&mut self, mut cache: Cache) -> Result<(), CacheError> {
+        cache.validate()?;
+        cache.rebuild_frontier();
+        // le generazioni si riferiscono alla KB che ha prodotto la cache, qui si ricomincia
+        cache.undecided.clear();
+        if cache.map_size() != self.size_map {
+            return Err(CacheError::OutOfBounds(Position::new(
+                cache.map_size(),
+                cache.map_size(),
+            )));
+        }
+        self.cache = cache;
+        self.plan = None;
+        Ok(())
+    }
 
-                    if let Some(plan) = self.plan.clone() {
-                        // assert!(!self.cache.safe_neighbourhood(p));
-                        let pos = p.move_clone(direction);
-                        // let mut final_pos = false;
-                        for (i, pos2) in plan.iter().enumerate() {
-                            if *pos2 == pos {
-                                if i == plan.len() - 1 {
-                                    self.plan = None;
+    /// What the hero knows about every cell, indexed as grid[y][x].
+    pub fn knowledge_grid(&self) -> Vec<Vec<CellKnowledge>> {
+        (0..self.size_map)
+            .map(|y| {
+                (0..self.size_map)
+                    .map(|x| {
+                        let pos = Position::new(x, y);
+                        if self.cache.is_visited(&pos) {
+                            CellKnowledge::Visited
+                        } else if self.cache.is_safe(&pos) {
+                            CellKnowledge::Safe
+                        } else {
+                            match self.cache.unsafe_reason(&pos) {
+                                Some(UnsafeReason::Wumpus) => CellKnowledge::UnsafeWumpus,
+                                Some(UnsafeReason::Pit) => CellKnowledge::UnsafePit,
+                                Some(UnsafeReason::Undetermined) => {
+                                    CellKnowledge::UnsafeUnknownKind
                                 }
-                                return -((plan.len() - i - 1) as i32);
+                                None if self.cache.undecided(&pos).is_some() => {
+                                    CellKnowledge::Undecided
+                                }
+                                None => CellKnowledge::Unknown,
                             }
                         }
-                        return i32::MIN + 1;
-                    } else {
-                        i32::MIN
-                    }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The knowledge grid with the hero in `position` (x), the steps of the current plan
+    /// in order (1-9, + after the ninth), its target (*) and the known wumpuses (W).
+    pub fn render_knowledge(&self, position: Position) -> String {
+        let plan = self.current_plan().unwrap_or_default();
+        let mut result = String::new();
+        for (y, row) in self.knowledge_grid().into_iter().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                let pos = Position::new(x, y);
+                let symbol = if pos == position {
+                    'x'
+                } else if plan.last() == Some(&pos) {
+                    '*'
+                } else if let Some(step) = plan.iter().position(|step| *step == pos) {
+                    char::from_digit(step as u32 + 1, 10).unwrap_or('+')
+                } else if self.cache.wumpuses.contains(&pos) {
+                    'W'
                 } else {
-                    1
-                }
+                    cell.symbol()
+                };
+                result.push(symbol);
+                result.push(' ');
             }
-            Action::Grab => i32::MAX,
-            Action::Shoot(direction) => todo!(),
-            Action::Exit => i32::MIN,
+            result.push('\n');
         }
+        result.push_str(&format!("objective: {:?}\n", self.obj));
+        result
     }
 
-    // ATTENZIONE: il piano potrebbe rimanere null se non ha trovato nessun piano
-    fn create_plan_to_go_home(&mut self, actual_position: Position) {
-        assert!(self.plan.is_none());
+    pub fn current_objective(&self) -> &Objective {
+        &self.obj
+    }
 
-        // crea una frontiera e i nodi esplorati
-        let arena = Bump::new();
-        let problem = FindPlan::new(&self.cache, self.size_map, eq_to_zero, distance_to_zero);
-        let mut resolver = AStarExplorer::new(&problem, &arena);
-        let result = resolver.search(actual_position);
-        if let Some(plan) = result.actions.as_ref() {
-            println!("[INFO] Plan generated: {:?}", plan);
+    /// Steps of the current plan still to be taken.
+    pub fn current_plan(&self) -> Option<&[Position]> {
+        self.plan.as_ref().map(Plan::remaining)
+    }
+
+    /// What the cache says about pos, without asking the knowledge base.
+    pub fn belief(&self, pos: Position) -> Belief {
+        if self.cache.is_safe(&pos) {
+            Belief::Safe
+        } else if self.cache.is_unsafe(&pos) {
+            Belief::Unsafe
+        } else if self.cache.is_gamble(&pos) {
+            Belief::Gamble
         } else {
-            println!("[WARNING] The hero failed to find a plan");
+            Belief::Unknown
         }
-        self.plan = result.actions;
     }
 
-    fn create_plan_gold(&mut self, actual_position: Position) {
-        assert!(self.plan.is_none());
+    /// True while the hero is heading to a cell that is not provably safe.
+    pub fn is_gambling(&self) -> bool {
+        self.cache.gamble.is_some()
+    }
 
-        // crea una frontiera e i nodi esplorati
-        let arena = Bump::new();
-        let problem = FindPlan::new(
+    fn adopt_plan(&mut self, origin: Position, steps: Option<Vec<Position>>) {
+        if let Some(steps) = steps.as_ref() {
+            self.stats.plans += 1;
+            self.stats.plan_steps += steps.len();
+            if self.config.check_invariants {
+                self.check_plan(origin, steps);
+            }
+        }
+        self.plan = steps.map(|steps| Plan::new(origin, steps));
+    }
+
+    // il piano deve essere un cammino minimo verso la sua ultima casella
+    fn check_plan(&self, origin: Position, steps: &[Position]) {
+        let Some(last) = steps.last() else {
+            return;
+        };
+        let goals = HashSet::from([*last]);
+        let (shortest, _) = search_plan(
+            ExplorerKind::Bfs,
             &self.cache,
             self.size_map,
-            Cache::safe_but_not_visited,
-            no_heuristic,
+            &goals,
+            origin,
         );
-        let mut resolver = BFSExplorer::new(&problem, &arena);
-        let result = resolver.search(actual_position);
-        if let Some(plan) = result.actions.as_ref() {
-            println!("[INFO] Plan generated: {:?}", plan);
-        } else {
-            println!("[WARNING] The hero failed to find a plan");
+        let shortest = shortest.as_ref().map(Vec::len);
+        if shortest != Some(steps.len()) {
+            self.invariant_violated(
+                origin,
+                format!(
+                    "plan {:?} from {:?} has {} steps, the BFS shortest path has {:?}",
+                    steps,
+                    origin,
+                    steps.len(),
+                    shortest
+                ),
+            );
         }
-        self.plan = result.actions;
     }
 
-    // true se il piano è stato creato, false altrimenti
-    fn create_plan(&mut self, actual_position: Position) -> bool {
-        match self.obj {
-            Objective::TakeGold => {
+    fn invariant_violated(&self, position: Position, violation: String) -> ! {
+        error!(
+            "Invariant violated in position {:?}: {}",
+            position, violation
+        );
+        error!("Objective: {:?}, turn: {}", self.obj, self.t);
+        error!("Plan: {:?}", self.plan);
+        error!("Cache: {:#?}", self.cache);
+        panic!("invariant violated: {}", violation);
+    }
+
+    // caselle di frontiera verso cui pianificare secondo la politica scelta
+    fn frontier_goals(&self) -> HashSet<Position> {
+        let frontier = self.cache.frontier();
+        match self.config.frontier_policy {
+            FrontierPolicy::Nearest => frontier.clone(),
+            FrontierPolicy::InformationGain => {
+                // si tengono solo le caselle con il massimo numero di vicini sconosciuti,
+                // la ricerca multi-obbiettivo sceglie poi la più vicina tra queste
+                let unknown_neighbours = |pos: &Position| {
+                    pos.neighbor_positions(self.size_map)
+                        .filter(|next| !self.cache.is_safe(next) && !self.cache.is_unsafe(next))
+                        .count()
+                };
+                let best = frontier.iter().map(unknown_neighbours).max().unwrap_or(0);
+                frontier
+                    .iter()
+                    .filter(|pos| unknown_neighbours(pos) == best)
+                    .copied()
+                    .collect()
+            }
+        }
+    }
+
+    // ATTENZIONE: il piano potrebbe rimanere null se non ha trovato nessun piano
+    fn create_plan_to_go_home(&mut self, actual_position: Position) {
+        assert!(self.plan.is_none());
+
+        // crea una frontiera e i nodi esplorati
+        let goals = HashSet::from([Position::new(0, 0)]);
+        let explorer = self.config.home_explorer;
+        let (actions, _) = search_plan(
+            explorer,
+            &self.cache,
+            self.size_map,
+            &goals,
+            actual_position,
+        );
+        if let Some(plan) = actions.as_ref() {
+            info!("Plan generated with {:?}: {:?}", explorer, plan);
+        } else {
+            warn!("The hero failed to find a plan with {:?}", explorer);
+        }
+        self.adopt_plan(actual_position, actions);
+    }
+
+    fn create_plan_gold(&mut self, actual_position: Position) {
+        assert!(self.plan.is_none());
+
+        // obbiettivi: le caselle safe non ancora visitate, la distanza manhattan dalla più vicina
+        // è ammissibile perché ogni mossa cambia la distanza da una casella fissa al più di 1
+        let goals = self.frontier_goals();
+        if log_enabled!(Level::Debug) {
+            // in ordine per avere log uguali a ogni esecuzione
+            let mut listing: Vec<&Position> = goals.iter().collect();
+            listing.sort();
+            debug!("Frontier goals: {:?}", listing);
+        }
+
+        let explorer = self.config.explorer;
+        let (actions, n_iter) = search_plan(
+            explorer,
+            &self.cache,
+            self.size_map,
+            &goals,
+            actual_position,
+        );
+        if let Some(plan) = actions.as_ref() {
+            info!(
+                "Plan generated with {:?}: {:?} ({} iterations)",
+                explorer, plan, n_iter
+            );
+        } else {
+            warn!("The hero failed to find a plan with {:?}", explorer);
+        }
+
+        if self.config.cross_check_plans {
+            let (check, check_iter) = search_plan(
+                ExplorerKind::Bfs,
+                &self.cache,
+                self.size_map,
+                &goals,
+                actual_position,
+            );
+            let (planned, bfs) = (actions.as_ref().map(Vec::len), check.as_ref().map(Vec::len));
+            if planned != bfs {
+                warn!(
+                    "{:?} plan length {:?} differs from BFS plan length {:?}",
+                    explorer, planned, bfs
+                );
+            } else {
+                info!(
+                    "Plan cross-checked: {:?} {} iterations, BFS {} iterations",
+                    explorer, n_iter, check_iter
+                );
+            }
+        }
+        self.adopt_plan(actual_position, actions);
+    }
+
+    // piano verso la casella di mira più vicina, arrivati lì la direzione del tiro è in cache.aim
+    fn create_plan_to_shoot(&mut self, from: Position) {
+        assert!(self.plan.is_none());
+
+        if self.cache.is_aim_spot(&from) {
+            self.plan = Some(Plan::new(from, vec![]));
+            return;
+        }
+        let goals: HashSet<Position> = self.cache.aim.keys().copied().collect();
+        let (actions, _) = search_plan(
+            self.config.explorer,
+            &self.cache,
+            self.size_map,
+            &goals,
+            from,
+        );
+        if let Some(plan) = actions.as_ref() {
+            info!("Plan generated to shoot the Wumpus: {:?}", plan);
+        } else {
+            warn!("The hero failed to find a plan to shoot the Wumpus");
+        }
+        self.adopt_plan(from, actions);
+    }
+
+    fn create_plan_gamble(&mut self, actual_position: Position) {
+        assert!(self.plan.is_none());
+
+        let arena = Bump::new();
+        let Some(target) = self.cache.gamble else {
+            return;
+        };
+        let problem = FindPlan::new(
+            &self.cache,
+            self.size_map,
+            move |_: &Cache, pos: &Position| *pos == target,
+            no_heuristic,
+        );
+        let mut resolver = BFSExplorer::new(&problem, &arena);
+        let result = resolver.search(actual_position);
+        if let Some(plan) = result.actions.as_ref() {
+            info!("Plan generated to take the risk: {:?}", plan);
+        } else {
+            warn!("The hero failed to find a plan to take the risk");
+        }
+        self.adopt_plan(actual_position, result.actions);
+    }
+
+    // true se il piano è stato creato, false altrimenti
+    fn create_plan(&mut self, actual_position: Position) -> bool {
+        let start = self.config.timing.then(Instant::now);
+        let created = self.plan_for_objective(actual_position);
+        self.stats.timings.planning += elapsed(start);
+        created
+    }
+
+    fn plan_for_objective(&mut self, actual_position: Position) -> bool {
+        match self.obj {
+            Objective::TakeGold => {
                 if self.cache.safe_neighbourhood(&actual_position) {
                     return true;
                 } else {
                     self.create_plan_gold(actual_position);
                 }
             }
+            Objective::KillWumpus => self.create_plan_to_shoot(actual_position),
+            Objective::Gamble => self.create_plan_gamble(actual_position),
             Objective::GoHome => self.create_plan_to_go_home(actual_position),
         };
         self.plan.is_some()
     }
+}
 
-    fn utility_go_home(&mut self, a: &Action, p: &Position) -> i32 {
-        // inizia una ricarca A* per trovare il cammino ottimo per andare dalla posizione
-        // fino alla casella (0,0)
-        // euristica: distanza manhattan dalla posizione della cella fino al punto (0,0):
-        // quindi h(x,y) =(x - 0) + (y - 0) = x + y
+impl<K: KnowledgeBase<Query: fmt::Debug>, R: Rng> Hero<K, R> {
+    fn ask(&mut self, formula: &K::Query) -> Result<bool, SolverError> {
+        self.stats.asks += 1;
+        let start = self.config.timing.then(Instant::now);
+        let result = self.kb.ask(formula);
+        self.stats.timings.asks += elapsed(start);
+        result
+    }
 
-        // crea una funzione di utilità che preferisce tutte le mosse che portano
-        // dalla posizione corrente fino alla cella (0,0)
+    fn ask_batch(&mut self, formulas: &[K::Query]) -> Result<Vec<bool>, SolverError> {
+        self.stats.asks += formulas.len();
+        let start = self.config.timing.then(Instant::now);
+        let result = if self.config.parallel_queries {
+            self.kb.ask_batch_parallel(formulas)
+        } else {
+            self.kb.ask_batch(formulas)
+        };
+        self.stats.timings.asks += elapsed(start);
+        result
+    }
 
-        // Sia G il cammino ottimo [n,n',...,n_0] allora la funzione di utilità
-        // dovrà dare ad ogni nodo n la seguente utilità:
-        // -h(n.x,n.y)
-        // dato che l'agente cercarà di massimizzare l'utilità lo porterà alla cella (0,0)
+    fn tell(&mut self, formula: &K::Query) {
+        self.stats.tells += 1;
+        self.kb.tell(formula);
+    }
 
-        // G sarà il "piano" dell'agente, se il piano esiste allora usa quello esistente per
-        // dare l'utilità alle posizioni
-        // se il piano agente non esiste allora creane uno partendo dalla posizione attuale
+    /// Clauses and variables of the knowledge base.
+    pub fn kb_size(&self) -> (usize, usize) {
+        self.kb.size()
+    }
 
-        // Tutte le altre mosse hanno utilità -inf, tranne dell'azione Exit che avrà utilità +inf
+    /// Approximate bytes held by the knowledge base.
+    pub fn kb_memory(&self) -> MemoryFootprint {
+        self.kb.memory_footprint()
+    }
 
-        assert!(self.plan.is_some());
+    /// Work done so far in this game.
+    pub fn stats(&self) -> HeroStats {
+        HeroStats {
+            solver_calls: self.kb.solver_calls(),
+            cells_visited: self.cache.visited.len(),
+            cells_classified: self.cache.safe.len() + self.cache._unsafe.len(),
+            turns: self.t,
+            budget_exhausted: self.budget_exhausted as usize,
+            ..self.stats
+        }
+    }
 
-        let plan = self.plan.as_ref().expect("The plan was found");
+    // inferenza su più caselle insieme: (è sicura, è stata appena scoperta pericolosa) per ognuna.
+    // Le interrogazioni sono due in blocco, sicura/pericolosa e poi wumpus/pozzo: dire alla KB
+    // una conseguenza non cambia i suoi modelli, quindi il risultato è lo stesso di una casella alla volta
+    fn infer_cells(&mut self, cells: &[Position]) -> Result<Vec<(bool, bool)>, SolverError> {
+        let mut result = vec![(false, false); cells.len()];
+        let mut unknown = vec![];
+        for (i, pos) in cells.iter().enumerate() {
+            if self.cache.is_safe(pos) {
+                debug!("Cached Inference, SAFE position: {:?}", pos);
+                self.stats.cached_asks += 1;
+                result[i] = (true, false);
+            } else if self.cache.is_unsafe(pos) {
+                debug!("Cached Inference, UNSAFE position: {:?}", pos);
+                self.stats.cached_asks += 1;
+            } else if self
+                .cache
+                .undecided(pos)
+                .is_some_and(|(_, generation)| generation == self.kb_generation)
+            {
+                debug!(
+                    "Still undecided, nothing new since the last attempt: {:?}",
+                    pos
+                );
+                self.stats.cached_asks += 1;
+            } else if self.cache.knows(pos, Fact::Pit) {
+                // tornata sconosciuta dopo la morte del wumpus, ma il pozzo era già dimostrato
+                debug!("Known fact, PIT in position: {:?}", pos);
+                self.stats.cached_asks += 1;
+                self.cache
+                    .insert_pit(*pos)
+                    .expect("the knowledge base is consistent");
+            } else {
+                unknown.push(i);
+            }
+        }
+        if unknown.is_empty() {
+            return Ok(result);
+        }
 
-        match *a {
-            Action::Move(direction) => {
-                let mut found = false;
-                let next_pos = p.move_clone(direction);
-                let mut index = None;
-                for (i, pos) in plan.iter().enumerate() {
-                    if *pos == next_pos {
-                        found = true;
-                        index = i.into();
-                        break;
-                    }
+        // il wumpus morto non è un pericolo: basta che non ci sia un pozzo
+        let mut queries = Vec::with_capacity(2 * unknown.len());
+        for &i in &unknown {
+            if self.wumpus_dead {
+                queries.push(K::create_no_pit_formula(&cells[i]));
+                queries.push(K::create_pit_formula(&cells[i]));
+            } else {
+                queries.push(K::create_safe_formula(&cells[i]));
+                queries.push(K::create_unsafe_formula(&cells[i]));
+            }
+        }
+        let answers = self.ask_batch(&queries)?;
+        let mut discovered = vec![];
+        for (k, &i) in unknown.iter().enumerate() {
+            let pos = cells[i];
+            if answers[2 * k] {
+                self.tell(&queries[2 * k]);
+                self.cache
+                    .insert_safe(pos)
+                    .expect("the knowledge base is consistent");
+                self.cache.record_fact(pos, Fact::Safe, self.t);
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.inferred_safe.push(pos);
                 }
-                if found {
-                    index.unwrap() as i32
-                } else {
-                    i32::MIN
+                debug!("Inferred: {:?}", queries[2 * k]);
+                result[i] = (true, false);
+            } else if answers[2 * k + 1] {
+                info!("Unsafe Position: {:?}", pos);
+                self.tell(&queries[2 * k + 1]);
+                self.cache
+                    .insert_unsafe(pos)
+                    .expect("the knowledge base is consistent");
+                self.cache.record_fact(pos, Fact::Unsafe, self.t);
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.inferred_unsafe.push(pos);
                 }
+                discovered.push(pos);
+                result[i] = (false, true);
+            } else {
+                debug!("can't tell if the position {:?} is SAFE or UNSAFE", pos);
+                self.cache
+                    .undecided
+                    .insert(pos, (self.t, self.kb_generation));
+            }
+        }
+        if discovered.is_empty() {
+            return Ok(result);
+        }
+
+        // pericolosa senza il wumpus vuol dire pozzo, non serve chiederlo
+        let (known_pits, discovered): (Vec<Position>, Vec<Position>) = discovered
+            .into_iter()
+            .partition(|pos| self.cache.knows(pos, Fact::NoWumpus));
+        for pos in known_pits {
+            info!("Found a Pit: {:?}", pos);
+            self.stats.cached_asks += 1;
+            self.cache
+                .insert_pit(pos)
+                .expect("the knowledge base is consistent");
+            self.cache.record_fact(pos, Fact::Pit, self.t);
+        }
+        if discovered.is_empty() {
+            return Ok(result);
+        }
+
+        let mut queries = Vec::with_capacity(2 * discovered.len());
+        for pos in &discovered {
+            queries.push(K::create_wumpus_formula(pos));
+            queries.push(K::create_pit_formula(pos));
+        }
+        let answers = self.ask_batch(&queries)?;
+        for (k, pos) in discovered.into_iter().enumerate() {
+            if !self.wumpus_dead && answers[2 * k] {
+                self.tell(&queries[2 * k]);
+                info!("Found the Wumpus: {:?}", pos);
+                self.cache
+                    .insert_wumpus(pos)
+                    .expect("the knowledge base is consistent");
+                self.cache.record_fact(pos, Fact::Wumpus, self.t);
+            } else if answers[2 * k + 1] {
+                info!("Found a Pit: {:?}", pos);
+                self.tell(&queries[2 * k + 1]);
+                self.cache
+                    .insert_pit(pos)
+                    .expect("the knowledge base is consistent");
+                self.cache.record_fact(pos, Fact::Pit, self.t);
+            } else {
+                debug!("Can't tell if in {:?} there is a Pit or the Wumpus", pos);
             }
-            Action::Grab => i32::MAX,
-            Action::Shoot(direction) => i32::MIN,
-            Action::Exit => i32::MAX,
         }
+        Ok(result)
     }
 
-    fn utility(&mut self, a: &Action, p: &Position) -> i32 {
-        match self.obj {
-            Objective::TakeGold => self.utility_take_gold(a, p),
-            Objective::GoHome => self.utility_go_home(a, p),
+    // per ogni casella di cells dice se è sicura. Ogni casella scoperta pericolosa può rendere
+    // decidibili le caselle vicine a lei e alla posizione originale: vengono esaminate a livelli,
+    // ognuno con una sola inferenza in blocco, al più una volta per chiamata e fino a propagation_depth livelli
+    fn are_safe(
+        &mut self,
+        cells: &[Position],
+        original_position: Position,
+    ) -> Result<Vec<bool>, SolverError> {
+        let mut seen: HashSet<Position> = cells.iter().copied().collect();
+        let mut layer = cells.to_vec();
+        let mut result = None;
+        let mut depth = 0;
+        while !layer.is_empty() {
+            let inferred = self.infer_cells(&layer)?;
+            result.get_or_insert_with(|| inferred.iter().map(|(safe, _)| *safe).collect());
+            if self
+                .config
+                .propagation_depth
+                .is_some_and(|max| depth >= max)
+            {
+                break;
+            }
+            depth += 1;
+            let mut next_layer = vec![];
+            for (current, (_, discovered_unsafe)) in layer.into_iter().zip(inferred) {
+                if !discovered_unsafe {
+                    continue;
+                }
+                debug!(
+                    "searching for other inference around {:?} and {:?}",
+                    current, original_position
+                );
+                for center in [current, original_position] {
+                    for next in center.neighbor_positions(self.size_map) {
+                        if seen.insert(next) {
+                            next_layer.push(next);
+                        }
+                    }
+                }
+            }
+            layer = next_layer;
         }
+        Ok(result.unwrap_or_default())
     }
-}
 
-impl<K: KnowledgeBase<Query: fmt::Debug>> Hero<K> {
-    fn is_safe(&mut self, pos: Position, original_position: Position) -> bool {
-        if self.cache.is_safe(&pos) {
-            println!("[INFO] Cached Inference, SAFE position: {:?}", pos);
-            return true;
-        }
-        if self.cache.is_unsafe(&pos) {
-            println!("[INFO] Cached Inference, UNSAFE position: {:?}", pos);
-            return false;
-        }
-        let safe_formula = K::create_safe_formula(&pos);
-        if self.kb.ask(&safe_formula) {
-            self.kb.tell(&safe_formula);
-            self.cache.safe.insert(pos);
-            println!("[INFO] Inferred: {:?}", safe_formula);
-            true
-        } else {
-            let unsafe_formula = K::create_unsafe_formula(&pos);
-            if self.kb.ask(&unsafe_formula) {
-                println!("[INFO] Unsafe Position: {:?}", pos);
-                self.kb.tell(&unsafe_formula);
-                self.cache._unsafe.insert(pos.clone());
-                if self.kb.ask(&K::create_wumpus_formula(&pos)) {
-                    self.kb.tell(&K::create_wumpus_formula(&pos));
-                    println!("[INFO] Found the Wumpus: {:?}", pos);
-                    self.cache.wumpus = pos.into();
+    // cerca tra le caselle sicure quelle allineate con il wumpus, da cui una freccia lo colpisce
+    // sicuramente, true se ne esiste almeno una
+    fn prepare_shot(&mut self) -> Result<bool, SolverError> {
+        self.cache.aim.clear();
+        if !self.arrow {
+            return Ok(false);
+        }
+        // ordinate per avere un comportamento deterministico con un rng fissato
+        let mut spots: Vec<Position> = self.cache.safe.iter().copied().collect();
+        spots.sort();
+        for spot in spots {
+            for dir in Direction::ALL {
+                let ray = spot.ray(dir, self.size_map);
+                if ray.is_empty() {
+                    continue;
+                }
+                let hit = if !self.cache.wumpuses.is_empty() {
+                    ray.iter().any(|pos| self.cache.there_is_the_wumpus(pos))
+                } else if ray
+                    .iter()
+                    .all(|pos| self.cache.is_safe(pos) || self.cache.knows(pos, Fact::NoWumpus))
+                {
+                    // nessuna casella del raggio può contenere il wumpus
+                    self.stats.cached_asks += 1;
+                    false
                 } else {
-                    println!("[INFO] Found a Pit: {:?}", pos);
-                    self.kb.tell(&K::create_pit_formula(&pos));
+                    self.ask(&K::create_wumpus_in_formula(&ray))?
+                };
+                if hit {
+                    info!("The Wumpus can be shot from {:?} to {:?}", spot, dir);
+                    self.cache.aim.insert(spot, dir);
+                    break;
                 }
-                // use Direction::*;
-                // println!(
-                //     "[INFO] searching for other inference, searching around the point: {:?}",
-                //     pos
-                // );
-                // for dir in [North, Sud, East, Ovest] {
-                //     if pos.possible_move(dir, self.size_map) {
-                //         println!("    searching: {:?}", pos.move_clone(dir));
-                //         self.is_safe(pos.move_clone(dir), original_position);
-                //     }
-                // }
-                // println!(
-                //     "[INFO] searching for other inference, searching around the ORIGINAL point: {:?}",
-                //     original_position
-                // );
-                // for dir in [North, Sud, East, Ovest] {
-                //     if original_position.possible_move(dir, self.size_map) {
-                //         println!("    searching: {:?}", pos.move_clone(dir));
-                //         self.is_safe(original_position.move_clone(dir), original_position);
-                //     }
-                // }
-            } else {
-                println!(
-                    "[INFO] can't tell if the position {:?} is SAFE or UNSAFE",
-                    pos
-                );
             }
-            false
         }
+        Ok(!self.cache.aim.is_empty())
     }
 
-    pub fn next_action(&mut self, p: Perceptions) -> Action {
-        use crate::world::Action::*;
-        use crate::world::Direction::*;
+    // aggiorna KB e cache con l'esito dell'ultimo tiro
+    fn consume_shot(&mut self, p: &Perceptions) {
+        let Some((from, dir)) = self.last_shot.take() else {
+            return;
+        };
+        let ray = from.ray(dir, p.board_size);
+        self.kb_generation += 1;
+        if p.howl() {
+            info!("Howl heard, the Wumpus is dead");
+            self.wumpus_dead = true;
+            self.tell(&K::create_wumpus_in_formula(&ray));
+            // la freccia si ferma sul primo wumpus incontrato lungo il raggio
+            if let Some(wumpus) = ray.iter().find(|pos| self.cache.there_is_the_wumpus(pos)) {
+                // nel mondo ogni casella contiene al più un'entità, quindi lì non c'è un pozzo
+                self.tell(&K::create_no_pit_formula(wumpus));
+            }
+            // le caselle pericolose solo per il wumpus colpito tornano sicure o da rivalutare
+            self.cache.on_wumpus_killed(&ray);
+            // le caselle chiuse dal wumpus si possono esplorare
+            self.obj = Objective::TakeGold;
+        } else {
+            info!("No howl, the Wumpus is not in {:?}", ray);
+            self.tell(&K::create_no_wumpus_in_formula(&ray));
+            for pos in ray {
+                self.cache.record_fact(pos, Fact::NoWumpus, self.t);
+            }
+            // l'esplorazione era già esaurita e senza freccia non resta che uscire
+            self.obj = Objective::GoHome;
+            info!("Changed Plan, go home");
+        }
+    }
 
-        println!("{:?}", p);
+    // sceglie la casella di frontiera meno rischiosa, true se il rischio stimato è sotto la soglia
+    fn prepare_gamble(&mut self) -> Result<bool, SolverError> {
+        self.cache.gamble = None;
+        if self.config.risk_threshold <= 0.0 {
+            return Ok(false);
+        }
+        // frontiera: caselle non visitate e non note come pericolose, adiacenti a una casella visitata
+        let mut frontier = vec![];
+        for pos in &self.cache.visited {
+            for next in pos.neighbor_positions(self.size_map) {
+                if !self.cache.is_visited(&next)
+                    && !self.cache.is_unsafe(&next)
+                    && !frontier.contains(&next)
+                {
+                    frontier.push(next);
+                }
+            }
+        }
+        frontier.sort();
+        if frontier.is_empty() {
+            return Ok(false);
+        }
+        let risks = self
+            .kb
+            .hazard_estimates(&frontier, self.wumpus_dead, self.rng.random())?;
+        let (target, risk) = frontier
+            .into_iter()
+            .zip(risks)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("the frontier is not empty");
+        if risk < self.config.risk_threshold {
+            info!(
+                "Gamble: moving to {:?} with estimated risk {:.3}",
+                target, risk
+            );
+            self.cache.gamble = Some(target);
+            Ok(true)
+        } else {
+            info!(
+                "No gamble: the least risky cell {:?} has estimated risk {:.3}",
+                target, risk
+            );
+            Ok(false)
+        }
+    }
 
-        if !self.kb.consistency() {
-            println!("[FATAL ERROR] Inconsistency found in the knowledge base");
-            exit(1);
+    // se l'eroe è sopravvissuto nella casella rischiosa, allora era sicura
+    fn consume_gamble(&mut self, p: &Perceptions) {
+        if !self.cache.is_gamble(&p.position) {
+            return;
         }
+        info!("Gamble won, the position {:?} is safe", p.position);
+        let formula = if self.wumpus_dead {
+            K::create_no_pit_formula(&p.position)
+        } else {
+            K::create_safe_formula(&p.position)
+        };
+        self.tell(&formula);
+        self.kb_generation += 1;
+        self.cache
+            .insert_safe(p.position)
+            .expect("the gamble cell was not known as unsafe");
+        self.cache.gamble = None;
+        self.obj = Objective::TakeGold;
+        self.plan = None;
+    }
+
+    // azioni eseguibili dalla posizione corrente, le mosse verso caselle non in cache vengono inferite
+    fn suitable_actions(&mut self, p: &Perceptions) -> Result<Vec<Action>, HeroError> {
+        use crate::world::Action::*;
 
-        self.kb.tell(&K::create_ground_truth_from_perception(&p));
         let mut suitable_actions = vec![];
         let mut action_to_consider = Vec::with_capacity(9);
 
@@ -409,112 +1712,1807 @@ impl<K: KnowledgeBase<Query: fmt::Debug>> Hero<K> {
             suitable_actions.push(Exit);
         }
 
-        for dir in [North, Sud, East, Ovest] {
-            if p.position.possible_move(dir, p.board_size) {
-                if !self.cache.is_unsafe(&p.position.move_clone(dir)) {
-                    if self.cache.is_safe(&p.position.move_clone(dir)) {
-                        println!(
-                            "[INFO] Cached Inference, SAFE position: {:?}",
-                            &p.position.move_clone(dir)
-                        );
-                        suitable_actions.push(Move(dir));
-                    } else {
-                        action_to_consider.push(Move(dir));
-                    }
+        for (dir, next) in p.position.neighbors(p.board_size) {
+            if !self.cache.is_unsafe(&next) {
+                if self.cache.is_safe(&next) {
+                    debug!("Cached Inference, SAFE position: {:?}", &next);
+                    suitable_actions.push(Move(dir));
                 } else {
-                    println!(
-                        "[INFO] Cached Inference, UNSAFE position: {:?}",
-                        &p.position.move_clone(dir)
-                    );
+                    action_to_consider.push(Move(dir));
                 }
+            } else {
+                debug!("Cached Inference, UNSAFE position: {:?}", &next);
             }
         }
 
-        if p.glitter {
+        if p.glitter() {
             suitable_actions.push(Grab);
-            self.obj = Objective::GoHome;
-            self.plan = None;
-            println!("[INFO] Changed Plan,found gold, go home");
+            // in modalità esplorazione completa si prende l'oro e si continua ad esplorare
+            if !self.config.explore_all {
+                self.obj = Objective::GoHome;
+                self.plan = None;
+                info!("Changed Plan,found gold, go home");
+            }
         }
 
-        // TODO: add arrow
+        if self.obj == Objective::KillWumpus
+            && let Some(dir) = self.cache.aim.get(&p.position)
+        {
+            suitable_actions.push(Shoot(*dir));
+        }
 
-        for a in action_to_consider {
-            match a {
-                Move(direction) => {
-                    if self.is_safe(p.position.move_clone(direction), p.position.clone()) {
-                        suitable_actions.push(a);
-                    }
+        if self.obj == Objective::Gamble {
+            for (dir, next) in p.position.neighbors(p.board_size) {
+                if self.cache.is_gamble(&next) {
+                    suitable_actions.push(Move(dir));
                 }
-                Grab => panic!("is already considered action grabbing the gold"),
-                Shoot(direction) => todo!(),
-                Exit => panic!("is already considered action exit the dangeon"),
-            }
-
-            // let formula = K::create_query_from_action(&a, &p.position);
-            // if self.kb.ask(&formula) {
-            //     println!("[INFO] Inferred: {:?}", formula);
-            //     suitable_actions.push(a);
-            //     self.kb.tell(&formula);
-            //     for pos in self.kb.safe_positions(formula).into_iter() {
-            //         self.cache.safe.insert(pos);
-            //     }
-            // } else {
-            //     match a {
-            //         Move(dir) => {
-            //             if self.kb.is_unsafe(p.position.move_clone(dir)) {
-            //                 self.cache._unsafe.insert(p.position.move_clone(dir));
-            //             }
-            //         }
-            //         _ => {}
-            //     }
-            // }
+            }
+        }
+
+        // le caselle vicine ancora sconosciute vengono decise tutte insieme
+        action_to_consider.retain(|a| {
+            // Grab, Exit e Shoot sono già state considerate sopra
+            debug_assert!(matches!(a, Move(_)), "{:?} is already considered", a);
+            matches!(a, Move(_))
+        });
+        let candidates: Vec<Position> = action_to_consider
+            .iter()
+            .filter_map(|a| match a {
+                Move(direction) => p.position.checked_move(*direction, p.board_size),
+                _ => None,
+            })
+            .collect();
+        let safe = self.are_safe(&candidates, p.position)?;
+        for (a, safe) in action_to_consider.into_iter().zip(safe) {
+            if safe {
+                suitable_actions.push(a);
+            }
         }
         assert!(self.cache.is_safe(&p.position));
-        self.cache.visited.insert(p.position);
-        if self.plan.as_ref().map_or(true, |x| x.is_empty()) {
+        self.cache
+            .insert_visited(p.position)
+            .expect("the hero is in a safe position");
+        Ok(suitable_actions)
+    }
+
+    // crea un nuovo piano se quello corrente è esaurito, cambiando obbiettivo se serve
+    fn ensure_plan(&mut self, p: &Perceptions) -> Result<(), HeroError> {
+        if self.plan.as_ref().is_none_or(Plan::is_finished) {
             self.plan = None;
             if !self.create_plan(p.position) {
                 assert!(self.obj != Objective::GoHome);
-                self.obj = Objective::GoHome;
-                println!("[INFO] Changed Plan, go home");
-                assert!(self.create_plan(p.position))
+                let exploring = self.obj == Objective::TakeGold;
+                let mut switched = false;
+                if exploring && self.prepare_shot()? {
+                    self.obj = Objective::KillWumpus;
+                    switched = true;
+                    info!("Changed Plan, shoot the Wumpus");
+                } else if exploring && self.prepare_gamble()? {
+                    self.obj = Objective::Gamble;
+                    switched = true;
+                    info!("Changed Plan, take a risk");
+                }
+                if !switched || !self.create_plan(p.position) {
+                    self.obj = Objective::GoHome;
+                    info!("Changed Plan, go home");
+                    // dalla casella iniziale non serve nessun piano, l'eroe esce subito
+                    if !self.at_home_to_exit(p) && !self.create_plan(p.position) {
+                        return Err(HeroError::PlanningFailed(p.position));
+                    }
+                }
             }
         }
+        Ok(())
+    }
 
-        println!("[INFO] Suitable actions: {:?}", suitable_actions);
+    // l'eroe deve tornare a casa ed è già nella casella iniziale (senza oro da prendere)
+    fn at_home_to_exit(&self, p: &Perceptions) -> bool {
+        self.obj == Objective::GoHome && p.position == Position::new(0, 0) && !p.glitter()
+    }
 
-        let mut best = suitable_actions.get(0);
-        let mut best_utility = best.map_or(i32::MIN, |x| self.utility(x, &p.position));
-        for action in &suitable_actions {
-            let new_utility = self.utility(&action, &p.position);
-            if new_utility > best_utility
-            /* || (best_utility == i32::MIN && new_utility == i32::MIN) */
-            {
-                best = action.into();
-                best_utility = new_utility;
-            } else if new_utility == best_utility {
-                if self.rng.random_bool(0.5) {
-                    best = action.into();
+    // azione con utilità massima (i pareggi vengono risolti a caso), None se sono tutte proibite
+    fn select_action(&mut self, suitable_actions: &[Action], p: &Perceptions) -> Option<Action> {
+        let ctx = DecisionContext {
+            cache: &self.cache,
+            plan: self.plan.as_ref(),
+            objective: &self.obj,
+            perceptions: p,
+            recent: &self.recent,
+        };
+        let scored: Vec<(Action, Utility)> = suitable_actions
+            .iter()
+            .map(|action| (*action, self.strategy.score_action(&ctx, action)))
+            .collect();
+        if let Some(trace) = self.trace.as_mut() {
+            trace.candidates = scored.clone();
+        }
+        choose_best(scored, &mut self.rng)
+    }
+
+    // scarta il piano se uno dei passi rimanenti non è più considerato sicuro
+    // o se l'eroe non si trova dove il piano si aspetta
+    fn validate_plan(&mut self, position: Position) {
+        let Some(plan) = self.plan.as_ref() else {
+            return;
+        };
+        if plan.expected_position() != position {
+            info!(
+                "Plan invalidated, the hero is in {:?} instead of {:?}",
+                position,
+                plan.expected_position()
+            );
+            self.plan = None;
+        } else if let Some(step) = plan
+            .remaining()
+            .iter()
+            .find(|step| !self.cache.is_safe(step) && !self.cache.is_gamble(step))
+        {
+            info!("Plan invalidated, the step {:?} is no longer safe", step);
+            self.plan = None;
+        }
+    }
+
+    // ricorda la posizione e se l'eroe sta andando avanti e indietro tra due caselle
+    // (a, b, a, b) scarta il piano, che viene ricalcolato sulla cache aggiornata
+    fn track_oscillation(&mut self, position: Position) {
+        if self.recent.len() == RECENT_POSITIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(position);
+        if self.recent.len() < 4 {
+            return;
+        }
+        let n = self.recent.len();
+        let (a, b) = (self.recent[n - 1], self.recent[n - 2]);
+        if a != b && self.recent[n - 3] == a && self.recent[n - 4] == b {
+            warn!(
+                "The hero is oscillating between {:?} and {:?}, replanning",
+                a, b
+            );
+            self.plan = None;
+            self.recent.clear();
+        }
+    }
+
+    // se il prossimo passo del piano è adiacente e ancora sicuro restituisce la mossa corrispondente
+    // e avanza il piano, altrimenti il piano viene scartato
+    fn follow_plan(&mut self, p: &Perceptions) -> Option<Action> {
+        let next = self.plan.as_ref()?.next_step()?;
+        let dir = p
+            .position
+            .neighbors(p.board_size)
+            .find(|(_, pos)| *pos == next)
+            .map(|(dir, _)| dir);
+        let valid = self.cache.is_safe(&next) || self.cache.is_gamble(&next);
+        match dir {
+            Some(dir) if valid => {
+                self.plan.as_mut().unwrap().advance();
+                assert!(self.cache.is_safe(&p.position));
+                self.cache
+                    .insert_visited(p.position)
+                    .expect("the hero is in a safe position");
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.from_plan = true;
                 }
+                Some(Action::Move(dir))
+            }
+            _ => {
+                info!(
+                    "Plan invalidated, the step {:?} can't be taken from {:?}",
+                    next, p.position
+                );
+                self.plan = None;
+                None
             }
         }
+    }
+
+    fn commit_action(&mut self, a: Action, p: &Perceptions) -> Action {
+        use crate::world::Action::*;
 
-        if best_utility == i32::MIN || best_utility == i32::MIN + 1 {
-            println!("[WARNING] not good actions");
+        // self.kb.tell(self.create_action_tell(&a));
+        debug!("Action choosen: {:?}", a);
+        // la casella in cui entra l'eroe, None anche per una mossa contro il muro
+        let target = match a {
+            Move(dir) => p.position.checked_move(dir, p.board_size),
+            _ => None,
+        };
+        if let Some(trace) = self.trace.as_mut() {
+            trace.action = Some(a);
+            trace.objective = self.obj;
+            trace.plan = self
+                .plan
+                .as_ref()
+                .map_or(vec![], |plan| plan.remaining().to_vec());
+            if let Some(target) = target {
+                let support = if trace.inferred_safe.contains(&target) {
+                    Support::Inferred
+                } else if self.cache.is_gamble(&target) {
+                    Support::Gamble
+                } else {
+                    Support::Cached
+                };
+                trace.target = Some((target, support));
+            }
+        }
+        if let Move(dir) = a {
+            let safe = target
+                .is_some_and(|target| self.cache.is_safe(&target) || self.cache.is_gamble(&target));
+            if self.config.check_invariants && !safe {
+                self.invariant_violated(
+                    p.position,
+                    format!("move {:?} to {:?}, which is not safe", dir, target),
+                );
+            }
+        }
+        if let Shoot(dir) = a {
+            self.arrow = false;
+            self.last_shot = Some((p.position, dir));
+            self.cache.aim.clear();
+            // il prossimo obbiettivo dipende dall'urlo, lo sceglie consume_shot
             self.plan = None;
-            self.create_plan(p.position);
-            return self.next_action(p);
         }
+        self.t += 1;
+        a
+    }
 
-        if let Some(a) = best {
-            // self.kb.tell(self.create_action_tell(&a));
-            println!("[INFO] Action choosen: {:?}", a);
-            self.t += 1;
-            return *a;
-        } else {
-            println!("[ERROR] no action possible");
-            exit(1);
+    pub fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+        self.next_action_traced(p).map(|(a, _)| a)
+    }
+
+    /// Like next_action, together with the reasons of the choice.
+    pub fn next_action_traced(
+        &mut self,
+        p: Perceptions,
+    ) -> Result<(Action, DecisionTrace), HeroError> {
+        self.trace = Some(DecisionTrace::new(self.t, p.position, self.obj));
+        let start = self.config.timing.then(Instant::now);
+        let before = self.stats.timings;
+        let result = self.decide(p);
+        if let Some(start) = start {
+            self.stats.timings.turn += start.elapsed();
+            info!(
+                "Turn {} timings: {}",
+                self.t,
+                self.stats.timings.since(&before)
+            );
+        }
+        let trace = self
+            .trace
+            .take()
+            .expect("the trace is set for the whole turn");
+        result.map(|a| (a, trace))
+    }
+
+    fn decide(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+        debug!("{:?}", p);
+
+        let every = self.config.consistency_check_every;
+        if every > 0 && self.t.is_multiple_of(every) {
+            let start = self.config.timing.then(Instant::now);
+            let consistency = self.kb.consistency();
+            self.stats.timings.consistency += elapsed(start);
+            consistency.map_err(HeroError::InconsistentKb)?;
+        }
+
+        self.tell(&K::create_ground_truth_from_perception(&p));
+        if !self.cache.is_visited(&p.position) {
+            self.kb_generation += 1;
+        }
+        if p.glitter() {
+            self.cache.record_fact(p.position, Fact::Gold, self.t);
+        }
+        self.consume_shot(&p);
+        self.consume_gamble(&p);
+        self.validate_plan(p.position);
+        self.track_oscillation(p.position);
+
+        if !self.budget_exhausted && self.config.max_steps.is_some_and(|max| self.t >= max) {
+            warn!("Step budget of {} turns exhausted, go home", self.t);
+            self.budget_exhausted = true;
+            self.obj = Objective::GoHome;
+            self.plan = None;
+            self.cache.gamble = None;
+        }
+
+        if self.at_home_to_exit(&p) {
+            return Ok(self.commit_action(Action::Exit, &p));
+        }
+
+        // con l'oro nella casella si prende l'oro invece di seguire il piano
+        if !p.glitter()
+            && let Some(a) = self.follow_plan(&p)
+        {
+            return Ok(self.commit_action(a, &p));
+        }
+
+        for _ in 0..self.config.planning_attempts {
+            let suitable_actions = self.suitable_actions(&p)?;
+            self.ensure_plan(&p)?;
+            if self.at_home_to_exit(&p) {
+                return Ok(self.commit_action(Action::Exit, &p));
+            }
+            if !p.glitter()
+                && let Some(a) = self.follow_plan(&p)
+            {
+                return Ok(self.commit_action(a, &p));
+            }
+            debug!("Suitable actions: {:?}", suitable_actions);
+
+            let Some(a) = self.select_action(&suitable_actions, &p) else {
+                warn!("not good actions");
+                self.plan = None;
+                self.create_plan(p.position);
+                continue;
+            };
+            return Ok(self.commit_action(a, &p));
+        }
+
+        // la pianificazione continua a fallire: si torna a casa in modo deterministico
+        warn!(
+            "no good action after {} attempts, going home",
+            self.config.planning_attempts
+        );
+        if p.position == Position::new(0, 0) {
+            return Ok(self.commit_action(Action::Exit, &p));
+        }
+        self.obj = Objective::GoHome;
+        self.plan = None;
+        if self.create_plan(p.position) {
+            let suitable_actions = self.suitable_actions(&p)?;
+            if let Some(a) = self.follow_plan(&p) {
+                return Ok(self.commit_action(a, &p));
+            }
+            if let Some(a) = self.select_action(&suitable_actions, &p) {
+                return Ok(self.commit_action(a, &p));
+            }
+        }
+        Err(HeroError::NoActionAvailable(p.position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Strategy nominato esplicitamente, altrimenti si scontra con quello di crate::strategy
+    use proptest::{prelude::*, strategy::Strategy};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{
+        fixtures,
+        kb::{Var, init_kb},
+        world::{ActionResult, GameSeeds, World},
+    };
+
+    // una cache con qualcosa in ogni mappa che viene salvata
+    fn explored_cache() -> Cache {
+        let mut cache = Cache::new(4);
+        for (x, y) in [(0, 1), (1, 0), (1, 1), (2, 1)] {
+            cache.insert_safe(Position::new(x, y)).unwrap();
+        }
+        cache.insert_visited(Position::new(0, 0)).unwrap();
+        cache.insert_visited(Position::new(1, 0)).unwrap();
+        cache.record_fact(Position::new(1, 1), Fact::Safe, 2);
+        cache.record_fact(Position::new(1, 1), Fact::NoWumpus, 3);
+        cache.undecided.insert(Position::new(3, 3), (4, 1));
+        cache.aim.insert(Position::new(2, 1), Direction::North);
+        cache.insert_pit(Position::new(0, 2)).unwrap();
+        cache.insert_wumpus(Position::new(3, 1)).unwrap();
+        cache.insert_unsafe(Position::new(2, 0)).unwrap();
+        cache
+    }
+
+    #[test]
+    fn cache_rejects_safe_and_unsafe_cells() {
+        let mut cache = Cache::new(3);
+        let pos = Position::new(1, 0);
+        cache.insert_safe(pos).unwrap();
+        assert_eq!(
+            cache.insert_unsafe(pos),
+            Err(CacheError::SafeAndUnsafe(pos))
+        );
+        assert_eq!(cache.insert_pit(pos), Err(CacheError::SafeAndUnsafe(pos)));
+
+        let pos = Position::new(2, 2);
+        cache.insert_wumpus(pos).unwrap();
+        assert_eq!(cache.insert_safe(pos), Err(CacheError::SafeAndUnsafe(pos)));
+        assert!(!cache.safe().contains(&pos));
+        assert_eq!(cache.unsafe_reason(&pos), Some(UnsafeReason::Wumpus));
+    }
+
+    #[test]
+    fn cache_rejects_unsafe_visits_and_cells_outside_the_map() {
+        let mut cache = Cache::new(3);
+        let pos = Position::new(0, 2);
+        assert_eq!(
+            cache.insert_visited(pos),
+            Err(CacheError::VisitedNotSafe(pos))
+        );
+        let outside = Position::new(3, 0);
+        assert_eq!(
+            cache.insert_safe(outside),
+            Err(CacheError::OutOfBounds(outside))
+        );
+        assert_eq!(
+            cache.insert_pit(outside),
+            Err(CacheError::OutOfBounds(outside))
+        );
+        assert_eq!(cache.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_finds_the_broken_invariants() {
+        let mut cache = explored_cache();
+        cache.safe.remove(&Position::new(1, 0));
+        assert_eq!(
+            cache.validate(),
+            Err(CacheError::VisitedNotSafe(Position::new(1, 0)))
+        );
+
+        let mut cache = explored_cache();
+        cache._unsafe.insert(Position::new(1, 1), UnsafeReason::Pit);
+        assert_eq!(
+            cache.validate(),
+            Err(CacheError::SafeAndUnsafe(Position::new(1, 1)))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn cache_json_round_trip() {
+        let cache = explored_cache();
+        let json = serde_json::to_string(&cache).expect("the cache is serializable");
+        let mut restored: Cache = serde_json::from_str(&json).expect("the cache is readable");
+        // la frontiera non viene salvata, la ricostruisce restore_cache
+        restored.rebuild_frontier();
+        assert_eq!(restored, cache);
+        assert_eq!(
+            restored.facts(&Position::new(1, 1)),
+            &[(Fact::Safe, 2), (Fact::NoWumpus, 3)]
+        );
+        assert_eq!(
+            restored.aim_direction(&Position::new(2, 1)),
+            Some(Direction::North)
+        );
+    }
+
+    #[test]
+    fn killed_wumpus_cell_becomes_plannable() {
+        let mut cache = Cache::new(3);
+        cache.insert_safe(Position::new(1, 0)).unwrap();
+        cache.insert_visited(Position::new(0, 0)).unwrap();
+        cache.insert_visited(Position::new(1, 0)).unwrap();
+        let wumpus = Position::new(2, 0);
+        let pit = Position::new(0, 1);
+        let unknown = Position::new(1, 1);
+        cache.insert_wumpus(wumpus).unwrap();
+        cache.insert_pit(pit).unwrap();
+        cache.insert_unsafe(unknown).unwrap();
+        let goals = HashSet::from([wumpus]);
+        let from = Position::new(1, 0);
+        let (plan, _) = search_plan(ExplorerKind::AStar, &cache, 3, &goals, from);
+        assert_eq!(plan, None);
+
+        // la freccia tirata da (0, 0) verso est colpisce il wumpus
+        cache.on_wumpus_killed(&[Position::new(1, 0), wumpus]);
+        assert!(cache.is_safe(&wumpus));
+        assert!(cache.frontier().contains(&wumpus));
+        assert!(cache.wumpuses().is_empty());
+        assert_eq!(cache.unsafe_reason(&pit), Some(UnsafeReason::Pit));
+        // non si sa perché fosse pericolosa: torna sconosciuta e verrà richiesta alla KB
+        assert!(!cache.is_unsafe(&unknown) && !cache.is_safe(&unknown));
+        let (plan, _) = search_plan(ExplorerKind::AStar, &cache, 3, &goals, from);
+        assert_eq!(plan, Some(vec![wumpus]));
+    }
+
+    #[test]
+    fn the_hero_shoots_the_wumpus_closing_the_way_to_the_gold() {
+        // all'oro in (2,2) si arriva solo dal wumpus in (1,2), (2,1) è un pozzo
+        let mut world = World::from_layout("x . .\n. . o\n. w g").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(1));
+        let mut shots = 0;
+        let result = loop {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            if let Action::Shoot(_) = action {
+                shots += 1;
+            }
+            let result = world.do_action(action).unwrap();
+            if result != ActionResult::Continue {
+                break result;
+            }
+        };
+        assert_eq!(result, ActionResult::Exited { gold: true });
+        assert_eq!(shots, 1);
+        assert!(!world.wumpus_alive());
+        // l'urlo è stato registrato: il wumpus non è più un pericolo per l'eroe
+        assert!(hero.cache().wumpuses().is_empty());
+        assert!(hero.cache().visited().contains(&Position::new(1, 2)));
+    }
+
+    #[test]
+    fn failed_planning_falls_back_to_going_home() {
+        let hero = || {
+            let mut kb = init_kb(3);
+            kb.use_in_process_solver();
+            Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(5))
+        };
+        let layout = "x . .\n. . .\nw . g";
+
+        // nessun tentativo di pianificazione: come se fallissero tutti
+        let mut world = World::from_layout(layout).unwrap();
+        let mut home = hero();
+        home.config.planning_attempts = 0;
+        assert_eq!(home.next_action(world.perceptions()).unwrap(), Action::Exit);
+
+        // a metà partita, senza piano, l'eroe torna sui suoi passi invece di ritentare
+        let mut away = hero();
+        let first = away.next_action(world.perceptions()).unwrap();
+        let Action::Move(dir) = first else {
+            panic!("the first action is {:?}", first);
+        };
+        world.do_action(first).unwrap();
+        away.config.planning_attempts = 0;
+        away.plan = None;
+        assert_eq!(
+            away.next_action(world.perceptions()).unwrap(),
+            Action::Move(dir.opposite())
+        );
+        assert_eq!(away.current_objective(), &Objective::GoHome);
+    }
+
+    // non sceglie mai niente e ricorda l'obbiettivo di ogni valutazione
+    struct Stubborn(std::rc::Rc<std::cell::RefCell<Vec<Objective>>>);
+
+    impl crate::strategy::Strategy for Stubborn {
+        fn score_action(&mut self, ctx: &DecisionContext, _a: &Action) -> Utility {
+            self.0.borrow_mut().push(*ctx.objective);
+            Utility::Forbidden
+        }
+    }
+
+    // la partita del seme 23 all'inizio del quarto turno: in (1,0) non ci sono caselle
+    // da esplorare e l'eroe passa a KillWumpus, ma il tiro non è ancora tra le azioni adatte:
+    // la prima scelta fallisce e il tiro arriva al secondo tentativo
+    fn seed_23_at_turn_three(config: HeroConfig) -> (Hero<Kb, StdRng>, World) {
+        let mut world = World::new_with_seed(5, 3, 23);
+        let mut kb = init_kb(5);
+        kb.use_in_process_solver();
+        let rng = StdRng::seed_from_u64(GameSeeds::new(23).agent);
+        let mut hero = Hero::with_rng(kb, 5, config, rng);
+        for _ in 0..3 {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            world.do_action(action).unwrap();
+        }
+        assert_eq!(world.hero_position(), Position::new(1, 0));
+        (hero, world)
+    }
+
+    #[test]
+    fn planning_retries_are_bounded_on_a_real_state() {
+        let (mut hero, world) = seed_23_at_turn_three(HeroConfig::default());
+        assert_eq!(
+            hero.next_action(world.perceptions()).unwrap(),
+            Action::Shoot(Direction::East)
+        );
+
+        // con un solo tentativo la scelta fallita manda l'eroe a casa
+        let config = HeroConfig {
+            planning_attempts: 1,
+            ..HeroConfig::default()
+        };
+        let (mut hero, world) = seed_23_at_turn_three(config);
+        assert_eq!(
+            hero.next_action(world.perceptions()).unwrap(),
+            Action::Move(Direction::Ovest)
+        );
+        assert_eq!(hero.current_objective(), &Objective::GoHome);
+
+        // una strategia che non sceglie mai: con la ricorsione di prima la scelta veniva
+        // ritentata senza fine, ora dopo planning_attempts tentativi l'eroe torna a casa
+        let (mut hero, world) = seed_23_at_turn_three(HeroConfig::default());
+        let scored = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        hero.set_strategy(Box::new(Stubborn(scored.clone())));
+        let (action, trace) = hero.next_action_traced(world.perceptions()).unwrap();
+        assert_eq!(action, Action::Move(Direction::Ovest));
+        assert!(trace.from_plan);
+        assert_eq!(hero.current_objective(), &Objective::GoHome);
+        // nel primo tentativo le azioni adatte sono calcolate prima di passare a
+        // KillWumpus e c'è solo la mossa verso ovest, negli altri tre anche il tiro
+        assert_eq!(*scored.borrow(), vec![Objective::KillWumpus; 1 + 3 * 2]);
+        assert_eq!(HeroConfig::default().planning_attempts, 4);
+    }
+
+    #[test]
+    fn after_the_shot_the_objective_follows_the_howl() {
+        // colpito: l'eroe torna ad esplorare passando dalla casella del wumpus
+        let (mut hero, mut world) = seed_23_at_turn_three(HeroConfig::default());
+        let shot = hero.next_action(world.perceptions()).unwrap();
+        assert_eq!(shot, Action::Shoot(Direction::East));
+        assert_eq!(hero.current_objective(), &Objective::KillWumpus);
+        world.do_action(shot).unwrap();
+        assert!(world.perceptions().howl());
+        assert_eq!(
+            hero.next_action(world.perceptions()).unwrap(),
+            Action::Move(Direction::East)
+        );
+        assert_eq!(hero.current_objective(), &Objective::TakeGold);
+
+        // mancato: nessun urlo e l'eroe esce
+        let mut world = World::from_layout("x . .\n. . .\nw . g").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(5));
+        hero.obj = Objective::KillWumpus;
+        hero.arrow = false;
+        hero.last_shot = Some((Position::new(0, 0), Direction::East));
+        world.do_action(Action::Shoot(Direction::East)).unwrap();
+        assert!(!world.perceptions().howl());
+        assert_eq!(hero.next_action(world.perceptions()).unwrap(), Action::Exit);
+        assert_eq!(hero.current_objective(), &Objective::GoHome);
+    }
+
+    #[test]
+    fn a_ring_of_pits_on_a_large_board_fits_a_small_stack() {
+        // un anello di pozzi intorno alla partenza, l'oro e il wumpus fuori
+        let size = 20;
+        let layout: Vec<String> = (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| match (x, y) {
+                        (0, 0) => "x",
+                        (19, 19) => "g",
+                        (10, 10) => "w",
+                        (x, y) if x.max(y) == 3 => "o",
+                        _ => ".",
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        let layout = layout.join("\n");
+        let game = std::thread::Builder::new()
+            .stack_size(512 * 1024)
+            .spawn(move || {
+                let mut world = World::from_layout(&layout).unwrap();
+                let mut kb = init_kb(size);
+                kb.use_in_process_solver();
+                let config = HeroConfig {
+                    consistency_check_every: 0,
+                    ..Default::default()
+                };
+                let mut hero = Hero::with_rng(kb, size, config, StdRng::seed_from_u64(2));
+                loop {
+                    let action = hero.next_action(world.perceptions()).unwrap();
+                    let result = world.do_action(action).unwrap();
+                    if result != ActionResult::Continue {
+                        return (result, hero.cache().visited().clone());
+                    }
+                }
+            })
+            .unwrap();
+        let (result, visited) = game.join().expect("the game does not overflow the stack");
+        assert_eq!(result, ActionResult::Exited { gold: false });
+        // l'eroe non esce mai dall'anello
+        assert!(
+            visited.iter().all(|pos| pos.x.max(pos.y) < 3),
+            "{:?}",
+            visited
+        );
+    }
+
+    // il risultato di una partita e le azioni con le loro tracce
+    type Game = (ActionResult, Vec<(Action, DecisionTrace)>);
+
+    // la partita generata dal seme giocata fino alla fine, con la KB in-process
+    fn play_seeded(seed: u64, config: HeroConfig) -> Game {
+        play_seeded_hero(seed, config).1
+    }
+
+    // come play_seeded, restituendo anche l'eroe a fine partita
+    fn play_seeded_hero(seed: u64, config: HeroConfig) -> (Hero<Kb, StdRng>, Game) {
+        let seeds = GameSeeds::new(seed);
+        let mut world = World::new_with_seed(5, 3, seed);
+        let mut kb = init_kb(5);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 5, config, StdRng::seed_from_u64(seeds.agent));
+        let mut turns = vec![];
+        loop {
+            let (action, trace) = hero.next_action_traced(world.perceptions()).unwrap();
+            turns.push((action, trace));
+            let result = world.do_action(action).unwrap();
+            if result != ActionResult::Continue {
+                return (hero, (result, turns));
+            }
+        }
+    }
+
+    #[test]
+    fn plans_are_followed_step_by_step() {
+        let mut followed = 0;
+        for seed in 0..8 {
+            let (result, turns) = play_seeded(seed, HeroConfig::default());
+            assert!(
+                !matches!(result, ActionResult::Dead(_)),
+                "seed {}: {:?}",
+                seed,
+                result
+            );
+            for pair in turns.windows(2) {
+                let [(_, before), (action, trace)] = pair else {
+                    unreachable!()
+                };
+                // un piano vuoto all'inizio del turno viene ricreato prima di essere seguito
+                if !trace.from_plan || before.plan.is_empty() {
+                    continue;
+                }
+                followed += 1;
+                // il passo del piano viene eseguito senza confrontare le utilità
+                assert!(trace.candidates.is_empty(), "seed {}", seed);
+                let Action::Move(dir) = action else {
+                    panic!("seed {}: {:?} from the plan", seed, action);
+                };
+                assert_eq!(
+                    trace.position.checked_move(*dir, 5),
+                    before.plan.first().copied(),
+                    "seed {}",
+                    seed
+                );
+                assert_eq!(&trace.plan[..], &before.plan[1..], "seed {}", seed);
+            }
+        }
+        assert!(followed > 0);
+    }
+
+    #[test]
+    fn ties_are_broken_uniformly() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let draws = 12_000;
+        for ties in [3, 4] {
+            let mut counts = vec![0usize; ties];
+            for _ in 0..draws {
+                // un'azione peggiore e una proibita non vengono mai scelte
+                let scored = (0..ties)
+                    .map(|i| (i, Utility::Score(5)))
+                    .chain([(ties, Utility::Score(1)), (ties + 1, Utility::Forbidden)]);
+                counts[choose_best(scored, &mut rng).unwrap()] += 1;
+            }
+            let expected = draws as f64 / ties as f64;
+            for (i, count) in counts.iter().enumerate() {
+                // più di quattro deviazioni standard dalla media
+                assert!(
+                    (*count as f64 - expected).abs() < 0.07 * expected,
+                    "{}-way tie: element {} chosen {} times out of {}",
+                    ties,
+                    i,
+                    count,
+                    draws
+                );
+            }
         }
+        assert_eq!(
+            choose_best([(0, Utility::Forbidden)], &mut rng),
+            None::<i32>
+        );
+    }
+
+    // una cache in cui ogni casella della mappa è sicura
+    fn all_safe_cache(size: usize) -> Cache {
+        let mut cache = Cache::new(size);
+        for x in 0..size {
+            for y in 0..size {
+                cache.insert_safe(Position::new(x, y)).unwrap();
+            }
+        }
+        cache
+    }
+
+    #[test]
+    fn closures_plan_to_a_target_chosen_at_runtime() {
+        let cache = all_safe_cache(4);
+        for target in [
+            Position::new(3, 2),
+            Position::new(0, 3),
+            Position::new(2, 2),
+        ] {
+            let problem = FindPlan::new(
+                &cache,
+                4,
+                |_: &Cache, pos: &Position| *pos == target,
+                |pos: &Position| pos.manhattan(&target) as i32,
+            );
+            let arena = Bump::new();
+            let plan = AStarExplorer::new(&problem, &arena)
+                .search(Position::new(0, 0))
+                .actions
+                .expect("every cell is reachable");
+            assert_eq!(plan.last(), Some(&target));
+            assert_eq!(plan.len(), target.manhattan(&Position::new(0, 0)));
+        }
+    }
+
+    #[test]
+    fn equidistant_goals_give_an_optimal_plan() {
+        let mut cache = all_safe_cache(5);
+        // un pozzo sulla strada verso uno dei quattro obbiettivi a distanza 4
+        cache.safe.remove(&Position::new(1, 0));
+        cache.insert_pit(Position::new(1, 0)).unwrap();
+        let goals = HashSet::from([
+            Position::new(4, 0),
+            Position::new(3, 1),
+            Position::new(2, 2),
+            Position::new(0, 4),
+        ]);
+        let heuristic = find_plan_to_goals(&cache, 5, &goals).heuristic;
+        assert_eq!(heuristic(&Position::new(0, 0)), 4);
+        assert_eq!(heuristic(&Position::new(4, 4)), 4);
+        assert_eq!(heuristic(&Position::new(3, 1)), 0);
+        for explorer in [
+            ExplorerKind::AStar,
+            ExplorerKind::Bfs,
+            ExplorerKind::Dijkstra,
+        ] {
+            let (plan, _) = search_plan(explorer, &cache, 5, &goals, Position::new(0, 0));
+            let plan = plan.expect("the goals are reachable");
+            assert_eq!(plan.len(), 4, "{:?}: {:?}", explorer, plan);
+            assert!(goals.contains(plan.last().unwrap()), "{:?}", explorer);
+            assert!(plan.iter().all(|pos| cache.is_safe(pos)), "{:?}", explorer);
+        }
+    }
+
+    #[test]
+    fn a_step_turned_unsafe_makes_the_hero_replan() {
+        let mut world = World::from_layout("x . . .\n. . . .\n. . . .\nw . o g").unwrap();
+        let mut kb = init_kb(4);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 4, HeroConfig::default(), StdRng::seed_from_u64(4));
+        // si gioca finché il piano non passa da una casella non ancora visitata
+        let step = loop {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            assert_eq!(world.do_action(action).unwrap(), ActionResult::Continue);
+            let next = hero.plan.as_ref().and_then(Plan::next_step);
+            if let Some(step) = next.filter(|step| !hero.cache.is_visited(step)) {
+                break step;
+            }
+        };
+        // una nuova inferenza (qui simulata) scopre che il passo è pericoloso
+        hero.cache.safe.remove(&step);
+        hero.cache.frontier.remove(&step);
+        hero.cache.insert_pit(step).unwrap();
+
+        let position = world.hero_position();
+        let action = hero.next_action(world.perceptions()).unwrap();
+        if let Action::Move(dir) = action {
+            assert_ne!(position.checked_move(dir, 4), Some(step));
+        }
+        assert!(!hero.current_plan().unwrap_or_default().contains(&step));
+        assert!(!matches!(
+            world.do_action(action).unwrap(),
+            ActionResult::Dead(_)
+        ));
+    }
+
+    #[test]
+    fn the_knowledge_grid_follows_the_inferences() {
+        use CellKnowledge::*;
+
+        let mut world = World::from_layout("x . o\n. . w\n. . g").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(1));
+        assert_eq!(
+            hero.knowledge_grid(),
+            vec![
+                vec![Safe, Unknown, Unknown],
+                vec![Unknown; 3],
+                vec![Unknown; 3]
+            ]
+        );
+        assert_eq!(hero.current_objective(), &Objective::TakeGold);
+        assert_eq!(hero.current_plan(), None);
+
+        // la partenza è tranquilla: le due vicine sono sicure
+        let action = hero.next_action(world.perceptions()).unwrap();
+        world.do_action(action).unwrap();
+        assert_eq!(
+            hero.knowledge_grid(),
+            vec![
+                vec![Visited, Safe, Unknown],
+                vec![Safe, Unknown, Unknown],
+                vec![Unknown; 3]
+            ]
+        );
+
+        hero.cache.insert_pit(Position::new(2, 0)).unwrap();
+        hero.cache.insert_wumpus(Position::new(2, 1)).unwrap();
+        hero.cache.insert_unsafe(Position::new(2, 2)).unwrap();
+        hero.cache.undecided.insert(Position::new(1, 1), (1, 1));
+        let grid = hero.knowledge_grid();
+        assert_eq!(grid[0][2], UnsafePit);
+        assert_eq!(grid[1][2], UnsafeWumpus);
+        assert_eq!(grid[2][2], UnsafeUnknownKind);
+        assert_eq!(grid[1][1], Undecided);
+
+        let steps = vec![Position::new(1, 0), Position::new(1, 1)];
+        hero.plan = Some(Plan::new(Position::new(0, 0), steps.clone()));
+        assert_eq!(hero.current_plan(), Some(&steps[..]));
+        hero.plan.as_mut().unwrap().advance();
+        assert_eq!(hero.current_plan(), Some(&steps[1..]));
+    }
+
+    #[derive(Clone, Debug)]
+    enum CacheOp {
+        Safe(Position),
+        // l'eroe entra in una casella della frontiera, scelta con l'indice modulo la sua dimensione
+        Visit(usize),
+        Unsafe(Position),
+        Shot(Position, Direction),
+    }
+
+    fn cache_op() -> impl Strategy<Value = CacheOp> {
+        let pos = (0..5usize, 0..5usize).prop_map(|(x, y)| Position::new(x, y));
+        let dir = prop::sample::select(Direction::ALL.to_vec());
+        prop_oneof![
+            3 => pos.clone().prop_map(CacheOp::Safe),
+            3 => any::<usize>().prop_map(CacheOp::Visit),
+            1 => pos.clone().prop_map(CacheOp::Unsafe),
+            1 => (pos, dir).prop_map(|(pos, dir)| CacheOp::Shot(pos, dir)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn the_incremental_frontier_matches_its_definition(
+            ops in prop::collection::vec(cache_op(), 0..60)
+        ) {
+            let mut cache = Cache::new(5);
+            for op in ops {
+                // le operazioni che violano gli invarianti vengono rifiutate senza effetti
+                match op {
+                    CacheOp::Safe(pos) => {
+                        let _ = cache.insert_safe(pos);
+                    }
+                    CacheOp::Visit(i) => {
+                        let mut frontier: Vec<Position> = cache.frontier().iter().copied().collect();
+                        frontier.sort();
+                        if !frontier.is_empty() {
+                            cache.insert_visited(frontier[i % frontier.len()]).unwrap();
+                        }
+                    }
+                    CacheOp::Unsafe(pos) => {
+                        let _ = cache.insert_wumpus(pos);
+                    }
+                    CacheOp::Shot(pos, dir) => cache.on_wumpus_killed(&pos.ray(dir, 5)),
+                }
+                prop_assert_eq!(cache.frontier(), &cache.recompute_frontier());
+                let unreachable: HashSet<Position> = cache
+                    .safe()
+                    .iter()
+                    .filter(|pos| !cache.is_visited(pos) && !cache.frontier().contains(pos))
+                    .copied()
+                    .collect();
+                prop_assert_eq!(cache.unreachable(), &unreachable);
+                prop_assert!(cache.validate().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn the_counters_of_a_fixed_game() {
+        let mut world = World::from_layout("x . g\n. . .\no . w").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(7));
+        loop {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            if world.do_action(action).unwrap() != ActionResult::Continue {
+                break;
+            }
+        }
+        // un'interrogazione in più cambia asks e solver_calls
+        assert_eq!(
+            hero.stats(),
+            HeroStats {
+                asks: 10,
+                cached_asks: 0,
+                tells: 11,
+                solver_calls: 12,
+                plans: 1,
+                plan_steps: 2,
+                cells_visited: 3,
+                cells_classified: 6,
+                turns: 6,
+                budget_exhausted: 0,
+                timings: Timings::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn only_all_forbidden_actions_trigger_the_retry() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let east = Action::Move(Direction::East);
+        let sud = Action::Move(Direction::Sud);
+        // anche il punteggio più basso è una scelta valida
+        assert_eq!(
+            choose_best(
+                [(east, Utility::Forbidden), (sud, Utility::Score(i32::MIN))],
+                &mut rng
+            ),
+            Some(sud)
+        );
+        assert_eq!(
+            choose_best(
+                [(east, Utility::Forbidden), (sud, Utility::Forbidden)],
+                &mut rng
+            ),
+            None
+        );
+        assert_eq!(choose_best(Vec::<(Action, Utility)>::new(), &mut rng), None);
+    }
+
+    #[test]
+    fn a_hero_off_its_home_plan_replans_once() {
+        let mut world = World::from_layout("x . g\n. . .\no . w").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(7));
+        while hero.current_objective() != &Objective::GoHome {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            assert_eq!(world.do_action(action).unwrap(), ActionResult::Continue);
+        }
+        let position = world.hero_position();
+        assert_ne!(position, Position::new(0, 0));
+        // un piano creato altrove: nessuna mossa dalla posizione dell'eroe lo segue
+        hero.plan = Some(Plan::new(
+            Position::new(1, 1),
+            vec![Position::new(0, 1), Position::new(0, 0)],
+        ));
+        let plans = hero.stats().plans;
+        let (action, trace) = hero.next_action_traced(world.perceptions()).unwrap();
+        assert_eq!(hero.stats().plans, plans + 1);
+        assert!(trace.from_plan);
+        let Action::Move(dir) = action else {
+            panic!("{:?} instead of a move home", action);
+        };
+        let next = position.checked_move(dir, 3).unwrap();
+        assert!(next.manhattan(&Position::new(0, 0)) < position.manhattan(&Position::new(0, 0)));
+        assert_eq!(
+            hero.current_plan().unwrap().last(),
+            Some(&Position::new(0, 0))
+        );
+    }
+
+    type Kb = crate::encoder::EncoderSAT<Var>;
+
+    #[test]
+    fn a_shallow_propagation_learns_the_same_with_fewer_asks() {
+        // una colonna di pozzi accanto alle caselle visitate: ogni pozzo scoperto fa
+        // riesaminare le vicine, ma oltre il primo livello non si impara niente
+        let world =
+            World::from_layout("x . o . .\n. . o . .\n. . o . .\n. . . . .\n. . w . g").unwrap();
+        let explore = |depth| {
+            let mut kb = init_kb(5);
+            kb.use_in_process_solver();
+            let config = HeroConfig {
+                propagation_depth: depth,
+                ..Default::default()
+            };
+            let mut hero = Hero::with_rng(kb, 5, config, StdRng::seed_from_u64(1));
+            for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)] {
+                let pos = Position::new(x, y);
+                hero.tell(&Kb::create_ground_truth_from_perception(
+                    &world.perceptions_at(pos),
+                ));
+                hero.cache.insert_safe(pos).unwrap();
+                hero.cache.insert_visited(pos).unwrap();
+            }
+            let safe = hero
+                .are_safe(&[Position::new(2, 0)], Position::new(1, 0))
+                .unwrap();
+            assert_eq!(safe, vec![false]);
+            (hero.stats().asks, hero.cache)
+        };
+        let (deep_asks, deep) = explore(None);
+        let (shallow_asks, shallow) = explore(Some(1));
+        assert_eq!(shallow.safe(), deep.safe());
+        assert_eq!(shallow.unsafe_cells(), deep.unsafe_cells());
+        assert_eq!(
+            shallow.pits(),
+            &HashSet::from([Position::new(2, 0), Position::new(2, 1)])
+        );
+        assert_eq!(shallow.pits(), deep.pits());
+        assert!(
+            shallow_asks < deep_asks,
+            "{} asks at depth 1, {} without a limit",
+            shallow_asks,
+            deep_asks
+        );
+    }
+
+    #[test]
+    fn the_diagnostic_knobs_do_not_change_the_games() {
+        // controlli e misure non devono cambiare le scelte dell'eroe
+        let checked = HeroConfig {
+            cross_check_plans: true,
+            check_invariants: true,
+            timing: true,
+            ..Default::default()
+        };
+        for seed in 0..6 {
+            let (result, turns) = play_seeded(seed, HeroConfig::default());
+            let (checked_result, checked_turns) = play_seeded(seed, checked);
+            assert_eq!(result, checked_result, "seed {}", seed);
+            let actions = |turns: &[(Action, DecisionTrace)]| -> Vec<Action> {
+                turns.iter().map(|(action, _)| *action).collect()
+            };
+            assert_eq!(actions(&turns), actions(&checked_turns), "seed {}", seed);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn missing_config_fields_take_the_defaults() {
+        let config: HeroConfig =
+            serde_json::from_str(r#"{"risk_threshold": 0.25, "max_steps": 40}"#).unwrap();
+        assert_eq!(config.risk_threshold, 0.25);
+        assert_eq!(config.max_steps, Some(40));
+        let default = HeroConfig::default();
+        assert_eq!(config.propagation_depth, default.propagation_depth);
+        assert_eq!(config.planning_attempts, default.planning_attempts);
+        assert_eq!(config.consistency_check_every, 1);
+        assert_eq!(config.explorer, ExplorerKind::AStar);
+        assert_eq!(config.frontier_policy, FrontierPolicy::Nearest);
+        assert!(!config.explore_all && !config.check_invariants);
+    }
+
+    #[test]
+    fn the_traces_explain_a_scripted_game() {
+        let mut world = World::from_layout("x . g\n. . .\no . w").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(7));
+        let mut traces = vec![];
+        loop {
+            let (action, trace) = hero.next_action_traced(world.perceptions()).unwrap();
+            assert_eq!(trace.action, Some(action));
+            traces.push(trace);
+            if world.do_action(action).unwrap() != ActionResult::Continue {
+                break;
+            }
+        }
+        let at = Position::new;
+        let east = Action::Move(Direction::East);
+        let ovest = Action::Move(Direction::Ovest);
+        assert_eq!(
+            traces.iter().map(|t| t.action.unwrap()).collect::<Vec<_>>(),
+            vec![east, east, Action::Grab, ovest, ovest, Action::Exit]
+        );
+
+        // la prima mossa va in una casella appena dimostrata sicura, a parità di utilità
+        let first = &traces[0];
+        assert_eq!((first.turn, first.position), (0, at(0, 0)));
+        assert_eq!(first.objective, Objective::TakeGold);
+        assert!(!first.from_plan);
+        let inferred: HashSet<Position> = first.inferred_safe.iter().copied().collect();
+        assert_eq!(inferred, HashSet::from([at(1, 0), at(0, 1)]));
+        assert!(first.inferred_unsafe.is_empty());
+        assert!(
+            first
+                .candidates
+                .contains(&(Action::Exit, Utility::Forbidden))
+        );
+        assert!(first.candidates.contains(&(east, Utility::Score(1))));
+        assert!(
+            first
+                .candidates
+                .contains(&(Action::Move(Direction::Sud), Utility::Score(1)))
+        );
+        assert_eq!(first.target, Some((at(1, 0), Support::Inferred)));
+
+        // dopo aver preso l'oro il ritorno segue il piano sulle caselle già note
+        let grab = &traces[2];
+        assert_eq!(grab.objective, Objective::GoHome);
+        assert_eq!(grab.plan, vec![at(1, 0), at(0, 0)]);
+        assert_eq!(grab.target, None);
+        let back = &traces[3];
+        assert!(back.from_plan && back.candidates.is_empty());
+        assert_eq!(back.plan, vec![at(0, 0)]);
+        assert_eq!(back.target, Some((at(1, 0), Support::Cached)));
+    }
+
+    #[test]
+    fn a_start_between_two_pits_exits_at_once() {
+        let mut world = World::from_layout("x o .\no . .\n. w g").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(0));
+        let (action, trace) = hero.next_action_traced(world.perceptions()).unwrap();
+        assert_eq!(action, Action::Exit);
+        assert_eq!(hero.current_objective(), &Objective::GoHome);
+        // nessun piano per tornare a casa dalla casa stessa
+        assert_eq!(hero.stats().plans, 0);
+        assert!(trace.plan.is_empty());
+        assert_eq!(
+            world.do_action(action).unwrap(),
+            ActionResult::Exited { gold: false }
+        );
+    }
+
+    // due brezze intorno alla partenza e nessuna casella di frontiera dimostrabilmente sicura:
+    // (1,1) spiega entrambe le brezze ed è la più rischiosa
+    const NO_SAFE_FRONTIER: &str = "x . . .\n. o . .\n. . . .\n. . g w";
+
+    // gioca finché l'eroe non esce o muore, con le tracce di ogni turno
+    fn play_layout(layout: &str, config: HeroConfig) -> (Hero<Kb, StdRng>, Game) {
+        let mut world = World::from_layout(layout).unwrap();
+        let size = world.size();
+        let mut kb = init_kb(size);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, size, config, StdRng::seed_from_u64(3));
+        let mut turns = vec![];
+        loop {
+            let (action, trace) = hero.next_action_traced(world.perceptions()).unwrap();
+            turns.push((action, trace));
+            let result = world.do_action(action).unwrap();
+            if result != ActionResult::Continue {
+                return (hero, (result, turns));
+            }
+        }
+    }
+
+    #[test]
+    fn without_a_risk_threshold_the_hero_does_not_gamble() {
+        let (hero, (result, turns)) = play_layout(NO_SAFE_FRONTIER, HeroConfig::default());
+        assert_eq!(result, ActionResult::Exited { gold: false });
+        assert!(
+            turns
+                .iter()
+                .all(|(_, trace)| trace.objective != Objective::Gamble)
+        );
+        let mut visited: Vec<_> = hero.cache().visited().iter().copied().collect();
+        visited.sort();
+        let at = Position::new;
+        assert_eq!(visited, vec![at(0, 0), at(1, 0), at(0, 1)]);
+    }
+
+    #[test]
+    fn a_risk_threshold_gambles_on_the_least_risky_cell() {
+        let config = HeroConfig {
+            risk_threshold: 0.9,
+            ..Default::default()
+        };
+        let (hero, (result, turns)) = play_layout(NO_SAFE_FRONTIER, config);
+        let (first, trace) = turns
+            .iter()
+            .find(|(_, trace)| trace.objective == Objective::Gamble)
+            .expect("the hero gambles");
+        // la prima scommessa è su una delle due caselle che spiegano una sola brezza
+        let target = *trace.plan.last().unwrap();
+        let at = Position::new;
+        assert!(
+            target == at(2, 0) || target == at(0, 2),
+            "{:?} {:?}",
+            first,
+            trace
+        );
+        // la scommessa vinta rende la casella sicura e l'esplorazione riprende
+        assert!(hero.cache().is_safe(&target));
+        assert!(!hero.is_gambling());
+        assert_eq!(result, ActionResult::Exited { gold: true });
+    }
+
+    #[test]
+    fn explore_all_keeps_exploring_after_the_gold() {
+        // l'oro è accanto alla partenza, il resto della mappa è quasi tutto sicuro
+        let layout = "x g . .\n. . . .\n. . . o\n. . . w";
+        let (quick, (result, _)) = play_layout(layout, HeroConfig::default());
+        assert_eq!(result, ActionResult::Exited { gold: true });
+
+        let config = HeroConfig {
+            explore_all: true,
+            ..Default::default()
+        };
+        let (hero, (result, turns)) = play_layout(layout, config);
+        assert_eq!(result, ActionResult::Exited { gold: true });
+        let grab = turns
+            .iter()
+            .position(|(action, _)| *action == Action::Grab)
+            .unwrap();
+        // dopo l'oro l'esplorazione continua finché la frontiera non è vuota
+        assert!(turns[grab + 1..].iter().any(|(action, trace)| {
+            matches!(action, Action::Move(_)) && trace.objective == Objective::TakeGold
+        }));
+        assert!(hero.cache().frontier().is_empty());
+        assert_eq!(turns.last().unwrap().0, Action::Exit);
+
+        let (quick, full) = (quick.stats(), hero.stats());
+        assert!(full.cells_visited > quick.cells_visited);
+        assert!(full.cells_classified > quick.cells_classified);
+    }
+
+    #[test]
+    fn a_small_step_budget_sends_the_hero_home() {
+        // senza limite il seme 8 esplora per 40 turni
+        let (free, (_, turns)) = play_seeded_hero(8, HeroConfig::default());
+        assert_eq!(turns.len(), 40);
+        assert_eq!(free.stats().budget_exhausted, 0);
+
+        let budget = 10;
+        let config = HeroConfig {
+            max_steps: Some(budget),
+            ..Default::default()
+        };
+        let (hero, (result, turns)) = play_seeded_hero(8, config);
+        assert_eq!(result, ActionResult::Exited { gold: false });
+        assert_eq!(hero.stats().budget_exhausted, 1);
+        // finito il budget l'eroe non esplora più, torna a casa ed esce
+        let home = turns
+            .iter()
+            .position(|(_, trace)| trace.objective == Objective::GoHome)
+            .unwrap();
+        assert!(home <= budget);
+        assert!(
+            turns[home..]
+                .iter()
+                .all(|(_, trace)| trace.objective == Objective::GoHome)
+        );
+        assert!(turns.len() < 40);
+        assert_eq!(turns.last().unwrap().0, Action::Exit);
+    }
+
+    #[test]
+    fn information_gain_wins_as_often_as_nearest() {
+        let play_all = |policy| {
+            let config = HeroConfig {
+                frontier_policy: policy,
+                ..Default::default()
+            };
+            (0..30)
+                .map(|seed| play_seeded(seed, config))
+                .collect::<Vec<_>>()
+        };
+        let nearest = play_all(FrontierPolicy::Nearest);
+        let gain = play_all(FrontierPolicy::InformationGain);
+        let wins = |games: &[Game]| {
+            games
+                .iter()
+                .filter(|(result, _)| *result == ActionResult::Exited { gold: true })
+                .count()
+        };
+        for (result, _) in nearest.iter().chain(&gain) {
+            assert!(!matches!(result, ActionResult::Dead(_)));
+        }
+        // esplorare prima le caselle più informative non rende l'eroe meno capace
+        assert_eq!(wins(&gain), wins(&nearest));
+        let actions = |turns: &[(Action, DecisionTrace)]| -> Vec<Action> {
+            turns.iter().map(|(action, _)| *action).collect()
+        };
+        assert!(
+            nearest
+                .iter()
+                .zip(&gain)
+                .any(|((_, a), (_, b))| actions(a) != actions(b)),
+            "the policies never chose differently"
+        );
+    }
+
+    #[test]
+    fn each_hero_error_can_be_forced() {
+        let hero = |config| {
+            let mut kb = init_kb(3);
+            kb.use_in_process_solver();
+            Hero::with_rng(kb, 3, config, StdRng::seed_from_u64(0))
+        };
+        // un eroe che si ritrova in una casella sicura lontano da casa, senza aver mai visto
+        // le caselle in mezzo
+        let stranded = Perceptions::at(Position::new(2, 0), 3)
+            .breeze()
+            .stench()
+            .build();
+
+        let mut planner = hero(HeroConfig::default());
+        planner.cache.insert_safe(Position::new(2, 0)).unwrap();
+        assert!(matches!(
+            planner.next_action(stranded.clone()),
+            Err(HeroError::PlanningFailed(pos)) if pos == Position::new(2, 0)
+        ));
+
+        let mut hopeless = hero(HeroConfig {
+            planning_attempts: 0,
+            ..Default::default()
+        });
+        hopeless.cache.insert_safe(Position::new(2, 0)).unwrap();
+        assert!(matches!(
+            hopeless.next_action(stranded),
+            Err(HeroError::NoActionAvailable(pos)) if pos == Position::new(2, 0)
+        ));
+
+        // la stessa casella con e senza brezza
+        let mut confused = hero(HeroConfig::default());
+        for p in [
+            Perceptions::at(Position::new(0, 0), 3).breeze().build(),
+            fixtures::start_clean(3),
+        ] {
+            confused.tell(&Kb::create_ground_truth_from_perception(&p));
+        }
+        assert!(matches!(
+            confused.next_action(fixtures::start_clean(3)),
+            Err(HeroError::InconsistentKb(KbError::Inconsistent { .. }))
+        ));
+    }
+
+    #[test]
+    fn every_explorer_finds_plans_of_the_same_length() {
+        // un labirinto: le caselle # sono pozzi, tutte le altre sono sicure
+        let maze = ". . . # .\n# # . # .\n. . . . .\n. # # # .\n. . . # .";
+        let mut cache = Cache::new(5);
+        for (y, row) in maze.lines().enumerate() {
+            for (x, cell) in row.split_whitespace().enumerate() {
+                let pos = Position::new(x, y);
+                if cell == "#" {
+                    cache.insert_pit(pos).unwrap();
+                } else {
+                    cache.insert_safe(pos).unwrap();
+                }
+            }
+        }
+        let goal_sets = [
+            vec![Position::new(4, 0)],
+            vec![Position::new(0, 4)],
+            vec![Position::new(4, 4), Position::new(0, 2)],
+            vec![Position::new(0, 0)],
+        ];
+        for from in [
+            Position::new(0, 0),
+            Position::new(2, 2),
+            Position::new(4, 4),
+        ] {
+            for goals in &goal_sets {
+                let goals: HashSet<Position> = goals.iter().copied().collect();
+                let lengths: Vec<Option<usize>> = [
+                    ExplorerKind::AStar,
+                    ExplorerKind::Bfs,
+                    ExplorerKind::Dijkstra,
+                ]
+                .into_iter()
+                .map(|explorer| {
+                    search_plan(explorer, &cache, 5, &goals, from)
+                        .0
+                        .map(|plan| plan.len())
+                })
+                .collect();
+                assert!(lengths[0].is_some(), "{:?} -> {:?}", from, goals);
+                assert!(
+                    lengths.iter().all(|len| *len == lengths[0]),
+                    "{:?} -> {:?}: {:?}",
+                    from,
+                    goals,
+                    lengths
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn astar_expands_fewer_nodes_than_bfs_on_a_large_explored_region() {
+        // una regione 20x20 tutta esplorata, il piano attraversa la mappa lungo una riga
+        let size = 20;
+        let cache = all_safe_cache(size);
+        let goals: HashSet<Position> = [Position::new(size - 1, 10)].into();
+        let from = Position::new(0, 10);
+        let (astar, astar_iters) = search_plan(ExplorerKind::AStar, &cache, size, &goals, from);
+        let (bfs, bfs_iters) = search_plan(ExplorerKind::Bfs, &cache, size, &goals, from);
+        let (astar, bfs) = (astar.unwrap(), bfs.unwrap());
+        assert_eq!(astar.len(), size - 1);
+        assert_eq!(astar.len(), bfs.len());
+        assert!(
+            astar_iters < bfs_iters,
+            "A* {} iterations, BFS {}",
+            astar_iters,
+            bfs_iters
+        );
+    }
+
+    #[test]
+    fn two_known_wumpuses_are_both_avoided() {
+        let mut cache = all_safe_cache(3);
+        let first = Position::new(1, 0);
+        let second = Position::new(1, 2);
+        for wumpus in [first, second] {
+            cache.safe.remove(&wumpus);
+            cache.frontier.remove(&wumpus);
+            cache.insert_wumpus(wumpus).unwrap();
+        }
+        assert_eq!(cache.wumpuses(), &HashSet::from([first, second]));
+        let goals = HashSet::from([Position::new(2, 0)]);
+        let (plan, _) = search_plan(ExplorerKind::AStar, &cache, 3, &goals, Position::new(0, 0));
+        let plan = plan.expect("the middle row is free");
+        assert!(
+            !plan.contains(&first) && !plan.contains(&second),
+            "{:?}",
+            plan
+        );
+
+        // la freccia lungo la prima riga colpisce solo il primo wumpus
+        cache.on_wumpus_killed(&Position::new(0, 0).ray(Direction::East, 3));
+        assert!(cache.is_safe(&first));
+        assert_eq!(cache.wumpuses(), &HashSet::from([second]));
+        assert_eq!(cache.unsafe_reason(&second), Some(UnsafeReason::Wumpus));
+        let goals = HashSet::from([Position::new(2, 2)]);
+        let (plan, _) = search_plan(ExplorerKind::AStar, &cache, 3, &goals, Position::new(0, 2));
+        assert!(!plan.expect("the way round is free").contains(&second));
+    }
+
+    #[test]
+    fn safe_cells_behind_unknown_ones_stay_out_of_the_frontier() {
+        let mut cache = Cache::new(4);
+        cache.insert_safe(Position::new(1, 0)).unwrap();
+        cache.insert_visited(Position::new(0, 0)).unwrap();
+        cache.insert_visited(Position::new(1, 0)).unwrap();
+        // dimostrata sicura, ma (2,0) e (3,1) sono sconosciute e (2,1) un pozzo
+        let island = Position::new(3, 0);
+        cache.insert_pit(Position::new(2, 1)).unwrap();
+        cache.insert_safe(island).unwrap();
+        assert!(cache.unreachable().contains(&island));
+        assert!(cache.frontier().is_empty());
+        // dalla casella accanto non c'è niente da esplorare, quindi si pianifica
+        assert!(!cache.safe_neighbourhood(&Position::new(1, 0)));
+        let (plan, _) = search_plan(
+            ExplorerKind::AStar,
+            &cache,
+            4,
+            cache.frontier(),
+            Position::new(1, 0),
+        );
+        assert_eq!(plan, None);
+
+        // una nuova casella sicura collega l'isola
+        cache.insert_safe(Position::new(2, 0)).unwrap();
+        assert!(cache.unreachable().is_empty());
+        assert_eq!(
+            cache.frontier(),
+            &HashSet::from([Position::new(2, 0), island])
+        );
+        assert!(cache.safe_neighbourhood(&Position::new(1, 0)));
+    }
+
+    #[test]
+    fn known_facts_are_not_asked_again() {
+        let mut world = World::from_layout("x . g\n. . .\no . w").unwrap();
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(7));
+        for _ in 0..3 {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            world.do_action(action).unwrap();
+        }
+        // dopo la presa dell'oro: le caselle attorno al percorso sono già classificate
+        let known: Vec<Position> = hero
+            .cache
+            .safe()
+            .iter()
+            .chain(hero.cache.unsafe_cells().keys())
+            .copied()
+            .collect();
+        assert!(known.len() > 3);
+        for pos in &known {
+            assert!(!hero.cache.facts(pos).is_empty() || *pos == Position::new(0, 0));
+        }
+        let stats = hero.stats();
+        let answers = hero.are_safe(&known, world.hero_position()).unwrap();
+        assert_eq!(
+            answers,
+            known
+                .iter()
+                .map(|pos| hero.cache.is_safe(pos))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(hero.stats().solver_calls, stats.solver_calls);
+        assert_eq!(hero.stats().asks, stats.asks);
+        assert_eq!(hero.stats().cached_asks, stats.cached_asks + known.len());
+    }
+
+    #[test]
+    fn undecided_cells_wait_for_new_knowledge() {
+        // il triangolo x + y <= 4 è sicuro, sulla diagonale x + y = 5 un pozzo sì e uno no:
+        // tutte le caselle del bordo sentono una brezza e nessuna casella oltre si può decidere
+        let size = 8;
+        let layout: Vec<String> = (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| match (x, y) {
+                        (0, 0) => "x",
+                        (6, 6) => "g",
+                        (7, 7) => "w",
+                        (x, y) if x + y == 5 && x % 2 == 0 => "o",
+                        _ => ".",
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        let layout = layout.join("\n");
+        let (hero, (result, _)) = play_layout(&layout, HeroConfig::default());
+        assert_eq!(result, ActionResult::Exited { gold: false });
+        // il bordo della diagonale è rimasto indeciso
+        let front: Vec<Position> = (0..=5).map(|x| Position::new(x, 5 - x)).collect();
+        assert!(
+            front
+                .iter()
+                .all(|pos| hero.cache().undecided(pos).is_some())
+        );
+
+        // richieste di nuovo restano indecise, con la generazione attuale della KB
+        let mut hero = hero;
+        let home = Position::new(0, 0);
+        let undecided = vec![false; front.len()];
+        assert_eq!(hero.are_safe(&front, home).unwrap(), undecided);
+        // senza niente di nuovo nella KB non vengono più richieste
+        let before = hero.stats();
+        assert_eq!(hero.are_safe(&front, home).unwrap(), undecided);
+        assert_eq!(hero.stats().asks, before.asks);
+        assert_eq!(hero.stats().cached_asks, before.cached_asks + front.len());
+        // una nuova generazione della KB le fa richiedere
+        hero.kb_generation += 1;
+        assert_eq!(hero.are_safe(&front, home).unwrap(), undecided);
+        assert_eq!(hero.stats().asks, before.asks + 2 * front.len());
+    }
+
+    #[test]
+    fn skipping_the_undecided_cells_saves_asks() {
+        // forget = true dimentica le caselle indecise prima di ogni turno, come senza il salto
+        let play = |forget: bool| {
+            let mut world = World::new_with_seed(8, 10, 39);
+            let mut kb = init_kb(8);
+            kb.use_in_process_solver();
+            let mut hero = Hero::with_rng(kb, 8, HeroConfig::default(), StdRng::seed_from_u64(39));
+            let mut actions = vec![];
+            loop {
+                if forget {
+                    hero.cache.undecided.clear();
+                }
+                let action = hero.next_action(world.perceptions()).unwrap();
+                actions.push(action);
+                if world.do_action(action).unwrap() != ActionResult::Continue {
+                    return (hero.stats(), actions);
+                }
+            }
+        };
+        let (stats, actions) = play(false);
+        let (forgetful, same_actions) = play(true);
+        // le risposte sono le stesse, cambia solo quante volte vengono chieste
+        assert_eq!(actions, same_actions);
+        assert!(
+            stats.asks < forgetful.asks,
+            "{} asks, {} re-asking the undecided cells",
+            stats.asks,
+            forgetful.asks
+        );
+    }
+
+    fn checked_hero() -> Hero<Kb, StdRng> {
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        let config = HeroConfig {
+            check_invariants: true,
+            ..Default::default()
+        };
+        Hero::with_rng(kb, 3, config, StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    #[should_panic(expected = "invariant violated")]
+    fn a_detour_plan_trips_the_invariant_checker() {
+        let mut hero = checked_hero();
+        hero.cache = all_safe_cache(3);
+        // (0, 1) è a un passo, il piano fa il giro lungo
+        let detour = vec![
+            Position::new(1, 0),
+            Position::new(1, 1),
+            Position::new(0, 1),
+        ];
+        hero.adopt_plan(Position::new(0, 0), Some(detour));
+    }
+
+    #[test]
+    #[should_panic(expected = "invariant violated")]
+    fn a_move_into_an_unproven_cell_trips_the_invariant_checker() {
+        let world = World::from_layout("x . g\n. . .\no . w").unwrap();
+        let mut hero = checked_hero();
+        // nessuna casella è stata dimostrata sicura
+        hero.commit_action(Action::Move(Direction::East), &world.perceptions());
+    }
+
+    #[test]
+    fn a_two_cycle_drops_the_plan() {
+        let mut hero = checked_hero();
+        hero.cache = all_safe_cache(3);
+        let (a, b) = (Position::new(0, 0), Position::new(1, 0));
+        hero.plan = Some(Plan::new(a, vec![b, a]));
+        hero.track_oscillation(a);
+        hero.track_oscillation(b);
+        hero.track_oscillation(a);
+        assert!(hero.plan.is_some());
+        hero.track_oscillation(b);
+        assert!(hero.plan.is_none());
+        assert!(hero.recent.is_empty());
+    }
+
+    #[test]
+    fn seeded_games_never_ping_pong() {
+        for seed in 0..12 {
+            let (_, turns) = play_seeded(seed, HeroConfig::default());
+            let positions: Vec<Position> = turns.iter().map(|(_, trace)| trace.position).collect();
+            // dopo a, b, a, b l'eroe ripianifica, un quinto passo sulla stessa coppia non arriva mai
+            for w in positions.windows(5) {
+                assert!(
+                    !(w[0] != w[1] && w[0] == w[2] && w[2] == w[4] && w[1] == w[3]),
+                    "seed {} oscillates: {:?}",
+                    seed,
+                    w
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_plan_is_drawn_over_the_knowledge() {
+        let mut kb = init_kb(4);
+        kb.use_in_process_solver();
+        let mut hero = Hero::with_rng(kb, 4, HeroConfig::default(), StdRng::seed_from_u64(1));
+        hero.cache = explored_cache();
+        let origin = Position::new(1, 0);
+        hero.plan = Some(Plan::new(
+            origin,
+            vec![Position::new(1, 1), Position::new(2, 1)],
+        ));
+        assert_eq!(
+            hero.render_knowledge(origin),
+            "_ x ! ? \n\
+             . 1 * W \n\
+             o ? ? ? \n\
+             ? ? ? ~ \n\
+             objective: TakeGold\n"
+        );
+    }
+
+    #[test]
+    fn the_timed_parts_add_up_to_the_turns() {
+        // la partita del seed 6, con il tempo di next_action misurato da fuori
+        let play = |config: HeroConfig| {
+            let mut world = World::new_with_seed(5, 3, 6);
+            let mut kb = init_kb(5);
+            kb.use_in_process_solver();
+            let rng = StdRng::seed_from_u64(GameSeeds::new(6).agent);
+            let mut hero = Hero::with_rng(kb, 5, config, rng);
+            let mut measured = Duration::ZERO;
+            loop {
+                let start = Instant::now();
+                let action = hero.next_action(world.perceptions()).unwrap();
+                measured += start.elapsed();
+                if world.do_action(action).unwrap() != ActionResult::Continue {
+                    return (hero.stats().timings, measured);
+                }
+            }
+        };
+        let (timings, measured) = play(HeroConfig {
+            timing: true,
+            ..Default::default()
+        });
+        let parts = timings.consistency + timings.asks + timings.planning;
+        // le parti non si sovrappongono e stanno dentro il turno, che sta dentro la misura
+        // fatta da fuori; il resto è contabilità e deve essere piccolo
+        assert!(parts <= timings.turn, "{:?}", timings);
+        assert!(timings.turn <= measured);
+        assert!(
+            parts.as_secs_f64() >= 0.5 * measured.as_secs_f64(),
+            "{:?} of {:?}",
+            timings,
+            measured
+        );
+        // senza timing non si misura niente
+        assert_eq!(play(HeroConfig::default()).0, Timings::default());
     }
 }
@@ -1,8 +1,20 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+
 use crate::{
     encoder::{
         EncoderSAT,
         Literal::{self, Neg},
+        MemoryFootprint,
     },
+    fixtures,
+    prop::Prop,
+    solver::{SatResult, SolverError},
     world::{Action, Direction, Perceptions, Position},
 };
 
@@ -28,98 +40,270 @@ impl Default for Var {
 
 pub type Formula = Vec<Vec<Literal<Var>>>;
 
+// ogni quante clausole comunicate con tell la KB viene semplificata
+const SIMPLIFY_EVERY: usize = 64;
+// campioni usati per stimare il rischio delle caselle
+const RISK_SAMPLES: usize = 32;
+
+// la KB deve poter essere spostata e condivisa tra thread (simulazioni parallele, ask speculative)
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<EncoderSAT<Var>>;
+};
+
+#[derive(Debug)]
+pub enum KbError {
+    Solver(SolverError),
+    // la KB è insoddisfacibile, dump contiene il prefisso dei file scritti se richiesti
+    Inconsistent { dump: Option<PathBuf> },
+    DumpFailed(std::io::Error),
+}
+
+impl fmt::Display for KbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KbError::Solver(e) => write!(f, "{}", e),
+            KbError::Inconsistent { dump: Some(path) } => write!(
+                f,
+                "inconsistent knowledge base, state dumped to {}.*",
+                path.display()
+            ),
+            KbError::Inconsistent { dump: None } => write!(f, "inconsistent knowledge base"),
+            KbError::DumpFailed(e) => write!(f, "could not dump the knowledge base: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for KbError {}
+
+impl From<SolverError> for KbError {
+    fn from(value: SolverError) -> Self {
+        KbError::Solver(value)
+    }
+}
+
+// scrive nella cartella dir la CNF con i nomi delle variabili e le formule comunicate con tell,
+// ritorna il prefisso comune dei file
+fn dump_kb(kb: &EncoderSAT<Var>, dir: &Path) -> std::io::Result<PathBuf> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let prefix = dir.join(format!("kb-{}-{:09}", now.as_secs(), now.subsec_nanos()));
+    fs::create_dir_all(dir)?;
+
+    fs::write(prefix.with_extension("cnf"), kb.encode_commented())?;
+
+    let mut told = String::from("# formulas told to the knowledge base, one per line\n");
+    for formula in kb.told() {
+        told.push_str(&format!("{:?}\n", formula));
+    }
+    fs::write(prefix.with_extension("told"), told)?;
+
+    // il solver da riga di comando non fornisce il core insoddisfacibile
+    fs::write(
+        prefix.with_extension("core"),
+        "# UNSAT core not available with the configured solver\n",
+    )?;
+
+    Ok(prefix)
+}
+
 pub trait KnowledgeBase {
     type Query;
 
-    // @return true iff KB |= formula
-    fn ask(&mut self, formula: &Self::Query) -> bool;
+    // @return Ok(true) iff KB |= formula, Err se il solver non ha dato una risposta
+    fn ask(&mut self, formula: &Self::Query) -> Result<bool, SolverError>;
+    // come ask su ogni formula, ma le interrogazioni vengono risolte insieme quando possibile
+    fn ask_batch(&mut self, formulas: &[Self::Query]) -> Result<Vec<bool>, SolverError>;
+    // come ask_batch, ma le interrogazioni possono essere divise tra più thread
+    fn ask_batch_parallel(&mut self, formulas: &[Self::Query]) -> Result<Vec<bool>, SolverError> {
+        self.ask_batch(formulas)
+    }
     fn tell(&mut self, formula: &Self::Query);
 
-    fn consistency(&mut self) -> bool;
+    // Ok(()) se la KB è soddisfacibile
+    fn consistency(&mut self) -> Result<(), KbError>;
 
-    fn create_query_from_action(a: &Action, p: &Position) -> Self::Query;
+    // la formula conseguenza della KB quando l'azione ha senso: la casella di arrivo è sicura,
+    // c'è l'oro, il wumpus è sulla traiettoria della freccia, l'eroe esce dalla partenza
+    fn create_query_from_action(a: &Action, p: &Perceptions) -> Self::Query;
     fn create_safe_formula(p: &Position) -> Self::Query;
     fn create_unsafe_formula(p: &Position) -> Self::Query;
     fn create_wumpus_formula(p: &Position) -> Self::Query;
     fn create_pit_formula(p: &Position) -> Self::Query;
+    fn create_no_pit_formula(p: &Position) -> Self::Query;
+    // il wumpus si trova in almeno una delle caselle
+    fn create_wumpus_in_formula(cells: &[Position]) -> Self::Query;
+    // il wumpus non si trova in nessuna delle caselle
+    fn create_no_wumpus_in_formula(cells: &[Position]) -> Self::Query;
     fn create_ground_truth_from_perception(p: &Perceptions) -> Self::Query;
 
-    fn is_unsafe(&mut self, p: Position) -> bool;
-    fn safe_positions(&self, query: Self::Query) -> Vec<Position>;
+    /// Estimated probability that each cell contains a hazard (only pits if the wumpus is dead).
+    /// It is a ranking more than an exact probability, see EncoderSAT::approx_count.
+    fn hazard_estimates(
+        &self,
+        cells: &[Position],
+        wumpus_dead: bool,
+        seed: u64,
+    ) -> Result<Vec<f64>, SolverError>;
+    // numero di chiamate al solver fatte finora, per le statistiche
+    fn solver_calls(&self) -> usize;
+    // (clausole, variabili) della KB, per le statistiche
+    fn size(&self) -> (usize, usize);
+    // memoria occupata dalla KB, per le statistiche
+    fn memory_footprint(&self) -> MemoryFootprint;
 }
 
 impl KnowledgeBase for EncoderSAT<Var> {
     type Query = Formula;
 
-    fn ask(&mut self, formula: &Formula) -> bool {
-        let result: bool;
+    fn ask(&mut self, formula: &Formula) -> Result<bool, SolverError> {
+        if formula.is_empty() {
+            return Ok(false);
+        }
+        if let [clause] = formula.as_slice() {
+            // la negazione di una sola clausola è un insieme di letterali unitari:
+            // basta risolvere sotto assunzioni, senza snapshot della KB
+            let assumptions: Vec<Literal<Var>> =
+                clause.iter().map(|literal| literal.not()).collect();
+            return Ok(self.solve_under_assumptions(&assumptions)? == SatResult::Unsat);
+        }
         self.snapshot(); // prendi una foto dello stato della KB
-        if formula.len() > 1 {
-            let mut tseytin_clause = vec![];
-            for clause in formula {
-                // la formula da aggiungere alla KB è (t_1 or t_2 or ... or t_n) and (t_1 <-> not c_1) and ... and (t_n <-> not c_2)
-                // dove c_1, c_2, ..., c_n sono le clausole della formula in input originale (non negata)
-                // Questa nuova formula è inferenzialmente equivalente alla formula di partenza negata.
-                // Però dobbiamo renderla in CNF in questo modo:
-                // Crea una variabile di tseytin t_i per clausola c_i
-                // aggiungi alla KB la clausola (t_i or c_i)
-                // siano alpha_1 or alpha_2 or ... or alpha_k i letterali della clausola c_i
-                // aggiungi alla KB le clausole (not t_i or not alpha_1) and ... and (not t_i or not alpha_k)
-                // aggiungi la clausola (t_1 or t_2 or ... or t_n)
-                let tseytin = self.create_raw_variable();
-                tseytin_clause.push(tseytin.clone());
-                for literal in clause {
-                    let not_literal = self.register_literal(literal.not());
-                    let not_tseytin = tseytin.not();
-                    self.add_raw_clause(vec![not_literal, not_tseytin]);
+        // KB |= formula se e solo se KB and not formula è insoddisfacibile,
+        // la negazione viene portata in CNF dalla trasformazione di Tseytin di Prop
+        self.add_prop(&Prop::from_cnf(formula).not());
+        let result = self.sat().map(|sat| !sat);
+        self.rewind(); // rimuovi le modifiche e lo snapshot della KB, anche in caso di errore
+        result
+    }
+
+    fn ask_batch(&mut self, formulas: &[Formula]) -> Result<Vec<bool>, SolverError> {
+        // le formule di un solo letterale vengono decise insieme, le altre una alla volta
+        let mut units = vec![];
+        let mut unit_indices = vec![];
+        let mut result = vec![false; formulas.len()];
+        for (i, formula) in formulas.iter().enumerate() {
+            match formula.as_slice() {
+                [clause] if clause.len() == 1 => {
+                    units.push(clause[0].clone());
+                    unit_indices.push(i);
                 }
-                let mut raw_clause = self.register_clause(clause.clone());
-                raw_clause.push(tseytin.clone());
-                self.add_raw_clause(raw_clause); // aggiunta clausola t or clausola
+                _ => result[i] = self.ask(formula)?,
             }
-            self.add_raw_clause(tseytin_clause);
-        } else {
-            if let Some(clause) = formula.get(0) {
-                for literal in clause {
-                    self.add(vec![literal.not()]);
+        }
+        if !units.is_empty() {
+            for (i, entailed) in unit_indices
+                .into_iter()
+                .zip(self.entailed_literals(&units)?)
+            {
+                result[i] = entailed;
+            }
+        }
+        Ok(result)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn ask_batch_parallel(&mut self, formulas: &[Formula]) -> Result<Vec<bool>, SolverError> {
+        use rayon::prelude::*;
+
+        // come ask_batch: le formule di un solo letterale vengono decise insieme su questa KB,
+        // così le chiamate al solver sono le stesse della versione seriale
+        let mut units = vec![];
+        let mut unit_indices = vec![];
+        let mut others = vec![];
+        for (i, formula) in formulas.iter().enumerate() {
+            match formula.as_slice() {
+                [clause] if clause.len() == 1 => {
+                    units.push(clause[0].clone());
+                    unit_indices.push(i);
                 }
-            } else {
-                self.rewind(); // rimuovi lo snapshot
-                return false;
+                _ => others.push(i),
+            }
+        }
+        // le altre, una chiamata ciascuna, sono divise tra i thread:
+        // ogni thread interroga una propria copia della KB, l'originale non viene toccata
+        let chunk = others.len().div_ceil(rayon::current_num_threads()).max(1);
+        let this = &*self;
+        let answers: Vec<(Vec<bool>, usize)> = others
+            .par_chunks(chunk)
+            .map(|chunk| {
+                let mut copy = this.clone();
+                let answers = chunk
+                    .iter()
+                    .map(|i| copy.ask(&formulas[*i]))
+                    .collect::<Result<_, _>>()?;
+                Ok((answers, copy.solver_calls() - this.solver_calls()))
+            })
+            .collect::<Result<_, SolverError>>()?;
+        let mut result = vec![false; formulas.len()];
+        let mut others = others.into_iter();
+        for (answers, calls) in answers {
+            for (entailed, i) in answers.into_iter().zip(others.by_ref()) {
+                result[i] = entailed;
+            }
+            self.add_solver_calls(calls);
+        }
+        if !units.is_empty() {
+            for (i, entailed) in unit_indices
+                .into_iter()
+                .zip(self.entailed_literals(&units)?)
+            {
+                result[i] = entailed;
             }
         }
-        result = !self.picosat_sat(); // TODO: generalize for all the solvers
-        self.rewind(); // rimuovi le modifiche e lo snapshot della KB
-        return result;
+        Ok(result)
     }
 
     fn tell(&mut self, formula: &Formula) {
+        self.record_told(formula.clone());
         for clause in formula {
             self.add(clause.clone());
         }
+        if let Some(report) = self.simplify_if_due(SIMPLIFY_EVERY) {
+            debug!(
+                "KB simplified: {} clauses removed, {} strengthened, {} -> {} literals",
+                report.removed, report.strengthened, report.literals_before, report.literals_after
+            );
+            let elimination = self.elimination_report();
+            debug!(
+                "variable elimination: {} -> {} clauses, {} -> {} literals sent to the solver",
+                elimination.clauses_before,
+                elimination.clauses_after,
+                elimination.literals_before,
+                elimination.literals_after
+            );
+        }
     }
 
-    fn consistency(&mut self) -> bool {
-        let result = self.picosat_sat();
-        if !result {
-            println!("{:?}", self);
+    fn consistency(&mut self) -> Result<(), KbError> {
+        if self.sat()? {
+            return Ok(());
         }
-        result
+        let dump = match self.dump_on_unsat() {
+            Some(dir) => Some(dump_kb(self, dir).map_err(KbError::DumpFailed)?),
+            None => None,
+        };
+        Err(KbError::Inconsistent { dump: dump })
     }
 
-    fn create_query_from_action(a: &Action, p: &Position) -> Self::Query {
+    fn create_query_from_action(a: &Action, p: &Perceptions) -> Self::Query {
         use Var::*;
 
         match *a {
-            Action::Move(direction) => vec![vec![
-                Safe {
-                    pos: p.move_clone(direction),
-                }
-                .into(),
-            ]],
-            Action::Grab => vec![vec![Gold { pos: *p }.into()]],
-            Action::Shoot(direction) => todo!(),
-            Action::Exit => todo!(),
+            Action::Move(direction) => match p.position.checked_move(direction, p.board_size) {
+                Some(next) => vec![vec![Safe { pos: next }.into()]],
+                // la clausola vuota non è mai conseguenza: contro il muro non ci si muove
+                None => vec![vec![]],
+            },
+            Action::Grab => vec![vec![Gold { pos: p.position }.into()]],
+            Action::Shoot(direction) => {
+                Self::create_wumpus_in_formula(&p.position.ray(direction, p.board_size))
+            }
+            // si esce solo dalla partenza, che la KB sa essere sicura
+            Action::Exit if p.position == Position::new(0, 0) => {
+                Self::create_safe_formula(&p.position)
+            }
+            Action::Exit => vec![vec![]],
         }
     }
     fn create_ground_truth_from_perception(p: &Perceptions) -> Self::Query {
@@ -127,60 +311,67 @@ impl KnowledgeBase for EncoderSAT<Var> {
 
         let mut formula = Vec::new();
         let mut var: Literal<Var> = Breeze { pos: p.position }.into();
-        if !p.breeze {
+        if !p.breeze() {
             var = var.not();
         }
         formula.push(vec![var]);
         var = Gold { pos: p.position }.into();
-        if p.glitter {
+        if p.glitter() {
             formula.push(vec![var]);
         }
         var = Stench { pos: p.position }.into();
-        if !p.stench {
+        if !p.stench() {
             var = var.not();
         }
         formula.push(vec![var]);
+        if p.howl() {
+            formula.push(vec![Howl.into()]);
+        }
 
-        // TODO: bump and howl
+        // TODO: bump
 
         formula
     }
 
-    fn is_unsafe(&mut self, p: Position) -> bool {
+    fn hazard_estimates(
+        &self,
+        cells: &[Position],
+        wumpus_dead: bool,
+        seed: u64,
+    ) -> Result<Vec<f64>, SolverError> {
         use Var::*;
 
-        let phi = vec![vec![Wumpus { pos: p }.into(), Pit { pos: p }.into()]];
-
-        if self.ask(&phi) {
-            self.tell(&phi);
-            println!("[INFO] Position {:?} is UNSAFE", p);
-            if self.ask(&vec![vec![Pit { pos: p }.into()]]) {
-                self.tell(&vec![vec![Pit { pos: p }.into()]]);
-                println!("[INFO] Pit in position: {:?}", p);
-            } else {
-                self.tell(&vec![vec![Wumpus { pos: p }.into()]]);
-                println!("[INFO] Wumpus in position: {:?}", p);
-            };
+        let targets: Vec<Var> = cells
+            .iter()
+            .map(|pos| {
+                if wumpus_dead {
+                    Pit { pos: *pos }
+                } else {
+                    Safe { pos: *pos }
+                }
+            })
+            .collect();
+        // con la KB inconsistente ogni casella è considerata pericolosa
+        let estimates = self
+            .approx_count(&targets, RISK_SAMPLES, seed)?
+            .unwrap_or_else(|| vec![if wumpus_dead { 1.0 } else { 0.0 }; cells.len()]);
+        Ok(if wumpus_dead {
+            estimates
+        } else {
+            estimates.into_iter().map(|safe| 1.0 - safe).collect()
+        })
+    }
 
-            return true;
-        }
+    fn solver_calls(&self) -> usize {
+        EncoderSAT::solver_calls(self)
+    }
 
-        return false;
+    fn size(&self) -> (usize, usize) {
+        (self.clause_count(), self.variable_count())
     }
 
-    fn safe_positions(&self, query: Self::Query) -> Vec<Position> {
-        let mut result = vec![];
-        for clause in query {
-            for literal in clause.into_iter().map(|x| x.inner()) {
-                match literal {
-                    Var::Safe { pos } => {
-                        result.push(pos);
-                    }
-                    _ => {}
-                }
-            }
-        }
-        result
+    fn memory_footprint(&self) -> MemoryFootprint {
+        EncoderSAT::memory_footprint(self)
     }
 
     fn create_safe_formula(p: &Position) -> Self::Query {
@@ -202,6 +393,29 @@ impl KnowledgeBase for EncoderSAT<Var> {
         use Var::*;
         vec![vec![Pit { pos: *p }.into()]]
     }
+
+    fn create_no_pit_formula(p: &Position) -> Self::Query {
+        use Var::*;
+        vec![vec![Neg(Pit { pos: *p })]]
+    }
+
+    fn create_wumpus_in_formula(cells: &[Position]) -> Self::Query {
+        use Var::*;
+        vec![
+            cells
+                .iter()
+                .map(|pos| Wumpus { pos: *pos }.into())
+                .collect(),
+        ]
+    }
+
+    fn create_no_wumpus_in_formula(cells: &[Position]) -> Self::Query {
+        use Var::*;
+        cells
+            .iter()
+            .map(|pos| vec![Neg(Wumpus { pos: *pos })])
+            .collect()
+    }
 }
 
 pub fn init_kb(size: usize) -> EncoderSAT<Var> {
@@ -222,7 +436,7 @@ pub fn init_kb(size: usize) -> EncoderSAT<Var> {
         }
     }
     kb = clause.end();
-    println!("[INFO] At least one Wumpus");
+    debug!("At least one Wumpus");
 
     // la stanza 0 0 è sicura
     clause = kb.clause();
@@ -230,7 +444,7 @@ pub fn init_kb(size: usize) -> EncoderSAT<Var> {
         pos: Position::new(0, 0),
     });
     kb = clause.end();
-    println!("[INFO] The cell 0 0 is safe");
+    debug!("The cell 0 0 is safe");
 
     // il wumpus si trova in esattamente una posizione
     // il wumpus non si può trovare in due posizioni diverse
@@ -260,7 +474,7 @@ pub fn init_kb(size: usize) -> EncoderSAT<Var> {
         }
     }
 
-    println!("[INFO] at most one wumpus and one gold");
+    debug!("at most one wumpus and one gold");
 
     // l'oro si trova in almeno una posizione
     clause = kb.clause();
@@ -272,60 +486,25 @@ pub fn init_kb(size: usize) -> EncoderSAT<Var> {
         }
     }
     kb = clause.end();
-    println!("[INFO] at least one gold");
-
-    // in una stanza c'è vento se e solo se in una stanza adiacente c'è il pozzo
-    let mut vento_implica_pozzi = vec![];
-    // let mut pozzo_implica_vento = vec![];
-    // in una stanza c'è puzza se e solo se in una stanza adiacente c'è il Wumpus
-    let mut puzza_implica_wumpus = vec![];
-    // let mut wumpus_implica_puzza = vec![];
-
-    use crate::world::Direction::*;
+    debug!("at least one gold");
 
     for i in 0..size {
         for j in 0..size {
             let pos = Position::new(i, j);
-            vento_implica_pozzi.push(Neg(Breeze { pos: pos }));
-            puzza_implica_wumpus.push(Neg(Stench { pos: pos }));
-            for dir in [North, Sud, East, Ovest] {
-                if pos.possible_move(dir, size) {
-                    // vento_implica_pozzo
-                    clause = kb.clause();
-                    clause.add(Neg(Pit { pos: pos }));
-                    clause.add(Breeze {
-                        pos: pos.move_clone(dir),
-                    });
-                    kb = clause.end();
-                    vento_implica_pozzi.push(
-                        Pit {
-                            pos: pos.move_clone(dir),
-                        }
-                        .into(),
-                    );
-                    // puzza_implica_wumpus
-                    clause = kb.clause();
-                    clause.add(Neg(Wumpus { pos: pos }));
-                    clause.add(Stench {
-                        pos: pos.move_clone(dir),
-                    });
-                    kb = clause.end();
-                    puzza_implica_wumpus.push(
-                        Wumpus {
-                            pos: pos.move_clone(dir),
-                        }
-                        .into(),
-                    );
-                }
+            let mut pozzi_adiacenti = vec![];
+            let mut wumpus_adiacenti = vec![];
+            for next in pos.neighbor_positions(size) {
+                pozzi_adiacenti.push(Prop::var(Pit { pos: next }));
+                wumpus_adiacenti.push(Prop::var(Wumpus { pos: next }));
             }
-            kb.add(vento_implica_pozzi);
-            kb.add(puzza_implica_wumpus);
-            vento_implica_pozzi = vec![];
-            puzza_implica_wumpus = vec![];
+            // in una stanza c'è vento se e solo se in una stanza adiacente c'è il pozzo
+            kb.add_prop(&Prop::var(Breeze { pos: pos }).iff(Prop::or(pozzi_adiacenti)));
+            // in una stanza c'è puzza se e solo se in una stanza adiacente c'è il Wumpus
+            kb.add_prop(&Prop::var(Stench { pos: pos }).iff(Prop::or(wumpus_adiacenti)));
         }
     }
 
-    println!("[INFO] physics of the world");
+    debug!("physics of the world");
 
     // se una casella è safe allora non c'è il wumpus e non c'è il pozzo
     // se in una casella non c'è il wumpus e non c'è il pozzo allora è safe
@@ -362,10 +541,455 @@ pub fn init_kb(size: usize) -> EncoderSAT<Var> {
         }
     }
 
-    println!("[INFO] safety rules");
+    debug!("safety rules");
 
-    // se il wumpus ha urlato, allora la cella dove stava il wumpus è sicura
+    // il wumpus morto resta nella sua casella: dopo l'urlo l'eroe considera sicure
+    // le caselle senza pozzo (vedi Hero::is_safe), le conseguenze del tiro sono comunicate dall'eroe
     // println!("{:?}", kb);
-    // se ha sentito il rumore della freccia sbattere, allora in tutte le celle in cui è passata la freccia non ci sta il wumpus
     kb
 }
+
+/// The knowledge bases whose DIMACS encoding is kept in golden files, with the name of the
+/// file: the empty 2x2 and 3x3 boards and a 3x3 board after the first two perceptions.
+pub fn golden_kbs() -> Vec<(&'static str, EncoderSAT<Var>)> {
+    let mut told = init_kb(3);
+    for p in [
+        fixtures::start_clean(3),
+        Perceptions::at(Position::new(1, 0), 3).breeze().build(),
+    ] {
+        told.tell(&EncoderSAT::<Var>::create_ground_truth_from_perception(&p));
+    }
+    vec![
+        ("init-2", init_kb(2)),
+        ("init-3", init_kb(3)),
+        ("told-3", told),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::test_backends;
+
+    type Kb = EncoderSAT<Var>;
+
+    // una KB per ogni backend disponibile, con le percezioni già dette
+    fn told(size: usize, perceptions: &[Perceptions]) -> Vec<(String, Kb)> {
+        test_backends()
+            .into_iter()
+            .map(|backend| {
+                let mut kb = init_kb(size);
+                kb.set_solver(backend.clone());
+                for p in perceptions {
+                    kb.tell(&Kb::create_ground_truth_from_perception(p));
+                }
+                (backend.name().to_string(), kb)
+            })
+            .collect()
+    }
+
+    fn entails(kb: &mut Kb, formula: Formula) -> bool {
+        kb.ask(&formula).expect("the solver answers")
+    }
+
+    fn at(x: usize, y: usize) -> Position {
+        Position::new(x, y)
+    }
+
+    // la risposta di sat() e quelle di ask() su ogni letterale delle variabili della KB
+    fn answers(kb: &mut Kb) -> Vec<bool> {
+        let mut answers = vec![kb.sat().expect("the solver answers")];
+        for var in kb.encode().1 {
+            for literal in [Literal::Pos(var), Neg(var)] {
+                answers.push(entails(kb, vec![vec![literal]]));
+            }
+        }
+        answers
+    }
+
+    #[test]
+    fn elimination_keeps_the_answers_of_the_golden_kbs() {
+        for (name, mut kb) in golden_kbs() {
+            kb.use_in_process_solver();
+            let expected = answers(&mut kb);
+            for growth in [0, 2] {
+                kb.set_elimination(Some(growth));
+                assert_eq!(answers(&mut kb), expected, "{} growth {}", name, growth);
+            }
+            // senza crescita ammessa le clausole mandate al solver non aumentano
+            kb.set_elimination(Some(0));
+            let report = kb.elimination_report();
+            assert!(report.clauses_after <= report.clauses_before, "{}", name);
+        }
+    }
+
+    #[test]
+    fn a_quiet_start_makes_its_neighbors_safe() {
+        for (name, mut kb) in told(2, &[fixtures::start_clean(2)]) {
+            assert!(
+                entails(&mut kb, Kb::create_safe_formula(&at(1, 0))),
+                "{}",
+                name
+            );
+            assert!(
+                entails(&mut kb, Kb::create_safe_formula(&at(0, 1))),
+                "{}",
+                name
+            );
+            // nessuna percezione dice niente della diagonale
+            assert!(
+                !entails(&mut kb, Kb::create_safe_formula(&at(1, 1))),
+                "{}",
+                name
+            );
+            assert!(kb.consistency().is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn two_breezes_pin_the_pit() {
+        let perceptions = [
+            fixtures::start_clean(3),
+            Perceptions::at(at(1, 0), 3).breeze().build(),
+            Perceptions::at(at(0, 1), 3).build(),
+        ];
+        for (name, mut kb) in told(3, &perceptions) {
+            // la brezza in (1,0) non viene da (1,1), che (0,1) dice senza pozzo
+            assert!(
+                entails(&mut kb, Kb::create_safe_formula(&at(1, 1))),
+                "{}",
+                name
+            );
+            assert!(
+                entails(&mut kb, Kb::create_pit_formula(&at(2, 0))),
+                "{}",
+                name
+            );
+            assert!(
+                !entails(&mut kb, Kb::create_safe_formula(&at(2, 0))),
+                "{}",
+                name
+            );
+            assert!(
+                !entails(&mut kb, Kb::create_pit_formula(&at(0, 2))),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn two_stenches_pin_the_wumpus() {
+        let perceptions = [
+            fixtures::start_clean(3),
+            Perceptions::at(at(1, 0), 3).stench().build(),
+            Perceptions::at(at(0, 1), 3).stench().build(),
+        ];
+        for (name, mut kb) in told(3, &perceptions) {
+            // (1,1) è l'unica casella vicina a entrambe, e il wumpus è uno solo
+            assert!(
+                entails(&mut kb, Kb::create_wumpus_formula(&at(1, 1))),
+                "{}",
+                name
+            );
+            for cell in [at(2, 0), at(0, 2)] {
+                assert!(
+                    !entails(&mut kb, Kb::create_wumpus_formula(&cell)),
+                    "{}",
+                    name
+                );
+                assert!(entails(&mut kb, Kb::create_safe_formula(&cell)), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn shots_and_exits_are_asked_like_the_other_actions() {
+        let perceptions = [
+            fixtures::start_clean(3),
+            Perceptions::at(at(1, 0), 3).stench().build(),
+            Perceptions::at(at(0, 1), 3).stench().build(),
+        ];
+        let from = |x, y| Perceptions::at(at(x, y), 3).build();
+        for (name, mut kb) in told(3, &perceptions) {
+            // il wumpus in (1,1) è sulla traiettoria verso est da (0,1) e verso sud da (1,0)
+            for (action, p, expected) in [
+                (Action::Shoot(Direction::East), from(0, 1), true),
+                (Action::Shoot(Direction::Sud), from(1, 0), true),
+                (Action::Shoot(Direction::East), from(1, 0), false),
+                // contro il muro la freccia non attraversa nessuna casella
+                (Action::Shoot(Direction::North), from(0, 0), false),
+                (Action::Exit, from(0, 0), true),
+                (Action::Exit, from(1, 0), false),
+            ] {
+                assert_eq!(
+                    entails(&mut kb, Kb::create_query_from_action(&action, &p)),
+                    expected,
+                    "{}: {:?} from {:?}",
+                    name,
+                    action,
+                    p.position
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_is_safe_without_evidence() {
+        for size in [2, 3] {
+            for (name, mut kb) in told(size, &[]) {
+                for y in 0..size {
+                    for x in 0..size {
+                        if (x, y) == (0, 0) {
+                            continue;
+                        }
+                        let safe = Kb::create_safe_formula(&at(x, y));
+                        assert!(!entails(&mut kb, safe), "{} {:?}", name, (x, y));
+                    }
+                }
+            }
+        }
+        // con la brezza alla partenza nessuna delle due vicine è sicura
+        for (name, mut kb) in told(3, &[fixtures::start_breezy(3)]) {
+            assert!(
+                !entails(&mut kb, Kb::create_safe_formula(&at(1, 0))),
+                "{}",
+                name
+            );
+            assert!(
+                !entails(&mut kb, Kb::create_safe_formula(&at(0, 1))),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn clones_answer_the_same_from_several_threads() {
+        let perceptions = [
+            fixtures::start_clean(4),
+            Perceptions::at(at(1, 0), 4).breeze().build(),
+            Perceptions::at(at(0, 1), 4).stench().build(),
+        ];
+        let (_, mut kb) = told(4, &perceptions).remove(0);
+        let cells: Vec<Position> = (0..4).flat_map(|y| (0..4).map(move |x| at(x, y))).collect();
+        let queries: Vec<Formula> = cells
+            .iter()
+            .flat_map(|cell| {
+                [
+                    Kb::create_safe_formula(cell),
+                    Kb::create_pit_formula(cell),
+                    Kb::create_wumpus_formula(cell),
+                ]
+            })
+            .collect();
+        let expected: Vec<bool> = queries
+            .iter()
+            .map(|q| entails(&mut kb, q.clone()))
+            .collect();
+        // ogni thread interroga la sua copia su una parte diversa delle domande
+        let answers: Vec<(usize, bool)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|t| {
+                    let mut copy = kb.clone();
+                    let queries = &queries;
+                    scope.spawn(move || {
+                        (t..queries.len())
+                            .step_by(4)
+                            .map(|i| (i, entails(&mut copy, queries[i].clone())))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("the thread does not panic"))
+                .collect()
+        });
+        assert_eq!(answers.len(), queries.len());
+        for (i, answer) in answers {
+            assert_eq!(answer, expected[i], "{:?}", queries[i]);
+        }
+        // le copie non toccano l'originale
+        assert_eq!(
+            queries
+                .iter()
+                .map(|q| entails(&mut kb, q.clone()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn simplify_shrinks_a_late_game_kb() {
+        let size = 4;
+        let mut kb = init_kb(size);
+        kb.use_in_process_solver();
+        // le percezioni di una partita avanzata, dette senza la semplificazione periodica
+        for (x, y) in [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (3, 0),
+            (0, 1),
+            (1, 1),
+            (2, 1),
+            (0, 2),
+        ] {
+            let p = Perceptions::at(at(x, y), size).build();
+            for clause in &Kb::create_ground_truth_from_perception(&p) {
+                kb.add(clause.clone());
+            }
+        }
+        let queries: Vec<Formula> = [(3, 1), (1, 2), (3, 3)]
+            .into_iter()
+            .map(|(x, y)| Kb::create_safe_formula(&at(x, y)))
+            .collect();
+        let expected: Vec<bool> = queries
+            .iter()
+            .map(|q| entails(&mut kb, q.clone()))
+            .collect();
+        let dimacs_before = kb.encode().0.len();
+
+        kb.snapshot();
+        assert!(
+            kb.simplify().is_none(),
+            "no simplification with a live snapshot"
+        );
+        kb.rewind();
+
+        let report = kb.simplify().expect("no snapshot is live");
+        assert!(report.removed > 0);
+        assert!(report.strengthened > 0);
+        assert!(report.clauses_after < report.clauses_before);
+        assert!(report.literals_after < report.literals_before);
+        let dimacs_after = kb.encode().0.len();
+        assert!(
+            dimacs_after < dimacs_before,
+            "{} -> {} bytes",
+            dimacs_before,
+            dimacs_after
+        );
+        // la semplificazione non cambia le risposte
+        let answers: Vec<bool> = queries
+            .iter()
+            .map(|q| entails(&mut kb, q.clone()))
+            .collect();
+        assert_eq!(answers, expected);
+    }
+
+    #[test]
+    fn an_inconsistency_dumps_the_kb_to_files() {
+        let dir = std::env::temp_dir().join(format!("wumpus-dump-{}", std::process::id()));
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        kb.set_dump_on_unsat(Some(dir.clone()));
+        kb.consistency().expect("the empty board is consistent");
+        assert!(
+            !dir.exists(),
+            "nothing is dumped while the KB is consistent"
+        );
+
+        // la stessa casella con e senza brezza
+        kb.tell(&Kb::create_ground_truth_from_perception(
+            &Perceptions::at(at(0, 0), 3).breeze().build(),
+        ));
+        kb.tell(&Kb::create_ground_truth_from_perception(
+            &Perceptions::at(at(0, 0), 3).build(),
+        ));
+        let prefix = match kb.consistency() {
+            Err(KbError::Inconsistent { dump: Some(prefix) }) => prefix,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(()) => panic!("the KB is inconsistent"),
+        };
+        assert!(prefix.starts_with(&dir));
+
+        let cnf = fs::read_to_string(prefix.with_extension("cnf")).unwrap();
+        assert!(cnf.lines().any(|line| line.starts_with("c 1 ")));
+        assert!(cnf.lines().any(|line| line.starts_with("p cnf ")));
+        let told = fs::read_to_string(prefix.with_extension("told")).unwrap();
+        assert!(told.starts_with("# formulas told"));
+        // l'intestazione più le due formule
+        assert_eq!(told.lines().count(), 1 + 2);
+        let core = fs::read_to_string(prefix.with_extension("core")).unwrap();
+        assert!(core.starts_with("# UNSAT core"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batched_asks_use_fewer_solver_calls() {
+        let perceptions = [
+            fixtures::start_clean(4),
+            Perceptions::at(at(1, 0), 4).breeze().build(),
+            Perceptions::at(at(0, 1), 4).stench().build(),
+        ];
+        for (name, kb) in told(4, &perceptions) {
+            let queries: Vec<Formula> = [(2, 0), (1, 1), (0, 2), (3, 3)]
+                .into_iter()
+                .flat_map(|(x, y)| {
+                    [
+                        Kb::create_safe_formula(&at(x, y)),
+                        Kb::create_unsafe_formula(&at(x, y)),
+                    ]
+                })
+                .collect();
+
+            let mut single = kb.clone();
+            let before = single.solver_calls();
+            let expected: Vec<bool> = queries
+                .iter()
+                .map(|q| entails(&mut single, q.clone()))
+                .collect();
+            let single_calls = single.solver_calls() - before;
+
+            let mut batch = kb.clone();
+            let before = batch.solver_calls();
+            assert_eq!(batch.ask_batch(&queries).unwrap(), expected, "{}", name);
+            let batch_calls = batch.solver_calls() - before;
+
+            assert_eq!(single_calls, queries.len(), "{}", name);
+            assert!(
+                batch_calls < single_calls,
+                "{}: {} solver calls in a batch, {} one at a time",
+                name,
+                batch_calls,
+                single_calls
+            );
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_asks_match_the_serial_ones() {
+        let perceptions = [
+            fixtures::start_clean(4),
+            Perceptions::at(at(1, 0), 4).breeze().build(),
+            Perceptions::at(at(0, 1), 4).stench().build(),
+        ];
+        for (name, kb) in told(4, &perceptions) {
+            // formule di un solo letterale e formule con più clausole mescolate
+            let queries: Vec<Formula> = (0..4)
+                .flat_map(|y| (0..4).map(move |x| at(x, y)))
+                .flat_map(|cell| {
+                    [
+                        Kb::create_safe_formula(&cell),
+                        Kb::create_unsafe_formula(&cell),
+                        Kb::create_pit_formula(&cell),
+                    ]
+                })
+                .collect();
+
+            let mut serial = kb.clone();
+            let expected = serial.ask_batch(&queries).unwrap();
+            let mut parallel = kb.clone();
+            assert_eq!(
+                parallel.ask_batch_parallel(&queries).unwrap(),
+                expected,
+                "{}",
+                name
+            );
+            assert_eq!(parallel.solver_calls(), serial.solver_calls(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+// la libreria scrive solo con il crate log, l'output per l'utente è compito del binario
+#![deny(clippy::print_stdout, clippy::print_stderr)]
+// nel repo gli inizializzatori scrivono sempre campo: campo
+#![allow(clippy::redundant_field_names)]
+
+pub mod agents;
+pub mod analysis;
+pub mod encoder;
+pub mod environment;
+pub mod experiments;
+pub mod fixtures;
+pub mod hero;
+pub mod kb;
+pub mod orientation;
+mod prop;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod replay;
+pub mod simulation;
+pub mod solver;
+pub mod stats;
+pub mod strategy;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod world;
@@ -1,27 +1,1261 @@
-mod encoder;
-mod hero;
-mod kb;
-mod world;
+// nel repo gli inizializzatori scrivono sempre campo: campo
+#![allow(clippy::redundant_field_names)]
 
-use crate::{hero::Hero, kb::init_kb, world::World};
+use std::{
+    collections::HashSet,
+    env,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-// true se trova l'oro false altrimenti
-fn simulate(dim: usize, pit_number: usize) -> bool {
-    let mut world = World::new(dim, pit_number);
-    let mut hero = Hero::new(init_kb(dim), dim);
+use clap::{Args, FromArgMatches, Parser, Subcommand, builder::FalseyValueParser};
+use log::{LevelFilter, error, info, warn};
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    agents::{Agent, AgentKind, HumanAgent, RandomAgent},
+    analysis::{check_soundness_against, knowledge_reachable},
+    encoder::{EncoderSAT, MemoryFootprint},
+    experiments::{self, Matrix},
+    hero::{DecisionTrace, ExplorerKind, FrontierPolicy, Hero, HeroConfig, HeroStats},
+    kb::{KnowledgeBase, Var, init_kb},
+    orientation::play_oriented_games,
+    replay::{GameRecord, Step, write_frames},
+    simulation::{
+        NewGame, Outcome, Progress, Screen, SimulationResult, TurnLog, View, check_invariants,
+        interrupted, play_games, score_change, simulate,
+    },
+    solver::Backend,
+    stats::{BatchSummary, PairedComparison, Z_95, wilson_interval},
+    strategy::GreedyStrategy,
+    world::{ActionResult, GameSeeds, Position, World},
+};
+
+// dimensione della mappa e numero di pozzi delle partite
+const DIM: usize = 10;
+const PITS: usize = 12;
+// partite di una serie
+const GAMES: u64 = 100;
+
+/// Plays the Wumpus World with a hero that reasons with a SAT solver. Without a
+/// subcommand plays a batch of games like `simulate`.
+#[derive(Parser, Debug)]
+#[command(name = "wumpus", version, about)]
+struct Cli {
+    #[command(flatten)]
+    common: Common,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// The world and the agent, the same options for every subcommand. Most of them can
+/// also be set with the WUMPUS_* environment variables; for the flags any value but
+/// false, no, off, n, f or 0 turns them on.
+#[derive(Args, Clone, Copy, Debug)]
+struct Common {
+    /// Seed of the game, it decides both the dungeon and the agent; game i of a batch
+    /// uses seed ^ i
+    #[arg(long, global = true, env = "WUMPUS_SEED")]
+    seed: Option<u64>,
+    /// Side of the dungeon
+    #[arg(long, global = true, default_value_t = DIM)]
+    dim: usize,
+    /// Pits in the dungeon
+    #[arg(long, global = true, default_value_t = PITS)]
+    pits: usize,
+    /// Who plays: hero, greedy, random or human
+    #[arg(long, global = true, env = "WUMPUS_AGENT", default_value = "hero")]
+    agent: AgentKind,
+    /// Strategy of the hero: classic, or greedy like --agent greedy
+    #[arg(long, global = true, env = "WUMPUS_STRATEGY")]
+    strategy: Option<StrategyKind>,
+    /// Abandons the games lasting longer than these seconds
+    #[arg(long, global = true, value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+    /// Also shows the maps and the reasoning of the hero; RUST_LOG, if set, wins
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Only the errors and the final summary
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Plays the games of a batch on more threads (rayon feature)
+    #[arg(long, global = true, env = "WUMPUS_PARALLEL_GAMES", value_parser = FalseyValueParser::new())]
+    parallel_games: bool,
+    /// Plays in the oriented world: the hero turns left or right and moves or shoots only
+    /// where it faces, every turn costs like a step
+    #[arg(long, global = true, env = "WUMPUS_ORIENTED", value_parser = FalseyValueParser::new())]
+    oriented: bool,
+    #[command(flatten)]
+    hero: HeroArgs,
+}
+
+impl Common {
+    // --strategy greedy equivale a --agent greedy
+    fn agent(&self) -> AgentKind {
+        if self.agent == AgentKind::Hero && self.strategy == Some(StrategyKind::Greedy) {
+            return AgentKind::Greedy;
+        }
+        self.agent
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StrategyKind {
+    Classic,
+    Greedy,
+}
+
+impl FromStr for StrategyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "classic" => Ok(StrategyKind::Classic),
+            "greedy" => Ok(StrategyKind::Greedy),
+            other => Err(format!("unknown strategy {}", other)),
+        }
+    }
+}
+
+/// The tunables of the hero, see HeroConfig; the ones not given keep their default.
+#[derive(Args, Clone, Copy, Debug)]
+#[command(next_help_heading = "Hero")]
+struct HeroArgs {
+    /// Enters the least risky frontier cell when no move is provably safe, if its
+    /// estimated risk is below this threshold (0 = never)
+    #[arg(long, global = true, env = "WUMPUS_RISK_THRESHOLD")]
+    risk_threshold: Option<f64>,
+    /// Also plans with BFS and checks that the plans have the same length
+    #[arg(long, global = true, env = "WUMPUS_CHECK_PLANS", value_parser = FalseyValueParser::new())]
+    check_plans: bool,
+    /// Explores every provably safe cell before exiting, even after grabbing the gold
+    #[arg(long, global = true, env = "WUMPUS_EXPLORE_ALL", value_parser = FalseyValueParser::new())]
+    explore_all: bool,
+    /// Panics at the first plan longer than the shortest path or move into an unsafe cell
+    #[arg(long, global = true, env = "WUMPUS_CHECK_INVARIANTS", value_parser = FalseyValueParser::new())]
+    check_invariants: bool,
+    /// Measures the time spent in each part of the turn
+    #[arg(long, global = true, env = "WUMPUS_TIMING", value_parser = FalseyValueParser::new())]
+    timing: bool,
+    /// How many times in a turn a cell found unsafe makes its neighbours be asked again
+    #[arg(long, global = true, env = "WUMPUS_PROPAGATION_DEPTH")]
+    propagation_depth: Option<usize>,
+    /// Checks the consistency of the knowledge base every these turns (0 = never)
+    #[arg(long, global = true, env = "WUMPUS_CONSISTENCY_EVERY")]
+    consistency_every: Option<usize>,
+    /// Turns after which the hero stops exploring and goes home
+    #[arg(long, global = true, env = "WUMPUS_MAX_STEPS")]
+    max_steps: Option<usize>,
+    /// Planning attempts in a turn before falling back to going home
+    #[arg(long, global = true, env = "WUMPUS_PLANNING_ATTEMPTS")]
+    planning_attempts: Option<usize>,
+    /// Search of the exploration plans: astar, bfs or dijkstra
+    #[arg(long, global = true, env = "WUMPUS_EXPLORER")]
+    explorer: Option<ExplorerKind>,
+    /// Search of the plans to go home: astar, bfs or dijkstra
+    #[arg(long, global = true, env = "WUMPUS_HOME_EXPLORER")]
+    home_explorer: Option<ExplorerKind>,
+    /// Frontier cell to explore: nearest or information-gain
+    #[arg(long, global = true, env = "WUMPUS_FRONTIER")]
+    frontier: Option<FrontierPolicy>,
+    /// Splits the safety asks of each turn of the hero between threads (rayon feature)
+    #[arg(long, global = true, env = "WUMPUS_PARALLEL", value_parser = FalseyValueParser::new())]
+    parallel_queries: bool,
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{}", e))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Plays a batch of games and prints a summary
+    Simulate(SimulateArgs),
+    /// Plays a game from the keyboard
+    Play {
+        /// Shows only the cells already visited
+        #[arg(long)]
+        fog: bool,
+    },
+    /// Replays a recorded game, or with a number the game of a batch with that seed
+    Replay {
+        /// A game recorded with --record, or a seed
+        target: String,
+        /// Checks that a new agent built from the seed of the recorded game makes the
+        /// same choices
+        #[arg(long)]
+        check_agent: bool,
+    },
+    /// Times init_kb and the asks of the hero on a dungeon
+    Bench {
+        /// Repetitions of each measure
+        #[arg(long, default_value_t = 20)]
+        iterations: u32,
+    },
+    /// Plays every combination of sizes and pits of a TOML file, a CSV row for each
+    Experiments {
+        /// The experiments file
+        config: PathBuf,
+        /// Writes the CSV in this file instead of `out` of the experiments file
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Success rate against the density of the pits, as columns for gnuplot or matplotlib
+    Sweep {
+        /// Lowest density, pits over cells
+        #[arg(long, default_value_t = 0.05)]
+        from: f64,
+        /// Highest density
+        #[arg(long, default_value_t = 0.3)]
+        to: f64,
+        #[arg(long, default_value_t = 0.05)]
+        step: f64,
+        /// Games for each density
+        #[arg(long, default_value_t = GAMES)]
+        trials: u64,
+        /// Writes the data in this file instead of on stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Plays the same games with two agents and compares them game by game
+    Compare {
+        #[arg(long, default_value = "hero")]
+        a: AgentKind,
+        #[arg(long, default_value = "random")]
+        b: AgentKind,
+        #[arg(long, default_value_t = GAMES)]
+        games: u64,
+    },
+    /// Compares the maps that can be won with the games won by the agent
+    Ceiling {
+        #[arg(long, default_value_t = GAMES)]
+        games: u64,
+    },
+    /// Checks that the knowledge base never proves a false fact on random dungeons and that
+    /// its solver always agrees with the in-process DPLL
+    Soundness {
+        #[arg(long, default_value_t = GAMES)]
+        games: u64,
+    },
+    /// Checks that the deterministic hero still makes the choices of the golden traces
+    Golden {
+        #[arg(default_value = "tests/golden")]
+        dir: PathBuf,
+        /// Plays --games games from --seed (default 0) and rewrites the traces
+        #[arg(long)]
+        update: bool,
+        #[arg(long, default_value_t = 10)]
+        games: u64,
+    },
+    /// Checks that the DIMACS encoding of the golden knowledge bases did not change
+    GoldenDimacs {
+        #[arg(default_value = "tests/dimacs")]
+        dir: PathBuf,
+        /// Rewrites the golden files with the current encoding
+        #[arg(long)]
+        update: bool,
+    },
+    /// A game in the terminal UI
+    Tui {
+        /// The actions come from the keyboard
+        #[arg(long)]
+        human: bool,
+        /// Hides the cells not visited yet, only with --human
+        #[arg(long)]
+        fog: bool,
+        /// Milliseconds between two turns when the agent runs on its own
+        #[arg(long, default_value_t = 300)]
+        tick: u64,
+    },
+}
+
+#[derive(Args, Debug)]
+struct SimulateArgs {
+    #[arg(long, default_value_t = GAMES)]
+    games: u64,
+    /// Animates a single game, pausing these milliseconds after every turn
+    #[arg(long)]
+    watch: Option<u64>,
+    /// What --watch shows: world, knowledge or both
+    #[arg(long, default_value = "both")]
+    view: View,
+    /// Writes each turn as a text file in this directory, a subdirectory per game
+    #[arg(long)]
+    frames: Option<PathBuf>,
+    /// What the frames show: world, knowledge or both
+    #[arg(long, default_value = "world")]
+    frames_view: View,
+    /// Writes each turn as a JSON line in this file (serde feature)
+    #[arg(long)]
+    turn_log: Option<PathBuf>,
+    /// Saves every game in this directory to replay it (serde feature)
+    #[arg(long, env = "WUMPUS_RECORD")]
+    record: Option<PathBuf>,
+}
+
+// `simulate` senza argomenti, con i valori di default e le variabili d'ambiente
+impl Default for SimulateArgs {
+    fn default() -> Self {
+        let matches = SimulateArgs::augment_args(clap::Command::new("simulate"))
+            .get_matches_from(["simulate"]);
+        SimulateArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+}
+
+// con un seed sia il mondo che l'agente sono deterministici: il seed è quello della partita,
+// l'agente usa GameSeeds::agent
+fn create_agent(
+    kind: AgentKind,
+    dim: usize,
+    config: HeroConfig,
+    seed: Option<u64>,
+    timeout: Option<Duration>,
+) -> Box<dyn Agent> {
+    let rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(GameSeeds::new(seed).agent),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
+    match kind {
+        AgentKind::Random => return Box::new(RandomAgent::new(rng, 4 * dim * dim)),
+        AgentKind::Human => return Box::new(HumanAgent::new()),
+        AgentKind::Hero | AgentKind::Greedy => {}
+    }
+    let mut hero = Hero::with_rng(new_kb(dim, timeout), dim, config, rng);
+    if kind == AgentKind::Greedy {
+        hero.set_strategy(Box::new(GreedyStrategy));
+    }
+    Box::new(hero)
+}
+
+fn new_kb(dim: usize, timeout: Option<Duration>) -> EncoderSAT<Var> {
+    let mut kb = init_kb(dim);
+    // se impostata, la KB inconsistente viene salvata in questa cartella
+    kb.set_dump_on_unsat(env::var_os("WUMPUS_DUMP_DIR").map(PathBuf::from));
+    // nessuna chiamata al solver può durare più dell'intera partita
+    kb.set_timeout(timeout);
+    kb.set_solver(solver_from_env().solver());
+    kb
+}
+
+// WUMPUS_SOLVER sceglie il backend: picosat (default), dpll risolve nel processo senza
+// bisogno di picosat, portfolio li fa correre entrambi, cryptominisat con la sua feature
+fn solver_from_env() -> Backend {
+    let Ok(name) = env::var("WUMPUS_SOLVER") else {
+        return Backend::default();
+    };
+    match name.parse() {
+        Ok(backend) => backend,
+        Err(e) => {
+            error!("WUMPUS_SOLVER: {}", e);
+            exit(1);
+        }
+    }
+}
+
+// il mondo e l'agente di una partita con le dimensioni di common
+fn new_game(
+    common: &Common,
+    kind: AgentKind,
+    config: HeroConfig,
+    seed: Option<u64>,
+) -> (World, Box<dyn Agent>) {
+    let world = match seed {
+        Some(seed) => World::new_with_seed(common.dim, common.pits, seed),
+        None => World::new(common.dim, common.pits),
+    };
+    let agent = create_agent(kind, common.dim, config, seed, common.timeout);
+    (world, agent)
+}
+
+// le partite di una serie, con la feature rayon e --parallel-games su più thread; con
+// --oriented nel mondo orientato e sempre in sequenza
+fn play_batch(
+    games: u64,
+    seed: u64,
+    new_game: &NewGame<'_>,
+    common: &Common,
+    progress: &Progress,
+) -> Vec<(SimulationResult, Vec<TurnLog>)> {
+    if !common.oriented {
+        return play_games(
+            games,
+            Some(seed),
+            common.parallel_games,
+            new_game,
+            common.timeout,
+            progress,
+        );
+    }
+    if common.parallel_games {
+        warn!("The oriented games are played in sequence, --parallel-games is ignored");
+    }
+    play_oriented_games(games, Some(seed), new_game, common.timeout, progress)
+}
+
+// con la feature serde e WUMPUS_TRACE impostata ogni decisione viene scritta nel file come una riga JSON
+#[cfg(feature = "serde")]
+fn trace_sink() -> Box<dyn FnMut(&DecisionTrace)> {
+    use std::{fs::File, io::Write};
+
+    let Some(path) = env::var_os("WUMPUS_TRACE") else {
+        return Box::new(|_| {});
+    };
+    let mut file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Can't create the trace file {:?}: {}", path, e);
+            exit(1);
+        }
+    };
+    Box::new(move |trace| {
+        let line = serde_json::to_string(trace).expect("the trace is serializable");
+        if let Err(e) = writeln!(file, "{}", line) {
+            error!("Can't write the trace: {}", e);
+            exit(1);
+        }
+    })
+}
+
+#[cfg(not(feature = "serde"))]
+fn trace_sink() -> Box<dyn FnMut(&DecisionTrace)> {
+    Box::new(|_| {})
+}
+
+// riceve il numero della partita e il turno appena giocato
+type TurnSink = Box<dyn FnMut(usize, &TurnLog)>;
+
+// con la feature serde e --turn-log=<file> ogni turno viene scritto nel file come una riga JSON,
+// con il numero della partita
+#[cfg(feature = "serde")]
+fn turn_log_sink(path: Option<PathBuf>) -> TurnSink {
+    use std::{fs::File, io::Write};
+
+    let Some(path) = path else {
+        return Box::new(|_, _| {});
+    };
+    let mut file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Can't create the turn log {:?}: {}", path, e);
+            exit(1);
+        }
+    };
+    Box::new(move |game, turn| {
+        let mut line = serde_json::to_value(turn).expect("the turn is serializable");
+        line["game"] = game.into();
+        if let Err(e) = writeln!(file, "{}", line) {
+            error!("Can't write the turn log: {}", e);
+            exit(1);
+        }
+    })
+}
+
+#[cfg(not(feature = "serde"))]
+fn turn_log_sink(path: Option<PathBuf>) -> TurnSink {
+    if path.is_some() {
+        warn!("The turn log needs the serde feature, --turn-log is ignored");
+    }
+    Box::new(|_, _| {})
+}
+
+// salva la partita in dir/game-<n>.json, si può rigiocare con `wumpus replay <file>`
+#[cfg(feature = "serde")]
+fn record_game(dir: &Path, game: usize, common: &Common, seed: u64, history: Vec<Step>) {
+    let path = dir.join(format!("game-{}.json", game));
+    if let Err(e) = GameRecord::new(seed, common.dim, common.pits, history).save(&path) {
+        error!("Can't record the game in {:?}: {}", path, e);
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn record_game(_dir: &Path, _game: usize, _common: &Common, _seed: u64, _history: Vec<Step>) {}
+
+// rigioca una partita registrata controllando le percezioni, con --check-agent controlla
+// anche che un nuovo agente costruito con lo stesso seed faccia le stesse scelte
+#[cfg(feature = "serde")]
+fn run_replay(path: &Path, check_agent: bool, common: &Common, config: HeroConfig) {
+    use wumpus::replay::replay;
+
+    let record = match GameRecord::load(path) {
+        Ok(record) => record,
+        Err(e) => {
+            error!("Can't load the recorded game {:?}: {}", path, e);
+            exit(1);
+        }
+    };
+    let mut agent = check_agent
+        .then(|| create_agent(common.agent(), record.dim, config, Some(record.seed), None));
+    let agent = agent.as_mut().map(|agent| agent.as_mut() as &mut dyn Agent);
+    match replay(&record, agent) {
+        Ok(()) => println!(
+            "[REPLAY] {} turns replayed without divergences",
+            record.steps.len()
+        ),
+        Err(divergence) => {
+            println!("[REPLAY] first divergence at {}", divergence);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn run_replay(_path: &Path, _check_agent: bool, _common: &Common, _config: HeroConfig) {
+    error!("Replaying a recorded game needs the serde feature");
+    exit(1);
+}
+
+// controlla che l'eroe deterministico (configurazione di default) scelga ancora le azioni
+// salvate in dir/golden-<seed>.json; con update gioca games partite a partire da --seed
+// (default 0) e riscrive i file
+#[cfg(feature = "serde")]
+fn run_golden(dir: &Path, update: bool, games: u64, common: &Common) {
+    use wumpus::replay::{GoldenTrace, check_golden};
+
+    let hero = |seed: u64| {
+        create_agent(
+            AgentKind::Hero,
+            common.dim,
+            HeroConfig::default(),
+            Some(seed),
+            None,
+        )
+    };
+    if update {
+        let base = common.seed.unwrap_or(0);
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            error!("Can't create {:?}: {}", dir, e);
+            exit(1);
+        }
+        for seed in (0..games).map(|game| base ^ game) {
+            let mut world = World::new_with_seed(common.dim, common.pits, seed);
+            let mut agent = hero(seed);
+            let result = simulate(
+                &mut world,
+                agent.as_mut(),
+                &mut |_| {},
+                Screen::hidden(),
+                None,
+            );
+            if interrupted() {
+                println!("[GOLDEN] interrupted, the traces of the later seeds were not written");
+                exit(130);
+            }
+            let golden = GoldenTrace {
+                seed: seed,
+                dim: common.dim,
+                pits: common.pits,
+                actions: result.history.iter().map(|step| step.action).collect(),
+                outcome: result.outcome.label().to_string(),
+            };
+            let path = dir.join(format!("golden-{}.json", seed));
+            if let Err(e) = golden.save(&path) {
+                error!("Can't write {:?}: {}", path, e);
+                exit(1);
+            }
+        }
+        println!("[GOLDEN] {} traces written in {:?}", games, dir);
+        return;
+    }
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect(),
+        Err(e) => {
+            error!("Can't read the golden traces in {:?}: {}", dir, e);
+            exit(1);
+        }
+    };
+    paths.sort();
+    let mut failed = 0;
+    for path in &paths {
+        let golden = match GoldenTrace::load(path) {
+            Ok(golden) => golden,
+            Err(e) => {
+                error!("Can't load {:?}: {}", path, e);
+                exit(1);
+            }
+        };
+        let mut agent = create_agent(
+            AgentKind::Hero,
+            golden.dim,
+            HeroConfig::default(),
+            Some(golden.seed),
+            None,
+        );
+        if let Err(mismatch) = check_golden(&golden, agent.as_mut()) {
+            failed += 1;
+            println!(
+                "[GOLDEN] seed {} ({:?}) diverges at {}",
+                golden.seed, path, mismatch
+            );
+        }
+    }
+    println!(
+        "[GOLDEN] {} of {} traces match",
+        paths.len() - failed,
+        paths.len()
+    );
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+// la KB segue le percezioni delle caselle senza pericoli di ogni dungeon e non deve mai
+// dimostrare un fatto falso; WUMPUS_SOLVER sceglie il backend come per le partite
+fn run_soundness(games: u64, common: &Common) {
+    let seed = common.seed.unwrap_or_else(rand::random);
+    let mut checked = 0;
+    let mut asked = 0;
+    for game in 0..games {
+        if interrupted() {
+            break;
+        }
+        let world = World::new_with_seed(common.dim, common.pits, seed ^ game);
+        let mut kb = new_kb(common.dim, common.timeout);
+        // il DPLL nel processo fa da riferimento: ogni risposta diversa è un errore
+        let mut reference = new_kb(common.dim, common.timeout);
+        reference.use_in_process_solver();
+        match check_soundness_against(&world, &mut kb, &mut reference) {
+            Ok(facts) => asked += facts,
+            Err(e) => {
+                println!("[SOUNDNESS] dungeon seed {}:\n{}", world.seed(), world);
+                println!("[SOUNDNESS] {}", e);
+                exit(1);
+            }
+        }
+        checked += 1;
+    }
+    println!(
+        "[SOUNDNESS] {} dungeons {}x{} with {} pits from seed {}, {} facts asked to {:?} and to the reference, none proven false",
+        checked,
+        common.dim,
+        common.dim,
+        common.pits,
+        seed,
+        asked,
+        solver_from_env()
+    );
+}
+
+// le codifiche DIMACS di golden_kbs confrontate byte per byte con i file in dir: la
+// numerazione delle variabili non deve cambiare per caso
+fn run_golden_dimacs(dir: &Path, update: bool) {
+    use wumpus::{encoder::check_dimacs, kb::golden_kbs};
+
+    let created = if update {
+        std::fs::create_dir_all(dir)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = created {
+        error!("Can't create {:?}: {}", dir, e);
+        exit(1);
+    }
+    let kbs = golden_kbs();
+    let mut failed = 0;
+    for (name, kb) in &kbs {
+        let (encoding, _) = kb.encode();
+        let path = dir.join(format!("{}.cnf", name));
+        if let Err(e) = check_dimacs(&encoding) {
+            failed += 1;
+            println!("[GOLDEN] {}: inconsistent header, {}", name, e);
+            continue;
+        }
+        if update {
+            if let Err(e) = std::fs::write(&path, &encoding) {
+                error!("Can't write {:?}: {}", path, e);
+                exit(1);
+            }
+            continue;
+        }
+        let golden = match std::fs::read_to_string(&path) {
+            Ok(golden) => golden,
+            Err(e) => {
+                error!("Can't read {:?}: {}", path, e);
+                exit(1);
+            }
+        };
+        if golden != encoding {
+            failed += 1;
+            let line = golden
+                .lines()
+                .zip(encoding.lines())
+                .position(|(a, b)| a != b)
+                .unwrap_or(golden.lines().count().min(encoding.lines().count()));
+            println!(
+                "[GOLDEN] {} ({:?}) differs at line {}",
+                name,
+                path,
+                line + 1
+            );
+        }
+    }
+    if update {
+        println!("[GOLDEN] {} encodings written in {:?}", kbs.len(), dir);
+    } else {
+        println!(
+            "[GOLDEN] {} of {} encodings match",
+            kbs.len() - failed,
+            kbs.len()
+        );
+    }
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn run_golden(_dir: &Path, _update: bool, _games: u64, _common: &Common) {
+    error!("The golden traces need the serde feature");
+    exit(1);
+}
+
+// con la feature serde e WUMPUS_SUMMARY impostata il riepilogo viene salvato anche in JSON
+#[cfg(feature = "serde")]
+fn export_summary(summary: &BatchSummary) {
+    let Some(path) = env::var_os("WUMPUS_SUMMARY") else {
+        return;
+    };
+    let written = std::fs::File::create(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| serde_json::to_writer_pretty(file, summary).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        error!("Can't write the summary to {:?}: {}", path, e);
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn export_summary(_summary: &BatchSummary) {}
+
+// partita giocata da tastiera; con fog si vedono solo le caselle già visitate, lo stesso seed
+// dà lo stesso dungeon e può essere condiviso
+fn run_play(common: &Common, fog: bool) {
+    let seed = common.seed.unwrap_or_else(rand::random);
+    println!("[PLAY] dungeon seed {}", seed);
+    let mut world = World::new_with_seed(common.dim, common.pits, seed);
+    let mut agent = HumanAgent::new();
+    let mut seen = HashSet::from([Position::new(0, 0)]);
+    let mut steps = 0;
+    let mut score = 0;
+    let result = loop {
+        if interrupted() {
+            println!(
+                "[PLAY] interrupted after {} actions, dungeon seed {}",
+                steps, seed
+            );
+            return;
+        }
+        if fog {
+            print!("{}", world.fogged(&seen));
+        } else {
+            print!("{}", world);
+        }
+        println!("score: {}", score);
+        // l'agente umano fallisce solo se stdin è chiuso
+        let Ok(a) = agent.next_action(world.perceptions()) else {
+            println!(
+                "[PLAY] no more input after {} actions, dungeon seed {}",
+                steps, seed
+            );
+            return;
+        };
+        let result = match world.do_action(a) {
+            Ok(result) => result,
+            Err(e) => {
+                println!("[PLAY] {}", e);
+                continue;
+            }
+        };
+        steps += 1;
+        score += score_change(a, result);
+        seen.insert(world.perceptions().position);
+        if result != ActionResult::Continue {
+            break result;
+        }
+    };
     print!("{}", world);
-    loop {
-        let p = world.perceptions();
-        let a = hero.next_action(p);
-        let (finish, gold) = world.do_action(a);
-        print!("{}", world);
-        if finish {
-            return gold;
+    match result {
+        ActionResult::Exited { gold: true } => println!("[PLAY] you escaped with the gold"),
+        ActionResult::Exited { gold: false } => println!("[PLAY] you escaped without the gold"),
+        ActionResult::Dead(cause) => println!("[PLAY] you were killed by {:?}", cause),
+        ActionResult::Continue => unreachable!("the game ended"),
+    }
+    println!(
+        "[PLAY] {} actions, score {}, dungeon seed {}",
+        steps, score, seed
+    );
+}
+
+// i fotogrammi della libreria vanno su stdout, l'avanzamento delle serie su stderr
+fn print_frame(frame: &str) {
+    let mut out = io::stdout().lock();
+    let _ = write!(out, "{}", frame);
+    let _ = out.flush();
+}
+
+// con l'animazione su un terminale si ripulisce lo schermo, così i log della decisione
+// successiva compaiono sotto la mappa invece di mescolarsi
+fn animate_frame(frame: &str) {
+    let mut out = io::stdout().lock();
+    if out.is_terminal() {
+        let _ = write!(out, "\x1B[2J\x1B[H");
+    }
+    let _ = write!(out, "{}", frame);
+    let _ = out.flush();
+}
+
+fn report(line: &str) {
+    eprintln!("{}", line);
+}
+
+// una sola partita animata: ogni turno la mappa scelta con --view, poi una pausa di delay
+fn run_watch(delay: Duration, args: &SimulateArgs, common: &Common, config: HeroConfig) {
+    // i fotogrammi e `replay <seed>` ricostruiscono la partita dal seed, quindi serve sempre
+    let seed = common.seed.unwrap_or_else(rand::random);
+    let (mut world, mut agent) = new_game(common, common.agent(), config, Some(seed));
+    let screen = Screen {
+        view: args.view,
+        delay: Some(delay),
+        output: animate_frame,
+    };
+    let result = simulate(&mut world, agent.as_mut(), &mut |_| {}, screen, None);
+    println!(
+        "[WATCH] {}: {} actions, score {}, seed {}",
+        result.outcome.label(),
+        result.steps,
+        result.score,
+        seed
+    );
+    if let Some(dir) = &args.frames {
+        save_frames(dir, seed, &result.history, args.frames_view, common, config);
+    }
+}
+
+// scrive in dir un file per turno della partita con la vista scelta, la partita e l'agente
+// vengono ricostruiti dal seed
+fn save_frames(
+    dir: &Path,
+    seed: u64,
+    history: &[Step],
+    view: View,
+    common: &Common,
+    config: HeroConfig,
+) {
+    let record = GameRecord::new(seed, common.dim, common.pits, history.to_vec());
+    let kind = common.agent();
+    let mut agent = (matches!(view, View::Knowledge | View::Both) && kind != AgentKind::Human)
+        .then(|| create_agent(kind, common.dim, config, Some(seed), None));
+    let agent = agent.as_mut().map(|agent| agent.as_mut() as &mut dyn Agent);
+    if let Err(e) = write_frames(&record, agent, view, dir) {
+        error!("Can't write the frames in {:?}: {}", dir, e);
+    }
+}
+
+// quanto costano la costruzione della KB e le interrogazioni dell'eroe: la KB conosce le
+// percezioni della casella di partenza e si chiede se ogni casella è sicura
+fn run_bench(iterations: u32, common: &Common) {
+    let iterations = iterations.max(1);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        init_kb(common.dim);
+    }
+    let init = start.elapsed() / iterations;
+    println!("[BENCH] init_kb({}): {:?}", common.dim, init);
+    let seed = common.seed.unwrap_or_else(rand::random);
+    let world = World::new_with_seed(common.dim, common.pits, seed);
+    let mut kb = init_kb(common.dim);
+    // si misura il backend scelto con WUMPUS_SOLVER, come nelle partite
+    kb.set_solver(solver_from_env().solver());
+    kb.tell(&EncoderSAT::<Var>::create_ground_truth_from_perception(
+        &world.perceptions(),
+    ));
+    let queries: Vec<_> = (0..common.dim)
+        .flat_map(|y| (0..common.dim).map(move |x| Position::new(x, y)))
+        .map(|pos| EncoderSAT::<Var>::create_safe_formula(&pos))
+        .collect();
+    let calls = kb.solver_calls();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for query in &queries {
+            if let Err(e) = kb.ask(query) {
+                error!("The solver failed: {}", e);
+                exit(1);
+            }
+        }
+    }
+    let asks = queries.len() as u32 * iterations;
+    println!(
+        "[BENCH] ask safe: {:?} per ask, {} asks, {} solver calls, seed {}",
+        start.elapsed() / asks,
+        asks,
+        kb.solver_calls() - calls,
+        seed
+    );
+}
+
+// tutte le combinazioni di dims e pits del file TOML, trials partite ciascuna, una riga CSV per
+// combinazione su stdout o nel file di --out, altrimenti in quello di `out` nel file
+#[cfg(feature = "serde")]
+fn run_experiments(path: &Path, out: Option<PathBuf>, common: &Common, config: HeroConfig) {
+    use wumpus::experiments::ExperimentsFile;
+
+    let file = match ExperimentsFile::load(path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Can't load the experiments {:?}: {}", path, e);
+            exit(1);
         }
+    };
+    // --seed ha la precedenza sul seed del file
+    let seed = common.seed.or(file.seed).unwrap_or_else(|| {
+        let seed: u64 = rand::random();
+        info!("Running the experiments with seed {}", seed);
+        seed
+    });
+    let matrix = file.matrix(seed);
+    if let Err(e) = matrix.validate() {
+        error!("{:?}: {}", path, e);
+        exit(1);
+    }
+    let kind = common.agent();
+    let timeout = common.timeout;
+    let new_game = move |dim: usize, pits: usize, seed: Option<u64>| {
+        let world = match seed {
+            Some(seed) => World::new_with_seed(dim, pits, seed),
+            None => World::new(dim, pits),
+        };
+        (world, create_agent(kind, dim, config, seed, timeout))
+    };
+    let parallel = common.parallel_games;
+    let rows = experiments::run(&matrix, &new_game, parallel, timeout, common.quiet, report);
+    let written = match out.or(file.out) {
+        Some(path) => std::fs::File::create(path)
+            .and_then(|mut file| experiments::write_csv(&rows, &mut file)),
+        None => experiments::write_csv(&rows, &mut std::io::stdout()),
+    };
+    if let Err(e) = written {
+        error!("Can't write the results of the experiments: {}", e);
+        exit(1);
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn run_experiments(_path: &Path, _out: Option<PathBuf>, _common: &Common, _config: HeroConfig) {
+    error!("The experiments file needs the serde feature");
+    exit(1);
+}
+
+// la percentuale di successo al variare della densità dei pozzi con --dim fissata: ogni punto
+// è una cella degli esperimenti, con gli stessi seed
+fn run_sweep(
+    densities: (f64, f64, f64),
+    trials: u64,
+    out: Option<PathBuf>,
+    common: &Common,
+    config: HeroConfig,
+) {
+    let (from, to, step) = densities;
+    if step <= 0.0 {
+        error!("The step of the densities must be positive");
+        exit(1);
+    }
+    let pits = experiments::density_pits(common.dim, from, to, step);
+    if pits.is_empty() {
+        error!(
+            "No density from {} to {} fits a {}x{} dungeon",
+            from, to, common.dim, common.dim
+        );
+        exit(1);
+    }
+    let seed = common.seed.unwrap_or_else(|| {
+        let seed: u64 = rand::random();
+        info!("Running the sweep with seed {}", seed);
+        seed
+    });
+    let matrix = Matrix {
+        dims: vec![common.dim],
+        pits: pits,
+        trials: trials,
+        seed: seed,
+    };
+    let kind = common.agent();
+    let timeout = common.timeout;
+    let new_game = move |dim: usize, pits: usize, seed: Option<u64>| {
+        let world = match seed {
+            Some(seed) => World::new_with_seed(dim, pits, seed),
+            None => World::new(dim, pits),
+        };
+        (world, create_agent(kind, dim, config, seed, timeout))
+    };
+    let parallel = common.parallel_games;
+    let rows = experiments::run(&matrix, &new_game, parallel, timeout, common.quiet, report);
+    let written = match out {
+        Some(path) => std::fs::File::create(path)
+            .and_then(|mut file| experiments::write_curve(&rows, &mut file)),
+        None => experiments::write_curve(&rows, &mut std::io::stdout()),
+    };
+    if let Err(e) = written {
+        error!("Can't write the curve: {}", e);
+        exit(1);
     }
 }
 
+// i due agenti giocano le stesse partite: ogni mondo viene ricostruito dal suo seed per
+// entrambi, così i risultati si possono confrontare a coppie
+fn run_compare(kinds: [AgentKind; 2], games: u64, common: &Common, config: HeroConfig) {
+    if kinds.contains(&AgentKind::Human) {
+        error!("The human agent can't be compared");
+        exit(1);
+    }
+    let seed = common.seed.unwrap_or_else(rand::random);
+    let mut results = kinds.map(|kind| {
+        let progress = Progress::new(games as usize, common.quiet, report);
+        let new_game = |seed: Option<u64>| new_game(common, kind, config, seed);
+        let results: Vec<SimulationResult> = play_batch(games, seed, &new_game, common, &progress)
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect();
+        progress.finish();
+        results
+    });
+    if interrupted() {
+        // si confrontano solo le partite finite da entrambi gli agenti
+        let [a, b] = &mut results;
+        let seeds_a: HashSet<u64> = a.iter().map(|result| result.seed).collect();
+        b.retain(|result| seeds_a.contains(&result.seed));
+        let seeds_b: HashSet<u64> = b.iter().map(|result| result.seed).collect();
+        a.retain(|result| seeds_b.contains(&result.seed));
+        println!(
+            "[COMPARE] interrupted, {} of {} games played by both agents",
+            a.len(),
+            games
+        );
+    }
+    let comparison = PairedComparison::new(&results[0], &results[1]);
+    println!(
+        "[COMPARE] A = {:?}, B = {:?}, {} games from seed {}",
+        kinds[0], kinds[1], games, seed
+    );
+    for (kind, results) in kinds.iter().zip(&results) {
+        let summary = BatchSummary::new(results);
+        println!(
+            "[COMPARE] {:?}: success rate {:.3}, 95% CI [{:.3}, {:.3}], mean score {:.1}",
+            kind,
+            summary.success_rate,
+            summary.success_interval.0,
+            summary.success_interval.1,
+            summary.score.mean
+        );
+    }
+    for line in comparison.to_string().lines() {
+        println!("[COMPARE] {}", line);
+    }
+    for (a, b) in results[0].iter().zip(&results[1]) {
+        if a.gold != b.gold {
+            println!(
+                "[COMPARE] seed {}: A {}, B {}",
+                a.seed,
+                a.outcome.label(),
+                b.outcome.label()
+            );
+        }
+    }
+}
+
+// una partita nell'interfaccia a terminale, giocata dall'agente oppure con human dalla tastiera
+#[cfg(feature = "tui")]
+fn run_tui(human: bool, fog: bool, tick: Duration, common: &Common, config: HeroConfig) {
+    use wumpus::{
+        environment::EnvConfig,
+        tui::{self, Driver},
+    };
+
+    let seed = common.seed.unwrap_or_else(rand::random);
+    let driver = if human {
+        Driver::Human { fog: fog }
+    } else {
+        Driver::Agent(create_agent(
+            common.agent(),
+            common.dim,
+            config,
+            Some(seed),
+            None,
+        ))
+    };
+    let env = EnvConfig {
+        dim: common.dim,
+        pits: common.pits,
+    };
+    match tui::run(env, seed, driver, tick) {
+        Ok(outcome) => println!(
+            "[TUI] {}: {} actions, score {}, dungeon seed {}",
+            outcome.ending.as_deref().unwrap_or("interrupted"),
+            outcome.steps,
+            outcome.score,
+            seed
+        ),
+        Err(e) => {
+            error!("The terminal UI failed: {}", e);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_human: bool, _fog: bool, _tick: Duration, _common: &Common, _config: HeroConfig) {
+    error!("The terminal UI needs the tui feature");
+    exit(1);
+}
+
+// su games mappe confronta le percentuali di mappe vincibili (l'oro raggiungibile senza pozzi,
+// e raggiungibile passando solo per caselle dimostrabilmente sicure) con le vittorie
+// dell'agente sulle stesse mappe
+fn run_ceiling(games: u64, common: &Common, config: HeroConfig) {
+    let seed = common.seed.unwrap_or_else(rand::random);
+    let mut solvable = 0;
+    let mut reachable = 0;
+    for game in 0..games {
+        let world = World::new_with_seed(common.dim, common.pits, seed ^ game);
+        if world.is_solvable() {
+            solvable += 1;
+        }
+        if knowledge_reachable(&world) {
+            reachable += 1;
+        }
+    }
+    let kind = common.agent();
+    let new_game = |seed: Option<u64>| new_game(common, kind, config, seed);
+    let progress = Progress::new(games as usize, common.quiet, report);
+    let results = play_batch(games, seed, &new_game, common, &progress);
+    progress.finish();
+    // dopo Ctrl-C le vittorie si contano sulle partite finite
+    let played = results.len();
+    let won = results.iter().filter(|(result, _)| result.gold).count();
+    println!(
+        "[CEILING] {} maps {}x{} with {} pits from seed {}",
+        games, common.dim, common.dim, common.pits, seed
+    );
+    if interrupted() {
+        println!("[CEILING] interrupted, the agent played {} maps", played);
+    }
+    for (label, count, maps) in [
+        ("gold reachable without pits", solvable, games as usize),
+        (
+            "gold reachable through provably safe cells",
+            reachable,
+            games as usize,
+        ),
+        ("won by the agent", won, played),
+    ] {
+        let (low, high) = wilson_interval(count, maps, Z_95);
+        println!(
+            "[CEILING] {:<43} {:.3} (95% CI [{:.3}, {:.3}])",
+            label,
+            count as f64 / maps.max(1) as f64,
+            low,
+            high
+        );
+    }
+}
+
+// la partita con quel seed di una serie, con la mappa e il ragionamento dell'eroe a ogni turno
+fn run_replay_seed(seed: u64, common: &Common, config: HeroConfig) {
+    let (mut world, mut agent) = new_game(common, common.agent(), config, Some(seed));
+    let screen = Screen {
+        view: View::Both,
+        delay: None,
+        output: print_frame,
+    };
+    let result = simulate(&mut world, agent.as_mut(), &mut |_| {}, screen, None);
+    println!(
+        "[REPLAY] seed {}: {}, {} actions, score {}",
+        seed,
+        result.outcome.label(),
+        result.steps,
+        result.score
+    );
+}
+
+// parte dai valori di default e sovrascrive quelli dati sulla riga di comando o nelle
+// variabili d'ambiente
+fn hero_config(common: &Common) -> HeroConfig {
+    let args = common.hero;
+    let default = HeroConfig::default();
+    HeroConfig {
+        risk_threshold: args.risk_threshold.unwrap_or(default.risk_threshold),
+        cross_check_plans: args.check_plans,
+        explore_all: args.explore_all,
+        propagation_depth: args.propagation_depth,
+        consistency_check_every: args
+            .consistency_every
+            .unwrap_or(default.consistency_check_every),
+        planning_attempts: args.planning_attempts.unwrap_or(default.planning_attempts),
+        explorer: args.explorer.unwrap_or(default.explorer),
+        home_explorer: args.home_explorer.unwrap_or(default.home_explorer),
+        max_steps: args.max_steps,
+        frontier_policy: args.frontier.unwrap_or(default.frontier_policy),
+        parallel_queries: args.parallel_queries,
+        check_invariants: args.check_invariants,
+        timing: args.timing,
+    }
+}
+
+// il primo Ctrl-C ferma le partite e stampa le statistiche di quelle finite, il secondo
+// esce subito
+#[cfg(not(target_arch = "wasm32"))]
+fn handle_ctrl_c() {
+    let handler = ctrlc::set_handler(|| {
+        if interrupted() {
+            exit(130);
+        }
+        warn!("Interrupted, stopping the games (Ctrl-C again to quit now)");
+        wumpus::simulation::interrupt();
+    });
+    if let Err(e) = handler {
+        warn!("Can't install the Ctrl-C handler: {}", e);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn handle_ctrl_c() {}
+
 fn main() {
+    let cli = Cli::parse();
+    let common = cli.common;
+    let command = cli
+        .command
+        .unwrap_or_else(|| Command::Simulate(SimulateArgs::default()));
+    // di default solo avvisi ed errori, --verbose mostra anche mappa e ragionamento dell'eroe,
+    // --quiet solo gli errori e il riepilogo finale; RUST_LOG, se impostata, ha la precedenza
+    // tranne che con --quiet. `replay <seed>` rigioca la partita in modalità verbosa
+    let replay_seed = match &command {
+        Command::Replay { target, .. } => target.parse::<u64>().ok(),
+        _ => None,
+    };
+    let verbose = common.verbose || replay_seed.is_some();
+    let mut logger = env_logger::Builder::new();
+    if common.quiet {
+        logger.filter_level(LevelFilter::Error);
+    } else {
+        logger
+            .filter_level(if verbose {
+                LevelFilter::Info
+            } else {
+                LevelFilter::Warn
+            })
+            .parse_default_env();
+    }
+    logger.init();
+    if let Err(e) = World::check_size(common.dim, common.pits) {
+        error!("--dim {} --pits {}: {}", common.dim, common.pits, e);
+        exit(1);
+    }
+    handle_ctrl_c();
     // let dim = 20;
     // let mut world = World::new(dim, 40);
     // let mut hero = Hero::new(init_kb(dim), dim);
@@ -32,11 +1266,222 @@ fn main() {
     //     world.do_action(a);
     //     print!("{}", world);
     // }
+    let config = hero_config(&common);
+    match command {
+        Command::Simulate(args) => match args.watch {
+            Some(delay) => run_watch(Duration::from_millis(delay), &args, &common, config),
+            None => run_simulate(&args, &common, config),
+        },
+        Command::Play { fog } => run_play(&common, fog),
+        Command::Replay {
+            target,
+            check_agent,
+        } => match replay_seed {
+            Some(seed) => run_replay_seed(seed, &common, config),
+            None => run_replay(Path::new(&target), check_agent, &common, config),
+        },
+        Command::Bench { iterations } => run_bench(iterations, &common),
+        Command::Experiments { config: path, out } => run_experiments(&path, out, &common, config),
+        Command::Sweep {
+            from,
+            to,
+            step,
+            trials,
+            out,
+        } => run_sweep((from, to, step), trials, out, &common, config),
+        Command::Compare { a, b, games } => run_compare([a, b], games, &common, config),
+        Command::Ceiling { games } => run_ceiling(games, &common, config),
+        Command::Soundness { games } => run_soundness(games, &common),
+        Command::Golden { dir, update, games } => run_golden(&dir, update, games, &common),
+        Command::GoldenDimacs { dir, update } => run_golden_dimacs(&dir, update),
+        Command::Tui { human, fog, tick } => {
+            run_tui(human, fog, Duration::from_millis(tick), &common, config)
+        }
+    }
+}
+
+// una serie di partite con il riepilogo finale: --seed rende riproducibile l'intera sequenza,
+// senza se ne sceglie uno a caso; la partita i usa il seed base ^ i e si rigioca con
+// `replay <seed>`
+fn run_simulate(args: &SimulateArgs, common: &Common, config: HeroConfig) {
+    let games = args.games;
+    let seed: u64 = common.seed.unwrap_or_else(|| {
+        let base = rand::random();
+        info!("Playing the games with seed {}", base);
+        base
+    });
+    // con --record ogni partita viene salvata in quella cartella per poterla rigiocare
+    let record_dir = args.record.clone();
+    if record_dir.is_some() && cfg!(not(feature = "serde")) {
+        warn!("Recording the games needs the serde feature, --record is ignored");
+    }
     let mut gold_found = 0;
-    for _ in 0..100 {
-        if simulate(10, 12) {
+    // morti in caselle dimostrate sicure: la KB ha sbagliato e il processo esce con errore
+    let mut dead_on_safe_cells = 0;
+    let mut total_steps = 0;
+    let mut total_score = 0;
+    let mut total_sat_calls = 0;
+    let mut total_duration = Duration::ZERO;
+    let mut total_stats = HeroStats::default();
+    let mut games_with_stats = 0;
+    let mut peak_memory: Option<MemoryFootprint> = None;
+    let mut on_decision = trace_sink();
+    let mut on_turn = turn_log_sink(args.turn_log.clone());
+    // nelle build di debug le partite sono sempre controllate
+    check_invariants(cfg!(debug_assertions) || config.check_invariants);
+    let progress = Progress::new(games as usize, common.quiet, report);
+    let kind = common.agent();
+    let new_game = |seed: Option<u64>| new_game(common, kind, config, seed);
+    if common.oriented && args.turn_log.is_some() {
+        warn!("The oriented games have no turn log, --turn-log is ignored");
+    }
+    let results = play_batch(games, seed, &new_game, common, &progress);
+    progress.finish();
+    let mut finished: Vec<SimulationResult> = Vec::with_capacity(results.len());
+    for (game, (mut result, turns)) in results.into_iter().enumerate() {
+        // i turni vengono scritti in ordine di partita anche se giocate in parallelo
+        for turn in &turns {
+            on_turn(game, turn);
+            if let Some(trace) = &turn.decision {
+                on_decision(trace);
+            }
+        }
+        // con --frames ogni partita diventa una sequenza di file in dir/game-<n>
+        if let Some(dir) = args.frames.as_ref() {
+            save_frames(
+                &dir.join(format!("game-{:03}", game)),
+                result.seed,
+                &result.history,
+                args.frames_view,
+                common,
+                config,
+            );
+        }
+        if let Some(dir) = record_dir.as_ref() {
+            let history = std::mem::take(&mut result.history);
+            record_game(dir, game, common, result.seed, history);
+        }
+        if result.gold {
             gold_found += 1;
         }
+        // quante sono finite in ciascun modo lo dice il riepilogo, qui solo i dettagli
+        match &result.outcome {
+            Outcome::Failed(e) => error!("Game {} (seed {}) failed: {}", game, result.seed, e),
+            Outcome::IllegalAction(e) => error!(
+                "Game {} (seed {}) stopped by an illegal action: {}",
+                game, result.seed, e
+            ),
+            Outcome::TimedOut => warn!(
+                "Game {} (seed {}) abandoned after {:?}",
+                game, result.seed, result.duration
+            ),
+            Outcome::Dead => warn!(
+                "Game {} (seed {}) lost, killed by {:?}",
+                game,
+                result.seed,
+                result.death_cause.expect("a dead hero has a cause")
+            ),
+            Outcome::DeadOnSafeCell => {
+                dead_on_safe_cells += 1;
+                error!(
+                    "Game {} (seed {}) lost in a cell inferred as safe, killed by {:?}",
+                    game,
+                    result.seed,
+                    result.death_cause.expect("a dead hero has a cause")
+                );
+            }
+            Outcome::Gold | Outcome::NoGold | Outcome::Interrupted => {}
+        }
+        total_steps += result.steps;
+        total_score += result.score;
+        total_sat_calls += result.sat_calls;
+        total_duration += result.duration;
+        if let Some(memory) = result.peak_memory {
+            peak_memory = Some(peak_memory.map_or(memory, |peak| peak.max(memory)));
+        }
+        if let Some(stats) = result.stats {
+            if config.timing {
+                info!(
+                    "Game {} (seed {}) timings: {}, rendering {:?}",
+                    game, result.seed, stats.timings, result.render_time
+                );
+            }
+            total_stats += stats;
+            games_with_stats += 1;
+        }
+        finished.push(result);
+    }
+    // dopo Ctrl-C le statistiche riguardano solo le partite finite
+    if interrupted() {
+        println!(
+            "[FINISH] interrupted, summary of the {} games completed out of {}",
+            finished.len(),
+            games
+        );
+    }
+    let per_game = |total: f64| total / finished.len().max(1) as f64;
+    println!("[FINISH] gold found: {} ", per_game(gold_found as f64));
+    let summary = BatchSummary::new(&finished);
+    for line in summary.to_string().lines() {
+        println!("[FINISH] {}", line);
+    }
+    export_summary(&summary);
+    println!(
+        "[FINISH] average per game: {:.1} steps, {:.1} score, {:.1} SAT calls",
+        per_game(total_steps as f64),
+        per_game(total_score as f64),
+        per_game(total_sat_calls as f64)
+    );
+    println!(
+        "[FINISH] average time per game: {:.1} ms",
+        per_game(total_duration.as_secs_f64() * 1000.0)
+    );
+    if games_with_stats > 0 {
+        let average = |total: usize| (total as f64) / (games_with_stats as f64);
+        println!(
+            "[FINISH] average per game: {:.1} asks ({:.1} from the cache), {:.1} tells, {:.1} solver calls",
+            average(total_stats.asks),
+            average(total_stats.cached_asks),
+            average(total_stats.tells),
+            average(total_stats.solver_calls)
+        );
+        println!(
+            "[FINISH] average per game: {:.1} plans ({:.1} steps), {:.1} turns",
+            average(total_stats.plans),
+            average(total_stats.plan_steps),
+            average(total_stats.turns)
+        );
+        println!(
+            "[FINISH] average coverage: {:.1} cells visited, {:.1} cells classified",
+            average(total_stats.cells_visited),
+            average(total_stats.cells_classified)
+        );
+        if config.timing {
+            let average = |total: Duration| total / games_with_stats as u32;
+            let timings = &total_stats.timings;
+            println!(
+                "[FINISH] average time per game: {:?} consistency, {:?} asks, {:?} planning, {:?} per turn",
+                average(timings.consistency),
+                average(timings.asks),
+                average(timings.planning),
+                timings.turn / total_stats.turns.max(1) as u32
+            );
+        }
+        if let Some(memory) = peak_memory {
+            println!("[FINISH] peak KB memory: {}", memory);
+        }
+        if total_stats.budget_exhausted > 0 {
+            println!(
+                "[FINISH] games ended by the step budget: {}",
+                total_stats.budget_exhausted
+            );
+        }
+    }
+    if dead_on_safe_cells > 0 {
+        error!(
+            "The hero died {} times in a cell inferred as safe",
+            dead_on_safe_cells
+        );
+        exit(1);
     }
-    println!("[FINISH] gold found: {} ", (gold_found as f64) / 100.0);
 }
@@ -0,0 +1,426 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    agents::Agent,
+    hero::HeroError,
+    replay::Step,
+    simulation::{
+        ACTION_COST, NewGame, Outcome, Progress, SimulationResult, TurnLog, interrupted,
+        score_change,
+    },
+    world::{Action, ActionResult, Direction, IllegalAction, Perceptions, World},
+};
+
+/// Where the hero faces at the start of an oriented game, as in AIMA.
+pub const START_FACING: Direction = Direction::East;
+
+/// The actions of the world in oriented mode: the hero turns in place and moves or shoots
+/// only where it faces.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OrientedAction {
+    TurnLeft,
+    TurnRight,
+    Forward,
+    Grab,
+    Shoot,
+    Exit,
+}
+
+/// The perceptions of an OrientedWorld, with the direction the hero faces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrientedPerceptions {
+    pub perceptions: Perceptions,
+    pub facing: Direction,
+}
+
+/// The fewest turns to face `to` from `from`: none, one, or two for a U-turn.
+pub fn turns(from: Direction, to: Direction) -> Vec<OrientedAction> {
+    if from == to {
+        vec![]
+    } else if from.turn_left() == to {
+        vec![OrientedAction::TurnLeft]
+    } else if from.turn_right() == to {
+        vec![OrientedAction::TurnRight]
+    } else {
+        // inversione di marcia, a destra o a sinistra costa uguale
+        vec![OrientedAction::TurnRight, OrientedAction::TurnRight]
+    }
+}
+
+/// The oriented actions doing action while facing `facing`, with the direction faced
+/// after them.
+pub fn compile(facing: Direction, action: Action) -> (Vec<OrientedAction>, Direction) {
+    match action {
+        Action::Move(dir) => {
+            let mut actions = turns(facing, dir);
+            actions.push(OrientedAction::Forward);
+            (actions, dir)
+        }
+        Action::Shoot(dir) => {
+            let mut actions = turns(facing, dir);
+            actions.push(OrientedAction::Shoot);
+            (actions, dir)
+        }
+        Action::Grab => (vec![OrientedAction::Grab], facing),
+        Action::Exit => (vec![OrientedAction::Exit], facing),
+    }
+}
+
+/// The oriented actions needed by a plan of the hero starting from `facing`: every turn
+/// costs like a step.
+pub fn plan_cost(facing: Direction, plan: &[Action]) -> usize {
+    let mut facing = facing;
+    plan.iter()
+        .map(|action| {
+            let (actions, next) = compile(facing, *action);
+            facing = next;
+            actions.len()
+        })
+        .sum()
+}
+
+/// A World played with the oriented actions, the hero starts facing START_FACING.
+pub struct OrientedWorld {
+    world: World,
+    facing: Direction,
+    // l'ultima azione è stata una rotazione: l'urlo si sente solo subito dopo il tiro
+    turned: bool,
+}
+
+impl OrientedWorld {
+    pub fn new(world: World) -> Self {
+        Self {
+            world: world,
+            facing: START_FACING,
+            turned: false,
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn facing(&self) -> Direction {
+        self.facing
+    }
+
+    /// The action of the underlying World done by `action` from the current facing, None
+    /// for the turns.
+    pub fn cardinal(&self, action: OrientedAction) -> Option<Action> {
+        match action {
+            OrientedAction::TurnLeft | OrientedAction::TurnRight => None,
+            OrientedAction::Forward => Some(Action::Move(self.facing)),
+            OrientedAction::Shoot => Some(Action::Shoot(self.facing)),
+            OrientedAction::Grab => Some(Action::Grab),
+            OrientedAction::Exit => Some(Action::Exit),
+        }
+    }
+
+    pub fn perceptions(&self) -> OrientedPerceptions {
+        let mut p = self.world.perceptions();
+        if self.turned {
+            p.set_howl(false);
+        }
+        OrientedPerceptions {
+            perceptions: p,
+            facing: self.facing,
+        }
+    }
+
+    /// Plays an action like World::do_action: a turn always succeeds, Forward into a wall
+    /// and the other illegal actions are refused and change nothing.
+    pub fn do_action(&mut self, action: OrientedAction) -> Result<ActionResult, IllegalAction> {
+        let Some(action) = self.cardinal(action) else {
+            self.facing = if action == OrientedAction::TurnLeft {
+                self.facing.turn_left()
+            } else {
+                self.facing.turn_right()
+            };
+            self.turned = true;
+            return Ok(ActionResult::Continue);
+        };
+        let result = self.world.do_action(action)?;
+        self.turned = false;
+        Ok(result)
+    }
+}
+
+/// Plays an agent deciding in cardinal directions in an OrientedWorld: each of its actions
+/// becomes the turns and the single step or shot that do it.
+pub struct Oriented<A> {
+    agent: A,
+    // le azioni compilate dall'ultima decisione dell'agente non ancora giocate
+    pending: VecDeque<OrientedAction>,
+}
+
+impl<A: Agent> Oriented<A> {
+    pub fn new(agent: A) -> Self {
+        Self {
+            agent: agent,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn agent(&self) -> &A {
+        &self.agent
+    }
+
+    /// The next oriented action; the agent is asked for a new decision only when the
+    /// previous one has been played completely.
+    pub fn next_action(&mut self, p: OrientedPerceptions) -> Result<OrientedAction, HeroError> {
+        if let Some(action) = self.pending.pop_front() {
+            return Ok(action);
+        }
+        let action = self.agent.next_action(p.perceptions)?;
+        let (actions, _) = compile(p.facing, action);
+        self.pending = actions.into();
+        Ok(self
+            .pending
+            .pop_front()
+            .expect("every action compiles to at least one oriented action"))
+    }
+}
+
+/// Plays a game like simulation::simulate but in an OrientedWorld: every turn of the hero
+/// costs a step and ACTION_COST like a move. The history keeps only the actions of the
+/// underlying World, so it is replayed in the World with the cardinal directions.
+pub fn simulate_oriented<A: Agent>(
+    world: World,
+    agent: A,
+    timeout: Option<Duration>,
+) -> SimulationResult {
+    let solvable = world.is_solvable();
+    let start = Instant::now();
+    let expired = || timeout.is_some_and(|timeout| start.elapsed() >= timeout);
+    let mut world = OrientedWorld::new(world);
+    let mut agent = Oriented::new(agent);
+    let mut steps = 0;
+    let mut score = 0;
+    let mut history = vec![];
+    let mut peak_memory = agent.agent().kb_memory();
+    let (outcome, gold, death_cause) = loop {
+        if expired() {
+            break (Outcome::TimedOut, false, None);
+        }
+        if interrupted() {
+            break (Outcome::Interrupted, false, None);
+        }
+        let p = world.perceptions();
+        let perceptions = p.perceptions.clone();
+        let action = match agent.next_action(p) {
+            Ok(action) => action,
+            Err(_) if expired() => break (Outcome::TimedOut, false, None),
+            Err(_) if interrupted() => break (Outcome::Interrupted, false, None),
+            Err(e) => break (Outcome::Failed(e), false, None),
+        };
+        steps += 1;
+        let cardinal = world.cardinal(action);
+        let result = match world.do_action(action) {
+            Ok(result) => result,
+            Err(e) => break (Outcome::IllegalAction(e), false, None),
+        };
+        score += match cardinal {
+            Some(a) => {
+                history.push(Step {
+                    perceptions: perceptions,
+                    action: a,
+                });
+                score_change(a, result)
+            }
+            None => -ACTION_COST,
+        };
+        if let Some(memory) = agent.agent().kb_memory() {
+            peak_memory = Some(peak_memory.map_or(memory, |peak| peak.max(memory)));
+        }
+        match result {
+            ActionResult::Continue => {}
+            ActionResult::Exited { gold: true } => break (Outcome::Gold, true, None),
+            ActionResult::Exited { gold: false } => break (Outcome::NoGold, false, None),
+            ActionResult::Dead(cause) if agent.agent().accepts_risk() => {
+                break (Outcome::Dead, false, Some(cause));
+            }
+            ActionResult::Dead(cause) => break (Outcome::DeadOnSafeCell, false, Some(cause)),
+        }
+    };
+    let stats = agent.agent().stats();
+    SimulationResult {
+        seed: world.world().seed(),
+        outcome: outcome,
+        steps: steps,
+        score: score,
+        gold: gold,
+        death_cause: death_cause,
+        solvable: solvable,
+        sat_calls: stats.map_or(0, |stats| stats.solver_calls),
+        duration: start.elapsed(),
+        stats: stats,
+        render_time: Duration::ZERO,
+        peak_memory: peak_memory,
+        history: history,
+    }
+}
+
+/// Plays `games` games in oriented mode like simulation::play_games, always in sequence and
+/// without the turns of the agent.
+pub fn play_oriented_games(
+    games: u64,
+    seed: Option<u64>,
+    new_game: &NewGame<'_>,
+    timeout: Option<Duration>,
+    progress: &Progress,
+) -> Vec<(SimulationResult, Vec<TurnLog>)> {
+    let mut results = vec![];
+    for game in 0..games {
+        // dopo interrupt la partita in corso viene scartata come in play_games
+        if interrupted() {
+            break;
+        }
+        let (world, agent) = new_game(seed.map(|seed| seed ^ game));
+        let result = simulate_oriented(world, agent, timeout);
+        if let Outcome::Interrupted = result.outcome {
+            break;
+        }
+        progress.record(&result);
+        results.push((result, vec![]));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{
+        hero::{Hero, HeroConfig},
+        kb::init_kb,
+    };
+
+    #[test]
+    fn the_fewest_turns_are_used() {
+        for from in Direction::ALL {
+            assert!(turns(from, from).is_empty());
+            assert_eq!(
+                turns(from, from.turn_left()),
+                vec![OrientedAction::TurnLeft]
+            );
+            assert_eq!(
+                turns(from, from.turn_right()),
+                vec![OrientedAction::TurnRight]
+            );
+            assert_eq!(turns(from, from.opposite()).len(), 2);
+        }
+    }
+
+    #[test]
+    fn plans_cost_their_turns() {
+        let east = Action::Move(Direction::East);
+        let west = Action::Move(Direction::Ovest);
+        let south = Action::Move(Direction::Sud);
+        assert_eq!(plan_cost(Direction::East, &[east, east]), 2);
+        // un'inversione costa due rotazioni più i passi
+        assert_eq!(plan_cost(Direction::East, &[west, west]), 2 + 2);
+        assert_eq!(plan_cost(Direction::East, &[east, west]), 1 + 2 + 1);
+        assert_eq!(plan_cost(Direction::East, &[south, east, south]), 2 + 2 + 2);
+        assert_eq!(plan_cost(Direction::East, &[Action::Grab, Action::Exit]), 2);
+        assert_eq!(
+            compile(Direction::North, Action::Shoot(Direction::Sud)),
+            (
+                vec![
+                    OrientedAction::TurnRight,
+                    OrientedAction::TurnRight,
+                    OrientedAction::Shoot
+                ],
+                Direction::Sud
+            )
+        );
+    }
+
+    #[test]
+    fn turning_changes_only_the_facing() {
+        let mut world = OrientedWorld::new(World::from_layout("x . g\n. o .\n. . w").unwrap());
+        let start = world.perceptions();
+        assert_eq!(start.facing, START_FACING);
+        world.do_action(OrientedAction::TurnLeft).unwrap();
+        let p = world.perceptions();
+        assert_eq!(p.facing, Direction::North);
+        assert_eq!(p.perceptions, start.perceptions);
+        // verso nord da (0,0) c'è il muro
+        assert!(world.do_action(OrientedAction::Forward).is_err());
+        world.do_action(OrientedAction::TurnRight).unwrap();
+        world.do_action(OrientedAction::TurnRight).unwrap();
+        assert_eq!(world.facing(), Direction::Sud);
+        assert_eq!(
+            world.do_action(OrientedAction::Forward),
+            Ok(ActionResult::Continue)
+        );
+        assert_eq!(world.world().hero_position().y, 1);
+    }
+
+    #[test]
+    fn the_hero_wins_with_the_oriented_actions() {
+        let layout = "x . g\n. . .\no . w";
+        let hero = || {
+            let mut kb = init_kb(3);
+            kb.use_in_process_solver();
+            Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(7))
+        };
+        // le scelte dell'eroe nel mondo con le direzioni cardinali
+        let mut world = World::from_layout(layout).unwrap();
+        let mut cardinal = hero();
+        let mut plan = vec![];
+        loop {
+            let action = cardinal.next_action(world.perceptions()).unwrap();
+            plan.push(action);
+            if world.do_action(action).unwrap() != ActionResult::Continue {
+                break;
+            }
+        }
+
+        let mut world = OrientedWorld::new(World::from_layout(layout).unwrap());
+        let mut oriented = Oriented::new(hero());
+        let mut played = 0;
+        let result = loop {
+            let action = oriented.next_action(world.perceptions()).unwrap();
+            played += 1;
+            let result = world.do_action(action).unwrap();
+            if result != ActionResult::Continue {
+                break result;
+            }
+        };
+        assert_eq!(result, ActionResult::Exited { gold: true });
+        assert_eq!(played, plan_cost(START_FACING, &plan));
+    }
+
+    #[test]
+    fn an_oriented_game_pays_its_turns() {
+        let layout = "x . g\n. . .\no . w";
+        let hero = || {
+            let mut kb = init_kb(3);
+            kb.use_in_process_solver();
+            Hero::with_rng(kb, 3, HeroConfig::default(), StdRng::seed_from_u64(7))
+        };
+        let cardinal = crate::simulation::simulate(
+            &mut World::from_layout(layout).unwrap(),
+            &mut hero(),
+            &mut |_| {},
+            crate::simulation::Screen::hidden(),
+            None,
+        );
+        let plan: Vec<Action> = cardinal.history.iter().map(|step| step.action).collect();
+        let result = simulate_oriented(World::from_layout(layout).unwrap(), hero(), None);
+        assert!(matches!(result.outcome, Outcome::Gold));
+        // la storia è quella del mondo cardinale, le rotazioni contano solo nei passi
+        assert_eq!(result.history, cardinal.history);
+        assert_eq!(result.steps, plan_cost(START_FACING, &plan));
+        assert!(result.steps > cardinal.steps);
+        assert_eq!(
+            cardinal.score - result.score,
+            (result.steps - cardinal.steps) as i32 * ACTION_COST
+        );
+    }
+}
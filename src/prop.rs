@@ -0,0 +1,328 @@
+use std::{fmt, hash::Hash};
+
+use crate::encoder::{EncoderSAT, Literal};
+
+// oltre questa soglia di clausole la distribuzione di un Or sugli And viene sostituita
+// dalla trasformazione di Tseytin
+const DISTRIBUTION_LIMIT: usize = 64;
+
+#[derive(Clone, Debug)]
+pub enum Prop<T> {
+    Var(T),
+    Not(Box<Prop<T>>),
+    And(Vec<Prop<T>>),
+    Or(Vec<Prop<T>>),
+    Implies(Box<Prop<T>>, Box<Prop<T>>),
+    Iff(Box<Prop<T>>, Box<Prop<T>>),
+}
+
+impl<T> Prop<T> {
+    pub fn var(t: T) -> Self {
+        Prop::Var(t)
+    }
+
+    pub fn not(self) -> Self {
+        Prop::Not(Box::new(self))
+    }
+
+    pub fn and<I: IntoIterator<Item = Prop<T>>>(props: I) -> Self {
+        Prop::And(props.into_iter().collect())
+    }
+
+    pub fn or<I: IntoIterator<Item = Prop<T>>>(props: I) -> Self {
+        Prop::Or(props.into_iter().collect())
+    }
+
+    pub fn implies(self, other: Prop<T>) -> Self {
+        Prop::Implies(Box::new(self), Box::new(other))
+    }
+
+    pub fn iff(self, other: Prop<T>) -> Self {
+        Prop::Iff(Box::new(self), Box::new(other))
+    }
+}
+
+impl<T: Clone> Prop<T> {
+    /// Builds the proposition equivalent to a formula already in CNF.
+    pub fn from_cnf(formula: &[Vec<Literal<T>>]) -> Self {
+        Prop::and(
+            formula
+                .iter()
+                .map(|clause| Prop::or(clause.iter().cloned().map(Prop::from))),
+        )
+    }
+
+    // forma normale negata: le negazioni compaiono solo davanti alle variabili
+    fn nnf(&self, negated: bool) -> Nnf<T> {
+        match self {
+            Prop::Var(t) => {
+                if negated {
+                    Nnf::Lit(Literal::Neg(t.clone()))
+                } else {
+                    Nnf::Lit(Literal::Pos(t.clone()))
+                }
+            }
+            Prop::Not(p) => p.nnf(!negated),
+            Prop::And(ps) => {
+                let children = ps.iter().map(|p| p.nnf(negated)).collect();
+                if negated {
+                    Nnf::or(children)
+                } else {
+                    Nnf::and(children)
+                }
+            }
+            Prop::Or(ps) => {
+                let children = ps.iter().map(|p| p.nnf(negated)).collect();
+                if negated {
+                    Nnf::and(children)
+                } else {
+                    Nnf::or(children)
+                }
+            }
+            Prop::Implies(a, b) => {
+                if negated {
+                    // not (a -> b) = a and not b
+                    Nnf::and(vec![a.nnf(false), b.nnf(true)])
+                } else {
+                    Nnf::or(vec![a.nnf(true), b.nnf(false)])
+                }
+            }
+            Prop::Iff(a, b) => {
+                if negated {
+                    // not (a <-> b) = (a or b) and (not a or not b)
+                    Nnf::and(vec![
+                        Nnf::or(vec![a.nnf(false), b.nnf(false)]),
+                        Nnf::or(vec![a.nnf(true), b.nnf(true)]),
+                    ])
+                } else {
+                    Nnf::and(vec![
+                        Nnf::or(vec![a.nnf(true), b.nnf(false)]),
+                        Nnf::or(vec![a.nnf(false), b.nnf(true)]),
+                    ])
+                }
+            }
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone + fmt::Debug> Prop<T> {
+    /// Converts the proposition into an equisatisfiable CNF over the encoder variables.
+    /// Top-level conjunctions and small disjunctions become plain clauses, bigger
+    /// subformulas are replaced by Tseytin definition literals whose defining clauses are
+    /// added directly to the encoder.
+    pub fn to_cnf(&self, encoder: &mut EncoderSAT<T>) -> Vec<Vec<Literal<usize>>> {
+        self.nnf(false).clausify(encoder)
+    }
+}
+
+impl<T: Eq + Hash + Clone + fmt::Debug> EncoderSAT<T> {
+    pub fn add_prop(&mut self, prop: &Prop<T>) {
+        for clause in prop.to_cnf(self) {
+            self.add_raw_clause(clause);
+        }
+    }
+}
+
+impl<T> From<Literal<T>> for Prop<T> {
+    fn from(value: Literal<T>) -> Self {
+        match value {
+            Literal::Pos(t) => Prop::Var(t),
+            Literal::Neg(t) => Prop::Var(t).not(),
+        }
+    }
+}
+
+enum Nnf<T> {
+    Lit(Literal<T>),
+    And(Vec<Nnf<T>>),
+    Or(Vec<Nnf<T>>),
+}
+
+impl<T> Nnf<T> {
+    fn and(children: Vec<Nnf<T>>) -> Self {
+        let mut flat = vec![];
+        for child in children {
+            match child {
+                Nnf::And(grandchildren) => flat.extend(grandchildren),
+                other => flat.push(other),
+            }
+        }
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            Nnf::And(flat)
+        }
+    }
+
+    fn or(children: Vec<Nnf<T>>) -> Self {
+        let mut flat = vec![];
+        for child in children {
+            match child {
+                Nnf::Or(grandchildren) => flat.extend(grandchildren),
+                other => flat.push(other),
+            }
+        }
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            Nnf::Or(flat)
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone + fmt::Debug> Nnf<T> {
+    fn clausify(self, encoder: &mut EncoderSAT<T>) -> Vec<Vec<Literal<usize>>> {
+        match self {
+            Nnf::Lit(literal) => vec![vec![encoder.register_literal(literal)]],
+            Nnf::And(children) => children
+                .into_iter()
+                .flat_map(|child| child.clausify(encoder))
+                .collect(),
+            Nnf::Or(children) => {
+                let mut parts: Vec<Vec<Vec<Literal<usize>>>> = children
+                    .into_iter()
+                    .map(|child| child.clausify(encoder))
+                    .collect();
+                let product = parts
+                    .iter()
+                    .fold(1usize, |acc, part| acc.saturating_mul(part.len()));
+                if product > DISTRIBUTION_LIMIT {
+                    // ogni parte con più di una clausola viene sostituita dal suo letterale di definizione
+                    for part in parts.iter_mut() {
+                        if part.len() > 1 {
+                            let definitions = part
+                                .drain(..)
+                                .map(|clause| encoder.add_or(clause))
+                                .collect();
+                            *part = vec![vec![encoder.add_and(definitions)]];
+                        }
+                    }
+                }
+                // distribuzione dell'Or sulle clausole delle parti
+                let mut result = vec![vec![]];
+                for part in parts {
+                    let mut next = Vec::with_capacity(result.len() * part.len());
+                    for prefix in &result {
+                        for clause in &part {
+                            let mut joined: Vec<Literal<usize>> = prefix.clone();
+                            joined.extend(clause.iter().cloned());
+                            next.push(joined);
+                        }
+                    }
+                    result = next;
+                }
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::solver::{Dpll, SatResult};
+
+    const VARS: u8 = 4;
+
+    fn eval(prop: &Prop<u8>, assignment: u32) -> bool {
+        match prop {
+            Prop::Var(v) => assignment >> v & 1 == 1,
+            Prop::Not(p) => !eval(p, assignment),
+            Prop::And(ps) => ps.iter().all(|p| eval(p, assignment)),
+            Prop::Or(ps) => ps.iter().any(|p| eval(p, assignment)),
+            Prop::Implies(a, b) => !eval(a, assignment) || eval(b, assignment),
+            Prop::Iff(a, b) => eval(a, assignment) == eval(b, assignment),
+        }
+    }
+
+    // gli assegnamenti delle variabili originali che si estendono a un modello delle clausole,
+    // le variabili di Tseytin restano libere
+    fn projected_models(prop: &Prop<u8>) -> Vec<u32> {
+        let mut encoder = EncoderSAT::<u8>::new();
+        encoder.set_solver(Arc::new(Dpll));
+        for v in 0..VARS {
+            encoder.register_literal(Literal::Pos(v));
+        }
+        encoder.add_prop(prop);
+        (0u32..1 << VARS)
+            .filter(|assignment| {
+                let assumptions: Vec<Literal<u8>> = (0..VARS)
+                    .map(|v| {
+                        if assignment >> v & 1 == 1 {
+                            Literal::Pos(v)
+                        } else {
+                            Literal::Neg(v)
+                        }
+                    })
+                    .collect();
+                encoder.solve_under_assumptions(&assumptions).unwrap() == SatResult::Sat
+            })
+            .collect()
+    }
+
+    fn prop() -> impl Strategy<Value = Prop<u8>> {
+        let leaf = (0..VARS).prop_map(Prop::var);
+        leaf.prop_recursive(4, 24, 4, |inner| {
+            prop_oneof![
+                inner.clone().prop_map(Prop::not),
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Prop::and),
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Prop::or),
+                (inner.clone(), inner.clone()).prop_map(|(a, b)| a.implies(b)),
+                (inner.clone(), inner).prop_map(|(a, b)| a.iff(b)),
+            ]
+        })
+    }
+
+    // le clausole scritte a mano da init_kb prima di Prop: B -> P1 or ... or Pn e Pi -> B
+    #[test]
+    fn the_breeze_biconditional_matches_the_hand_written_clauses() {
+        for neighbors in 1..VARS {
+            let pits: Vec<u8> = (1..=neighbors).collect();
+            let mut by_hand = vec![
+                std::iter::once(Literal::Neg(0))
+                    .chain(pits.iter().map(|p| Literal::Pos(*p)))
+                    .collect::<Vec<_>>(),
+            ];
+            by_hand.extend(pits.iter().map(|p| vec![Literal::Neg(*p), Literal::Pos(0)]));
+            let built = Prop::var(0).iff(Prop::or(pits.iter().map(|p| Prop::var(*p))));
+            assert_eq!(
+                projected_models(&built),
+                projected_models(&Prop::from_cnf(&by_hand))
+            );
+        }
+    }
+
+    // abbastanza clausole da superare DISTRIBUTION_LIMIT: le parti diventano definizioni
+    #[test]
+    fn big_disjunctions_use_tseytin_definitions() {
+        let part = |a: u8, b: u8| {
+            Prop::and([
+                Prop::var(a),
+                Prop::var(b).not(),
+                Prop::var(a).iff(Prop::var((b + 1) % VARS)),
+            ])
+        };
+        let prop = Prop::or([part(0, 1), part(1, 2), part(2, 3), part(3, 0)]);
+        let mut encoder = EncoderSAT::<u8>::new();
+        let before = encoder.encode().0;
+        prop.to_cnf(&mut encoder);
+        assert_ne!(encoder.encode().0, before, "no definition was added");
+        let expected: Vec<u32> = (0u32..1 << VARS).filter(|a| eval(&prop, *a)).collect();
+        assert_eq!(projected_models(&prop), expected);
+    }
+
+    proptest! {
+        #[test]
+        fn the_cnf_has_the_models_of_the_proposition(prop in prop()) {
+            let expected: Vec<u32> = (0u32..1 << VARS).filter(|a| eval(&prop, *a)).collect();
+            prop_assert_eq!(projected_models(&prop), expected.clone());
+            // la negazione usata da ask: i modelli sono il complemento
+            let complement: Vec<u32> = (0u32..1 << VARS).filter(|a| !expected.contains(a)).collect();
+            prop_assert_eq!(projected_models(&prop.not()), complement);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// binding Python di WumpusEnv per scrivere agenti in Python. Il modulo si compila con maturin e
+// si prova con lo script in python/:
+//
+//     pip install maturin pytest
+//     maturin develop --features extension-module
+//     pytest python
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+
+use crate::{
+    agents::HumanAgent,
+    environment::{EnvConfig, Info, Observation, WumpusEnv},
+};
+
+/// The environment seen from Python: observations and ground truth are dicts, the
+/// actions are the commands of the human player (n/s/e/w, g, x, "shoot <dir>").
+#[pyclass(name = "WumpusEnv")]
+pub struct PyWumpusEnv {
+    env: WumpusEnv,
+    done: bool,
+}
+
+#[pymethods]
+impl PyWumpusEnv {
+    #[new]
+    #[pyo3(signature = (dim = 4, pits = 3))]
+    pub fn new(dim: usize, pits: usize) -> PyResult<Self> {
+        // il mondo deve avere posto per i pozzi, il wumpus, l'oro e l'eroe
+        if dim == 0 || dim * dim <= pits + 2 {
+            return Err(PyValueError::new_err(format!(
+                "a {}x{} dungeon can't hold {} pits",
+                dim, dim, pits
+            )));
+        }
+        Ok(Self {
+            env: WumpusEnv::new(EnvConfig {
+                dim: dim,
+                pits: pits,
+            }),
+            done: true,
+        })
+    }
+
+    /// Starts an episode on a new dungeon, the same seed gives the same dungeon.
+    #[pyo3(signature = (seed = None))]
+    pub fn reset<'py>(
+        &mut self,
+        py: Python<'py>,
+        seed: Option<u64>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let observation = self.env.reset(seed);
+        self.done = false;
+        observation_dict(py, &observation)
+    }
+
+    /// Plays an action: (observation, reward, done, info).
+    #[allow(clippy::type_complexity)]
+    pub fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: &str,
+    ) -> PyResult<(Bound<'py, PyDict>, i32, bool, Bound<'py, PyDict>)> {
+        if self.done {
+            return Err(PyRuntimeError::new_err("the episode is over, call reset"));
+        }
+        let Some(action) = HumanAgent::parse(action) else {
+            return Err(PyValueError::new_err(format!(
+                "unknown action {:?}",
+                action
+            )));
+        };
+        let (observation, reward, done, info) = self.env.step(action);
+        self.done = done;
+        Ok((
+            observation_dict(py, &observation)?,
+            reward,
+            done,
+            info_dict(py, &info)?,
+        ))
+    }
+
+    /// True before the first reset and after the end of an episode.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The true board, as printed by the simulator.
+    pub fn render(&self) -> PyResult<String> {
+        match self.env.world() {
+            Some(world) => Ok(world.to_string()),
+            None => Err(PyRuntimeError::new_err("call reset first")),
+        }
+    }
+}
+
+fn observation_dict<'py>(
+    py: Python<'py>,
+    observation: &Observation,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("glitter", observation.glitter)?;
+    dict.set_item("stench", observation.stench)?;
+    dict.set_item("breeze", observation.breeze)?;
+    dict.set_item("howl", observation.howl)?;
+    dict.set_item("bump", observation.bump)?;
+    dict.set_item("position", (observation.position.x, observation.position.y))?;
+    dict.set_item("board_size", observation.board_size)?;
+    Ok(dict)
+}
+
+fn info_dict<'py>(py: Python<'py>, info: &Info) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (key, value) in info {
+        dict.set_item(*key, value)?;
+    }
+    Ok(dict)
+}
+
+#[pymodule]
+fn wumpus(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyWumpusEnv>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // senza interprete si prova solo la parte Rust della classe; il resto lo prova python/
+    #[test]
+    fn the_pyclass_is_built_from_rust() {
+        let env = PyWumpusEnv::new(4, 3).expect("a 4x4 dungeon holds 3 pits");
+        assert!(env.is_done());
+        assert!(env.render().is_err());
+        assert!(PyWumpusEnv::new(2, 3).is_err());
+        assert!(PyWumpusEnv::new(0, 0).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    fmt::{self, Write as _},
+    fs, io,
+    path::Path,
+};
+
+use crate::{
+    agents::Agent,
+    hero::HeroError,
+    simulation::{View, check_move, check_position, checking_invariants},
+    world::{Action, ActionResult, IllegalAction, Perceptions, World},
+};
+
+/// One turn of a recorded game: what the agent perceived and what it did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Step {
+    pub perceptions: Perceptions,
+    pub action: Action,
+}
+
+/// A recorded game. The world and the agent are rebuilt from the seed, so the recording
+/// only needs the perceptions and the actions of every turn.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord {
+    pub seed: u64,
+    pub dim: usize,
+    pub pits: usize,
+    pub steps: Vec<Step>,
+}
+
+impl GameRecord {
+    pub fn new(seed: u64, dim: usize, pits: usize, steps: Vec<Step>) -> Self {
+        Self {
+            seed: seed,
+            dim: dim,
+            pits: pits,
+            steps: steps,
+        }
+    }
+
+    /// The world of the recorded game, in its initial state.
+    pub fn world(&self) -> World {
+        World::new_with_seed(self.dim, self.pits, self.seed)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(file, self).map_err(std::io::Error::other)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: &std::path::Path) -> std::io::Result<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        serde_json::from_reader(file).map_err(std::io::Error::other)
+    }
+}
+
+/// The actions chosen by the deterministic hero on a seed and how the game ended, stored
+/// to detect changes of behaviour.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GoldenTrace {
+    pub seed: u64,
+    pub dim: usize,
+    pub pits: usize,
+    pub actions: Vec<Action>,
+    // etichetta dell'Outcome della partita
+    pub outcome: String,
+}
+
+impl GoldenTrace {
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = io::BufReader::new(fs::File::open(path)?);
+        serde_json::from_reader(file).map_err(io::Error::other)
+    }
+}
+
+/// First turn where the agent does not follow a golden trace, with the board at that turn.
+#[derive(Debug)]
+pub struct GoldenMismatch {
+    pub turn: usize,
+    // None quando la partita finisce prima o dopo quella registrata
+    pub expected: Option<Action>,
+    pub actual: Option<Action>,
+    pub board: String,
+    pub knowledge: Option<String>,
+}
+
+impl fmt::Display for GoldenMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action =
+            |a: Option<Action>| a.map_or("the end of the game".to_string(), |a| format!("{:?}", a));
+        writeln!(
+            f,
+            "turn {}: expected {}, got {}",
+            self.turn,
+            action(self.expected),
+            action(self.actual)
+        )?;
+        write!(f, "{}", self.board)?;
+        if let Some(knowledge) = &self.knowledge {
+            write!(f, "{}", knowledge)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GoldenMismatch {}
+
+/// Plays the golden seed with `agent`, built with the same seed, and checks that it chooses
+/// the stored actions and that the game ends after the last one.
+pub fn check_golden(golden: &GoldenTrace, agent: &mut dyn Agent) -> Result<(), GoldenMismatch> {
+    let mut world = World::new_with_seed(golden.dim, golden.pits, golden.seed);
+    let mismatch =
+        |turn: usize, world: &World, agent: &dyn Agent, actual: Option<Action>| GoldenMismatch {
+            turn: turn,
+            expected: golden.actions.get(turn).copied(),
+            actual: actual,
+            board: world.to_string(),
+            knowledge: agent.render_knowledge(world.perceptions().position),
+        };
+    let mut turn = 0;
+    let mut history = vec![];
+    loop {
+        let perceptions = world.perceptions();
+        // un errore dell'agente conta come la fine della partita
+        let actual = agent.next_action(perceptions.clone()).ok();
+        if actual != golden.actions.get(turn).copied() {
+            return Err(mismatch(turn, &world, agent, actual));
+        }
+        let Some(a) = actual else {
+            return Ok(());
+        };
+        turn += 1;
+        history.push(Step {
+            perceptions: perceptions,
+            action: a,
+        });
+        if checking_invariants() {
+            check_move(&world, agent, a, &history);
+        }
+        // un'azione rifiutata dal mondo chiude la partita come la registrazione
+        let Ok(result) = world.do_action(a) else {
+            break;
+        };
+        if checking_invariants() {
+            check_position(&world, agent, result, &history);
+        }
+        if result != ActionResult::Continue {
+            break;
+        }
+    }
+    if turn < golden.actions.len() {
+        return Err(mismatch(turn, &world, agent, None));
+    }
+    Ok(())
+}
+
+/// First difference between a replay and its recording.
+#[derive(Debug)]
+pub enum Divergence {
+    // il mondo ricostruito non dà le percezioni registrate
+    Perceptions {
+        turn: usize,
+        recorded: Perceptions,
+        replayed: Perceptions,
+    },
+    // l'agente sceglie un'azione diversa da quella registrata
+    Action {
+        turn: usize,
+        recorded: Action,
+        replayed: Action,
+    },
+    AgentFailed {
+        turn: usize,
+        error: HeroError,
+    },
+    // il mondo ricostruito rifiuta l'azione registrata
+    Illegal {
+        turn: usize,
+        action: Action,
+        error: IllegalAction,
+    },
+    // la partita finisce prima dell'ultimo turno registrato
+    Ended {
+        turn: usize,
+        result: ActionResult,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Perceptions {
+                turn,
+                recorded,
+                replayed,
+            } => write!(
+                f,
+                "turn {}: recorded perceptions {:?}, replayed {:?}",
+                turn, recorded, replayed
+            ),
+            Divergence::Action {
+                turn,
+                recorded,
+                replayed,
+            } => write!(
+                f,
+                "turn {}: recorded action {:?}, the agent chose {:?}",
+                turn, recorded, replayed
+            ),
+            Divergence::AgentFailed { turn, error } => {
+                write!(f, "turn {}: the agent failed: {}", turn, error)
+            }
+            Divergence::Illegal {
+                turn,
+                action,
+                error,
+            } => write!(f, "turn {}: {:?} refused: {}", turn, action, error),
+            Divergence::Ended { turn, result } => {
+                write!(f, "turn {}: the game ended early with {:?}", turn, result)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Plays the recorded actions again on the rebuilt world and checks that every turn gives the
+/// recorded perceptions. With an agent (built with the recorded seed) also checks that it
+/// makes the recorded choices.
+pub fn replay(record: &GameRecord, mut agent: Option<&mut dyn Agent>) -> Result<(), Divergence> {
+    let mut world = record.world();
+    for (turn, step) in record.steps.iter().enumerate() {
+        let p = world.perceptions();
+        if p != step.perceptions {
+            return Err(Divergence::Perceptions {
+                turn: turn,
+                recorded: step.perceptions.clone(),
+                replayed: p,
+            });
+        }
+        if let Some(agent) = agent.as_mut() {
+            let a = agent
+                .next_action(p)
+                .map_err(|error| Divergence::AgentFailed {
+                    turn: turn,
+                    error: error,
+                })?;
+            if a != step.action {
+                return Err(Divergence::Action {
+                    turn: turn,
+                    recorded: step.action,
+                    replayed: a,
+                });
+            }
+        }
+        let result = world
+            .do_action(step.action)
+            .map_err(|e| Divergence::Illegal {
+                turn: turn,
+                action: step.action,
+                error: e,
+            })?;
+        if result != ActionResult::Continue && turn + 1 < record.steps.len() {
+            return Err(Divergence::Ended {
+                turn: turn,
+                result: result,
+            });
+        }
+    }
+    Ok(())
+}
+
+// le percezioni di un turno su una riga, solo quelle presenti
+fn perception_line(p: &Perceptions) -> String {
+    let mut line = format!("position ({}, {}):", p.position.x, p.position.y);
+    let flags = [
+        ("glitter", p.glitter()),
+        ("stench", p.stench()),
+        ("breeze", p.breeze()),
+        ("howl", p.howl()),
+        ("bump", p.bump()),
+    ];
+    let mut any = false;
+    for (name, present) in flags {
+        if present {
+            line.push(' ');
+            line.push_str(name);
+            any = true;
+        }
+    }
+    if !any {
+        line.push_str(" nothing");
+    }
+    line
+}
+
+/// Writes a text file per turn of the recorded game in `dir`, named frame-00000.txt,
+/// frame-00001.txt, ... so that they sort in play order, plus one with the final board.
+/// Each frame has the board chosen by `view`, the perceptions and the action of the turn.
+/// The knowledge view needs the agent, rebuilt with the recorded seed. Returns the number
+/// of frames written.
+pub fn write_frames(
+    record: &GameRecord,
+    mut agent: Option<&mut dyn Agent>,
+    view: View,
+    dir: &Path,
+) -> io::Result<usize> {
+    fs::create_dir_all(dir)?;
+    let mut world = record.world();
+    let frame = |turn: usize, world: &World, agent: Option<&dyn Agent>, action: Option<Action>| {
+        let p = world.perceptions();
+        let mut text = format!("turn {}\n", turn);
+        if matches!(view, View::World | View::Both) {
+            let _ = write!(text, "{}", world);
+        }
+        let knowledge = agent.and_then(|agent| agent.render_knowledge(p.position));
+        if let (View::Knowledge | View::Both, Some(knowledge)) = (view, knowledge) {
+            text.push_str(&knowledge);
+        }
+        text.push_str(&perception_line(&p));
+        text.push('\n');
+        match action {
+            Some(a) => {
+                let _ = writeln!(text, "action: {:?}", a);
+            }
+            None => text.push_str("action: none, the game is over\n"),
+        }
+        fs::write(dir.join(format!("frame-{:05}.txt", turn)), text)
+    };
+    for (turn, step) in record.steps.iter().enumerate() {
+        // l'agente deve ricevere le percezioni per aggiornare quello che sa
+        if let Some(agent) = agent.as_mut() {
+            agent
+                .next_action(world.perceptions())
+                .map_err(io::Error::other)?;
+        }
+        frame(turn, &world, agent.as_deref(), Some(step.action))?;
+        if world.do_action(step.action) != Ok(ActionResult::Continue) {
+            break;
+        }
+    }
+    frame(record.steps.len(), &world, agent.as_deref(), None)?;
+    Ok(record.steps.len() + 1)
+}
@@ -0,0 +1,776 @@
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{Level, error, info, log_enabled};
+
+use crate::{
+    agents::Agent,
+    encoder::MemoryFootprint,
+    hero::{Belief, DecisionTrace, HeroError, HeroStats},
+    replay::Step,
+    solver::INTERRUPTED,
+    strategy::Utility,
+    world::{Action, ActionResult, DeathCause, IllegalAction, Perceptions, World},
+};
+
+/// Which boards are printed while a game is played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    Hidden,
+    // la mappa vera
+    World,
+    // quello che l'agente sa, con il suo piano
+    Knowledge,
+    Both,
+}
+
+impl FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hidden" => Ok(View::Hidden),
+            "world" => Ok(View::World),
+            "knowledge" => Ok(View::Knowledge),
+            "both" => Ok(View::Both),
+            other => Err(format!("unknown view {}", other)),
+        }
+    }
+}
+
+/// How a game is shown: the boards to draw, where to draw them and, to animate it, the
+/// pause between two turns. The library never prints, output receives every frame.
+#[derive(Clone, Copy, Debug)]
+pub struct Screen {
+    pub view: View,
+    pub delay: Option<Duration>,
+    pub output: fn(&str),
+}
+
+impl Screen {
+    pub fn hidden() -> Self {
+        Self {
+            view: View::Hidden,
+            delay: None,
+            output: |_| {},
+        }
+    }
+
+    /// The boards of view written in the log at the info level, one record per turn.
+    pub fn logged(view: View) -> Self {
+        Self {
+            view: view,
+            delay: None,
+            output: |frame| info!("\n{}", frame.trim_end()),
+        }
+    }
+
+    // un fotogramma passato a output. Restituisce il tempo passato a disegnare, senza la
+    // pausa dell'animazione
+    fn show(&self, world: &World, agent: &dyn Agent) -> Duration {
+        if self.view == View::Hidden {
+            return Duration::ZERO;
+        }
+        let start = Instant::now();
+        let mut frame = String::new();
+        if matches!(self.view, View::World | View::Both) {
+            frame.push_str(&world.to_string());
+        }
+        if matches!(self.view, View::Knowledge | View::Both)
+            && let Some(knowledge) = agent.render_knowledge(world.perceptions().position)
+        {
+            frame.push_str(&knowledge);
+        }
+        (self.output)(&frame);
+        let drawing = start.elapsed();
+        if let Some(delay) = self.delay {
+            thread::sleep(delay);
+        }
+        drawing
+    }
+}
+
+/// How a game ended.
+pub enum Outcome {
+    Gold,
+    NoGold,
+    // l'agente è morto in una casella che sapeva essere rischiosa
+    Dead,
+    // l'agente è morto in una casella che credeva sicura: la sua KB ha sbagliato
+    DeadOnSafeCell,
+    // l'agente non è riuscito a scegliere un'azione
+    Failed(HeroError),
+    // il mondo ha rifiutato l'azione scelta dall'agente
+    IllegalAction(IllegalAction),
+    // la partita è stata abbandonata perché ha superato il tempo massimo
+    TimedOut,
+    // la partita è stata abbandonata per un Ctrl-C
+    Interrupted,
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Gold => "gold",
+            Outcome::NoGold => "no gold",
+            Outcome::Dead => "dead",
+            Outcome::DeadOnSafeCell => "dead on a safe cell",
+            Outcome::Failed(_) => "failed",
+            Outcome::IllegalAction(_) => "illegal action",
+            Outcome::TimedOut => "timed out",
+            Outcome::Interrupted => "interrupted",
+        }
+    }
+}
+
+// punteggio classico: -1 per azione, -10 per la freccia, +1000 uscendo con l'oro, -1000 morendo
+pub const ACTION_COST: i32 = 1;
+pub const ARROW_COST: i32 = 10;
+pub const GOLD_REWARD: i32 = 1000;
+pub const DEATH_COST: i32 = 1000;
+
+/// Change of the score due to an action and its result.
+pub fn score_change(a: Action, result: ActionResult) -> i32 {
+    let mut change = -ACTION_COST;
+    if let Action::Shoot(_) = a {
+        change -= ARROW_COST;
+    }
+    match result {
+        ActionResult::Exited { gold: true } => change + GOLD_REWARD,
+        ActionResult::Dead(_) => change - DEATH_COST,
+        _ => change,
+    }
+}
+
+/// How one game ended and what it cost.
+pub struct SimulationResult {
+    // il seed della partita, si rigioca con `wumpus replay <seed>`
+    pub seed: u64,
+    pub outcome: Outcome,
+    pub steps: usize,
+    pub score: i32,
+    pub gold: bool,
+    pub death_cause: Option<DeathCause>,
+    // l'oro era raggiungibile senza attraversare pozzi, calcolato prima della partita
+    pub solvable: bool,
+    pub sat_calls: usize,
+    pub duration: Duration,
+    pub stats: Option<HeroStats>,
+    // tempo passato a disegnare il mondo e la conoscenza dell'agente, zero senza schermo
+    pub render_time: Duration,
+    // il massimo di ogni parte della memoria della KB durante la partita
+    pub peak_memory: Option<MemoryFootprint>,
+    // percezioni e azioni di ogni turno, per registrare la partita e rigiocarla
+    pub history: Vec<Step>,
+}
+
+/// What happened in one turn of a game, written as a JSON line by `--turn-log`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TurnLog {
+    pub turn: usize,
+    pub perceptions: Perceptions,
+    pub action: Action,
+    // vuoto se l'agente non spiega le sue scelte
+    pub candidates: Vec<(Action, Utility)>,
+    // dimensione della KB dopo la decisione, None per gli agenti senza KB
+    pub clauses: Option<usize>,
+    pub variables: Option<usize>,
+    // chiamate al solver fatte in questo turno
+    pub solver_calls: usize,
+    // punteggio dopo l'azione
+    pub score: i32,
+    // la decisione completa, scritta a parte da WUMPUS_TRACE
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub decision: Option<DecisionTrace>,
+}
+
+// gioca una partita fino all'uscita o alla morte dell'agente, o finché non passa timeout.
+// Il tempo si controlla tra un turno e l'altro: perché una sola decisione non lo superi di
+// molto anche le chiamate al solver dell'agente devono avere un timeout
+pub fn simulate(
+    world: &mut World,
+    agent: &mut dyn Agent,
+    on_turn: &mut dyn FnMut(&TurnLog),
+    screen: Screen,
+    timeout: Option<Duration>,
+) -> SimulationResult {
+    let solvable = world.is_solvable();
+    let start = Instant::now();
+    let expired = || timeout.is_some_and(|timeout| start.elapsed() >= timeout);
+    let mut render_time = screen.show(world, agent);
+    let mut steps = 0;
+    let mut score = 0;
+    let mut history = vec![];
+    let solver_calls = |agent: &dyn Agent| agent.stats().map_or(0, |stats| stats.solver_calls);
+    let mut last_solver_calls = solver_calls(agent);
+    let mut peak_memory = agent.kb_memory();
+    let (outcome, gold, death_cause) = loop {
+        if expired() {
+            break (Outcome::TimedOut, false, None);
+        }
+        if interrupted() {
+            break (Outcome::Interrupted, false, None);
+        }
+        let p = world.perceptions();
+        let perceptions = p.clone();
+        let (a, trace) = match agent.next_action_traced(p) {
+            Ok(decision) => decision,
+            // un solver fermato dal suo timeout fa fallire l'agente
+            Err(_) if expired() => break (Outcome::TimedOut, false, None),
+            // il solver è stato fermato da interrupt
+            Err(_) if interrupted() => break (Outcome::Interrupted, false, None),
+            Err(e) => break (Outcome::Failed(e), false, None),
+        };
+        history.push(Step {
+            perceptions: perceptions.clone(),
+            action: a,
+        });
+        steps += 1;
+        if checking_invariants() {
+            check_move(world, agent, a, &history);
+        }
+        let result = match world.do_action(a) {
+            Ok(result) => result,
+            Err(e) => break (Outcome::IllegalAction(e), false, None),
+        };
+        if checking_invariants() {
+            check_position(world, agent, result, &history);
+        }
+        score += score_change(a, result);
+        let calls = solver_calls(agent);
+        let size = agent.kb_size();
+        on_turn(&TurnLog {
+            turn: steps,
+            perceptions: perceptions,
+            action: a,
+            candidates: trace
+                .as_ref()
+                .map_or_else(Vec::new, |trace| trace.candidates.clone()),
+            clauses: size.map(|(clauses, _)| clauses),
+            variables: size.map(|(_, variables)| variables),
+            solver_calls: calls - last_solver_calls,
+            score: score,
+            decision: trace,
+        });
+        last_solver_calls = calls;
+        if let Some(memory) = agent.kb_memory() {
+            peak_memory = Some(peak_memory.map_or(memory, |peak| peak.max(memory)));
+        }
+        render_time += screen.show(world, agent);
+        match result {
+            ActionResult::Continue => {}
+            ActionResult::Exited { gold: true } => break (Outcome::Gold, true, None),
+            ActionResult::Exited { gold: false } => break (Outcome::NoGold, false, None),
+            ActionResult::Dead(cause) if agent.accepts_risk() => {
+                break (Outcome::Dead, false, Some(cause));
+            }
+            // un agente che non rischia è morto in una casella che credeva sicura
+            ActionResult::Dead(cause) => break (Outcome::DeadOnSafeCell, false, Some(cause)),
+        }
+    };
+    let stats = agent.stats();
+    SimulationResult {
+        seed: world.seed(),
+        outcome: outcome,
+        steps: steps,
+        score: score,
+        gold: gold,
+        death_cause: death_cause,
+        solvable: solvable,
+        sat_calls: stats.map_or(0, |stats| stats.solver_calls),
+        duration: start.elapsed(),
+        stats: stats,
+        render_time: render_time,
+        peak_memory: peak_memory,
+        history: history,
+    }
+}
+
+static CHECK_INVARIANTS: AtomicBool = AtomicBool::new(false);
+
+/// Turns on the checks of simulate and replay::check_golden on every game played after the
+/// call: the hero enters only cells it proved safe or chose to gamble on, never stands on a
+/// cell it proved unsafe and never stands on a pit or on the live wumpus without dying. The
+/// first violation logs the whole game and panics.
+pub fn check_invariants(enabled: bool) {
+    CHECK_INVARIANTS.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn checking_invariants() -> bool {
+    CHECK_INVARIANTS.load(Ordering::Relaxed)
+}
+
+// prima della mossa: la casella di arrivo deve essere sicura o una scommessa dell'eroe
+pub(crate) fn check_move(world: &World, agent: &dyn Agent, a: Action, history: &[Step]) {
+    let Action::Move(dir) = a else {
+        return;
+    };
+    let Some(target) = world.hero_position().checked_move(dir, world.size()) else {
+        return;
+    };
+    match agent.belief(target) {
+        None | Some(Belief::Safe) | Some(Belief::Gamble) => {}
+        Some(belief) => invariant_violated(
+            world,
+            agent,
+            history,
+            format!("move {:?} to {:?}, believed {:?}", dir, target, belief),
+        ),
+    }
+}
+
+// dopo l'azione: l'eroe non sta su una casella pericolosa, se non è morto
+pub(crate) fn check_position(
+    world: &World,
+    agent: &dyn Agent,
+    result: ActionResult,
+    history: &[Step],
+) {
+    let pos = world.hero_position();
+    if agent.belief(pos) == Some(Belief::Unsafe) {
+        invariant_violated(
+            world,
+            agent,
+            history,
+            format!("the hero stands on {:?}, proved unsafe", pos),
+        );
+    }
+    if let (Some(hazard), false) = (
+        world.hazard_at(pos),
+        matches!(result, ActionResult::Dead(_)),
+    ) {
+        invariant_violated(
+            world,
+            agent,
+            history,
+            format!(
+                "the hero stands on {:?} with {:?} and is alive",
+                pos, hazard
+            ),
+        );
+    }
+}
+
+fn invariant_violated(world: &World, agent: &dyn Agent, history: &[Step], violation: String) -> ! {
+    error!(
+        "Invariant violated in the game with seed {}: {}",
+        world.seed(),
+        violation
+    );
+    for (turn, step) in history.iter().enumerate() {
+        error!(
+            "turn {}: {} -> {:?}",
+            turn + 1,
+            step.perceptions,
+            step.action
+        );
+    }
+    error!("World:\n{}", world);
+    if let Some(knowledge) = agent.render_knowledge(world.hero_position()) {
+        error!("Knowledge:\n{}", knowledge);
+    }
+    panic!("invariant violated: {}", violation);
+}
+
+/// Stops the games being played, for Ctrl-C: play_games starts no new game and drops the
+/// interrupted ones, simulate ends its game before the next turn and the solvers running
+/// are cancelled. It can't be undone.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// avanzamento delle partite, ogni riga va a report (il binario la stampa su stderr); i
+// contatori sono atomici perché le partite possono finire su thread diversi
+pub struct Progress {
+    games: usize,
+    // ogni quante partite si produce una riga, con quiet solo alla fine
+    every: usize,
+    quiet: bool,
+    report: fn(&str),
+    start: Instant,
+    completed: AtomicUsize,
+    gold: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new(games: usize, quiet: bool, report: fn(&str)) -> Self {
+        Self {
+            games: games,
+            every: (games / 20).max(1),
+            quiet: quiet,
+            report: report,
+            start: Instant::now(),
+            completed: AtomicUsize::new(0),
+            gold: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, result: &SimulationResult) {
+        if result.gold {
+            self.gold.fetch_add(1, Ordering::Relaxed);
+        }
+        let completed = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.quiet && (completed.is_multiple_of(self.every) || completed == self.games) {
+            self.report(completed);
+        }
+    }
+
+    // con quiet l'unica riga di avanzamento è quella finale
+    pub fn finish(&self) {
+        if self.quiet {
+            self.report(self.completed.load(Ordering::Relaxed));
+        }
+    }
+
+    fn report(&self, completed: usize) {
+        let gold = self.gold.load(Ordering::Relaxed);
+        let elapsed = self.start.elapsed();
+        // la durata media delle partite finite finora stima quella delle rimanenti
+        let eta = elapsed.mul_f64((self.games - completed) as f64 / completed.max(1) as f64);
+        (self.report)(&format!(
+            "completed {}/{}, success {:.1}%, elapsed {:.1}s, ETA {:.1}s",
+            completed,
+            self.games,
+            100.0 * gold as f64 / completed.max(1) as f64,
+            elapsed.as_secs_f64(),
+            eta.as_secs_f64()
+        ));
+    }
+}
+
+/// Creates the world and the agent of a game from its seed, the same seed must give the same game.
+pub type NewGame<'a> = dyn Fn(Option<u64>) -> (World, Box<dyn Agent>) + Sync + 'a;
+
+// una partita completa, i turni vengono raccolti e restituiti per non mescolare quelli
+// di partite giocate in parallelo
+fn play_game(
+    seed: Option<u64>,
+    new_game: &NewGame<'_>,
+    screen: Screen,
+    timeout: Option<Duration>,
+    progress: &Progress,
+) -> Option<(SimulationResult, Vec<TurnLog>)> {
+    // dopo interrupt non si comincia una nuova partita e quella in corso viene scartata
+    if interrupted() {
+        return None;
+    }
+    let (mut world, mut agent) = new_game(seed);
+    let mut turns = vec![];
+    let result = simulate(
+        &mut world,
+        agent.as_mut(),
+        &mut |turn| turns.push(turn.clone()),
+        screen,
+        timeout,
+    );
+    if let Outcome::Interrupted = result.outcome {
+        return None;
+    }
+    progress.record(&result);
+    Some((result, turns))
+}
+
+/// Plays `games` games, the i-th with seed base_seed ^ i, and returns them in order with the
+/// turns of the agent, after interrupt only the completed ones. With the rayon feature and
+/// `parallel` the games are played by more threads: each one has its own world, agent and
+/// knowledge base, so the results do not change.
+#[cfg(feature = "rayon")]
+pub fn play_games(
+    games: u64,
+    seed: Option<u64>,
+    parallel: bool,
+    new_game: &NewGame<'_>,
+    timeout: Option<Duration>,
+    progress: &Progress,
+) -> Vec<(SimulationResult, Vec<TurnLog>)> {
+    use rayon::prelude::*;
+
+    if !parallel {
+        return play_games_sequentially(games, seed, new_game, timeout, progress);
+    }
+    // le mappe di più partite si mescolerebbero, in parallelo non si stampano
+    (0..games)
+        .into_par_iter()
+        .filter_map(|game| {
+            play_game(
+                seed.map(|seed| seed ^ game),
+                new_game,
+                Screen::hidden(),
+                timeout,
+                progress,
+            )
+        })
+        .collect()
+}
+
+/// Plays `games` games, the i-th with seed base_seed ^ i, and returns them in order with the
+/// turns of the agent, after interrupt only the completed ones. Without the rayon feature the
+/// games are always played in sequence.
+#[cfg(not(feature = "rayon"))]
+pub fn play_games(
+    games: u64,
+    seed: Option<u64>,
+    _parallel: bool,
+    new_game: &NewGame<'_>,
+    timeout: Option<Duration>,
+    progress: &Progress,
+) -> Vec<(SimulationResult, Vec<TurnLog>)> {
+    play_games_sequentially(games, seed, new_game, timeout, progress)
+}
+
+fn play_games_sequentially(
+    games: u64,
+    seed: Option<u64>,
+    new_game: &NewGame<'_>,
+    timeout: Option<Duration>,
+    progress: &Progress,
+) -> Vec<(SimulationResult, Vec<TurnLog>)> {
+    // le mappe finiscono nel log solo in modalità verbosa
+    let screen = if log_enabled!(Level::Info) {
+        Screen::logged(View::Both)
+    } else {
+        Screen::hidden()
+    };
+    (0..games)
+        .filter_map(|game| {
+            play_game(
+                seed.map(|seed| seed ^ game),
+                new_game,
+                screen,
+                timeout,
+                progress,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{
+        hero::{Hero, HeroConfig},
+        kb::init_kb,
+        solver::{Solver, SolverError},
+        world::{Direction, GameSeeds, Position},
+    };
+
+    // il mondo del seme e l'eroe con la KB in-process
+    fn new_game(seed: Option<u64>) -> (World, Box<dyn Agent>) {
+        let seed = seed.unwrap();
+        let world = World::new_with_seed(5, 3, seed);
+        let mut kb = init_kb(5);
+        kb.use_in_process_solver();
+        let rng = StdRng::seed_from_u64(GameSeeds::new(seed).agent);
+        let hero = Hero::with_rng(kb, 5, HeroConfig::default(), rng);
+        (world, Box::new(hero))
+    }
+
+    fn play(seed: u64) -> SimulationResult {
+        let (mut world, mut hero) = new_game(Some(seed));
+        simulate(
+            &mut world,
+            hero.as_mut(),
+            &mut |_| {},
+            Screen::hidden(),
+            None,
+        )
+    }
+
+    #[test]
+    fn the_same_seeds_give_byte_identical_games() {
+        for seed in 0..6 {
+            let first = play(seed);
+            let second = play(seed);
+            assert_eq!(
+                format!("{:?}", first.history),
+                format!("{:?}", second.history),
+                "seed {}",
+                seed
+            );
+            assert_eq!(first.outcome.label(), second.outcome.label());
+            assert_eq!(first.sat_calls, second.sat_calls);
+        }
+    }
+
+    // gioca le azioni date, poi si arrende
+    struct Scripted(std::vec::IntoIter<Action>);
+
+    impl Agent for Scripted {
+        fn next_action(&mut self, p: Perceptions) -> Result<Action, HeroError> {
+            self.0
+                .next()
+                .ok_or(HeroError::NoActionAvailable(p.position))
+        }
+    }
+
+    fn play_script(actions: &[Action]) -> SimulationResult {
+        let mut world = World::from_layout("x g o\n. . .\nw . .").unwrap();
+        let mut agent = Scripted(Vec::from(actions).into_iter());
+        simulate(&mut world, &mut agent, &mut |_| {}, Screen::hidden(), None)
+    }
+
+    #[test]
+    fn every_ending_fills_the_result() {
+        use Action::*;
+        use Direction::*;
+
+        let gold = play_script(&[Move(East), Grab, Move(Ovest), Exit]);
+        assert_eq!(gold.outcome.label(), "gold");
+        assert!(gold.gold && gold.death_cause.is_none());
+        assert_eq!((gold.steps, gold.score), (4, GOLD_REWARD - 4));
+
+        let no_gold = play_script(&[Exit]);
+        assert_eq!(no_gold.outcome.label(), "no gold");
+        assert_eq!((no_gold.steps, no_gold.score), (1, -1));
+
+        let pit = play_script(&[Move(East), Move(East)]);
+        assert_eq!(pit.outcome.label(), "dead");
+        assert_eq!(pit.death_cause, Some(DeathCause::Pit));
+        assert_eq!(pit.score, -2 - DEATH_COST);
+
+        let wumpus = play_script(&[Move(Sud), Move(Sud)]);
+        assert_eq!(wumpus.death_cause, Some(DeathCause::Wumpus));
+
+        let shot = play_script(&[Shoot(Sud), Exit]);
+        assert_eq!(shot.score, -2 - ARROW_COST);
+
+        let failed = play_script(&[Move(Sud)]);
+        assert!(
+            matches!(failed.outcome, Outcome::Failed(HeroError::NoActionAvailable(pos)) if pos == Position::new(0, 1))
+        );
+        assert_eq!(failed.steps, 1);
+
+        let illegal = play_script(&[Move(North)]);
+        assert!(matches!(
+            illegal.outcome,
+            Outcome::IllegalAction(IllegalAction::IntoWall(_, North))
+        ));
+        for result in [gold, no_gold, pit, wumpus, shot, failed, illegal] {
+            assert_eq!(result.steps, result.history.len());
+            // un agente senza KB non ha contatori né memoria
+            assert!(result.stats.is_none() && result.sat_calls == 0);
+            assert!(result.peak_memory.is_none());
+        }
+    }
+
+    #[test]
+    fn the_hero_games_count_their_solver_calls() {
+        for seed in 0..6 {
+            let result = play(seed);
+            assert!(result.steps > 0, "seed {}", seed);
+            assert_eq!(result.steps, result.history.len());
+            let stats = result.stats.unwrap();
+            assert!(result.sat_calls > 0);
+            assert_eq!(result.sat_calls, stats.solver_calls);
+            assert!(result.peak_memory.unwrap().total() > 0);
+            assert_eq!(result.gold, matches!(result.outcome, Outcome::Gold));
+            // l'eroe non rischia, quindi non muore
+            assert!(result.death_cause.is_none(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn a_death_while_gambling_is_not_a_kb_error() {
+        // le due caselle di frontiera meno rischiose sono entrambe pozzi
+        let mut world = World::from_layout("x . o .\n. . . .\no . . .\n. . g w").unwrap();
+        let mut kb = init_kb(4);
+        kb.use_in_process_solver();
+        let config = HeroConfig {
+            risk_threshold: 0.9,
+            ..Default::default()
+        };
+        let mut hero = Hero::with_rng(kb, 4, config, StdRng::seed_from_u64(3));
+        let result = simulate(&mut world, &mut hero, &mut |_| {}, Screen::hidden(), None);
+        assert_eq!(result.outcome.label(), "dead");
+        assert_eq!(result.death_cause, Some(DeathCause::Pit));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_games_match_the_sequential_ones() {
+        let games = 8;
+        let run = |parallel| {
+            let progress = Progress::new(games, true, |_| {});
+            play_games(games as u64, Some(11), parallel, &new_game, None, &progress)
+        };
+        let sequential = run(false);
+        let parallel = run(true);
+        assert_eq!(sequential.len(), games);
+        assert_eq!(parallel.len(), games);
+        for ((first, first_turns), (second, second_turns)) in sequential.iter().zip(&parallel) {
+            assert_eq!(first.seed, second.seed);
+            assert_eq!(first.outcome.label(), second.outcome.label());
+            assert_eq!(
+                (first.steps, first.score, first.sat_calls),
+                (second.steps, second.score, second.sat_calls)
+            );
+            assert_eq!(first.stats, second.stats);
+            assert_eq!(first_turns.len(), second_turns.len());
+        }
+    }
+
+    // un solver patologico: non risponde mai prima del suo timeout
+    struct Stalling;
+
+    impl Solver for Stalling {
+        fn name(&self) -> &str {
+            "stalling"
+        }
+
+        fn solve(
+            &self,
+            _dimacs: &str,
+            timeout: Option<Duration>,
+            stop: &AtomicBool,
+        ) -> Result<String, SolverError> {
+            let start = Instant::now();
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    return Err(SolverError::Cancelled);
+                }
+                if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                    return Err(SolverError::Timeout);
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    #[test]
+    fn a_batch_of_stalled_games_times_out_and_completes() {
+        let timeout = Duration::from_millis(50);
+        let new_game = |seed: Option<u64>| -> (World, Box<dyn Agent>) {
+            let seed = seed.unwrap();
+            let world = World::new_with_seed(5, 3, seed);
+            let mut kb = init_kb(5);
+            kb.set_solver(Arc::new(Stalling));
+            kb.set_timeout(Some(timeout));
+            let rng = StdRng::seed_from_u64(GameSeeds::new(seed).agent);
+            (
+                world,
+                Box::new(Hero::with_rng(kb, 5, HeroConfig::default(), rng)),
+            )
+        };
+        let start = Instant::now();
+        let progress = Progress::new(4, true, |_| {});
+        let results = play_games(4, Some(0), true, &new_game, Some(timeout), &progress);
+        assert_eq!(results.len(), 4);
+        for (result, _) in &results {
+            assert_eq!(result.outcome.label(), "timed out");
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,973 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum SolverError {
+    SpawnFailed(std::io::Error),
+    // stato di uscita diverso da quelli previsti dal solver (per picosat 10 = SAT, 20 = UNSAT)
+    NonZeroExit(Option<i32>),
+    UnparseableOutput(String),
+    Timeout,
+    // il solver è stato fermato dall'esterno (es. ha vinto l'altro solver del portfolio)
+    Cancelled,
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::SpawnFailed(e) => write!(f, "could not run the solver: {}", e),
+            SolverError::NonZeroExit(Some(code)) => {
+                write!(f, "the solver exited with status {}", code)
+            }
+            SolverError::NonZeroExit(None) => write!(f, "the solver was killed by a signal"),
+            SolverError::UnparseableOutput(line) => {
+                write!(f, "unparseable solver output: {:?}", line)
+            }
+            SolverError::Timeout => write!(f, "the solver timed out"),
+            SolverError::Cancelled => write!(f, "the solver was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+}
+
+// impostato da simulation::interrupt: ferma i solver lanciati dalle KB come il loro stop
+pub(crate) static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// A SAT backend. It receives a DIMACS CNF and answers in the SAT competition output
+/// format ("s SATISFIABLE" / "s UNSATISFIABLE" followed by the "v" model lines).
+/// When stop becomes true the backend must give up as soon as possible with SolverError::Cancelled.
+pub trait Solver: Send + Sync {
+    fn name(&self) -> &str;
+    /// True if the backend understands the "x" lines of the extended DIMACS format.
+    fn supports_xor(&self) -> bool {
+        false
+    }
+    fn solve(
+        &self,
+        dimacs: &str,
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError>;
+    /// Like solve, with the assumption literals (DIMACS numbers) fixed for this call only.
+    /// By default they are appended to the CNF as unit clauses.
+    fn solve_with_assumptions(
+        &self,
+        dimacs: &str,
+        assumptions: &[i32],
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        if assumptions.is_empty() {
+            return self.solve(dimacs, timeout, stop);
+        }
+        self.solve(&with_unit_clauses(dimacs, assumptions), timeout, stop)
+    }
+}
+
+// il DIMACS con le assunzioni aggiunte come clausole unitarie, con l'intestazione aggiornata
+fn with_unit_clauses(dimacs: &str, assumptions: &[i32]) -> String {
+    let assumed = assumptions
+        .iter()
+        .map(|lit| lit.unsigned_abs() as usize)
+        .max()
+        .unwrap_or(0);
+    let mut result = String::with_capacity(dimacs.len() + 8 * assumptions.len());
+    for line in dimacs.lines() {
+        let header = line.strip_prefix("p cnf").and_then(|header| {
+            let mut numbers = header.split_whitespace().map(str::parse::<usize>);
+            match (numbers.next(), numbers.next()) {
+                (Some(Ok(vars)), Some(Ok(clauses))) => Some((vars, clauses)),
+                _ => None,
+            }
+        });
+        match header {
+            Some((vars, clauses)) => result.push_str(&format!(
+                "p cnf {} {}\n",
+                vars.max(assumed),
+                clauses + assumptions.len()
+            )),
+            None => {
+                result.push_str(line);
+                result.push('\n');
+            }
+        }
+    }
+    for lit in assumptions {
+        result.push_str(&format!("{} 0\n", lit));
+    }
+    result
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct PicoSat;
+
+impl Solver for PicoSat {
+    fn name(&self) -> &str {
+        "picosat"
+    }
+
+    fn solve(
+        &self,
+        dimacs: &str,
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        run_solver_process(Command::new("picosat"), dimacs, timeout, stop)
+    }
+}
+
+/// In-process DPLL with two watched literals, slower than picosat on big formulas but it
+/// needs no subprocess, so it is the backend of the wasm32 builds. XOR lines are not
+/// supported.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Dpll;
+
+impl Solver for Dpll {
+    fn name(&self) -> &str {
+        "dpll"
+    }
+
+    fn solve(
+        &self,
+        dimacs: &str,
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        self.solve_with_assumptions(dimacs, &[], timeout, stop)
+    }
+
+    // le assunzioni sono assegnate prima della ricerca, senza diventare clausole
+    fn solve_with_assumptions(
+        &self,
+        dimacs: &str,
+        assumptions: &[i32],
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        let (vars, clauses) = parse_dimacs(dimacs)?;
+        let vars = assumptions
+            .iter()
+            .map(|lit| lit.unsigned_abs() as usize)
+            .fold(vars, usize::max);
+        // Instant non esiste su wasm32: senza timeout non viene mai letto l'orologio
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let interrupted = || {
+            if stop.load(Ordering::Relaxed) {
+                Some(SolverError::Cancelled)
+            } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                Some(SolverError::Timeout)
+            } else {
+                None
+            }
+        };
+        let mut state = DpllState::new(vars, clauses);
+        state.assumptions = assumptions.to_vec();
+        let Some(model) = state.solve(interrupted)? else {
+            return Ok("s UNSATISFIABLE\n".to_string());
+        };
+        let mut output = String::from("s SATISFIABLE\nv");
+        for (var, value) in model.iter().enumerate().skip(1) {
+            output.push_str(&format!(
+                " {}",
+                if *value { var as i64 } else { -(var as i64) }
+            ));
+        }
+        output.push_str(" 0\n");
+        Ok(output)
+    }
+}
+
+// numero di variabili e clausole di un DIMACS CNF
+fn parse_dimacs(dimacs: &str) -> Result<(usize, Vec<Vec<i32>>), SolverError> {
+    let mut vars = 0;
+    let mut clauses = vec![];
+    let mut clause = vec![];
+    for line in dimacs.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("p cnf") {
+            vars = header
+                .split_whitespace()
+                .next()
+                .and_then(|vars| vars.parse().ok())
+                .ok_or_else(|| SolverError::UnparseableOutput(line.to_string()))?;
+            continue;
+        }
+        for lit in line.split_whitespace() {
+            let lit: i32 = lit
+                .parse()
+                .map_err(|_| SolverError::UnparseableOutput(line.to_string()))?;
+            if lit == 0 {
+                clauses.push(std::mem::take(&mut clause));
+            } else {
+                vars = vars.max(lit.unsigned_abs() as usize);
+                clause.push(lit);
+            }
+        }
+    }
+    if !clause.is_empty() {
+        clauses.push(clause);
+    }
+    Ok((vars, clauses))
+}
+
+// indice di un letterale nelle liste dei watch: 2 * variabile, +1 se negato
+fn watch_index(lit: i32) -> usize {
+    2 * lit.unsigned_abs() as usize + (lit < 0) as usize
+}
+
+struct DpllState {
+    clauses: Vec<Vec<i32>>,
+    // per ogni letterale le clausole che lo osservano (nelle prime due posizioni)
+    watches: Vec<Vec<usize>>,
+    // 0 non assegnata, 1 vera, -1 falsa
+    values: Vec<i8>,
+    trail: Vec<i32>,
+    // letterali del trail ancora da propagare
+    propagated: usize,
+    // decisioni aperte: posizione nel trail, letterale scelto, se è già stato negato
+    decisions: Vec<(usize, i32, bool)>,
+    // letterali assunti veri per questa ricerca
+    assumptions: Vec<i32>,
+    // clausole unitarie e vuote trovate all'inizio
+    units: Vec<i32>,
+    empty: bool,
+}
+
+impl DpllState {
+    fn new(vars: usize, clauses: Vec<Vec<i32>>) -> Self {
+        let mut state = Self {
+            clauses: vec![],
+            watches: vec![vec![]; 2 * vars + 2],
+            values: vec![0; vars + 1],
+            trail: vec![],
+            propagated: 0,
+            decisions: vec![],
+            assumptions: vec![],
+            units: vec![],
+            empty: false,
+        };
+        for mut clause in clauses {
+            clause.sort_unstable();
+            clause.dedup();
+            // una clausola con x e -x è sempre vera
+            if clause.windows(2).any(|pair| pair[0] == -pair[1]) {
+                continue;
+            }
+            match clause.len() {
+                0 => state.empty = true,
+                1 => state.units.push(clause[0]),
+                _ => {
+                    let index = state.clauses.len();
+                    state.watches[watch_index(clause[0])].push(index);
+                    state.watches[watch_index(clause[1])].push(index);
+                    state.clauses.push(clause);
+                }
+            }
+        }
+        state
+    }
+
+    fn value(&self, lit: i32) -> i8 {
+        let value = self.values[lit.unsigned_abs() as usize];
+        if lit < 0 { -value } else { value }
+    }
+
+    fn assign(&mut self, lit: i32) {
+        self.values[lit.unsigned_abs() as usize] = if lit < 0 { -1 } else { 1 };
+        self.trail.push(lit);
+    }
+
+    // propaga le clausole unitarie, false se trova un conflitto
+    fn propagate(&mut self) -> bool {
+        while self.propagated < self.trail.len() {
+            let falsified = -self.trail[self.propagated];
+            self.propagated += 1;
+            let watching = std::mem::take(&mut self.watches[watch_index(falsified)]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = false;
+            for (i, &index) in watching.iter().enumerate() {
+                if conflict {
+                    kept.extend_from_slice(&watching[i..]);
+                    break;
+                }
+                let clause = &mut self.clauses[index];
+                if clause[0] == falsified {
+                    clause.swap(0, 1);
+                }
+                let other = clause[0];
+                let replacement = (2..clause.len()).find(|&k| {
+                    let lit = clause[k];
+                    let value = self.values[lit.unsigned_abs() as usize];
+                    (if lit < 0 { -value } else { value }) != -1
+                });
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    let watched = clause[1];
+                    self.watches[watch_index(watched)].push(index);
+                    continue;
+                }
+                kept.push(index);
+                match self.value(other) {
+                    1 => {}
+                    0 => self.assign(other),
+                    _ => conflict = true,
+                }
+            }
+            self.watches[watch_index(falsified)] = kept;
+            if conflict {
+                return false;
+            }
+        }
+        true
+    }
+
+    // torna all'ultima decisione non ancora negata e la nega, false se non ce ne sono
+    fn backtrack(&mut self) -> bool {
+        while let Some((position, lit, flipped)) = self.decisions.pop() {
+            for undone in self.trail.drain(position..) {
+                self.values[undone.unsigned_abs() as usize] = 0;
+            }
+            self.propagated = position;
+            if !flipped {
+                self.decisions.push((position, -lit, true));
+                self.assign(-lit);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn solve(
+        mut self,
+        interrupted: impl Fn() -> Option<SolverError>,
+    ) -> Result<Option<Vec<bool>>, SolverError> {
+        if self.empty {
+            return Ok(None);
+        }
+        // assunzioni e clausole unitarie fissano i loro letterali prima di ogni decisione,
+        // un'assunzione in conflitto rende la formula insoddisfacibile
+        let fixed: Vec<i32> = std::mem::take(&mut self.assumptions)
+            .into_iter()
+            .chain(std::mem::take(&mut self.units))
+            .collect();
+        for lit in fixed {
+            match self.value(lit) {
+                0 => self.assign(lit),
+                -1 => return Ok(None),
+                _ => {}
+            }
+        }
+        let mut next_var = 1;
+        loop {
+            if !self.propagate() {
+                if !self.backtrack() {
+                    return Ok(None);
+                }
+                if let Some(error) = interrupted() {
+                    return Err(error);
+                }
+                // dopo un backtrack possono esserci variabili libere prima di next_var
+                next_var = 1;
+                continue;
+            }
+            while next_var < self.values.len() && self.values[next_var] != 0 {
+                next_var += 1;
+            }
+            if next_var == self.values.len() {
+                return Ok(Some(self.values.iter().map(|value| *value == 1).collect()));
+            }
+            self.decisions
+                .push((self.trail.len(), -(next_var as i32), false));
+            self.assign(-(next_var as i32));
+        }
+    }
+}
+
+/// CryptoMiniSat 5, the only backend accepting native XOR constraints.
+#[cfg(feature = "cryptominisat")]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct CryptoMiniSat;
+
+#[cfg(feature = "cryptominisat")]
+impl Solver for CryptoMiniSat {
+    fn name(&self) -> &str {
+        "cryptominisat5"
+    }
+
+    fn supports_xor(&self) -> bool {
+        true
+    }
+
+    fn solve(
+        &self,
+        dimacs: &str,
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        let mut command = Command::new("cryptominisat5");
+        command.args(["--verb", "0"]);
+        run_solver_process(command, dimacs, timeout, stop)
+    }
+}
+
+/// Runs the same CNF on two backends in parallel and returns the first definitive answer,
+/// the other backend is cancelled and joined before returning.
+pub struct Portfolio {
+    first: Arc<dyn Solver>,
+    second: Arc<dyn Solver>,
+}
+
+impl Portfolio {
+    pub fn new(first: Arc<dyn Solver>, second: Arc<dyn Solver>) -> Self {
+        Self {
+            first: first,
+            second: second,
+        }
+    }
+}
+
+impl Solver for Portfolio {
+    fn name(&self) -> &str {
+        "portfolio"
+    }
+
+    // le righe XOR vengono scritte solo se entrambi i backend le capiscono
+    fn supports_xor(&self) -> bool {
+        self.first.supports_xor() && self.second.supports_xor()
+    }
+
+    fn solve(
+        &self,
+        dimacs: &str,
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        self.solve_with_assumptions(dimacs, &[], timeout, stop)
+    }
+
+    // ogni backend riceve le assunzioni nel modo che preferisce
+    fn solve_with_assumptions(
+        &self,
+        dimacs: &str,
+        assumptions: &[i32],
+        timeout: Option<Duration>,
+        stop: &AtomicBool,
+    ) -> Result<String, SolverError> {
+        let inner_stop = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for solver in [&self.first, &self.second] {
+                let sender = sender.clone();
+                let inner_stop = &inner_stop;
+                scope.spawn(move || {
+                    let _ = sender.send(solver.solve_with_assumptions(
+                        dimacs,
+                        assumptions,
+                        timeout,
+                        inner_stop,
+                    ));
+                });
+            }
+            drop(sender);
+
+            let mut first_error = None;
+            let mut answered = 0;
+            while answered < 2 {
+                match receiver.recv_timeout(Duration::from_millis(1)) {
+                    Ok(Ok(output)) if picosat_is_sat(output.clone()).is_ok() => {
+                        // risposta definitiva: l'altro solver viene fermato e atteso dallo scope
+                        inner_stop.store(true, Ordering::Relaxed);
+                        return Ok(output);
+                    }
+                    Ok(Ok(output)) => {
+                        answered += 1;
+                        first_error.get_or_insert(SolverError::UnparseableOutput(
+                            output.lines().next().unwrap_or_default().to_string(),
+                        ));
+                    }
+                    Ok(Err(e)) => {
+                        answered += 1;
+                        first_error.get_or_insert(e);
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        if stop.load(Ordering::Relaxed) {
+                            inner_stop.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            Err(first_error.unwrap_or(SolverError::Cancelled))
+        })
+    }
+}
+
+/// The SAT backends that can be chosen by name (WUMPUS_SOLVER in the binary).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    PicoSat,
+    Dpll,
+    // picosat e DPLL in parallelo, vince la prima risposta
+    Portfolio,
+    #[cfg(feature = "cryptominisat")]
+    CryptoMiniSat,
+}
+
+impl Backend {
+    pub fn solver(self) -> Arc<dyn Solver> {
+        match self {
+            Backend::PicoSat => Arc::new(PicoSat),
+            Backend::Dpll => Arc::new(Dpll),
+            Backend::Portfolio => Arc::new(Portfolio::new(Arc::new(PicoSat), Arc::new(Dpll))),
+            #[cfg(feature = "cryptominisat")]
+            Backend::CryptoMiniSat => Arc::new(CryptoMiniSat),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "picosat" => Ok(Backend::PicoSat),
+            "dpll" => Ok(Backend::Dpll),
+            "portfolio" => Ok(Backend::Portfolio),
+            #[cfg(feature = "cryptominisat")]
+            "cryptominisat" | "cryptominisat5" => Ok(Backend::CryptoMiniSat),
+            other => Err(format!("unknown solver {}", other)),
+        }
+    }
+}
+
+/// Runs a subprocess solver feeding the DIMACS on stdin, the child is always reaped.
+pub fn run_solver_process(
+    mut command: Command,
+    dimacs: &str,
+    timeout: Option<Duration>,
+    stop: &AtomicBool,
+) -> Result<String, SolverError> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(SolverError::SpawnFailed)?;
+
+    // l'output viene letto da un altro thread, così il solver non si blocca su una pipe piena
+    let mut stdout = child.stdout.take().expect("Failed to open stdout");
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let written = {
+        let mut stdin = child.stdin.take().expect("Failed to open stdin");
+        stdin.write_all(dimacs.as_bytes())
+    };
+    if let Err(e) = written {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(SolverError::SpawnFailed(e));
+    }
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    // attesa attiva con backoff: le query veloci non pagano un millisecondo di latenza
+    let mut pause = Duration::from_micros(50);
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(SolverError::SpawnFailed)? {
+            break status;
+        }
+        let error = if stop.load(Ordering::Relaxed) {
+            Some(SolverError::Cancelled)
+        } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(SolverError::Timeout)
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error);
+        }
+        thread::sleep(pause);
+        pause = (pause * 2).min(Duration::from_millis(1));
+    };
+
+    let output = reader
+        .join()
+        .expect("the solver output reader panicked")
+        .map_err(SolverError::SpawnFailed)?;
+
+    match status.code() {
+        Some(0) | Some(10) | Some(20) => Ok(output),
+        code => Err(SolverError::NonZeroExit(code)),
+    }
+}
+
+pub fn picosat_is_sat(output: String) -> Result<bool, SolverError> {
+    // la prima riga deve essere esattamente la risposta del solver, qualsiasi altra cosa
+    // (output vuoto, banner, crash) non può essere scambiata per UNSAT
+    match output.lines().next().map(str::trim) {
+        Some("s SATISFIABLE") => Ok(true),
+        Some("s UNSATISFIABLE") => Ok(false),
+        Some(line) => Err(SolverError::UnparseableOutput(line.to_string())),
+        None => Err(SolverError::UnparseableOutput(String::new())),
+    }
+}
+
+/// Parses the PicoSAT output file and returns a Vec<Option<bool>> where
+/// index 0 is unused, and each index i corresponds to variable i.
+/// An UNSAT answer is returned as an empty vector.
+pub fn parse_picosat_model(output: String, nvars: usize) -> Result<Vec<Option<bool>>, SolverError> {
+    if !picosat_is_sat(output.clone())? {
+        return Ok(vec![]); // UNSAT
+    }
+
+    // Prepare result vector: index 0 is unused
+    let mut result = vec![None; nvars + 1];
+
+    for line in output.lines().skip(1) {
+        if !line.starts_with("v ") {
+            continue;
+        }
+        for lit in line[2..].split_whitespace() {
+            let lit = lit
+                .parse::<i32>()
+                .map_err(|_| SolverError::UnparseableOutput(line.to_string()))?;
+            if lit == 0 {
+                continue;
+            }
+            let idx = lit.unsigned_abs() as usize;
+            if idx <= nvars {
+                result[idx] = Some(lit > 0);
+            }
+        }
+    }
+    Ok(result)
+}
+
+// i backend su cui girano i test: il DPLL sempre, picosat solo se è installato
+#[cfg(test)]
+pub(crate) fn test_backends() -> Vec<Arc<dyn Solver>> {
+    let mut backends: Vec<Arc<dyn Solver>> = vec![Arc::new(Dpll)];
+    if installed("picosat") {
+        backends.push(Arc::new(PicoSat));
+    }
+    backends
+}
+
+#[cfg(test)]
+pub(crate) fn installed(program: &str) -> bool {
+    Command::new(program)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAT: &str = "p cnf 3 3\n1 2 0\n-1 3 0\n-2 -3 0\n";
+    const UNSAT: &str = "p cnf 2 4\n1 2 0\n-1 2 0\n1 -2 0\n-1 -2 0\n";
+
+    // risponde UNSAT, sbagliando, solo dopo 30 secondi: se vince lui il test se ne accorge
+    struct SlowStub {
+        reaped: Arc<AtomicBool>,
+    }
+
+    impl Solver for SlowStub {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn solve(
+            &self,
+            _dimacs: &str,
+            _timeout: Option<Duration>,
+            stop: &AtomicBool,
+        ) -> Result<String, SolverError> {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_secs(30) {
+                if stop.load(Ordering::Relaxed) {
+                    self.reaped.store(true, Ordering::Relaxed);
+                    return Err(SolverError::Cancelled);
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            Ok("s UNSATISFIABLE\n".to_string())
+        }
+    }
+
+    // un processo che non risponde mai
+    struct SleepProcess;
+
+    impl Solver for SleepProcess {
+        fn name(&self) -> &str {
+            "sleep"
+        }
+
+        fn solve(
+            &self,
+            dimacs: &str,
+            timeout: Option<Duration>,
+            stop: &AtomicBool,
+        ) -> Result<String, SolverError> {
+            let mut command = Command::new("sleep");
+            command.arg("30");
+            run_solver_process(command, dimacs, timeout, stop)
+        }
+    }
+
+    fn answer(solver: &dyn Solver, dimacs: &str) -> bool {
+        let output = solver
+            .solve(dimacs, None, &AtomicBool::new(false))
+            .expect("the solver answers");
+        picosat_is_sat(output).expect("the output is well formed")
+    }
+
+    #[test]
+    fn portfolio_uses_the_fast_answer_and_cancels_the_slow_backend() {
+        for (dimacs, expected) in [(SAT, true), (UNSAT, false)] {
+            let reaped = Arc::new(AtomicBool::new(false));
+            let slow = SlowStub {
+                reaped: reaped.clone(),
+            };
+            let portfolio = Portfolio::new(Arc::new(slow), Arc::new(Dpll));
+            let start = Instant::now();
+            assert_eq!(answer(&portfolio, dimacs), expected);
+            assert!(start.elapsed() < Duration::from_secs(10));
+            // lo scope aspetta entrambi i thread, il lento ha già visto lo stop
+            assert!(reaped.load(Ordering::Relaxed));
+        }
+    }
+
+    #[test]
+    fn portfolio_answer_does_not_depend_on_the_order() {
+        for dimacs in [SAT, UNSAT] {
+            let first = Portfolio::new(
+                Arc::new(Dpll),
+                Arc::new(SlowStub {
+                    reaped: Arc::default(),
+                }),
+            );
+            let second = Portfolio::new(
+                Arc::new(SlowStub {
+                    reaped: Arc::default(),
+                }),
+                Arc::new(Dpll),
+            );
+            assert_eq!(answer(&first, dimacs), answer(&Dpll, dimacs));
+            assert_eq!(answer(&second, dimacs), answer(&Dpll, dimacs));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn portfolio_kills_the_losing_process() {
+        let portfolio = Portfolio::new(Arc::new(SleepProcess), Arc::new(Dpll));
+        let start = Instant::now();
+        assert!(answer(&portfolio, SAT));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_timeout_kills_the_child() {
+        let start = Instant::now();
+        let result = SleepProcess.solve(
+            SAT,
+            Some(Duration::from_millis(50)),
+            &AtomicBool::new(false),
+        );
+        assert!(matches!(result, Err(SolverError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn backends_are_parsed_by_name() {
+        assert_eq!("dpll".parse(), Ok(Backend::Dpll));
+        assert_eq!("PicoSAT".parse(), Ok(Backend::PicoSat));
+        assert_eq!("portfolio".parse(), Ok(Backend::Portfolio));
+        assert!("minisat".parse::<Backend>().is_err());
+        assert_eq!(Backend::Portfolio.solver().name(), "portfolio");
+    }
+
+    // risponde sempre lo stesso output, ben formato o no
+    struct Canned(&'static str);
+
+    impl Solver for Canned {
+        fn name(&self) -> &str {
+            "canned"
+        }
+
+        fn solve(
+            &self,
+            _dimacs: &str,
+            _timeout: Option<Duration>,
+            _stop: &AtomicBool,
+        ) -> Result<String, SolverError> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    const MALFORMED: [&str; 4] = [
+        "",
+        "PicoSAT 965, (C) 2006-2015 Armin Biere\ns SATISFIABLE\n",
+        "s UNKNOWN\n",
+        "Segmentation fault\n",
+    ];
+
+    #[test]
+    fn malformed_outputs_are_errors_and_not_unsat() {
+        for output in MALFORMED {
+            assert!(
+                matches!(
+                    picosat_is_sat(output.to_string()),
+                    Err(SolverError::UnparseableOutput(_))
+                ),
+                "{:?}",
+                output
+            );
+            assert!(parse_picosat_model(output.to_string(), 3).is_err());
+        }
+        assert!(matches!(
+            picosat_is_sat("s UNSATISFIABLE\n".to_string()),
+            Ok(false)
+        ));
+        assert!(matches!(
+            parse_picosat_model("s SATISFIABLE\nv 1 -x 0\n".to_string(), 2),
+            Err(SolverError::UnparseableOutput(line)) if line == "v 1 -x 0"
+        ));
+        assert_eq!(
+            parse_picosat_model("s SATISFIABLE\nv 1 -2 0\n".to_string(), 2).unwrap(),
+            vec![None, Some(true), Some(false)]
+        );
+    }
+
+    #[test]
+    fn ask_reports_a_broken_solver_instead_of_entailing() {
+        use crate::{
+            encoder::EncoderSAT,
+            kb::{KnowledgeBase, init_kb},
+            world::Position,
+        };
+
+        for output in MALFORMED {
+            let mut kb = init_kb(3);
+            kb.set_solver(Arc::new(Canned(output)));
+            let query = EncoderSAT::create_safe_formula(&Position::new(2, 2));
+            assert!(kb.ask(&query).is_err(), "{:?}", output);
+            assert!(kb.consistency().is_err(), "{:?}", output);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_failures_are_typed() {
+        let stop = AtomicBool::new(false);
+        let missing = Command::new("no-such-sat-solver");
+        assert!(matches!(
+            run_solver_process(missing, SAT, None, &stop),
+            Err(SolverError::SpawnFailed(_))
+        ));
+        let mut crashing = Command::new("sh");
+        crashing.args(["-c", "cat > /dev/null; exit 3"]);
+        assert!(matches!(
+            run_solver_process(crashing, SAT, None, &stop),
+            Err(SolverError::NonZeroExit(Some(3)))
+        ));
+    }
+
+    // passa al DPLL il DIMACS ricevuto, ricordandolo: usa le assunzioni di default del trait
+    #[derive(Default)]
+    struct Recording(std::sync::Mutex<Vec<String>>);
+
+    impl Solver for Recording {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn solve(
+            &self,
+            dimacs: &str,
+            timeout: Option<Duration>,
+            stop: &AtomicBool,
+        ) -> Result<String, SolverError> {
+            self.0.lock().unwrap().push(dimacs.to_string());
+            Dpll.solve(dimacs, timeout, stop)
+        }
+    }
+
+    #[test]
+    fn assumptions_are_native_in_dpll_and_unit_clauses_by_default() {
+        let stop = AtomicBool::new(false);
+        let recording = Recording::default();
+        // 4 non compare nella formula
+        for (assumptions, expected) in [
+            (vec![], true),
+            (vec![1], true),
+            (vec![1, 2], false),
+            (vec![-1, -2], false),
+            (vec![2, -3, 4], true),
+            (vec![1, -1], false),
+        ] {
+            let native = Dpll
+                .solve_with_assumptions(SAT, &assumptions, None, &stop)
+                .unwrap();
+            let units = recording
+                .solve_with_assumptions(SAT, &assumptions, None, &stop)
+                .unwrap();
+            assert_eq!(
+                picosat_is_sat(native.clone()).unwrap(),
+                expected,
+                "{:?}",
+                assumptions
+            );
+            assert_eq!(
+                picosat_is_sat(units).unwrap(),
+                expected,
+                "{:?}",
+                assumptions
+            );
+            // il modello rispetta le assunzioni
+            if expected {
+                let model = parse_picosat_model(native, 4).unwrap();
+                for lit in &assumptions {
+                    assert_eq!(model[lit.unsigned_abs() as usize], Some(*lit > 0));
+                }
+            }
+        }
+        // senza assunzioni il DIMACS passa com'è, altrimenti con le clausole unitarie in fondo
+        let seen = recording.0.lock().unwrap();
+        assert_eq!(seen[0], SAT);
+        assert_eq!(
+            seen[4],
+            "p cnf 4 6\n1 2 0\n-1 3 0\n-2 -3 0\n2 0\n-3 0\n4 0\n"
+        );
+    }
+}
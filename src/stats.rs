@@ -0,0 +1,498 @@
+use std::fmt;
+
+use crate::{
+    simulation::{Outcome, SimulationResult},
+    world::DeathCause,
+};
+
+/// z of the two-sided 95% confidence level.
+pub const Z_95: f64 = 1.959964;
+
+/// Wilson score interval of a success rate, better than the normal approximation when the
+/// rate is close to 0 or 1 or the trials are few.
+pub fn wilson_interval(successes: usize, trials: usize, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+/// Two-sided sign test: probability of a split at least as uneven as `a` against `b`
+/// if both agents were equally likely to win the games where they disagree.
+pub fn sign_test(a: usize, b: usize) -> f64 {
+    let n = a + b;
+    if n == 0 {
+        return 1.0;
+    }
+    // somma delle code della binomiale(n, 1/2) in logaritmi, 2^n non entra in un f64
+    let mut ln_choose = 0.0;
+    let mut tail = 0.0;
+    for i in 0..=a.min(b) {
+        if i > 0 {
+            ln_choose += ((n - i + 1) as f64).ln() - (i as f64).ln();
+        }
+        tail += (ln_choose - n as f64 * 2f64.ln()).exp();
+    }
+    (2.0 * tail).min(1.0)
+}
+
+/// Mean, median and sample standard deviation of a set of values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let n = values.len();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+        // deviazione standard campionaria, con un solo valore è 0
+        let stddev = if n > 1 {
+            let squares: f64 = values.iter().map(|v| (v - mean) * (v - mean)).sum();
+            (squares / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            count: n,
+            mean: mean,
+            median: median,
+            stddev: stddev,
+        }
+    }
+}
+
+/// Steps and score of the games that ended in the same way.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OutcomeSummary {
+    pub outcome: &'static str,
+    pub steps: Summary,
+    pub score: Summary,
+}
+
+/// How the games of a batch ended, the counts add up to the games.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Breakdown {
+    pub gold: usize,
+    pub no_gold: usize,
+    // uscite senza oro su mappe in cui l'oro non era raggiungibile
+    pub no_gold_unsolvable: usize,
+    pub pit: usize,
+    pub wumpus: usize,
+    // morti senza una causa registrata: un SimulationResult costruito a mano o letto da file
+    pub unknown_death: usize,
+    pub timed_out: usize,
+    pub failed: usize,
+}
+
+impl Breakdown {
+    pub fn new(results: &[SimulationResult]) -> Self {
+        let mut breakdown = Self::default();
+        for result in results {
+            match result.outcome {
+                Outcome::Gold => breakdown.gold += 1,
+                Outcome::NoGold => {
+                    breakdown.no_gold += 1;
+                    if !result.solvable {
+                        breakdown.no_gold_unsolvable += 1;
+                    }
+                }
+                Outcome::Dead | Outcome::DeadOnSafeCell => match result.death_cause {
+                    Some(DeathCause::Pit) => breakdown.pit += 1,
+                    Some(DeathCause::Wumpus) => breakdown.wumpus += 1,
+                    None => breakdown.unknown_death += 1,
+                },
+                Outcome::TimedOut => breakdown.timed_out += 1,
+                Outcome::Failed(_) | Outcome::IllegalAction(_) => breakdown.failed += 1,
+                // play_games scarta le partite interrotte
+                Outcome::Interrupted => {}
+            }
+        }
+        breakdown
+    }
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let games = self.gold
+            + self.no_gold
+            + self.pit
+            + self.wumpus
+            + self.unknown_death
+            + self.timed_out
+            + self.failed;
+        let mut rows = vec![
+            ("exited with gold", self.gold),
+            ("exited without gold", self.no_gold),
+            ("  unsolvable map", self.no_gold_unsolvable),
+            ("died in a pit", self.pit),
+            ("killed by the wumpus", self.wumpus),
+        ];
+        // le partite simulate hanno sempre la causa della morte, la riga compare solo se serve
+        if self.unknown_death > 0 {
+            rows.push(("died, cause unknown", self.unknown_death));
+        }
+        rows.extend([
+            ("timed out", self.timed_out),
+            ("planner errors", self.failed),
+        ]);
+        for (label, count) in rows {
+            writeln!(
+                f,
+                "{:<22} {:>6} {:>6.1}%",
+                label,
+                count,
+                100.0 * count as f64 / games.max(1) as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of a batch of games.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BatchSummary {
+    pub games: usize,
+    // partite finite uscendo con l'oro
+    pub successes: usize,
+    pub success_rate: f64,
+    pub success_interval: (f64, f64),
+    pub steps: Summary,
+    pub score: Summary,
+    pub by_outcome: Vec<OutcomeSummary>,
+    pub breakdown: Breakdown,
+    // seed ed esito delle partite finite male (morte, errore, timeout), per riprodurle
+    pub anomalies: Vec<(u64, &'static str)>,
+}
+
+impl BatchSummary {
+    pub fn new(results: &[SimulationResult]) -> Self {
+        let steps = |results: &[&SimulationResult]| {
+            Summary::of(&results.iter().map(|r| r.steps as f64).collect::<Vec<_>>())
+        };
+        let score = |results: &[&SimulationResult]| {
+            Summary::of(&results.iter().map(|r| r.score as f64).collect::<Vec<_>>())
+        };
+        let all: Vec<&SimulationResult> = results.iter().collect();
+        let successes = results.iter().filter(|r| r.gold).count();
+
+        let mut labels: Vec<&'static str> = vec![];
+        for result in results {
+            if !labels.contains(&result.outcome.label()) {
+                labels.push(result.outcome.label());
+            }
+        }
+        let by_outcome = labels
+            .into_iter()
+            .map(|label| {
+                let group: Vec<&SimulationResult> = results
+                    .iter()
+                    .filter(|r| r.outcome.label() == label)
+                    .collect();
+                OutcomeSummary {
+                    outcome: label,
+                    steps: steps(&group),
+                    score: score(&group),
+                }
+            })
+            .collect();
+
+        Self {
+            games: results.len(),
+            successes: successes,
+            success_rate: successes as f64 / results.len().max(1) as f64,
+            success_interval: wilson_interval(successes, results.len(), Z_95),
+            steps: steps(&all),
+            score: score(&all),
+            by_outcome: by_outcome,
+            breakdown: Breakdown::new(results),
+            anomalies: results
+                .iter()
+                .filter(|r| !matches!(r.outcome, Outcome::Gold | Outcome::NoGold))
+                .map(|r| (r.seed, r.outcome.label()))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "success rate: {:.3} ({} of {}), 95% CI [{:.3}, {:.3}]",
+            self.success_rate,
+            self.successes,
+            self.games,
+            self.success_interval.0,
+            self.success_interval.1
+        )?;
+        writeln!(
+            f,
+            "{:<10} {:>6} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "outcome", "games", "steps", "median", "stddev", "score", "median", "stddev"
+        )?;
+        let row = |f: &mut fmt::Formatter<'_>, label: &str, steps: &Summary, score: &Summary| {
+            writeln!(
+                f,
+                "{:<10} {:>6} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+                label,
+                steps.count,
+                steps.mean,
+                steps.median,
+                steps.stddev,
+                score.mean,
+                score.median,
+                score.stddev
+            )
+        };
+        for group in &self.by_outcome {
+            row(f, group.outcome, &group.steps, &group.score)?;
+        }
+        row(f, "all", &self.steps, &self.score)?;
+        write!(f, "{}", self.breakdown)?;
+        if !self.anomalies.is_empty() {
+            let seeds: Vec<String> = self
+                .anomalies
+                .iter()
+                .map(|(seed, outcome)| format!("{} ({})", seed, outcome))
+                .collect();
+            writeln!(
+                f,
+                "seeds to rerun with `wumpus replay <seed>`: {}",
+                seeds.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Two agents on the same seeds: how often each one found the gold where the other did not.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PairedComparison {
+    pub games: usize,
+    pub both_won: usize,
+    pub only_a: usize,
+    pub only_b: usize,
+    pub both_lost: usize,
+    // p-value del sign test sulle partite discordanti
+    pub p_value: f64,
+    // seed delle partite discordanti, per rigiocarle
+    pub disagreements: Vec<u64>,
+}
+
+impl PairedComparison {
+    /// `a` and `b` are the results of the same games, in the same order.
+    pub fn new(a: &[SimulationResult], b: &[SimulationResult]) -> Self {
+        assert_eq!(a.len(), b.len(), "the agents must play the same games");
+        let mut comparison = Self {
+            games: a.len(),
+            both_won: 0,
+            only_a: 0,
+            only_b: 0,
+            both_lost: 0,
+            p_value: 1.0,
+            disagreements: vec![],
+        };
+        for (a, b) in a.iter().zip(b) {
+            match (a.gold, b.gold) {
+                (true, true) => comparison.both_won += 1,
+                (true, false) => comparison.only_a += 1,
+                (false, true) => comparison.only_b += 1,
+                (false, false) => comparison.both_lost += 1,
+            }
+            if a.gold != b.gold {
+                comparison.disagreements.push(a.seed);
+            }
+        }
+        comparison.p_value = sign_test(comparison.only_a, comparison.only_b);
+        comparison
+    }
+}
+
+impl fmt::Display for PairedComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} games: both won {}, only A {}, only B {}, both lost {}",
+            self.games, self.both_won, self.only_a, self.only_b, self.both_lost
+        )?;
+        let verdict = if self.p_value < 0.05 {
+            "significant at 5%"
+        } else {
+            "not significant at 5%"
+        };
+        write!(f, "sign test p-value {:.4} ({})", self.p_value, verdict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        hero::HeroError,
+        world::{IllegalAction, Position},
+    };
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{:?} instead of {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn wilson_interval_matches_the_tables() {
+        assert_close(wilson_interval(8, 10, Z_95), (0.4902, 0.9433));
+        assert_close(wilson_interval(50, 100, Z_95), (0.4038, 0.5962));
+        assert_close(wilson_interval(1, 3, Z_95), (0.0615, 0.7923));
+        // agli estremi l'intervallo resta dentro [0, 1] e non si riduce a un punto
+        assert_close(wilson_interval(0, 10, Z_95), (0.0, 0.2775));
+        assert_close(wilson_interval(10, 10, Z_95), (0.7225, 1.0));
+        assert_eq!(wilson_interval(0, 0, Z_95), (0.0, 1.0));
+    }
+
+    #[test]
+    fn summary_of_a_few_values() {
+        let summary = Summary::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(summary.count, 8);
+        assert_eq!(summary.mean, 5.0);
+        assert_eq!(summary.median, 4.5);
+        assert!((summary.stddev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!(Summary::of(&[3.0]).stddev, 0.0);
+        assert_eq!(Summary::of(&[]), Summary::default());
+    }
+
+    fn result(seed: u64, gold: bool) -> SimulationResult {
+        SimulationResult {
+            seed: seed,
+            outcome: if gold { Outcome::Gold } else { Outcome::NoGold },
+            steps: 1,
+            score: 0,
+            gold: gold,
+            death_cause: None,
+            solvable: true,
+            sat_calls: 0,
+            duration: Duration::ZERO,
+            stats: None,
+            render_time: Duration::ZERO,
+            peak_memory: None,
+            history: vec![],
+        }
+    }
+
+    #[test]
+    fn sign_test_of_small_splits() {
+        assert_eq!(sign_test(0, 0), 1.0);
+        assert!((sign_test(3, 3) - 1.0).abs() < 1e-12);
+        // 2 * (1/2)^5
+        assert!((sign_test(0, 5) - 0.0625).abs() < 1e-12);
+        assert!((sign_test(10, 0) - 2.0 / 1024.0).abs() < 1e-12);
+        // 2 * (1 + 6) / 64
+        assert!((sign_test(1, 5) - 14.0 / 64.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn the_breakdown_counts_every_outcome() {
+        let ended = |outcome: Outcome, death_cause: Option<DeathCause>, solvable: bool| {
+            let mut r = result(0, matches!(outcome, Outcome::Gold));
+            r.outcome = outcome;
+            r.death_cause = death_cause;
+            r.solvable = solvable;
+            r
+        };
+        let results = [
+            ended(Outcome::Gold, None, true),
+            ended(Outcome::NoGold, None, true),
+            ended(Outcome::NoGold, None, false),
+            ended(Outcome::Dead, Some(DeathCause::Pit), true),
+            ended(Outcome::DeadOnSafeCell, Some(DeathCause::Pit), true),
+            ended(Outcome::Dead, Some(DeathCause::Wumpus), true),
+            ended(Outcome::Dead, None, true),
+            ended(Outcome::TimedOut, None, true),
+            ended(
+                Outcome::Failed(HeroError::NoActionAvailable(Position::new(0, 0))),
+                None,
+                true,
+            ),
+            ended(Outcome::IllegalAction(IllegalAction::NoArrow), None, true),
+            ended(Outcome::Interrupted, None, true),
+        ];
+        let breakdown = Breakdown::new(&results);
+        assert_eq!(
+            breakdown,
+            Breakdown {
+                gold: 1,
+                no_gold: 2,
+                no_gold_unsolvable: 1,
+                pit: 2,
+                wumpus: 1,
+                unknown_death: 1,
+                timed_out: 1,
+                failed: 2,
+            }
+        );
+        // la morte senza causa non è attribuita al wumpus e compare solo quando c'è
+        let table = breakdown.to_string();
+        assert!(table.contains("died, cause unknown"), "{}", table);
+        assert!(
+            !Breakdown::new(&results[..6])
+                .to_string()
+                .contains("unknown")
+        );
+    }
+
+    #[test]
+    fn paired_comparison_counts_the_pairs() {
+        let a = [
+            result(1, true),
+            result(2, true),
+            result(3, false),
+            result(4, false),
+        ];
+        let b = [
+            result(1, true),
+            result(2, false),
+            result(3, true),
+            result(4, false),
+        ];
+        let comparison = PairedComparison::new(&a, &b);
+        assert_eq!(
+            (
+                comparison.both_won,
+                comparison.only_a,
+                comparison.only_b,
+                comparison.both_lost
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(comparison.disagreements, vec![2, 3]);
+        assert!((comparison.p_value - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+
+use crate::{
+    hero::{Cache, Objective, Plan},
+    world::{Action, Perceptions, Position},
+};
+
+/// Score of an action: the hero picks the highest Score and never a Forbidden action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Utility {
+    // l'ordine delle varianti conta: Forbidden è minore di qualunque Score
+    Forbidden,
+    Score(i32),
+}
+
+/// What the hero knows when it has to choose an action without a plan to follow.
+pub struct DecisionContext<'a> {
+    pub cache: &'a Cache,
+    pub plan: Option<&'a Plan>,
+    pub objective: &'a Objective,
+    pub perceptions: &'a Perceptions,
+    /// The last positions of the hero, the current one at the back.
+    pub recent: &'a VecDeque<Position>,
+}
+
+// penalità per tornare in una casella visitata da poco: la più recente costa di più,
+// così senza piano l'eroe non rimbalza tra le ultime due caselle
+fn revisit_penalty(ctx: &DecisionContext, next: Position) -> i32 {
+    ctx.recent
+        .iter()
+        .rev()
+        .position(|pos| *pos == next)
+        .map_or(0, |age| (ctx.recent.len() - age) as i32)
+}
+
+/// A decision rule: the hero executes the suitable action with the highest score.
+pub trait Strategy {
+    fn score_action(&mut self, ctx: &DecisionContext, a: &Action) -> Utility;
+}
+
+// le utilità originali dell'eroe
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ClassicStrategy;
+
+impl ClassicStrategy {
+    fn utility_take_gold(&mut self, ctx: &DecisionContext, a: &Action) -> Utility {
+        match *a {
+            Action::Move(direction) => {
+                let p = ctx.perceptions;
+                match p.position.checked_move(direction, p.board_size) {
+                    // per tornare su caselle già visitate serve un piano verso una casella safe non ancora
+                    // visitata (BFS, ogni mossa costa 1 quindi il piano è ottimo), che viene eseguito
+                    // direttamente da next_action; con una casella nuova accanto si va lì
+                    Some(next)
+                        if ctx.cache.is_visited(&next)
+                            && (ctx.plan.is_some()
+                                || ctx.cache.safe_neighbourhood(&p.position)) =>
+                    {
+                        Utility::Forbidden
+                    }
+                    // senza piano e senza caselle nuove ci si sposta su una casella visitata,
+                    // penalizzando quelle lasciate da poco
+                    Some(next) if ctx.cache.is_visited(&next) => {
+                        Utility::Score(-revisit_penalty(ctx, next))
+                    }
+                    Some(_) => Utility::Score(1),
+                    // contro il muro
+                    None => Utility::Forbidden,
+                }
+            }
+            Action::Grab => Utility::Score(i32::MAX),
+            // mentre esplora l'eroe non tira, il tiro viene deciso dall'obbiettivo KillWumpus
+            Action::Shoot(_) => Utility::Forbidden,
+            Action::Exit => Utility::Forbidden,
+        }
+    }
+
+    fn utility_go_home(&mut self, _ctx: &DecisionContext, a: &Action) -> Utility {
+        // inizia una ricarca A* per trovare il cammino ottimo per andare dalla posizione
+        // fino alla casella (0,0)
+        // euristica: distanza manhattan dalla posizione della cella fino al punto (0,0):
+        // quindi h(x,y) =(x - 0) + (y - 0) = x + y
+
+        // crea una funzione di utilità che preferisce tutte le mosse che portano
+        // dalla posizione corrente fino alla cella (0,0)
+
+        // Sia G il cammino ottimo [n,n',...,n_0] allora la funzione di utilità
+        // dovrà dare ad ogni nodo n la seguente utilità:
+        // -h(n.x,n.y)
+        // dato che l'agente cercarà di massimizzare l'utilità lo porterà alla cella (0,0)
+
+        // G sarà il "piano" dell'agente ed è eseguito direttamente da next_action,
+        // le utilità vengono usate solo quando il piano è esaurito o non è valido
+
+        // Tutte le mosse sono proibite, l'azione Exit ha invece l'utilità massima
+
+        match *a {
+            Action::Move(_) => Utility::Forbidden,
+            Action::Grab => Utility::Score(i32::MAX),
+            Action::Shoot(_) => Utility::Forbidden,
+            Action::Exit => Utility::Score(i32::MAX),
+        }
+    }
+
+    fn utility_kill_wumpus(&mut self, ctx: &DecisionContext, a: &Action) -> Utility {
+        // il tiro ha la massima utilità solo dalla casella di mira e nella direzione giusta
+        match *a {
+            Action::Shoot(direction) => {
+                if ctx.cache.aim_direction(&ctx.perceptions.position) == Some(direction) {
+                    Utility::Score(i32::MAX)
+                } else {
+                    Utility::Forbidden
+                }
+            }
+            Action::Exit => Utility::Forbidden,
+            _ => self.utility_go_home(ctx, a),
+        }
+    }
+
+    fn utility_gamble(&mut self, ctx: &DecisionContext, a: &Action) -> Utility {
+        match *a {
+            Action::Shoot(_) | Action::Exit => Utility::Forbidden,
+            _ => self.utility_go_home(ctx, a),
+        }
+    }
+}
+
+impl Strategy for ClassicStrategy {
+    fn score_action(&mut self, ctx: &DecisionContext, a: &Action) -> Utility {
+        match ctx.objective {
+            Objective::TakeGold => self.utility_take_gold(ctx, a),
+            Objective::KillWumpus => self.utility_kill_wumpus(ctx, a),
+            Objective::Gamble => self.utility_gamble(ctx, a),
+            Objective::GoHome => self.utility_go_home(ctx, a),
+        }
+    }
+}
+
+// esplorazione golosa: tra le caselle non visitate preferisce quella con più vicini
+// ancora sconosciuti, per il resto si comporta come ClassicStrategy
+#[derive(Default, Clone, Copy, Debug)]
+pub struct GreedyStrategy;
+
+impl Strategy for GreedyStrategy {
+    fn score_action(&mut self, ctx: &DecisionContext, a: &Action) -> Utility {
+        match (ctx.objective, a) {
+            (Objective::TakeGold, Action::Move(direction)) => {
+                let size = ctx.perceptions.board_size;
+                let Some(next) = ctx.perceptions.position.checked_move(*direction, size) else {
+                    return Utility::Forbidden;
+                };
+                if ctx.cache.is_visited(&next) {
+                    return Utility::Forbidden;
+                }
+                let unknown = next
+                    .neighbor_positions(size)
+                    .filter(|pos| {
+                        !ctx.cache.is_visited(pos)
+                            && !ctx.cache.is_safe(pos)
+                            && !ctx.cache.is_unsafe(pos)
+                    })
+                    .count();
+                Utility::Score(unknown as i32 + 1)
+            }
+            _ => ClassicStrategy.score_action(ctx, a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{
+        hero::{Hero, HeroConfig},
+        kb::init_kb,
+        world::{ActionResult, Direction, GameSeeds, World},
+    };
+
+    // la partita del seme giocata con la strategia data: il risultato e le azioni
+    fn play(seed: u64, strategy: Box<dyn Strategy>) -> (ActionResult, Vec<Action>) {
+        let mut world = World::new_with_seed(5, 3, seed);
+        let mut kb = init_kb(5);
+        kb.use_in_process_solver();
+        let rng = StdRng::seed_from_u64(GameSeeds::new(seed).agent);
+        let mut hero = Hero::with_rng(kb, 5, HeroConfig::default(), rng);
+        hero.set_strategy(strategy);
+        let mut actions = vec![];
+        loop {
+            let action = hero.next_action(world.perceptions()).unwrap();
+            actions.push(action);
+            let result = world.do_action(action).unwrap();
+            if result != ActionResult::Continue {
+                return (result, actions);
+            }
+        }
+    }
+
+    #[test]
+    fn both_strategies_play_the_same_seeded_worlds() {
+        let mut different = 0;
+        for seed in 0..8 {
+            let (classic, classic_actions) = play(seed, Box::new(ClassicStrategy));
+            let (greedy, greedy_actions) = play(seed, Box::new(GreedyStrategy));
+            for result in [classic, greedy] {
+                assert!(
+                    matches!(result, ActionResult::Exited { .. }),
+                    "seed {}: {:?}",
+                    seed,
+                    result
+                );
+            }
+            // la stessa partita con la stessa strategia si ripete identica
+            assert_eq!(play(seed, Box::new(GreedyStrategy)).1, greedy_actions);
+            if classic_actions != greedy_actions {
+                different += 1;
+            }
+        }
+        assert!(different > 0, "the strategies never chose differently");
+    }
+
+    #[test]
+    fn forbidden_is_below_every_score() {
+        assert!(Utility::Forbidden < Utility::Score(i32::MIN));
+        assert!(Utility::Score(i32::MIN) < Utility::Score(i32::MIN + 1));
+        assert!(Utility::Score(-1) < Utility::Score(0));
+        assert_eq!(
+            [Utility::Score(3), Utility::Forbidden, Utility::Score(7)]
+                .into_iter()
+                .max(),
+            Some(Utility::Score(7))
+        );
+    }
+
+    #[test]
+    fn going_back_where_the_hero_just_was_is_penalized() {
+        use Direction::*;
+
+        let at = Position::new;
+        // (2,0) è stata visitata prima delle ultime quattro posizioni
+        let mut cache = Cache::new(3);
+        for pos in [at(0, 0), at(2, 0), at(1, 0), at(1, 1)] {
+            cache.insert_safe(pos).unwrap();
+            cache.insert_visited(pos).unwrap();
+        }
+        let recent = VecDeque::from([at(0, 0), at(1, 0), at(1, 1), at(1, 0)]);
+        let perceptions = Perceptions::at(at(1, 0), 3).build();
+        let ctx = DecisionContext {
+            cache: &cache,
+            plan: None,
+            objective: &Objective::TakeGold,
+            perceptions: &perceptions,
+            recent: &recent,
+        };
+        let score = |direction| ClassicStrategy.score_action(&ctx, &Action::Move(direction));
+        // la casella appena lasciata vale meno di quella lasciata prima, che vale meno di una
+        // dimenticata, ma nessuna è proibita
+        assert!(Utility::Forbidden < score(Sud));
+        assert!(score(Sud) < score(Ovest));
+        assert!(score(Ovest) < score(East));
+
+        // con una casella sicura nuova accanto le caselle visitate restano proibite
+        let mut cache = Cache::new(3);
+        for pos in [at(0, 0), at(2, 0), at(1, 0)] {
+            cache.insert_safe(pos).unwrap();
+            cache.insert_visited(pos).unwrap();
+        }
+        cache.insert_safe(at(1, 1)).unwrap();
+        let ctx = DecisionContext {
+            cache: &cache,
+            ..ctx
+        };
+        assert_eq!(
+            ClassicStrategy.score_action(&ctx, &Action::Move(Ovest)),
+            Utility::Forbidden
+        );
+        assert_eq!(
+            ClassicStrategy.score_action(&ctx, &Action::Move(Sud)),
+            Utility::Score(1)
+        );
+    }
+}
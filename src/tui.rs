@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+    time::Duration,
+};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    widgets::{Block, Paragraph},
+};
+
+use crate::{
+    agents::Agent,
+    environment::{EnvConfig, WumpusEnv},
+    world::{Action, Direction, Position},
+};
+
+// righe del pannello con le ultime decisioni
+const LOG_LINES: usize = 8;
+
+/// Who chooses the actions shown by the terminal UI.
+pub enum Driver {
+    Agent(Box<dyn Agent>),
+    // le azioni arrivano dalla tastiera, fog nasconde le caselle non ancora visitate
+    Human { fog: bool },
+}
+
+/// How a game played in the terminal UI ended.
+#[derive(Clone, Debug)]
+pub struct TuiOutcome {
+    pub steps: usize,
+    pub score: i32,
+    // None se la partita è stata interrotta prima della fine
+    pub ending: Option<String>,
+}
+
+struct App {
+    env: WumpusEnv,
+    driver: Driver,
+    seen: HashSet<Position>,
+    log: VecDeque<String>,
+    steps: usize,
+    score: i32,
+    ending: Option<String>,
+    running: bool,
+    // con la freccia armata la prossima direzione scocca invece di muovere
+    aiming: bool,
+}
+
+/// Plays a game in the terminal: the true world on the left, what the agent knows on the
+/// right and the last decisions at the bottom. With an agent space plays a turn, r runs a
+/// turn every `tick` and p pauses; a human moves with the arrows, shoots with f and an
+/// arrow, grabs with g and exits with x. q quits.
+pub fn run(config: EnvConfig, seed: u64, driver: Driver, tick: Duration) -> io::Result<TuiOutcome> {
+    let mut env = WumpusEnv::new(config);
+    env.reset(Some(seed));
+    let mut app = App {
+        env: env,
+        driver: driver,
+        seen: HashSet::from([Position::new(0, 0)]),
+        log: VecDeque::new(),
+        steps: 0,
+        score: 0,
+        ending: None,
+        running: false,
+        aiming: false,
+    };
+    app.push_log(format!("dungeon seed {}", seed));
+    let mut terminal = ratatui::init();
+    // il terminale va ripristinato anche se il disegno fallisce
+    let result = app.event_loop(&mut terminal, tick);
+    ratatui::restore();
+    result.map(|()| TuiOutcome {
+        steps: app.steps,
+        score: app.score,
+        ending: app.ending,
+    })
+}
+
+impl App {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal, tick: Duration) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            // in esecuzione si gioca un turno a ogni tick, altrimenti si aspetta un tasto
+            if self.running && self.ending.is_none() && !event::poll(tick)? {
+                self.agent_turn();
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                return Ok(());
+            }
+            if self.ending.is_some() {
+                continue;
+            }
+            match self.driver {
+                Driver::Agent(_) => match key.code {
+                    KeyCode::Char(' ') | KeyCode::Char('s') => self.agent_turn(),
+                    KeyCode::Char('r') => self.running = true,
+                    KeyCode::Char('p') => self.running = false,
+                    _ => {}
+                },
+                Driver::Human { .. } => self.human_key(key.code),
+            }
+        }
+    }
+
+    fn agent_turn(&mut self) {
+        let Driver::Agent(agent) = &mut self.driver else {
+            return;
+        };
+        let p = self
+            .env
+            .world()
+            .expect("the game has started")
+            .perceptions();
+        match agent.next_action_traced(p) {
+            Ok((a, trace)) => {
+                let reason = trace.map_or(String::new(), |trace| {
+                    format!(" for {:?}, plan {:?}", trace.objective, trace.plan)
+                });
+                self.push_log(format!("{}: {:?}{}", self.steps + 1, a, reason));
+                self.play(a);
+            }
+            Err(e) => {
+                self.push_log(format!("the agent failed: {}", e));
+                self.ending = Some(format!("the agent failed: {}", e));
+            }
+        }
+    }
+
+    fn human_key(&mut self, code: KeyCode) {
+        let dir = match code {
+            KeyCode::Up => Some(Direction::North),
+            KeyCode::Down => Some(Direction::Sud),
+            KeyCode::Right => Some(Direction::East),
+            KeyCode::Left => Some(Direction::Ovest),
+            _ => None,
+        };
+        let action = match (code, dir) {
+            (_, Some(dir)) if self.aiming => Action::Shoot(dir),
+            (_, Some(dir)) => Action::Move(dir),
+            (KeyCode::Char('f'), _) => {
+                self.aiming = !self.aiming;
+                return;
+            }
+            (KeyCode::Char('g'), _) => Action::Grab,
+            (KeyCode::Char('x'), _) => Action::Exit,
+            _ => return,
+        };
+        self.aiming = false;
+        self.push_log(format!("{}: {:?}", self.steps + 1, action));
+        self.play(action);
+    }
+
+    fn play(&mut self, a: Action) {
+        let (observation, reward, done, info) = self.env.step(a);
+        self.steps += 1;
+        self.score += reward;
+        self.seen.insert(observation.position);
+        if observation.bump {
+            self.push_log("bump".to_string());
+        }
+        if observation.howl {
+            self.push_log("the wumpus howls".to_string());
+        }
+        if !done {
+            return;
+        }
+        let ending = match info.get("death_cause") {
+            Some(cause) => format!("killed by {}", cause),
+            None if info["gold_in_dungeon"] == "false" => "escaped with the gold".to_string(),
+            None => "escaped without the gold".to_string(),
+        };
+        self.push_log(ending.clone());
+        self.ending = Some(ending);
+        self.running = false;
+    }
+
+    fn push_log(&mut self, line: String) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [top, bottom] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(LOG_LINES as u16 + 2)])
+                .areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+        let world = self.env.world().expect("the game has started");
+        let p = world.perceptions();
+        let board = match self.driver {
+            Driver::Human { fog: true } if self.ending.is_none() => {
+                world.fogged(&self.seen).to_string()
+            }
+            _ => world.to_string(),
+        };
+        let knowledge = match &self.driver {
+            Driver::Agent(agent) => agent
+                .render_knowledge(p.position)
+                .unwrap_or_else(|| "the agent has no knowledge base".to_string()),
+            Driver::Human { .. } => format!(
+                "glitter: {}\nstench: {}\nbreeze: {}\n{}",
+                p.glitter(),
+                p.stench(),
+                p.breeze(),
+                if self.aiming {
+                    "aiming: choose a direction"
+                } else {
+                    ""
+                }
+            ),
+        };
+        let status = match &self.ending {
+            Some(ending) => format!(" {}, score {}, q quits ", ending, self.score),
+            None => format!(" turn {}, score {} ", self.steps, self.score),
+        };
+        frame.render_widget(
+            Paragraph::new(board).block(Block::bordered().title(" world ").title_bottom(status)),
+            left,
+        );
+        frame.render_widget(
+            Paragraph::new(knowledge).block(Block::bordered().title(" knowledge ")),
+            right,
+        );
+        let log: Vec<String> = self.log.iter().cloned().collect();
+        frame.render_widget(
+            Paragraph::new(log.join("\n")).block(Block::bordered().title(" log ")),
+            bottom,
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use rand::{SeedableRng, rngs::StdRng};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    agents::Agent,
+    environment::{EnvConfig, Info, Observation, WumpusEnv},
+    hero::{Hero, HeroConfig},
+    kb::init_kb,
+    world::{Action, GameSeeds},
+};
+
+// lo stato restituito a JavaScript dopo ogni turno
+#[derive(serde::Serialize)]
+struct StepState<'a> {
+    turn: usize,
+    action: Option<Action>,
+    observation: Observation,
+    reward: i32,
+    score: i32,
+    done: bool,
+    // l'eroe non è riuscito a decidere, la partita finisce
+    error: Option<String>,
+    info: &'a Info,
+}
+
+/// A game played by the hero, driven one turn at a time from JavaScript.
+#[wasm_bindgen]
+pub struct WasmGame {
+    env: WumpusEnv,
+    hero: Box<dyn Agent>,
+    observation: Observation,
+    turn: usize,
+    score: i32,
+    done: bool,
+}
+
+#[wasm_bindgen]
+impl WasmGame {
+    /// A new dungeon and a new hero, both built from the seed of the game.
+    pub fn new_world(seed: u64, dim: usize, pits: usize) -> WasmGame {
+        let mut env = WumpusEnv::new(EnvConfig {
+            dim: dim,
+            pits: pits,
+        });
+        let observation = env.reset(Some(seed));
+        let hero = Hero::with_rng(
+            init_kb(dim),
+            dim,
+            HeroConfig::default(),
+            StdRng::seed_from_u64(GameSeeds::new(seed).agent),
+        );
+        WasmGame {
+            env: env,
+            hero: Box::new(hero),
+            observation: observation,
+            turn: 0,
+            score: 0,
+            done: false,
+        }
+    }
+
+    /// Plays a turn and returns the new state as JSON, after the end of the game the
+    /// state does not change any more.
+    pub fn step(&mut self) -> String {
+        let info = Info::new();
+        if self.done {
+            return self.state(None, 0, None, &info);
+        }
+        let p = self
+            .env
+            .world()
+            .expect("the game has started")
+            .perceptions();
+        let a = match self.hero.next_action(p) {
+            Ok(a) => a,
+            Err(e) => {
+                self.done = true;
+                return self.state(None, 0, Some(e.to_string()), &info);
+            }
+        };
+        let (observation, reward, done, info) = self.env.step(a);
+        self.observation = observation;
+        self.turn += 1;
+        self.score += reward;
+        self.done = done;
+        self.state(Some(a), reward, None, &info)
+    }
+
+    /// What the hero knows about the board, with its plan.
+    pub fn hero_knowledge(&self) -> String {
+        self.hero
+            .render_knowledge(self.observation.position)
+            .unwrap_or_default()
+    }
+}
+
+impl WasmGame {
+    fn state(
+        &self,
+        action: Option<Action>,
+        reward: i32,
+        error: Option<String>,
+        info: &Info,
+    ) -> String {
+        let state = StepState {
+            turn: self.turn,
+            action: action,
+            observation: self.observation,
+            reward: reward,
+            score: self.score,
+            done: self.done,
+            error: error,
+            info: info,
+        };
+        serde_json::to_string(&state).expect("the state is serializable")
+    }
+}
@@ -1,6 +1,7 @@
-use std::{fmt, process::exit};
+use std::{cmp::Ordering, collections::HashSet, fmt, iter, str::FromStr};
 
-use rand::Rng;
+use log::{info, warn};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
@@ -10,13 +11,59 @@ enum Entity {
     Gold,
 }
 
+/// With serde an action is an externally tagged enum: `"Grab"`, `"Exit"`,
+/// `{"Move": "north"}`, `{"Shoot": "west"}`.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     Move(Direction),
     Grab,
     Shoot(Direction),
     Exit,
 }
+/// What killed the hero.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeathCause {
+    Pit,
+    Wumpus,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActionResult {
+    Continue,
+    // l'eroe è uscito dal dungeon, gold è true se aveva l'oro
+    Exited { gold: bool },
+    Dead(DeathCause),
+}
+
+/// An action the world refuses, do_action leaves the world as it was.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IllegalAction {
+    IntoWall(Position, Direction),
+    // raccogliere in una casella senza l'oro
+    NothingToGrab(Position),
+    NoArrow,
+    // l'uscita è solo in (0,0)
+    ExitAwayFromStart(Position),
+}
+
+impl fmt::Display for IllegalAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalAction::IntoWall(pos, dir) => {
+                write!(f, "moving {:?} from {:?} goes into the wall", dir, pos)
+            }
+            IllegalAction::NothingToGrab(pos) => write!(f, "there is no gold to grab in {:?}", pos),
+            IllegalAction::NoArrow => write!(f, "the arrow was already shot"),
+            IllegalAction::ExitAwayFromStart(pos) => {
+                write!(f, "the exit is in (0,0), not in {:?}", pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IllegalAction {}
+
 type Dungeon = Vec<Vec<Option<Entity>>>;
 
 fn generate_random_position_not_covered<R: Rng + ?Sized>(
@@ -33,18 +80,229 @@ fn generate_random_position_not_covered<R: Rng + ?Sized>(
     (x, y)
 }
 
-#[derive(Default, Debug)]
+// un bit per ogni senso dell'eroe
+const GLITTER: u8 = 1 << 0;
+const STENCH: u8 = 1 << 1;
+const BREEZE: u8 = 1 << 2;
+const HOWL: u8 = 1 << 3;
+const BUMP: u8 = 1 << 4;
+
+const SENSES: [(u8, &str); 5] = [
+    (GLITTER, "glitter"),
+    (STENCH, "stench"),
+    (BREEZE, "breeze"),
+    (HOWL, "howl"),
+    (BUMP, "bump"),
+];
+
+/// What the hero perceives in a cell. The five senses are the bits of a byte, read with
+/// glitter(), stench()... and set with set_glitter() or the with_glitter() style builders.
+/// With serde the senses are five bools next to the position and the size of the board:
+/// `{"glitter": false, "stench": true, "breeze": false, "howl": false, "bump": false,
+/// "position": {"x": 0, "y": 1}, "board_size": 4}`.
+#[derive(Default, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "PerceptionsRepr", into = "PerceptionsRepr")
+)]
 pub struct Perceptions {
-    pub glitter: bool,
-    pub stench: bool,
-    pub breeze: bool,
-    pub howl: bool,
-    pub bump: bool,
+    senses: u8,
     pub position: Position,
     pub board_size: usize,
 }
 
+impl Perceptions {
+    /// Nothing perceived in position.
+    pub fn new(position: Position, board_size: usize) -> Self {
+        Self {
+            senses: 0,
+            position: position,
+            board_size: board_size,
+        }
+    }
+
+    fn has(&self, sense: u8) -> bool {
+        self.senses & sense != 0
+    }
+
+    fn set(&mut self, sense: u8, value: bool) {
+        if value {
+            self.senses |= sense;
+        } else {
+            self.senses &= !sense;
+        }
+    }
+
+    pub fn glitter(&self) -> bool {
+        self.has(GLITTER)
+    }
+
+    pub fn stench(&self) -> bool {
+        self.has(STENCH)
+    }
+
+    pub fn breeze(&self) -> bool {
+        self.has(BREEZE)
+    }
+
+    pub fn howl(&self) -> bool {
+        self.has(HOWL)
+    }
+
+    pub fn bump(&self) -> bool {
+        self.has(BUMP)
+    }
+
+    pub fn set_glitter(&mut self, value: bool) {
+        self.set(GLITTER, value);
+    }
+
+    pub fn set_stench(&mut self, value: bool) {
+        self.set(STENCH, value);
+    }
+
+    pub fn set_breeze(&mut self, value: bool) {
+        self.set(BREEZE, value);
+    }
+
+    pub fn set_howl(&mut self, value: bool) {
+        self.set(HOWL, value);
+    }
+
+    pub fn set_bump(&mut self, value: bool) {
+        self.set(BUMP, value);
+    }
+
+    pub fn with_glitter(mut self) -> Self {
+        self.set_glitter(true);
+        self
+    }
+
+    pub fn with_stench(mut self) -> Self {
+        self.set_stench(true);
+        self
+    }
+
+    pub fn with_breeze(mut self) -> Self {
+        self.set_breeze(true);
+        self
+    }
+
+    pub fn with_howl(mut self) -> Self {
+        self.set_howl(true);
+        self
+    }
+
+    pub fn with_bump(mut self) -> Self {
+        self.set_bump(true);
+        self
+    }
+
+    /// Starts a builder for the perceptions in position, nothing perceived until a sense
+    /// is added: `Perceptions::at(pos, 4).breeze().stench().build()`.
+    pub fn at(position: Position, board_size: usize) -> PerceptionsBuilder {
+        PerceptionsBuilder(Perceptions::new(position, board_size))
+    }
+}
+
+/// Perceptions built one sense at a time, see Perceptions::at.
+#[derive(Clone, Debug)]
+pub struct PerceptionsBuilder(Perceptions);
+
+impl PerceptionsBuilder {
+    pub fn glitter(self) -> Self {
+        Self(self.0.with_glitter())
+    }
+
+    pub fn stench(self) -> Self {
+        Self(self.0.with_stench())
+    }
+
+    pub fn breeze(self) -> Self {
+        Self(self.0.with_breeze())
+    }
+
+    pub fn howl(self) -> Self {
+        Self(self.0.with_howl())
+    }
+
+    pub fn bump(self) -> Self {
+        Self(self.0.with_bump())
+    }
+
+    pub fn build(self) -> Perceptions {
+        self.0
+    }
+}
+
+impl From<PerceptionsBuilder> for Perceptions {
+    fn from(builder: PerceptionsBuilder) -> Self {
+        builder.build()
+    }
+}
+
+// "breeze, stench @ (3,4)", "nothing @ (0,0)" se non si percepisce niente
+impl fmt::Display for Perceptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let senses: Vec<&str> = SENSES
+            .iter()
+            .filter(|(sense, _)| self.has(*sense))
+            .map(|(_, name)| *name)
+            .collect();
+        if senses.is_empty() {
+            write!(f, "nothing")?;
+        } else {
+            write!(f, "{}", senses.join(", "))?;
+        }
+        write!(f, " @ ({},{})", self.position.x, self.position.y)
+    }
+}
+
+// su file le percezioni restano cinque bool, come nelle tracce già registrate
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PerceptionsRepr {
+    glitter: bool,
+    stench: bool,
+    breeze: bool,
+    howl: bool,
+    bump: bool,
+    position: Position,
+    board_size: usize,
+}
+
+#[cfg(feature = "serde")]
+impl From<PerceptionsRepr> for Perceptions {
+    fn from(repr: PerceptionsRepr) -> Self {
+        let mut p = Perceptions::new(repr.position, repr.board_size);
+        p.set_glitter(repr.glitter);
+        p.set_stench(repr.stench);
+        p.set_breeze(repr.breeze);
+        p.set_howl(repr.howl);
+        p.set_bump(repr.bump);
+        p
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Perceptions> for PerceptionsRepr {
+    fn from(p: Perceptions) -> Self {
+        Self {
+            glitter: p.glitter(),
+            stench: p.stench(),
+            breeze: p.breeze(),
+            howl: p.howl(),
+            bump: p.bump(),
+            position: p.position,
+            board_size: p.board_size,
+        }
+    }
+}
+
+/// With serde a position is `{"x": 1, "y": 2}`, y grows towards south.
 #[derive(Default, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -55,65 +313,263 @@ impl Position {
         Self { x: x, y: y }
     }
 
-    pub fn move_clone(&self, dir: Direction) -> Self {
-        match dir {
-            Direction::North => Self::new(self.x, self.y - 1),
-            Direction::Sud => Self::new(self.x, self.y + 1),
-            Direction::East => Self::new(self.x + 1, self.y),
-            Direction::Ovest => Self::new(self.x - 1, self.y),
+    /// The number of moves between the two cells on a board without obstacles.
+    pub fn manhattan(&self, other: &Position) -> usize {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    // tutte le caselle attraversate da una freccia scoccata da questa posizione verso dir:
+    // la freccia avanza di un delta alla volta finché resta nel dungeon
+    pub fn ray(&self, dir: Direction, size: usize) -> Vec<Position> {
+        iter::successors(self.checked_move(dir, size), |pos| {
+            pos.checked_move(dir, size)
+        })
+        .collect()
+    }
+
+    pub fn possible_move(&self, dir: Direction, bounds: impl Into<Bounds>) -> bool {
+        self.checked_move(dir, bounds).is_some()
+    }
+
+    /// The cell next to this one towards dir, None when it is outside the board.
+    pub fn checked_move(&self, dir: Direction, bounds: impl Into<Bounds>) -> Option<Position> {
+        let bounds = bounds.into();
+        let (dx, dy) = dir.delta();
+        let x = self.x.checked_add_signed(dx as isize)?;
+        let y = self.y.checked_add_signed(dy as isize)?;
+        if x < bounds.width && y < bounds.height {
+            Some(Position::new(x, y))
+        } else {
+            None
         }
     }
 
-    pub fn move_in(&mut self, dir: Direction) {
-        match dir {
-            Direction::North => self.y -= 1,
-            Direction::Sud => self.y += 1,
-            Direction::East => self.x += 1,
-            Direction::Ovest => self.x -= 1,
+    /// The cells next to this one inside the board, with the direction to reach them, in
+    /// the order of Direction::ALL.
+    pub fn neighbors(
+        self,
+        bounds: impl Into<Bounds>,
+    ) -> impl Iterator<Item = (Direction, Position)> {
+        let bounds = bounds.into();
+        Direction::ALL
+            .into_iter()
+            .filter_map(move |dir| Some((dir, self.checked_move(dir, bounds)?)))
+    }
+
+    /// Like neighbors, only the cells.
+    pub fn neighbor_positions(self, bounds: impl Into<Bounds>) -> impl Iterator<Item = Position> {
+        self.neighbors(bounds).map(|(_, pos)| pos)
+    }
+
+    /// The cell next to this one towards dir, or this one when the move would leave the
+    /// board, like the hero bumping into a wall.
+    pub fn saturating_move(&self, dir: Direction, bounds: impl Into<Bounds>) -> Position {
+        self.checked_move(dir, bounds).unwrap_or(*self)
+    }
+}
+
+// ordine per righe come nella stampa della mappa: prima y, poi x
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The size of a board: the columns go from 0 to width and the rows from 0 to height. The
+/// dungeon is square, a usize is a board with that side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Bounds {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width,
+            height: height,
         }
     }
+}
+
+impl From<usize> for Bounds {
+    fn from(size: usize) -> Self {
+        Self::new(size, size)
+    }
+}
+
+/// The seeds of the dungeon and of the agent of a game, derived from the seed of the game
+/// so that one number decides the whole run. They are the first two outputs of a SplitMix64
+/// generator started from the seed of the game: the dungeon uses the first, the agent the
+/// second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameSeeds {
+    pub world: u64,
+    pub agent: u64,
+}
 
-    pub fn possible_move(&self, dir: Direction, size: usize) -> bool {
-        match dir {
-            Direction::North => self.y > 0,
-            Direction::Sud => self.y < size - 1,
-            Direction::East => self.x < size - 1,
-            Direction::Ovest => self.x > 0,
+impl GameSeeds {
+    pub fn new(seed: u64) -> Self {
+        let mut state = seed;
+        let world = splitmix64(&mut state);
+        let agent = splitmix64(&mut state);
+        Self {
+            world: world,
+            agent: agent,
         }
     }
 }
 
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 pub struct World {
+    // il seed da cui è stato generato il dungeon, per riprodurre la partita
+    seed: u64,
     dungeon: Vec<Vec<Option<Entity>>>,
     gold_in_dungeon: bool,
     hero_pos: Position,
     arrow: bool,
+    wumpus_alive: bool,
+    // il wumpus è stato colpito nell'ultima azione, l'eroe sente l'urlo
+    howl: bool,
 }
 
 impl World {
+    /// A dungeon from a random seed, World::seed tells which one.
     pub fn new(dim: usize, pit_number: usize) -> Self {
-        assert!(dim > 0);
-        assert!(dim * dim > pit_number + 1 + 1); // the cells needed are pitnumber plus one for the wumpus, one for the gold and one for the hero
+        Self::new_with_seed(dim, pit_number, rand::random())
+    }
+
+    /// Same seed, same dungeon. The seed is the one of the game, the dungeon is generated
+    /// from GameSeeds::world.
+    pub fn new_with_seed(dim: usize, pit_number: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(GameSeeds::new(seed).world);
+        Self::with_rng(dim, pit_number, seed, &mut rng)
+    }
+
+    /// Err if a dungeon of side dim can't hold pit_number pits besides the hero, the wumpus
+    /// and the gold; new and new_with_seed panic on these sizes.
+    pub fn check_size(dim: usize, pit_number: usize) -> Result<(), String> {
+        if dim == 0 {
+            return Err("the dungeon needs at least one cell".to_string());
+        }
+        // come in with_rng: i pozzi, il wumpus e l'oro non vanno sulla cella di partenza
+        if pit_number + 2 >= dim * dim {
+            return Err(format!(
+                "{} pits don't fit a {}x{} dungeon with the hero, the wumpus and the gold",
+                pit_number, dim, dim
+            ));
+        }
+        Ok(())
+    }
+
+    /// A dungeon drawn like its Display, a row per line from y = 0: `.` empty, `o` pit,
+    /// `w` wumpus, `g` gold, `x` the hero at the start. The cells can be separated by
+    /// spaces; there must be one wumpus and one gold and the start must be empty. The
+    /// seed of the world is 0.
+    pub fn from_layout(layout: &str) -> Result<Self, String> {
+        let rows: Vec<Vec<char>> = layout
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|row| !row.is_empty())
+            .collect();
+        let dim = rows.len();
+        let mut dungeon = vec![vec![None; dim]; dim];
+        let mut wumpuses = 0;
+        let mut golds = 0;
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != dim {
+                return Err(format!(
+                    "row {} has {} cells instead of {}",
+                    y,
+                    row.len(),
+                    dim
+                ));
+            }
+            for (x, cell) in row.iter().enumerate() {
+                dungeon[y][x] = match cell {
+                    '.' | 'x' => None,
+                    'o' => Some(Entity::Pit),
+                    'w' => {
+                        wumpuses += 1;
+                        Some(Entity::Wumpus)
+                    }
+                    'g' => {
+                        golds += 1;
+                        Some(Entity::Gold)
+                    }
+                    other => return Err(format!("unknown cell {:?} in ({},{})", other, x, y)),
+                };
+                if *cell == 'x' && (x, y) != (0, 0) {
+                    return Err(format!("the hero starts in (0,0), not in ({},{})", x, y));
+                }
+            }
+        }
+        if dim == 0 {
+            return Err("the layout is empty".to_string());
+        }
+        if dungeon[0][0].is_some() {
+            return Err("the start (0,0) must be empty".to_string());
+        }
+        if wumpuses != 1 || golds != 1 {
+            return Err(format!(
+                "a dungeon has one wumpus and one gold, not {} and {}",
+                wumpuses, golds
+            ));
+        }
+        Ok(World {
+            seed: 0,
+            dungeon: dungeon,
+            hero_pos: Position::new(0, 0),
+            arrow: true,
+            wumpus_alive: true,
+            howl: false,
+            gold_in_dungeon: true,
+        })
+    }
+
+    fn with_rng<R: Rng + ?Sized>(dim: usize, pit_number: usize, seed: u64, rng: &mut R) -> Self {
+        if let Err(e) = Self::check_size(dim, pit_number) {
+            panic!("{}", e);
+        }
         let mut dungeon = vec![vec![None; dim]; dim];
-        let mut rng = rand::rng();
 
         for _ in 0..pit_number {
-            let (x, y) = generate_random_position_not_covered(&dungeon, &mut rng);
+            let (x, y) = generate_random_position_not_covered(&dungeon, rng);
             dungeon[y][x] = Entity::Pit.into();
         }
 
-        let (x, y) = generate_random_position_not_covered(&dungeon, &mut rng);
+        let (x, y) = generate_random_position_not_covered(&dungeon, rng);
 
         dungeon[y][x] = Some(Entity::Wumpus);
 
-        let (x, y) = generate_random_position_not_covered(&dungeon, &mut rng);
+        let (x, y) = generate_random_position_not_covered(&dungeon, rng);
 
         dungeon[y][x] = Entity::Gold.into();
 
         World {
+            seed: seed,
             dungeon: dungeon,
             hero_pos: Position { x: 0, y: 0 },
             arrow: true,
+            wumpus_alive: true,
+            howl: false,
             gold_in_dungeon: true,
         }
     }
@@ -137,103 +593,164 @@ impl World {
         self.there_is_something(x, y, Entity::Gold)
     }
 
-    pub fn perceptions(&self) -> Perceptions {
-        let mut p = Perceptions::default();
-        p.board_size = self.dungeon.len();
-        p.position = self.hero_pos;
-        let x = self.hero_pos.x;
-        let y = self.hero_pos.y;
-        if self.there_is_gold(x, y) {
-            p.glitter = true;
-        }
-        // TODO: compatta
-        if self.hero_pos.x != 0 {
-            // controlla se ci sta qualcosa a sinistra
-            if self.there_is_a_pit(x - 1, y) {
-                p.breeze = true;
-            } else if self.there_is_the_wumpus(x - 1, y) {
-                p.stench = true;
-            }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn size(&self) -> usize {
+        self.dungeon.len()
+    }
+
+    pub fn hero_position(&self) -> Position {
+        self.hero_pos
+    }
+
+    /// What kills the hero in pos: a pit or the wumpus while it is alive.
+    pub fn hazard_at(&self, pos: Position) -> Option<DeathCause> {
+        match self.dungeon[pos.y][pos.x] {
+            Some(Entity::Pit) => Some(DeathCause::Pit),
+            Some(Entity::Wumpus) if self.wumpus_alive => Some(DeathCause::Wumpus),
+            _ => None,
         }
-        if self.hero_pos.y != 0 {
-            // controlla se ci sta qualcosa in alto
-            if self.there_is_a_pit(x, y - 1) {
-                p.breeze = true;
-            } else if self.there_is_the_wumpus(x, y - 1) {
-                p.stench = true;
+    }
+
+    pub fn has_arrow(&self) -> bool {
+        self.arrow
+    }
+
+    pub fn wumpus_alive(&self) -> bool {
+        self.wumpus_alive
+    }
+
+    pub fn gold_in_dungeon(&self) -> bool {
+        self.gold_in_dungeon
+    }
+
+    /// Whether the gold can be reached from the start without crossing a pit: the wumpus
+    /// can always be shot, so only the pits can make a map unwinnable.
+    pub fn is_solvable(&self) -> bool {
+        let size = self.dungeon.len();
+        let start = Position::new(0, 0);
+        let mut seen = HashSet::from([start]);
+        let mut queue = vec![start];
+        while let Some(pos) = queue.pop() {
+            if self.there_is_gold(pos.x, pos.y) {
+                return true;
             }
-        }
-        if self.hero_pos.x != self.dungeon.len() - 1 {
-            // controlla se c'è qualcosa a destra
-            if self.there_is_a_pit(x + 1, y) {
-                p.breeze = true;
-            } else if self.there_is_the_wumpus(x + 1, y) {
-                p.stench = true;
+            for next in pos.neighbor_positions(size) {
+                if !self.there_is_a_pit(next.x, next.y) && seen.insert(next) {
+                    queue.push(next);
+                }
             }
         }
-        if self.hero_pos.y != self.dungeon.len() - 1 {
-            // controlla se c'è qualcosa in basso
-            if self.there_is_a_pit(x, y + 1) {
-                p.breeze = true;
-            } else if self.there_is_the_wumpus(x, y + 1) {
-                p.stench = true;
+        false
+    }
+
+    pub fn perceptions(&self) -> Perceptions {
+        let mut p = self.perceptions_at(self.hero_pos);
+        p.set_howl(self.howl);
+        p
+    }
+
+    /// What the hero would perceive in `pos`, without the howl.
+    pub fn perceptions_at(&self, pos: Position) -> Perceptions {
+        let mut p = Perceptions::new(pos, self.dungeon.len());
+        if self.there_is_gold(pos.x, pos.y) {
+            p.set_glitter(true);
+        }
+        for next in pos.neighbor_positions(self.dungeon.len()) {
+            if self.there_is_a_pit(next.x, next.y) {
+                p.set_breeze(true);
+            } else if self.there_is_the_wumpus(next.x, next.y) {
+                p.set_stench(true);
             }
         }
         p
     }
 
-    // true se finisce la simulazione, il secondo booleano è se ha trovato l'oro oppure no
-    pub fn do_action(&mut self, action: Action) -> (bool, bool) {
+    /// Plays an action. The illegal ones are refused and change nothing, not even the howl.
+    pub fn do_action(&mut self, action: Action) -> Result<ActionResult, IllegalAction> {
+        let pos = self.hero_pos;
+        let size = self.dungeon.len();
         match action {
-            Action::Move(dir) => self.hero_pos.move_in(dir),
+            Action::Move(dir) => {
+                let next = pos
+                    .checked_move(dir, size)
+                    .ok_or(IllegalAction::IntoWall(pos, dir))?;
+                self.howl = false;
+                self.hero_pos = next;
+            }
             Action::Grab => {
-                if self.dungeon[self.hero_pos.y][self.hero_pos.x]
-                    .as_ref()
-                    .map_or(false, |x| *x != Entity::Gold)
-                {
-                    println!("[FATAL ERROR] The hero is trying to Grap the Gold where is no gold");
-                    exit(1)
+                if self.dungeon[pos.y][pos.x] != Some(Entity::Gold) {
+                    return Err(IllegalAction::NothingToGrab(pos));
                 }
+                self.howl = false;
                 self.gold_in_dungeon = false;
-                self.dungeon[self.hero_pos.y][self.hero_pos.x] = None
+                self.dungeon[pos.y][pos.x] = None
             }
-            Action::Shoot(dir) => todo!(),
-            Action::Exit => {
-                if self.hero_pos == Position::new(0, 0) {
-                    if !self.gold_in_dungeon {
-                        println!("[SUCCESS] The Hero succesfuly exit the dungeon WITH the gold");
-                    } else {
-                        println!("[SUCCESS] The Hero succesfuly exit the dungeon WITHOUT the gold")
+            Action::Shoot(dir) => {
+                if !self.arrow {
+                    return Err(IllegalAction::NoArrow);
+                }
+                self.howl = false;
+                self.arrow = false;
+                // il wumpus morto resta nella sua casella e continua a puzzare
+                for target in pos.ray(dir, size) {
+                    if self.there_is_the_wumpus(target.x, target.y) {
+                        self.wumpus_alive = false;
+                        self.howl = true;
+                        info!("The Wumpus was killed in position {:?}", target);
                     }
-                    return (true, !self.gold_in_dungeon);
+                }
+            }
+            Action::Exit => {
+                if pos != Position::new(0, 0) {
+                    return Err(IllegalAction::ExitAwayFromStart(pos));
+                }
+                if !self.gold_in_dungeon {
+                    info!("The Hero succesfuly exit the dungeon WITH the gold");
                 } else {
-                    println!(
-                        "[FATAL ERROR] The agent exited the dangeon in the position: {:?} But he can exit only in the position (0,0)",
-                        self.hero_pos
-                    );
-                    exit(1);
+                    info!("The Hero succesfuly exit the dungeon WITHOUT the gold")
                 }
+                return Ok(ActionResult::Exited {
+                    gold: !self.gold_in_dungeon,
+                });
             }
         }
-        if self.dungeon[self.hero_pos.y][self.hero_pos.x]
-            .as_ref()
-            .map(|x| *x == Entity::Wumpus || *x == Entity::Pit)
-            .unwrap_or(false)
-        {
-            println!("{}", self);
-            println!("[ERROR] The hero is dead");
-            exit(1);
+        if let Some(cause) = self.hazard_at(self.hero_pos) {
+            warn!("The hero is dead, killed by {:?}", cause);
+            return Ok(ActionResult::Dead(cause));
         }
-        return (false, false);
+        Ok(ActionResult::Continue)
     }
 }
 
-impl fmt::Display for World {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// The board showing only the cells already seen by the hero, the others are `#`.
+pub struct Fogged<'a> {
+    world: &'a World,
+    seen: &'a HashSet<Position>,
+}
+
+impl World {
+    pub fn fogged<'a>(&'a self, seen: &'a HashSet<Position>) -> Fogged<'a> {
+        Fogged {
+            world: self,
+            seen: seen,
+        }
+    }
+
+    // stampa la mappa, con seen le caselle non ancora viste restano coperte
+    fn write_board(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        seen: Option<&HashSet<Position>>,
+    ) -> fmt::Result {
         for (y, row) in self.dungeon.iter().enumerate() {
             for (x, cell) in row.iter().enumerate() {
                 if x == self.hero_pos.x && y == self.hero_pos.y {
                     write!(f, "x ")?;
+                } else if seen.is_some_and(|seen| !seen.contains(&Position::new(x, y))) {
+                    write!(f, "# ")?;
                 } else if let Some(e) = cell {
                     match e {
                         Entity::Pit => write!(f, "o ")?,
@@ -251,10 +768,598 @@ impl fmt::Display for World {
     }
 }
 
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_board(f, None)
+    }
+}
+
+impl fmt::Display for Fogged<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.world.write_board(f, Some(self.seen))
+    }
+}
+
+/// With serde a direction is one of `"north"`, `"south"`, `"east"`, `"west"`; the names
+/// of the variants are still read, for the traces recorded before.
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
+    #[cfg_attr(feature = "serde", serde(rename = "north", alias = "North"))]
     North,
+    #[cfg_attr(feature = "serde", serde(rename = "south", alias = "Sud"))]
     Sud,
+    #[cfg_attr(feature = "serde", serde(rename = "east", alias = "East"))]
     East,
+    #[cfg_attr(feature = "serde", serde(rename = "west", alias = "Ovest"))]
     Ovest,
 }
+
+impl Direction {
+    /// The four directions, in the order the hero considers them.
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::Sud,
+        Direction::East,
+        Direction::Ovest,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::North => Direction::Sud,
+            Direction::Sud => Direction::North,
+            Direction::East => Direction::Ovest,
+            Direction::Ovest => Direction::East,
+        }
+    }
+
+    // rotazione di 90 gradi in senso antiorario
+    pub fn turn_left(self) -> Self {
+        match self {
+            Direction::North => Direction::Ovest,
+            Direction::Ovest => Direction::Sud,
+            Direction::Sud => Direction::East,
+            Direction::East => Direction::North,
+        }
+    }
+
+    // rotazione di 90 gradi in senso orario
+    pub fn turn_right(self) -> Self {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::Sud,
+            Direction::Sud => Direction::Ovest,
+            Direction::Ovest => Direction::North,
+        }
+    }
+
+    /// The step (dx, dy) of a move in this direction: the rows grow towards Sud, so North
+    /// is (0, -1).
+    pub fn delta(self) -> (i8, i8) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::Sud => (0, 1),
+            Direction::East => (1, 0),
+            Direction::Ovest => (-1, 0),
+        }
+    }
+
+    /// The letter of the human player: n, s, e or w, in either case.
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            'n' => Some(Direction::North),
+            's' => Some(Direction::Sud),
+            'e' => Some(Direction::East),
+            'w' => Some(Direction::Ovest),
+            _ => None,
+        }
+    }
+}
+
+// la lettera del giocatore o il nome per esteso, in italiano o in inglese
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Direction::from_char(c).ok_or(format!("unknown direction {}", s));
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "north" | "nord" => Ok(Direction::North),
+            "sud" | "south" => Ok(Direction::Sud),
+            "east" | "est" => Ok(Direction::East),
+            "ovest" | "west" => Ok(Direction::Ovest),
+            other => Err(format!("unknown direction {}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn world() -> World {
+        World::from_layout("x . g\n. o .\n. . w").expect("the layout is valid")
+    }
+
+    #[test]
+    fn every_cell_perceives_the_hazards_at_distance_one() {
+        for seed in 0..20 {
+            let world = World::new_with_seed(5, 4, seed);
+            let cells: Vec<Position> = (0..5)
+                .flat_map(|y| (0..5).map(move |x| Position::new(x, y)))
+                .collect();
+            for pos in &cells {
+                let near = |cause: DeathCause| {
+                    cells.iter().any(|other| {
+                        pos.manhattan(other) == 1 && world.hazard_at(*other) == Some(cause)
+                    })
+                };
+                let p = world.perceptions_at(*pos);
+                assert_eq!(p.breeze(), near(DeathCause::Pit), "seed {} {:?}", seed, pos);
+                assert_eq!(
+                    p.stench(),
+                    near(DeathCause::Wumpus),
+                    "seed {} {:?}",
+                    seed,
+                    pos
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn illegal_actions_are_refused_and_change_nothing() {
+        let mut world = world();
+        let start = world.perceptions();
+        assert_eq!(
+            world.do_action(Action::Move(Direction::North)),
+            Err(IllegalAction::IntoWall(
+                Position::new(0, 0),
+                Direction::North
+            ))
+        );
+        assert_eq!(
+            world.do_action(Action::Move(Direction::Ovest)),
+            Err(IllegalAction::IntoWall(
+                Position::new(0, 0),
+                Direction::Ovest
+            ))
+        );
+        assert_eq!(world.perceptions(), start);
+
+        assert_eq!(
+            world.do_action(Action::Move(Direction::East)),
+            Ok(ActionResult::Continue)
+        );
+        assert_eq!(
+            world.do_action(Action::Exit),
+            Err(IllegalAction::ExitAwayFromStart(Position::new(1, 0)))
+        );
+        assert_eq!(
+            world.do_action(Action::Shoot(Direction::Sud)),
+            Ok(ActionResult::Continue)
+        );
+        assert!(!world.has_arrow());
+        assert_eq!(
+            world.do_action(Action::Shoot(Direction::Sud)),
+            Err(IllegalAction::NoArrow)
+        );
+        assert_eq!(world.hero_position(), Position::new(1, 0));
+    }
+
+    #[test]
+    fn grabbing_without_the_gold_is_refused() {
+        let mut world = world();
+        assert_eq!(
+            world.do_action(Action::Grab),
+            Err(IllegalAction::NothingToGrab(Position::new(0, 0)))
+        );
+        assert!(world.gold_in_dungeon());
+        // l'oro non si prende due volte
+        world.do_action(Action::Move(Direction::East)).unwrap();
+        world.do_action(Action::Move(Direction::East)).unwrap();
+        world.do_action(Action::Grab).unwrap();
+        assert_eq!(
+            world.do_action(Action::Grab),
+            Err(IllegalAction::NothingToGrab(Position::new(2, 0)))
+        );
+        world.do_action(Action::Move(Direction::Ovest)).unwrap();
+        world.do_action(Action::Move(Direction::Ovest)).unwrap();
+        assert_eq!(
+            world.do_action(Action::Exit),
+            Ok(ActionResult::Exited { gold: true })
+        );
+    }
+
+    #[test]
+    fn hazards_end_the_game() {
+        let mut world = world();
+        world.do_action(Action::Move(Direction::East)).unwrap();
+        assert_eq!(
+            world.do_action(Action::Move(Direction::Sud)),
+            Ok(ActionResult::Dead(DeathCause::Pit))
+        );
+    }
+
+    #[test]
+    fn the_gold_is_grabbed_and_carried_out() {
+        let mut world = world();
+        world.do_action(Action::Move(Direction::East)).unwrap();
+        world.do_action(Action::Move(Direction::East)).unwrap();
+        assert!(world.perceptions().glitter());
+        world.do_action(Action::Grab).unwrap();
+        assert!(!world.gold_in_dungeon() && !world.perceptions().glitter());
+        world.do_action(Action::Move(Direction::Ovest)).unwrap();
+        world.do_action(Action::Move(Direction::Ovest)).unwrap();
+        assert_eq!(
+            world.do_action(Action::Exit),
+            Ok(ActionResult::Exited { gold: true })
+        );
+    }
+
+    #[test]
+    fn sizes_without_room_are_refused() {
+        assert!(World::check_size(0, 0).is_err());
+        assert!(World::check_size(1, 0).is_err());
+        // 2x2: l'eroe, il wumpus, l'oro e al più un pozzo
+        assert!(World::check_size(2, 1).is_ok());
+        assert!(World::check_size(2, 2).is_err());
+        assert!(World::check_size(10, 97).is_ok());
+        assert!(World::check_size(10, 98).is_err());
+    }
+
+    #[test]
+    fn no_move_leaves_the_start_towards_north_or_ovest() {
+        let start = Position::new(0, 0);
+        for size in 1..5 {
+            assert_eq!(start.checked_move(Direction::North, size), None);
+            assert_eq!(start.checked_move(Direction::Ovest, size), None);
+            assert!(!start.possible_move(Direction::North, size));
+            assert!(!start.possible_move(Direction::Ovest, size));
+        }
+        assert_eq!(
+            start.checked_move(Direction::East, 2),
+            Some(Position::new(1, 0))
+        );
+        assert_eq!(
+            start.checked_move(Direction::Sud, 2),
+            Some(Position::new(0, 1))
+        );
+        let neighbors: Vec<_> = start.neighbors(2).collect();
+        assert_eq!(neighbors.len(), 2);
+    }
+
+    #[test]
+    fn direction_helpers() {
+        use Direction::*;
+
+        for dir in Direction::ALL {
+            assert_ne!(dir.opposite(), dir);
+            assert_eq!(dir.opposite().opposite(), dir);
+            assert_eq!(dir.turn_left().turn_right(), dir);
+            assert_eq!(dir.turn_left().turn_left(), dir.opposite());
+            // quattro rotazioni riportano alla direzione di partenza
+            assert_eq!(dir.turn_right().turn_right().turn_right().turn_right(), dir);
+            let (dx, dy) = dir.delta();
+            let (ox, oy) = dir.opposite().delta();
+            assert_eq!((dx + ox, dy + oy), (0, 0));
+            assert_eq!(dx.abs() + dy.abs(), 1);
+        }
+        assert_eq!(North.turn_left(), Ovest);
+        assert_eq!(North.turn_right(), East);
+        assert_eq!(
+            Direction::ALL.map(Direction::delta),
+            [(0, -1), (0, 1), (1, 0), (-1, 0)]
+        );
+        assert_eq!(
+            ['n', 's', 'E', 'W', 'x'].map(Direction::from_char),
+            [Some(North), Some(Sud), Some(East), Some(Ovest), None]
+        );
+        for (text, dir) in [
+            ("n", North),
+            ("Nord", North),
+            ("south", Sud),
+            ("sud", Sud),
+            ("EST", East),
+            ("west", Ovest),
+            ("ovest", Ovest),
+        ] {
+            assert_eq!(text.parse::<Direction>(), Ok(dir), "{}", text);
+        }
+        assert!("up".parse::<Direction>().is_err());
+        assert!("".parse::<Direction>().is_err());
+    }
+
+    #[test]
+    fn neighbors_of_corner_edge_and_interior_cells() {
+        use Direction::*;
+
+        let neighbors = |pos: Position, bounds: Bounds| -> Vec<(Direction, Position)> {
+            pos.neighbors(bounds).collect()
+        };
+        let square = Bounds::new(4, 4);
+        assert_eq!(
+            neighbors(Position::new(3, 3), square),
+            vec![(North, Position::new(3, 2)), (Ovest, Position::new(2, 3))]
+        );
+        assert_eq!(
+            neighbors(Position::new(2, 0), square),
+            vec![
+                (Sud, Position::new(2, 1)),
+                (East, Position::new(3, 0)),
+                (Ovest, Position::new(1, 0))
+            ]
+        );
+        assert_eq!(
+            neighbors(Position::new(1, 2), square),
+            vec![
+                (North, Position::new(1, 1)),
+                (Sud, Position::new(1, 3)),
+                (East, Position::new(2, 2)),
+                (Ovest, Position::new(0, 2))
+            ]
+        );
+        // su una mappa rettangolare i bordi sono diversi sui due assi
+        let wide = Bounds::new(5, 2);
+        assert_eq!(
+            Position::new(4, 1)
+                .neighbor_positions(wide)
+                .collect::<Vec<_>>(),
+            vec![Position::new(4, 0), Position::new(3, 1)]
+        );
+        assert_eq!(Position::new(2, 0).neighbors(wide).count(), 3);
+        assert_eq!(Position::new(0, 0).neighbors(Bounds::new(1, 1)).count(), 0);
+        assert_eq!(Position::new(0, 1).neighbors(Bounds::new(1, 3)).count(), 2);
+    }
+
+    #[test]
+    fn perceptions_display_and_bits() {
+        let pos = Position::new(3, 4);
+        assert_eq!(Perceptions::new(pos, 5).to_string(), "nothing @ (3,4)");
+        let p = Perceptions::new(pos, 5).with_breeze().with_stench();
+        assert_eq!(p.to_string(), "stench, breeze @ (3,4)");
+        assert!(p.breeze() && p.stench());
+        assert!(!p.glitter() && !p.howl() && !p.bump());
+
+        // ogni senso ha il suo bit: accenderlo e spegnerlo non tocca gli altri
+        let mut q = p.clone();
+        q.set_glitter(true);
+        q.set_bump(true);
+        q.set_howl(true);
+        assert_eq!(q.to_string(), "glitter, stench, breeze, howl, bump @ (3,4)");
+        q.set_glitter(false);
+        q.set_bump(false);
+        q.set_howl(false);
+        assert_eq!(q, p);
+        q.set_breeze(false);
+        assert_ne!(q, p);
+        assert_eq!(q, Perceptions::new(pos, 5).with_stench());
+
+        // uguali anche come chiavi, per confrontare le tracce
+        let set = std::collections::HashSet::from([p.clone(), q.clone(), p.clone()]);
+        assert_eq!(set.len(), 2);
+        assert_ne!(
+            p,
+            Perceptions::new(Position::new(4, 3), 5)
+                .with_breeze()
+                .with_stench()
+        );
+    }
+
+    #[test]
+    fn the_seeds_are_the_first_two_splitmix64_outputs() {
+        // i valori di riferimento di SplitMix64 dallo stato 0: se cambiano le partite
+        // registrate non si possono più rigiocare
+        assert_eq!(
+            GameSeeds::new(0),
+            GameSeeds {
+                world: 0xE220_A839_7B1D_CDAF,
+                agent: 0x6E78_9E6A_A1B9_65F4,
+            }
+        );
+        let seeds = GameSeeds::new(42);
+        assert_ne!(seeds.world, seeds.agent);
+        assert_eq!(seeds, GameSeeds::new(42));
+        assert_eq!(
+            World::new_with_seed(6, 4, 42).to_string(),
+            World::new_with_seed(6, 4, 42).to_string()
+        );
+    }
+
+    fn cell() -> impl Strategy<Value = (usize, Position)> {
+        (1usize..12).prop_flat_map(|size| {
+            (Just(size), 0..size, 0..size).prop_map(|(size, x, y)| (size, Position::new(x, y)))
+        })
+    }
+
+    fn direction() -> impl Strategy<Value = Direction> {
+        prop::sample::select(Direction::ALL.to_vec())
+    }
+
+    // una casella sul bordo di una mappa rettangolare, anche larga o alta una sola casella
+    fn border_cell() -> impl Strategy<Value = (Bounds, Position)> {
+        (
+            1usize..12,
+            1usize..12,
+            0usize..4,
+            any::<prop::sample::Index>(),
+        )
+            .prop_map(|(width, height, side, i)| {
+                let pos = match side {
+                    0 => Position::new(i.index(width), 0),
+                    1 => Position::new(i.index(width), height - 1),
+                    2 => Position::new(0, i.index(height)),
+                    _ => Position::new(width - 1, i.index(height)),
+                };
+                (Bounds::new(width, height), pos)
+            })
+    }
+
+    #[test]
+    fn moves_at_the_end_of_usize_do_not_overflow() {
+        let far = Position::new(usize::MAX, usize::MAX);
+        let bounds = Bounds::new(usize::MAX, usize::MAX);
+        assert_eq!(far.checked_move(Direction::East, bounds), None);
+        assert_eq!(far.checked_move(Direction::Sud, bounds), None);
+        // la casella è fuori dalla mappa, anche la mossa verso l'interno lo è
+        assert_eq!(far.checked_move(Direction::North, bounds), None);
+        assert_eq!(Position::new(0, 0).ray(Direction::North, 4), vec![]);
+        assert_eq!(
+            Position::new(1, 0).ray(Direction::East, 4),
+            vec![Position::new(2, 0), Position::new(3, 0)]
+        );
+    }
+
+    #[test]
+    fn positions_are_ordered_by_row() {
+        let mut cells = vec![
+            Position::new(2, 1),
+            Position::new(0, 2),
+            Position::new(1, 0),
+            Position::new(0, 1),
+            Position::new(0, 0),
+        ];
+        cells.sort();
+        assert_eq!(
+            cells,
+            vec![
+                Position::new(0, 0),
+                Position::new(1, 0),
+                Position::new(0, 1),
+                Position::new(2, 1),
+                Position::new(0, 2),
+            ]
+        );
+        // a parità di riga decide la colonna
+        assert!(Position::new(5, 0) < Position::new(0, 1));
+        assert!(Position::new(1, 3) > Position::new(0, 3));
+    }
+
+    // la rappresentazione è quella documentata sui tipi: le tracce registrate la usano, quindi
+    // questi testi esatti non devono cambiare
+    #[cfg(feature = "serde")]
+    #[test]
+    fn the_serde_representation_is_stable() {
+        use serde_json::{from_str, to_string};
+
+        let directions = Direction::ALL.map(|dir| to_string(&dir).unwrap());
+        assert_eq!(
+            directions,
+            [r#""north""#, r#""south""#, r#""east""#, r#""west""#]
+        );
+        // i nomi delle varianti delle tracce più vecchie
+        assert_eq!(from_str::<Direction>(r#""Sud""#).unwrap(), Direction::Sud);
+        assert_eq!(
+            from_str::<Direction>(r#""Ovest""#).unwrap(),
+            Direction::Ovest
+        );
+
+        let actions = [
+            (Action::Move(Direction::North), r#"{"Move":"north"}"#),
+            (Action::Shoot(Direction::Ovest), r#"{"Shoot":"west"}"#),
+            (Action::Grab, r#""Grab""#),
+            (Action::Exit, r#""Exit""#),
+        ];
+        for (action, json) in actions {
+            assert_eq!(to_string(&action).unwrap(), json);
+            assert_eq!(from_str::<Action>(json).unwrap(), action);
+        }
+
+        let pos = Position::new(1, 2);
+        assert_eq!(to_string(&pos).unwrap(), r#"{"x":1,"y":2}"#);
+        // l'ordine dei campi nel testo non conta
+        assert_eq!(from_str::<Position>(r#"{"y":2,"x":1}"#).unwrap(), pos);
+
+        let p = Perceptions::at(Position::new(0, 1), 4)
+            .stench()
+            .bump()
+            .build();
+        let json = r#"{"glitter":false,"stench":true,"breeze":false,"howl":false,"bump":true,"position":{"x":0,"y":1},"board_size":4}"#;
+        assert_eq!(to_string(&p).unwrap(), json);
+        assert_eq!(from_str::<Perceptions>(json).unwrap(), p);
+    }
+
+    #[cfg(feature = "serde")]
+    proptest! {
+        #[test]
+        fn perceptions_round_trip((size, pos) in cell(), senses in 0u8..32) {
+            let mut p = Perceptions::new(pos, size);
+            p.set_glitter(senses & 1 != 0);
+            p.set_stench(senses & 2 != 0);
+            p.set_breeze(senses & 4 != 0);
+            p.set_howl(senses & 8 != 0);
+            p.set_bump(senses & 16 != 0);
+            let json = serde_json::to_string(&p).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Perceptions>(&json).unwrap(), p);
+        }
+
+        #[test]
+        fn actions_round_trip(dir in direction(), kind in 0usize..4) {
+            let action = [Action::Move(dir), Action::Shoot(dir), Action::Grab, Action::Exit][kind];
+            let json = serde_json::to_string(&action).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Action>(&json).unwrap(), action);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn possible_move_agrees_with_checked_move((size, pos) in cell(), dir in direction()) {
+            prop_assert_eq!(pos.possible_move(dir, size), pos.checked_move(dir, size).is_some());
+            // la mossa è possibile esattamente quando la destinazione è nella mappa
+            let (dx, dy) = dir.delta();
+            let x = pos.x as isize + dx as isize;
+            let y = pos.y as isize + dy as isize;
+            let inside = (0..size as isize).contains(&x) && (0..size as isize).contains(&y);
+            prop_assert_eq!(pos.possible_move(dir, size), inside);
+        }
+
+        #[test]
+        fn border_moves_never_leave_the_map((bounds, pos) in border_cell(), dir in direction()) {
+            match pos.checked_move(dir, bounds) {
+                Some(next) => {
+                    prop_assert!(next.x < bounds.width && next.y < bounds.height);
+                    prop_assert_eq!(next.manhattan(&pos), 1);
+                }
+                None => {
+                    let (dx, dy) = dir.delta();
+                    let out_x = (dx < 0 && pos.x == 0) || (dx > 0 && pos.x + 1 == bounds.width);
+                    let out_y = (dy < 0 && pos.y == 0) || (dy > 0 && pos.y + 1 == bounds.height);
+                    prop_assert!(out_x || out_y);
+                }
+            }
+        }
+
+        #[test]
+        fn manhattan_is_a_distance((_, a) in cell(), (_, b) in cell(), (_, c) in cell()) {
+            prop_assert_eq!(a.manhattan(&b), b.manhattan(&a));
+            prop_assert_eq!(a.manhattan(&a), 0);
+            prop_assert_eq!(a.manhattan(&b) == 0, a == b);
+            prop_assert!(a.manhattan(&c) <= a.manhattan(&b) + b.manhattan(&c));
+            // l'ordine per righe è totale e coerente con l'uguaglianza
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert_eq!(a.cmp(&b) == std::cmp::Ordering::Equal, a == b);
+        }
+
+        #[test]
+        fn the_opposite_move_goes_back((size, pos) in cell(), dir in direction()) {
+            if let Some(next) = pos.checked_move(dir, size) {
+                prop_assert_eq!(next.checked_move(dir.opposite(), size), Some(pos));
+            }
+        }
+
+        #[test]
+        fn neighbors_stay_in_the_map((size, pos) in cell()) {
+            let neighbors: Vec<_> = pos.neighbors(size).collect();
+            for (dir, next) in &neighbors {
+                prop_assert!(next.x < size && next.y < size);
+                prop_assert_eq!(pos.checked_move(*dir, size), Some(*next));
+            }
+            let possible = Direction::ALL.iter().filter(|d| pos.possible_move(**d, size)).count();
+            prop_assert_eq!(neighbors.len(), possible);
+        }
+    }
+}
@@ -0,0 +1,487 @@
+// il binario lanciato come da terminale, con il solver nel processo così non serve picosat
+
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{self, Command, Output, Stdio},
+};
+
+fn command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_wumpus"));
+    command
+        .args(args)
+        .env("WUMPUS_SOLVER", "dpll")
+        .env_remove("RUST_LOG");
+    command
+}
+
+fn wumpus(args: &[&str]) -> Output {
+    command(args).output().expect("the binary starts")
+}
+
+// input è scritto su stdin, che poi viene chiuso
+fn wumpus_with_input(args: &[&str], input: &str) -> Output {
+    let mut child = command(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary starts");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// una cartella temporanea vuota, diversa per ogni test e processo
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("wumpus-cli-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn quiet_prints_only_the_summary() {
+    let output = wumpus(&[
+        "--quiet", "--seed", "3", "--dim", "4", "--pits", "1", "simulate", "--games", "3",
+    ]);
+    assert!(output.status.success());
+    let stdout = lines(&output.stdout);
+    assert!(!stdout.is_empty());
+    for line in &stdout {
+        assert!(line.starts_with("[FINISH]"), "{:?}", line);
+    }
+    // l'unica riga di avanzamento è quella finale
+    let stderr = lines(&output.stderr);
+    assert_eq!(stderr.len(), 1, "{:?}", stderr);
+    assert!(stderr[0].starts_with("completed 3/3"), "{:?}", stderr);
+}
+
+#[test]
+fn play_reads_the_moves_from_stdin() {
+    let args = ["--seed", "5", "--dim", "4", "--pits", "1", "play"];
+    // un comando sconosciuto e una mossa contro il muro vengono chiesti di nuovo
+    let output = wumpus_with_input(&args, "jump\nn\nx\n");
+    assert!(output.status.success());
+    let stdout = lines(&output.stdout);
+    assert_eq!(stdout[0], "[PLAY] dungeon seed 5");
+    assert!(stdout.contains(&"[PLAY] you escaped without the gold".to_string()));
+    assert_eq!(
+        stdout.last().unwrap(),
+        "[PLAY] 1 actions, score -1, dungeon seed 5"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown command \"jump\""), "{}", stderr);
+    assert!(stderr.contains("there is a wall"), "{}", stderr);
+
+    // stdin chiuso a metà partita la termina senza errori
+    let output = wumpus_with_input(&args, "");
+    assert!(output.status.success());
+    // la riga comincia con il prompt rimasto senza risposta
+    let last = lines(&output.stdout).pop().unwrap();
+    assert!(
+        last.ends_with("[PLAY] no more input after 0 actions, dungeon seed 5"),
+        "{:?}",
+        last
+    );
+}
+
+// i passi medi di una serie di una sola partita sono i passi di quella partita
+fn steps_of_the_only_game(stdout: &[String]) -> usize {
+    let line = stdout
+        .iter()
+        .find(|line| line.starts_with("[FINISH] average per game:") && line.ends_with("SAT calls"))
+        .expect("the summary has the steps");
+    let steps: f64 = line["[FINISH] average per game: ".len()..]
+        .split(' ')
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap();
+    steps as usize
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn the_turn_log_has_a_record_per_turn() {
+    let dir = temp_dir("turn-log");
+    let path = dir.join("turns.jsonl");
+    let output = wumpus(&[
+        "--seed",
+        "6",
+        "--dim",
+        "5",
+        "--pits",
+        "3",
+        "simulate",
+        "--games",
+        "1",
+        "--turn-log",
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let records: Vec<serde_json::Value> = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("every line is a JSON object"))
+        .collect();
+    assert_eq!(
+        records.len(),
+        steps_of_the_only_game(&lines(&output.stdout))
+    );
+    for (i, record) in records.iter().enumerate() {
+        assert_eq!(record["turn"], i + 1);
+        assert_eq!(record["game"], 0);
+        assert!(record["action"].is_string() || record["action"].is_object());
+        assert!(record["clauses"].as_u64().unwrap() > 0);
+        assert!(record["candidates"].is_array());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn an_agent_compared_with_itself_never_disagrees() {
+    let output = wumpus(&[
+        "--seed", "1", "--dim", "4", "--pits", "1", "compare", "--a", "hero", "--b", "hero",
+        "--games", "4",
+    ]);
+    assert!(output.status.success());
+    let stdout = lines(&output.stdout);
+    assert!(
+        stdout.contains(&"[COMPARE] A = Hero, B = Hero, 4 games from seed 1".to_string()),
+        "{:?}",
+        stdout
+    );
+    let pairs = stdout
+        .iter()
+        .find(|line| line.starts_with("[COMPARE] 4 games:"))
+        .unwrap();
+    assert!(pairs.contains("only A 0, only B 0"), "{}", pairs);
+    assert!(
+        stdout.contains(&"[COMPARE] sign test p-value 1.0000 (not significant at 5%)".to_string())
+    );
+    // nessuna riga per i seed su cui gli agenti non sono d'accordo
+    assert!(
+        !stdout
+            .iter()
+            .any(|line| line.starts_with("[COMPARE] seed "))
+    );
+}
+
+#[test]
+fn frames_are_numbered_in_play_order() {
+    let dir = temp_dir("frames");
+    // i fotogrammi vengono scritti anche con --quiet
+    let output = wumpus(&[
+        "--quiet",
+        "--seed",
+        "6",
+        "--dim",
+        "5",
+        "--pits",
+        "3",
+        "simulate",
+        "--games",
+        "1",
+        "--frames",
+        dir.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let steps = steps_of_the_only_game(&lines(&output.stdout));
+    let game = dir.join("game-000");
+    let mut names: Vec<String> = fs::read_dir(&game)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    // un fotogramma per turno più quello finale
+    let expected: Vec<String> = (0..=steps)
+        .map(|turn| format!("frame-{:05}.txt", turn))
+        .collect();
+    assert_eq!(names, expected);
+    let first = fs::read_to_string(game.join(&names[0])).unwrap();
+    assert!(first.starts_with("turn 0\n"), "{}", first);
+    let last = fs::read_to_string(game.join(names.last().unwrap())).unwrap();
+    assert!(
+        last.ends_with("action: none, the game is over\n"),
+        "{}",
+        last
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn the_same_seed_gives_identical_turn_logs() {
+    let dir = temp_dir("determinism");
+    let run = |name: &str| {
+        let path = dir.join(name);
+        let output = wumpus(&[
+            "--quiet",
+            "--seed",
+            "17",
+            "--dim",
+            "5",
+            "--pits",
+            "3",
+            "simulate",
+            "--games",
+            "4",
+            "--turn-log",
+            path.to_str().unwrap(),
+        ]);
+        assert!(output.status.success());
+        fs::read_to_string(path).unwrap()
+    };
+    let first = run("first.jsonl");
+    assert!(!first.is_empty());
+    assert_eq!(first, run("second.jsonl"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// le interrogazioni divise tra i thread danno le stesse risposte di quelle in serie
+#[cfg(feature = "serde")]
+#[test]
+fn parallel_queries_play_the_same_games() {
+    let dir = temp_dir("parallel-queries");
+    let run = |name: &str, parallel: bool| {
+        let path = dir.join(name);
+        let mut args = vec![
+            "--quiet",
+            "--seed",
+            "17",
+            "--dim",
+            "5",
+            "--pits",
+            "3",
+            "simulate",
+            "--games",
+            "4",
+            "--turn-log",
+            path.to_str().unwrap(),
+        ];
+        if parallel {
+            args.push("--parallel-queries");
+        }
+        let output = wumpus(&args);
+        assert!(output.status.success());
+        fs::read_to_string(path).unwrap()
+    };
+    assert_eq!(run("serial.jsonl", false), run("parallel.jsonl", true));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_environment_variables_are_listed_in_the_help() {
+    let output = wumpus(&["--help"]);
+    assert!(output.status.success());
+    let help = String::from_utf8_lossy(&output.stdout);
+    for name in [
+        "WUMPUS_RISK_THRESHOLD",
+        "WUMPUS_CHECK_PLANS",
+        "WUMPUS_EXPLORE_ALL",
+        "WUMPUS_CHECK_INVARIANTS",
+        "WUMPUS_TIMING",
+        "WUMPUS_PROPAGATION_DEPTH",
+        "WUMPUS_CONSISTENCY_EVERY",
+        "WUMPUS_MAX_STEPS",
+        "WUMPUS_PLANNING_ATTEMPTS",
+        "WUMPUS_EXPLORER",
+        "WUMPUS_HOME_EXPLORER",
+        "WUMPUS_FRONTIER",
+        "WUMPUS_PARALLEL",
+        "WUMPUS_PARALLEL_GAMES",
+        "WUMPUS_STRATEGY",
+        "WUMPUS_ORIENTED",
+    ] {
+        assert!(help.contains(&format!("[env: {}=", name)), "{}", name);
+    }
+    let output = wumpus(&["simulate", "--help"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[env: WUMPUS_RECORD="));
+}
+
+#[test]
+fn oriented_games_also_pay_the_turns() {
+    let args = [
+        "--seed", "6", "--dim", "5", "--pits", "3", "simulate", "--games", "1",
+    ];
+    let cardinal = wumpus(&args);
+    let oriented = wumpus(&[&["--oriented"], &args[..]].concat());
+    assert!(cardinal.status.success() && oriented.status.success());
+    let (cardinal, oriented) = (lines(&cardinal.stdout), lines(&oriented.stdout));
+    // le stesse scelte, in più solo le rotazioni
+    let gold = |stdout: &[String]| {
+        stdout
+            .iter()
+            .find(|line| line.starts_with("[FINISH] gold found"))
+            .cloned()
+    };
+    assert_eq!(gold(&oriented), gold(&cardinal));
+    assert!(steps_of_the_only_game(&oriented) > steps_of_the_only_game(&cardinal));
+}
+
+// un'opzione dell'eroe data sulla riga di comando o con la sua variabile d'ambiente
+#[cfg(feature = "serde")]
+#[test]
+fn hero_options_and_their_variables_play_the_same_games() {
+    let dir = temp_dir("hero-options");
+    let run = |name: &str, options: &[&str], vars: &[(&str, &str)]| {
+        let path = dir.join(name);
+        let mut args = vec!["--quiet", "--seed", "17", "--dim", "5", "--pits", "3"];
+        args.extend(options);
+        args.extend([
+            "simulate",
+            "--games",
+            "4",
+            "--turn-log",
+            path.to_str().unwrap(),
+        ]);
+        let output = command(&args).envs(vars.iter().copied()).output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::read_to_string(path).unwrap()
+    };
+    let flags = run("flags.jsonl", &["--max-steps", "6", "--explore-all"], &[]);
+    let vars = run(
+        "vars.jsonl",
+        &[],
+        &[("WUMPUS_MAX_STEPS", "6"), ("WUMPUS_EXPLORE_ALL", "1")],
+    );
+    assert_eq!(flags, vars);
+    assert_ne!(flags, run("default.jsonl", &[], &[]));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// senza sottocomando la serie legge comunque WUMPUS_RECORD
+#[cfg(feature = "serde")]
+#[test]
+fn the_default_batch_records_its_games() {
+    let dir = temp_dir("record");
+    let output = command(&["--quiet", "--seed", "2", "--dim", "4", "--pits", "1"])
+        .env("WUMPUS_RECORD", &dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.join("game-0.json").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_subcommand_starts_and_exits_cleanly() {
+    let small = ["--seed", "6", "--dim", "4", "--pits", "1"];
+    let run = |args: &[&str]| {
+        let args: Vec<&str> = small.iter().chain(args).copied().collect();
+        let output = wumpus(&args);
+        assert!(
+            output.status.success(),
+            "{:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        lines(&output.stdout)
+    };
+    assert!(
+        run(&["simulate", "--games", "1"])
+            .iter()
+            .any(|line| line.starts_with("[FINISH]"))
+    );
+    // senza sottocomando è una serie come simulate
+    assert!(run(&[]).iter().any(|line| line.starts_with("[FINISH]")));
+    assert!(
+        run(&["replay", "6"])
+            .iter()
+            .any(|line| line.starts_with("[REPLAY]"))
+    );
+    let bench = run(&["bench", "--iterations", "1"]);
+    assert!(bench[0].starts_with("[BENCH] init_kb(4)"), "{:?}", bench);
+    assert!(bench[1].starts_with("[BENCH] ask safe"), "{:?}", bench);
+    let play = wumpus_with_input(&[&small[..], &["play"]].concat(), "x\n");
+    assert!(play.status.success());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn experiments_runs_a_minimal_file() {
+    let dir = temp_dir("experiments");
+    let config = dir.join("experiments.toml");
+    fs::write(&config, "dims = [4]\npits = [1]\ntrials = 2\nseed = 1\n").unwrap();
+    let output = wumpus(&["--quiet", "experiments", config.to_str().unwrap()]);
+    assert!(output.status.success());
+    // l'intestazione del CSV e una riga per la sola combinazione
+    assert_eq!(
+        lines(&output.stdout).len(),
+        2,
+        "{:?}",
+        lines(&output.stdout)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bad_command_lines_are_explained() {
+    let unknown = wumpus(&["frobnicate"]);
+    assert_eq!(unknown.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&unknown.stderr);
+    assert!(
+        stderr.contains("unrecognized subcommand 'frobnicate'"),
+        "{}",
+        stderr
+    );
+    let missing = wumpus(&["replay"]);
+    assert_eq!(missing.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&missing.stderr);
+    assert!(
+        stderr.contains("required arguments were not provided"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("<TARGET>"), "{}", stderr);
+}
+
+#[test]
+fn sweep_writes_the_curve_file() {
+    let dir = temp_dir("sweep");
+    let out = dir.join("curve.dat");
+    let output = wumpus(&[
+        "--quiet",
+        "--seed",
+        "1",
+        "--dim",
+        "4",
+        "--agent",
+        "random",
+        "sweep",
+        "--from",
+        "0.1",
+        "--to",
+        "0.3",
+        "--step",
+        "0.1",
+        "--trials",
+        "10",
+        "--out",
+        out.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let curve = fs::read_to_string(&out).unwrap();
+    let rows: Vec<&str> = curve.lines().collect();
+    assert_eq!(rows.len(), 4, "{}", curve);
+    assert!(rows[0].starts_with('#'));
+    assert!(rows[1..].iter().all(|row| row.split(' ').count() == 5));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,33 @@
+// le codifiche DIMACS di tests/dimacs, scritte con `wumpus golden-dimacs --update`: un
+// cambiamento dell'encoder che sposta anche un solo byte va rigenerato di proposito
+
+use std::path::Path;
+
+use wumpus::{encoder::check_dimacs, kb::golden_kbs};
+
+#[test]
+fn the_encodings_match_the_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/dimacs");
+    for (name, kb) in golden_kbs() {
+        let (encoding, _) = kb.encode();
+        check_dimacs(&encoding).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        let path = dir.join(format!("{}.cnf", name));
+        let golden = std::fs::read(&path).unwrap_or_else(|e| panic!("{:?}: {}", path, e));
+        assert!(
+            golden == encoding.as_bytes(),
+            "{} differs from {:?}",
+            name,
+            path
+        );
+    }
+}
+
+#[test]
+fn inconsistent_headers_are_refused() {
+    assert!(check_dimacs("p cnf 2 1\n1 -2 0\n").is_ok());
+    // una clausola in più, una in meno, un letterale oltre le variabili dichiarate
+    assert!(check_dimacs("p cnf 2 1\n1 -2 0\n2 0\n").is_err());
+    assert!(check_dimacs("p cnf 2 2\n1 -2 0\n").is_err());
+    assert!(check_dimacs("p cnf 2 1\n1 -3 0\n").is_err());
+    assert!(check_dimacs("").is_err());
+}
@@ -0,0 +1,127 @@
+// partite complete dell'eroe deterministico su mappe disegnate a mano, con l'esito noto:
+// oltre all'esito si controlla che l'eroe non entri mai in un pericolo e un limite ai passi
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    hero::{Hero, HeroConfig},
+    kb::init_kb,
+    simulation::{Outcome, Screen, SimulationResult, check_invariants, simulate},
+    world::{Position, World},
+};
+
+fn play(layout: &str) -> (SimulationResult, World) {
+    check_invariants(true);
+    let mut world = World::from_layout(layout).expect("the layout is valid");
+    let mut kb = init_kb(world.size());
+    kb.use_in_process_solver();
+    let mut hero = Hero::with_rng(
+        kb,
+        world.size(),
+        HeroConfig::default(),
+        StdRng::seed_from_u64(7),
+    );
+    let result = simulate(&mut world, &mut hero, &mut |_| {}, Screen::hidden(), None);
+    (result, world)
+}
+
+// le caselle in cui l'eroe è stato, dalle percezioni di ogni turno
+fn visited(result: &SimulationResult) -> Vec<Position> {
+    result
+        .history
+        .iter()
+        .map(|step| step.perceptions.position)
+        .collect()
+}
+
+// il wumpus ucciso resta nella sua casella ma non è più un pericolo, quindi basta il mondo
+// alla fine della partita: se l'eroe ci fosse entrato da vivo la partita sarebbe finita lì
+fn assert_no_hazard_entered(result: &SimulationResult, world: &World) {
+    for pos in visited(result) {
+        assert_eq!(world.hazard_at(pos), None, "the hero entered {:?}", pos);
+    }
+}
+
+#[test]
+fn gold_behind_safe_cells_is_taken() {
+    let (result, world) = play(
+        "
+        x . . .
+        . . o .
+        . . . .
+        w . . g
+        ",
+    );
+    assert!(
+        matches!(result.outcome, Outcome::Gold),
+        "{}",
+        result.outcome.label()
+    );
+    assert_no_hazard_entered(&result, &world);
+    assert!(result.steps <= 40, "{} steps", result.steps);
+}
+
+// la mappa di AIMA (figura 7.2) con y che cresce verso il basso: pozzi in (2,0), (2,2) e
+// (3,3), il wumpus in (0,2) e l'oro in (1,2)
+#[test]
+fn the_aima_layout_is_won() {
+    let (result, world) = play(
+        "
+        x . o .
+        . . . .
+        w g o .
+        . . . o
+        ",
+    );
+    assert!(
+        matches!(result.outcome, Outcome::Gold),
+        "{}",
+        result.outcome.label()
+    );
+    assert_no_hazard_entered(&result, &world);
+    assert!(result.steps <= 40, "{} steps", result.steps);
+}
+
+// due pozzi attorno alla partenza: nessuna mossa è sicura e l'eroe deve uscire subito vivo
+#[test]
+fn a_start_between_pits_is_left_alive() {
+    let (result, world) = play(
+        "
+        x o .
+        o . g
+        . . w
+        ",
+    );
+    assert!(
+        matches!(result.outcome, Outcome::NoGold),
+        "{}",
+        result.outcome.label()
+    );
+    assert_no_hazard_entered(&result, &world);
+    assert_eq!(visited(&result), vec![Position::new(0, 0)]);
+    assert!(result.steps <= 1, "{} steps", result.steps);
+}
+
+// brezza in (1,0) e niente brezza in (0,1): il pozzo vicino a (1,0) non può essere in
+// (1,1), quindi la diagonale della partenza è sicura e il pozzo è in (2,0)
+#[test]
+fn the_safe_diagonal_is_inferred() {
+    let (result, world) = play(
+        "
+        x . o .
+        . . . .
+        . . . .
+        . w . g
+        ",
+    );
+    assert!(
+        matches!(result.outcome, Outcome::Gold),
+        "{}",
+        result.outcome.label()
+    );
+    assert_no_hazard_entered(&result, &world);
+    let visited = visited(&result);
+    assert!(visited.contains(&Position::new(1, 1)));
+    assert!(!visited.contains(&Position::new(2, 0)));
+    assert!(result.steps <= 40, "{} steps", result.steps);
+}
@@ -0,0 +1,80 @@
+// le tracce di tests/golden, scritte con `wumpus golden --update --dim 6 --pits 4 --games 8`
+// e WUMPUS_SOLVER=dpll: l'eroe deterministico deve fare ancora le stesse scelte. Se un
+// cambiamento di comportamento è voluto si rigenerano con lo stesso comando
+#![cfg(feature = "serde")]
+
+use std::path::{Path, PathBuf};
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    agents::Agent,
+    hero::{Hero, HeroConfig},
+    kb::init_kb,
+    replay::{GoldenTrace, check_golden},
+    simulation::check_invariants,
+    world::{Action, GameSeeds},
+};
+
+fn traces() -> Vec<(PathBuf, GoldenTrace)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .expect("tests/golden exists")
+        .map(|entry| entry.expect("tests/golden is readable").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let golden = GoldenTrace::load(&path).expect("the trace is valid");
+            (path, golden)
+        })
+        .collect()
+}
+
+// come create_agent del binario, ma sempre con il solver nel processo
+fn hero(golden: &GoldenTrace) -> impl Agent {
+    let mut kb = init_kb(golden.dim);
+    kb.use_in_process_solver();
+    let rng = StdRng::seed_from_u64(GameSeeds::new(golden.seed).agent);
+    Hero::with_rng(kb, golden.dim, HeroConfig::default(), rng)
+}
+
+#[test]
+fn the_hero_still_follows_the_golden_traces() {
+    check_invariants(true);
+    let traces = traces();
+    assert!(!traces.is_empty(), "no golden traces in tests/golden");
+    let mismatches: Vec<String> = traces
+        .iter()
+        .filter_map(|(path, golden)| {
+            let error = check_golden(golden, &mut hero(golden)).err()?;
+            Some(format!("{:?} diverges at {}", path, error))
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn a_changed_trace_is_reported_at_its_first_different_turn() {
+    check_invariants(true);
+    let (_, mut golden) = traces().remove(0);
+    let turn = golden.actions.len() / 2;
+    let original = golden.actions[turn];
+    golden.actions[turn] = if original == Action::Grab {
+        Action::Exit
+    } else {
+        Action::Grab
+    };
+    let mismatch = check_golden(&golden, &mut hero(&golden)).unwrap_err();
+    assert_eq!(mismatch.turn, turn);
+    assert_eq!(mismatch.actual, Some(original));
+    assert_eq!(mismatch.expected, golden.actions.get(turn).copied());
+    assert!(!mismatch.board.is_empty());
+
+    golden.actions[turn] = original;
+    golden.actions.truncate(turn);
+    let mismatch = check_golden(&golden, &mut hero(&golden)).unwrap_err();
+    assert_eq!((mismatch.turn, mismatch.expected), (turn, None));
+}
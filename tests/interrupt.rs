@@ -0,0 +1,57 @@
+// interrupt non si può annullare, quindi ha un binario di test tutto suo: il flag viene
+// alzato a metà serie come farebbe il Ctrl-C
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    agents::Agent,
+    hero::{Hero, HeroConfig},
+    kb::init_kb,
+    simulation::{
+        Outcome, Progress, Screen, check_invariants, interrupt, interrupted, play_games, simulate,
+    },
+    world::{GameSeeds, World},
+};
+
+const SEED: u64 = 100;
+
+#[test]
+fn an_interrupt_mid_batch_keeps_the_completed_games() {
+    check_invariants(true);
+    // la quarta partita alza il flag mentre viene creata
+    let new_game = |seed: Option<u64>| -> (World, Box<dyn Agent>) {
+        let seed = seed.unwrap();
+        if seed == SEED ^ 3 {
+            interrupt();
+        }
+        let world = World::new_with_seed(4, 1, seed);
+        let mut kb = init_kb(4);
+        kb.use_in_process_solver();
+        let rng = StdRng::seed_from_u64(GameSeeds::new(seed).agent);
+        (
+            world,
+            Box::new(Hero::with_rng(kb, 4, HeroConfig::default(), rng)),
+        )
+    };
+    let progress = Progress::new(10, true, |_| {});
+    let results = play_games(10, Some(SEED), false, &new_game, None, &progress);
+    assert!(interrupted());
+    // la partita interrotta viene scartata e le successive non cominciano
+    let seeds: Vec<u64> = results.iter().map(|(result, _)| result.seed).collect();
+    assert_eq!(seeds, vec![SEED, SEED ^ 1, SEED ^ 2]);
+    for (result, _) in &results {
+        assert!(!matches!(result.outcome, Outcome::Interrupted));
+    }
+
+    // dopo l'interruzione una partita finisce prima del primo turno
+    let (mut world, mut agent) = new_game(Some(SEED));
+    let result = simulate(
+        &mut world,
+        agent.as_mut(),
+        &mut |_| {},
+        Screen::hidden(),
+        None,
+    );
+    assert_eq!(result.outcome.label(), "interrupted");
+    assert_eq!(result.steps, 0);
+}
@@ -0,0 +1,88 @@
+// i controlli di simulate provati con agenti guasti apposta: la partita deve fermarsi con un
+// panic alla prima violazione
+
+use std::cell::Cell;
+
+use wumpus::{
+    agents::Agent,
+    hero::{Belief, HeroError},
+    simulation::{Screen, check_invariants, simulate},
+    world::{Action, Direction, Perceptions, Position, World},
+};
+
+const LAYOUT: &str = "
+    x . .
+    . . .
+    w g o
+";
+
+// cammina verso est e crede pericolosa la casella (1,0)
+struct IntoUnsafe;
+
+impl Agent for IntoUnsafe {
+    fn next_action(&mut self, _p: Perceptions) -> Result<Action, HeroError> {
+        Ok(Action::Move(Direction::East))
+    }
+
+    fn belief(&self, pos: Position) -> Option<Belief> {
+        if pos == Position::new(1, 0) {
+            Some(Belief::Unsafe)
+        } else {
+            Some(Belief::Safe)
+        }
+    }
+}
+
+// entra in (1,0) credendola sicura e subito dopo la dà per dimostrata pericolosa:
+// la prima domanda è quella prima della mossa, le altre arrivano dopo
+#[derive(Default)]
+struct ChangesItsMind {
+    asked: Cell<usize>,
+}
+
+impl Agent for ChangesItsMind {
+    fn next_action(&mut self, _p: Perceptions) -> Result<Action, HeroError> {
+        Ok(Action::Move(Direction::East))
+    }
+
+    fn belief(&self, _pos: Position) -> Option<Belief> {
+        self.asked.set(self.asked.get() + 1);
+        if self.asked.get() == 1 {
+            Some(Belief::Safe)
+        } else {
+            Some(Belief::Unsafe)
+        }
+    }
+}
+
+fn play(agent: &mut dyn Agent) {
+    check_invariants(true);
+    let mut world = World::from_layout(LAYOUT).expect("the layout is valid");
+    simulate(&mut world, agent, &mut |_| {}, Screen::hidden(), None);
+}
+
+#[test]
+#[should_panic(expected = "move East to Position { x: 1, y: 0 }, believed Unsafe")]
+fn a_move_into_a_cell_believed_unsafe_panics() {
+    play(&mut IntoUnsafe);
+}
+
+#[test]
+#[should_panic(expected = "stands on Position { x: 1, y: 0 }, proved unsafe")]
+fn standing_on_a_cell_proved_unsafe_panics() {
+    play(&mut ChangesItsMind::default());
+}
+
+#[test]
+fn an_agent_without_beliefs_is_not_checked() {
+    // come RandomAgent: nessuna opinione sulle caselle, la morte non viola niente
+    struct Blind;
+
+    impl Agent for Blind {
+        fn next_action(&mut self, _p: Perceptions) -> Result<Action, HeroError> {
+            Ok(Action::Move(Direction::Sud))
+        }
+    }
+
+    play(&mut Blind);
+}
@@ -0,0 +1,85 @@
+// l'oro è dietro il wumpus: l'eroe esplora tutto il resto, lo localizza, si mette in linea,
+// tira, sente l'urlo e torna a cercare l'oro passando dalla casella del wumpus
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    hero::{Hero, HeroConfig, Objective},
+    kb::init_kb,
+    simulation::{Screen, TurnLog, check_invariants, simulate},
+    world::{Action, Direction, Position, World},
+};
+
+// (3,1) è un pozzo, quindi all'oro in (3,0) si arriva solo da (2,0)
+const LAYOUT: &str = "
+    x . w g
+    . . . o
+    . . . .
+    . . . .
+";
+
+#[test]
+fn the_hero_kills_the_wumpus_guarding_the_gold() {
+    check_invariants(true);
+    let mut world = World::from_layout(LAYOUT).expect("the layout is valid");
+    let mut kb = init_kb(world.size());
+    kb.use_in_process_solver();
+    let mut hero = Hero::with_rng(
+        kb,
+        world.size(),
+        HeroConfig::default(),
+        StdRng::seed_from_u64(3),
+    );
+    let mut turns: Vec<TurnLog> = vec![];
+    let result = simulate(
+        &mut world,
+        &mut hero,
+        &mut |turn| turns.push(turn.clone()),
+        Screen::hidden(),
+        None,
+    );
+    assert_eq!(result.outcome.label(), "gold");
+
+    let shots: Vec<usize> = (0..turns.len())
+        .filter(|i| matches!(turns[*i].action, Action::Shoot(_)))
+        .collect();
+    assert_eq!(shots.len(), 1, "the hero has a single arrow");
+    let shot = &turns[shots[0]];
+    let Action::Shoot(dir) = shot.action else {
+        unreachable!()
+    };
+    let objective = |turn: &TurnLog| turn.decision.as_ref().expect("the hero explains").objective;
+    assert_eq!(objective(shot), Objective::KillWumpus);
+    // da una casella sicura allineata con il wumpus in (2,0)
+    let from = shot.perceptions.position;
+    assert!(
+        (from.y == 0 && dir == Direction::East && from.x < 2)
+            || (from.x == 2 && dir == Direction::North),
+        "{:?} from {:?} misses the wumpus",
+        dir,
+        from
+    );
+    assert!(
+        turns[shots[0] + 1].perceptions.howl(),
+        "the arrow missed the wumpus"
+    );
+    assert!(!world.wumpus_alive());
+    assert!(
+        turns
+            .iter()
+            .all(|turn| turn.perceptions.position != Position::new(3, 1)),
+        "the hero walked into the pit"
+    );
+
+    let mut objectives: Vec<Objective> = turns.iter().map(objective).collect();
+    objectives.dedup();
+    assert_eq!(
+        objectives,
+        vec![
+            Objective::TakeGold,
+            Objective::KillWumpus,
+            Objective::TakeGold,
+            Objective::GoHome
+        ]
+    );
+}
@@ -0,0 +1,107 @@
+// la libreria usata da fuori come farebbe un altro crate: un dungeon fisso, l'eroe con la
+// sua KB e simulate
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    agents::Agent,
+    encoder::EncoderSAT,
+    fixtures,
+    hero::{Hero, HeroConfig, HeroError},
+    kb::{KnowledgeBase, Var, init_kb},
+    simulation::{Outcome, Screen, check_invariants, simulate},
+    world::{Action, DeathCause, Direction, Perceptions, Position, World},
+};
+
+const LAYOUT: &str = "
+    x . g
+    . . .
+    o . w
+";
+
+#[test]
+fn hero_wins_a_tiny_fixed_world() {
+    check_invariants(true);
+    let mut world = World::from_layout(LAYOUT).expect("the layout is valid");
+    let mut kb = init_kb(world.size());
+    kb.use_in_process_solver();
+    let mut hero = Hero::with_rng(
+        kb,
+        world.size(),
+        HeroConfig::default(),
+        StdRng::seed_from_u64(7),
+    );
+    let mut turns = 0;
+    let result = simulate(
+        &mut world,
+        &mut hero,
+        &mut |_| turns += 1,
+        Screen::hidden(),
+        None,
+    );
+    assert!(matches!(result.outcome, Outcome::Gold));
+    assert!(result.gold && result.solvable);
+    assert_eq!(result.steps, turns);
+    // due passi fino all'oro, la presa, due passi indietro e l'uscita
+    assert_eq!(result.steps, 6);
+    assert_eq!(result.score, 1000 - 6);
+    assert!(result.sat_calls > 0);
+}
+
+// un agente che si crede al sicuro e cammina verso est, dritto nel pozzo
+struct Reckless;
+
+impl Agent for Reckless {
+    fn next_action(&mut self, _p: Perceptions) -> Result<Action, HeroError> {
+        Ok(Action::Move(Direction::East))
+    }
+
+    fn accepts_risk(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn death_on_a_safe_cell_is_an_outcome() {
+    check_invariants(true);
+    let mut world = World::from_layout("x o w\n. . .\n. . g").expect("the layout is valid");
+    let result = simulate(
+        &mut world,
+        &mut Reckless,
+        &mut |_| {},
+        Screen::hidden(),
+        None,
+    );
+    assert!(matches!(result.outcome, Outcome::DeadOnSafeCell));
+    assert_eq!(result.death_cause, Some(DeathCause::Pit));
+    assert_eq!(result.steps, 1);
+}
+
+#[test]
+fn bad_layouts_are_refused() {
+    assert!(World::from_layout("x . .\n. w .").is_err());
+    assert!(World::from_layout("x . g\n. . .\n. . .").is_err());
+    assert!(World::from_layout("o . g\n. w .\n. . .").is_err());
+    assert!(World::from_layout(". . g\n. w x\n. . .").is_err());
+    assert!(World::from_layout("x . g\n. w ?\n. . .").is_err());
+}
+
+// le fixture sono esportate anche per i test di chi usa il crate
+#[test]
+fn fixtures_are_exported() {
+    let told = |p: Perceptions| {
+        let mut kb = init_kb(3);
+        kb.use_in_process_solver();
+        kb.tell(&EncoderSAT::<Var>::create_ground_truth_from_perception(&p));
+        kb
+    };
+    let safe = |kb: &mut EncoderSAT<Var>, pos: Position| {
+        kb.ask(&EncoderSAT::<Var>::create_safe_formula(&pos))
+            .unwrap()
+    };
+    let mut clean = told(fixtures::start_clean(3));
+    assert!(safe(&mut clean, Position::new(1, 0)));
+    assert!(safe(&mut clean, Position::new(0, 1)));
+    let mut breezy = told(fixtures::start_breezy(3));
+    assert!(!safe(&mut breezy, Position::new(1, 0)));
+}
@@ -0,0 +1,76 @@
+// il wumpus in (2,3) si può colpire solo dalla colonna 2: sulla riga 3 ci sono due pozzi e
+// l'oro, mai dimostrato sicuro. Quando decide di tirare l'eroe è in (0,2), a tre passi dalla
+// casella di tiro
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use wumpus::{
+    hero::{Hero, HeroConfig, Objective},
+    kb::init_kb,
+    simulation::{Outcome, Screen, TurnLog, check_invariants, simulate},
+    world::{Action, Direction, Position, World},
+};
+
+const LAYOUT: &str = "
+    x . . .
+    . . . .
+    . o . .
+    g o w o
+";
+
+#[test]
+fn the_hero_walks_to_the_shooting_position() {
+    check_invariants(true);
+    let mut world = World::from_layout(LAYOUT).expect("the layout is valid");
+    let mut kb = init_kb(world.size());
+    kb.use_in_process_solver();
+    let mut hero = Hero::with_rng(
+        kb,
+        world.size(),
+        HeroConfig::default(),
+        StdRng::seed_from_u64(3),
+    );
+    let mut turns: Vec<TurnLog> = vec![];
+    let result = simulate(
+        &mut world,
+        &mut hero,
+        &mut |turn| turns.push(turn.clone()),
+        Screen::hidden(),
+        None,
+    );
+    assert!(
+        matches!(result.outcome, Outcome::Gold | Outcome::NoGold),
+        "{}",
+        result.outcome.label()
+    );
+
+    // i turni con l'obbiettivo KillWumpus fino al tiro compreso
+    let approach: Vec<&TurnLog> = turns
+        .iter()
+        .skip_while(|turn| turn.decision.as_ref().unwrap().objective != Objective::KillWumpus)
+        .take_while(|turn| turn.decision.as_ref().unwrap().objective == Objective::KillWumpus)
+        .collect();
+    let first = approach[0].decision.as_ref().unwrap();
+    assert_eq!(first.position, Position::new(0, 2));
+    // il piano porta dritto alla casella di tiro
+    assert_eq!(first.plan.last(), Some(&Position::new(2, 1)));
+    let actions: Vec<Action> = approach.iter().map(|turn| turn.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            Action::Move(Direction::North),
+            Action::Move(Direction::East),
+            Action::Move(Direction::East),
+            Action::Shoot(Direction::Sud),
+        ]
+    );
+    let shot = turns
+        .iter()
+        .position(|turn| matches!(turn.action, Action::Shoot(_)))
+        .unwrap();
+    assert!(
+        turns[shot + 1].perceptions.howl(),
+        "the arrow missed the wumpus"
+    );
+    assert!(!world.wumpus_alive());
+}
@@ -0,0 +1,50 @@
+// il gioco compilato per wasm32 con il solver nel processo, senza browser (wasm-bindgen-cli
+// installato, gira con node):
+// CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner \
+//     cargo test --target wasm32-unknown-unknown --features wasm --test wasm
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use wumpus::wasm::WasmGame;
+
+fn state(json: &str) -> serde_json::Value {
+    serde_json::from_str(json).expect("step returns JSON")
+}
+
+#[wasm_bindgen_test]
+fn the_hero_plays_a_few_turns() {
+    let mut game = WasmGame::new_world(7, 4, 2);
+    let mut last = None;
+    for turn in 1..=5 {
+        let state = state(&game.step());
+        assert_eq!(state["error"], serde_json::Value::Null);
+        if state["done"] == true {
+            last = Some(state);
+            break;
+        }
+        assert_eq!(state["turn"], turn);
+        assert!(state["action"].is_string() || state["action"].is_object());
+        last = Some(state);
+    }
+    let last = last.expect("at least a turn was played");
+    assert_eq!(last["observation"]["board_size"], 4);
+    assert!(!game.hero_knowledge().is_empty());
+}
+
+#[wasm_bindgen_test]
+fn the_state_does_not_change_after_the_end() {
+    let mut game = WasmGame::new_world(3, 4, 2);
+    let mut state = state(&game.step());
+    for _ in 0..200 {
+        if state["done"] == true {
+            break;
+        }
+        state = self::state(&game.step());
+    }
+    assert_eq!(state["done"], true);
+    let after = self::state(&game.step());
+    assert_eq!(after["turn"], state["turn"]);
+    assert_eq!(after["score"], state["score"]);
+    assert_eq!(after["action"], serde_json::Value::Null);
+}